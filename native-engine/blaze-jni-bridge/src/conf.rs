@@ -14,7 +14,7 @@
 
 use datafusion::common::Result;
 
-use crate::{jni_call_static, jni_new_string};
+use crate::{jni_call_static, jni_get_string, jni_new_string};
 
 macro_rules! define_conf {
     ($conftype:ty, $name:ident) => {
@@ -31,6 +31,7 @@ macro_rules! define_conf {
 define_conf!(IntConf, BATCH_SIZE);
 define_conf!(DoubleConf, MEMORY_FRACTION);
 define_conf!(BooleanConf, SMJ_INEQUALITY_JOIN_ENABLE);
+define_conf!(BooleanConf, RANGE_JOIN_ENABLE);
 define_conf!(BooleanConf, BHJ_FALLBACKS_TO_SMJ_ENABLE);
 define_conf!(IntConf, BHJ_FALLBACKS_TO_SMJ_ROWS_THRESHOLD);
 define_conf!(IntConf, BHJ_FALLBACKS_TO_SMJ_MEM_THRESHOLD);
@@ -41,6 +42,11 @@ define_conf!(BooleanConf, IGNORE_CORRUPTED_FILES);
 define_conf!(BooleanConf, PARTIAL_AGG_SKIPPING_ENABLE);
 define_conf!(DoubleConf, PARTIAL_AGG_SKIPPING_RATIO);
 define_conf!(IntConf, PARTIAL_AGG_SKIPPING_MIN_ROWS);
+define_conf!(IntConf, COLLECT_MAX_ELEMENTS);
+define_conf!(StringConf, SHUFFLE_COMPRESSION_CODEC);
+define_conf!(IntConf, SHUFFLE_COMPRESSION_ZSTD_LEVEL);
+define_conf!(IntConf, SHUFFLE_COMPRESSION_TARGET_BUF_SIZE);
+define_conf!(StringConf, SHUFFLE_CHECKSUM_ALGORITHM);
 
 pub trait BooleanConf {
     fn key(&self) -> &'static str;
@@ -73,3 +79,12 @@ pub trait DoubleConf {
         jni_call_static!(BlazeConf.doubleConf(key.as_obj()) -> f64)
     }
 }
+
+pub trait StringConf {
+    fn key(&self) -> &'static str;
+    fn value(&self) -> Result<String> {
+        let key = jni_new_string!(self.key())?;
+        let value = jni_call_static!(BlazeConf.stringConf(key.as_obj()) -> JObject)?;
+        jni_get_string!(value.as_obj().into())
+    }
+}
@@ -400,6 +400,7 @@ pub struct JavaClasses<'a> {
     pub cSparkUDFWrapperContext: SparkUDFWrapperContext<'a>,
     pub cBlazeConf: BlazeConf<'a>,
     pub cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase<'a>,
+    pub cBlazeChecksummedFileSegment: BlazeChecksummedFileSegment<'a>,
     pub cBlazeCallNativeWrapper: BlazeCallNativeWrapper<'a>,
     pub cBlazeOnHeapSpillManager: BlazeOnHeapSpillManager<'a>,
     pub cBlazeNativeParquetSinkUtils: BlazeNativeParquetSinkUtils<'a>,
@@ -464,6 +465,7 @@ impl JavaClasses<'static> {
                 cSparkUDFWrapperContext: SparkUDFWrapperContext::new(env).unwrap(),
                 cBlazeConf: BlazeConf::new(env).unwrap(),
                 cBlazeRssPartitionWriterBase: BlazeRssPartitionWriterBase::new(env).unwrap(),
+                cBlazeChecksummedFileSegment: BlazeChecksummedFileSegment::new(env).unwrap(),
                 cBlazeCallNativeWrapper: BlazeCallNativeWrapper::new(env).unwrap(),
                 cBlazeOnHeapSpillManager: BlazeOnHeapSpillManager::new(env).unwrap(),
                 cBlazeNativeParquetSinkUtils: BlazeNativeParquetSinkUtils::new(env).unwrap(),
@@ -1140,6 +1142,8 @@ pub struct BlazeConf<'a> {
     pub method_longConf_ret: ReturnType,
     pub method_doubleConf: JStaticMethodID,
     pub method_doubleConf_ret: ReturnType,
+    pub method_stringConf: JStaticMethodID,
+    pub method_stringConf_ret: ReturnType,
 }
 
 impl<'a> BlazeConf<'_> {
@@ -1165,6 +1169,14 @@ impl<'a> BlazeConf<'_> {
                 .get_static_method_id(class, "doubleConf", "(Ljava/lang/String;)D")
                 .unwrap(),
             method_doubleConf_ret: ReturnType::Primitive(Primitive::Double),
+            method_stringConf: env
+                .get_static_method_id(
+                    class,
+                    "stringConf",
+                    "(Ljava/lang/String;)Ljava/lang/String;",
+                )
+                .unwrap(),
+            method_stringConf_ret: ReturnType::Object,
         })
     }
 }
@@ -1200,6 +1212,39 @@ impl<'a> BlazeRssPartitionWriterBase<'_> {
     }
 }
 
+#[allow(non_snake_case)]
+pub struct BlazeChecksummedFileSegment<'a> {
+    pub class: JClass<'a>,
+    pub method_blockId: JMethodID,
+    pub method_blockId_ret: ReturnType,
+    pub method_fileSegment: JMethodID,
+    pub method_fileSegment_ret: ReturnType,
+    pub method_checksum: JMethodID,
+    pub method_checksum_ret: ReturnType,
+}
+
+impl<'a> BlazeChecksummedFileSegment<'_> {
+    pub const SIG_TYPE: &'static str =
+        "org/apache/spark/sql/execution/blaze/shuffle/BlazeChecksummedFileSegment";
+
+    pub fn new(env: &JNIEnv<'a>) -> JniResult<BlazeChecksummedFileSegment<'a>> {
+        let class = get_global_jclass(env, Self::SIG_TYPE)?;
+        Ok(BlazeChecksummedFileSegment {
+            class,
+            method_blockId: env.get_method_id(class, "blockId", "()Ljava/lang/String;")?,
+            method_blockId_ret: ReturnType::Object,
+            method_fileSegment: env.get_method_id(
+                class,
+                "fileSegment",
+                "()Lorg/apache/spark/storage/FileSegment;",
+            )?,
+            method_fileSegment_ret: ReturnType::Object,
+            method_checksum: env.get_method_id(class, "checksum", "()J")?,
+            method_checksum_ret: ReturnType::Primitive(Primitive::Long),
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 pub struct SparkUDFWrapperContext<'a> {
     pub class: JClass<'a>,
@@ -20,13 +20,14 @@ use std::{
 };
 
 use arrow::{
-    compute::SortOptions,
+    array::new_empty_array,
+    compute::{cast, SortOptions},
     datatypes::{Field, FieldRef, SchemaRef},
 };
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use chrono::DateTime;
 use datafusion::{
-    common::stats::Precision,
+    common::{stats::Precision, ScalarValue},
     datasource::{
         listing::{FileRange, PartitionedFile},
         object_store::ObjectStoreUrl,
@@ -47,17 +48,18 @@ use datafusion::{
         },
         joins::utils::{ColumnIndex, JoinFilter},
         union::UnionExec,
-        ColumnStatistics, ExecutionPlan, Partitioning, PhysicalExpr, Statistics,
+        ColumnStatistics, ExecutionPlan, PhysicalExpr, Statistics,
     },
 };
 use datafusion_ext_exprs::{
-    cast::TryCastExpr, get_indexed_field::GetIndexedFieldExpr, get_map_value::GetMapValueExpr,
+    bloom_filter_might_contain::BloomFilterMightContainExpr, cast::TryCastExpr,
+    get_indexed_field::GetIndexedFieldExpr, get_map_value::GetMapValueExpr,
     named_struct::NamedStructExpr, spark_scalar_subquery_wrapper::SparkScalarSubqueryWrapperExpr,
     spark_udf_wrapper::SparkUDFWrapperExpr, string_contains::StringContainsExpr,
     string_ends_with::StringEndsWithExpr, string_starts_with::StringStartsWithExpr,
 };
 use datafusion_ext_plans::{
-    agg::{create_agg, AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr},
+    agg::{create_sorted_agg, AggExecMode, AggExpr, AggFunction, AggMode, GroupingExpr},
     agg_exec::AggExec,
     broadcast_join_exec::BroadcastJoinExec,
     broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec,
@@ -76,10 +78,15 @@ use datafusion_ext_plans::{
     project_exec::ProjectExec,
     rename_columns_exec::RenameColumnsExec,
     rss_shuffle_writer_exec::RssShuffleWriterExec,
+    shuffle::{Partitioning, RangeBounds},
     shuffle_writer_exec::ShuffleWriterExec,
+    shuffled_hash_join_exec::ShuffledHashJoinExec,
     sort_exec::SortExec,
     sort_merge_join_exec::SortMergeJoinExec,
-    window::{WindowExpr, WindowFunction, WindowRankType},
+    window::{
+        window_frame::{WindowFrame, WindowFrameBound, WindowFrameUnits},
+        WindowExpr, WindowFunction, WindowOffsetType, WindowRankType,
+    },
     window_exec::WindowExec,
 };
 use object_store::{path::Path, ObjectMeta};
@@ -89,7 +96,8 @@ use crate::{
     error::PlanSerDeError,
     from_proto_binary_op, into_required, proto_error, protobuf,
     protobuf::{
-        physical_expr_node::ExprType, physical_plan_node::PhysicalPlanType, GenerateFunction,
+        physical_expr_node::ExprType, physical_plan_node::PhysicalPlanType,
+        physical_repartition::RepartitionType, GenerateFunction,
     },
     Schema,
 };
@@ -246,7 +254,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             PhysicalPlanType::ShuffleWriter(shuffle_writer) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_hash_partitioning(
+                let output_partitioning = parse_protobuf_partitioning(
                     input.clone(),
                     shuffle_writer.output_partitioning.as_ref(),
                 )?;
@@ -256,13 +264,15 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     output_partitioning.unwrap(),
                     shuffle_writer.output_data_file.clone(),
                     shuffle_writer.output_index_file.clone(),
+                    Some(shuffle_writer.output_checksum_file.clone()).filter(|f| !f.is_empty()),
+                    Some(shuffle_writer.output_split_index_file.clone()).filter(|f| !f.is_empty()),
                 )?))
             }
             PhysicalPlanType::RssShuffleWriter(rss_shuffle_writer) => {
                 let input: Arc<dyn ExecutionPlan> =
                     convert_box_required!(rss_shuffle_writer.input)?;
 
-                let output_partitioning = parse_protobuf_hash_partitioning(
+                let output_partitioning = parse_protobuf_partitioning(
                     input.clone(),
                     rss_shuffle_writer.output_partitioning.as_ref(),
                 )?;
@@ -386,12 +396,77 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     })
                     .map_or(Ok(None), |v: Result<_, PlanSerDeError>| v.map(Some))?;
 
+                let broadcast_side = protobuf::JoinSide::try_from(broadcast_join.broadcast_side)
+                    .expect("invalid JoinSide");
+                let cached_build_hash_map_id = Some(&broadcast_join.cached_build_hash_map_id)
+                    .filter(|id| !id.is_empty())
+                    .cloned();
+
                 Ok(Arc::new(BroadcastJoinExec::try_new(
                     left,
                     right,
                     on,
                     join_type.into(),
                     join_filter,
+                    broadcast_side.into(),
+                    cached_build_hash_map_id,
+                )?))
+            }
+            PhysicalPlanType::ShuffledHashJoin(shj) => {
+                let left: Arc<dyn ExecutionPlan> = convert_box_required!(shj.left)?;
+                let right: Arc<dyn ExecutionPlan> = convert_box_required!(shj.right)?;
+                let on: Vec<(Arc<dyn PhysicalExpr>, Arc<dyn PhysicalExpr>)> = shj
+                    .on
+                    .iter()
+                    .map(|col| {
+                        let left_col: Column = into_required!(col.left)?;
+                        let left_col_binded: Arc<dyn PhysicalExpr> =
+                            Arc::new(Column::new_with_schema(left_col.name(), &left.schema())?);
+                        let right_col: Column = into_required!(col.right)?;
+                        let right_col_binded: Arc<dyn PhysicalExpr> =
+                            Arc::new(Column::new_with_schema(right_col.name(), &right.schema())?);
+                        Ok((left_col_binded, right_col_binded))
+                    })
+                    .collect::<Result<_, Self::Error>>()?;
+
+                let join_type =
+                    protobuf::JoinType::try_from(shj.join_type).expect("invalid JoinType");
+                let join_filter = shj
+                    .join_filter
+                    .as_ref()
+                    .map(|f| {
+                        let schema = Arc::new(convert_required!(f.schema)?);
+                        let expression = try_parse_physical_expr_required(&f.expression, &schema)?;
+                        let column_indices = f
+                            .column_indices
+                            .iter()
+                            .map(|i| {
+                                let side =
+                                    protobuf::JoinSide::try_from(i.side).expect("invalid JoinSide");
+                                Ok(ColumnIndex {
+                                    index: i.index as usize,
+                                    side: side.into(),
+                                })
+                            })
+                            .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+
+                        Ok(JoinFilter::new(
+                            bind(expression, &schema)?,
+                            column_indices,
+                            schema.as_ref().clone(),
+                        ))
+                    })
+                    .map_or(Ok(None), |v: Result<_, PlanSerDeError>| v.map(Some))?;
+                let build_side =
+                    protobuf::JoinSide::try_from(shj.build_side).expect("invalid JoinSide");
+
+                Ok(Arc::new(ShuffledHashJoinExec::try_new(
+                    left,
+                    right,
+                    on,
+                    join_type.into(),
+                    join_filter,
+                    build_side.into(),
                 )?))
             }
             PhysicalPlanType::BroadcastNestedLoopJoin(bnlj) => {
@@ -521,11 +596,31 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                     .and_then(|expr| Ok(bind(expr, &input_schema)?))
                             })
                             .collect::<Result<Vec<_>, _>>()?;
+                        let agg_order_by = agg_node
+                            .order_by
+                            .iter()
+                            .map(|sort_expr| {
+                                let expr = sort_expr.expr.as_ref().ok_or_else(|| {
+                                    proto_error("Unexpected empty aggregate ordering expression")
+                                })?;
+                                Ok(PhysicalSortExpr {
+                                    expr: bind(
+                                        try_parse_physical_expr(expr, &input_schema)?,
+                                        &input_schema,
+                                    )?,
+                                    options: SortOptions {
+                                        descending: !sort_expr.asc,
+                                        nulls_first: sort_expr.nulls_first,
+                                    },
+                                })
+                            })
+                            .collect::<Result<Vec<_>, PlanSerDeError>>()?;
 
                         Ok(AggExpr {
-                            agg: create_agg(
+                            agg: create_sorted_agg(
                                 AggFunction::from(agg_function),
                                 &agg_children_exprs,
+                                agg_order_by,
                                 &input_schema,
                             )?,
                             mode,
@@ -621,6 +716,43 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::WindowFunction::DenseRank => {
                                     WindowFunction::RankLike(WindowRankType::DenseRank)
                                 }
+                                protobuf::WindowFunction::PercentRank => {
+                                    WindowFunction::RankLike(WindowRankType::PercentRank)
+                                }
+                                protobuf::WindowFunction::CumeDist => {
+                                    WindowFunction::RankLike(WindowRankType::CumeDist)
+                                }
+                                protobuf::WindowFunction::Ntile => {
+                                    WindowFunction::RankLike(WindowRankType::NTile(w.offset))
+                                }
+                                protobuf::WindowFunction::Lag => {
+                                    WindowFunction::Offset(WindowOffsetType::Lag {
+                                        offset: w.offset,
+                                        ignore_nulls: w.ignore_nulls,
+                                    })
+                                }
+                                protobuf::WindowFunction::Lead => {
+                                    WindowFunction::Offset(WindowOffsetType::Lead {
+                                        offset: w.offset,
+                                        ignore_nulls: w.ignore_nulls,
+                                    })
+                                }
+                                protobuf::WindowFunction::NthValue => {
+                                    WindowFunction::Offset(WindowOffsetType::NthValue {
+                                        nth: w.offset,
+                                        ignore_nulls: w.ignore_nulls,
+                                    })
+                                }
+                                protobuf::WindowFunction::FirstValue => {
+                                    WindowFunction::Offset(WindowOffsetType::FirstValue {
+                                        ignore_nulls: w.ignore_nulls,
+                                    })
+                                }
+                                protobuf::WindowFunction::LastValue => {
+                                    WindowFunction::Offset(WindowOffsetType::LastValue {
+                                        ignore_nulls: w.ignore_nulls,
+                                    })
+                                }
                             },
                             protobuf::WindowFunctionType::Agg => match w.agg_func() {
                                 protobuf::AggFunction::Min => WindowFunction::Agg(AggFunction::Min),
@@ -642,9 +774,73 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::AggFunction::FirstIgnoresNull => {
                                     WindowFunction::Agg(AggFunction::FirstIgnoresNull)
                                 }
+                                protobuf::AggFunction::StddevSamp => {
+                                    WindowFunction::Agg(AggFunction::StddevSamp)
+                                }
+                                protobuf::AggFunction::StddevPop => {
+                                    WindowFunction::Agg(AggFunction::StddevPop)
+                                }
+                                protobuf::AggFunction::VarSamp => {
+                                    WindowFunction::Agg(AggFunction::VarSamp)
+                                }
+                                protobuf::AggFunction::VarPop => {
+                                    WindowFunction::Agg(AggFunction::VarPop)
+                                }
+                                protobuf::AggFunction::CovarSamp => {
+                                    WindowFunction::Agg(AggFunction::CovarSamp)
+                                }
+                                protobuf::AggFunction::CovarPop => {
+                                    WindowFunction::Agg(AggFunction::CovarPop)
+                                }
+                                protobuf::AggFunction::Corr => {
+                                    WindowFunction::Agg(AggFunction::Corr)
+                                }
+                                protobuf::AggFunction::ApproxCountDistinct => {
+                                    WindowFunction::Agg(AggFunction::ApproxCountDistinct)
+                                }
+                                protobuf::AggFunction::ApproxPercentile => {
+                                    WindowFunction::Agg(AggFunction::ApproxPercentile)
+                                }
+                                protobuf::AggFunction::BloomFilter => {
+                                    WindowFunction::Agg(AggFunction::BloomFilter)
+                                }
+                                protobuf::AggFunction::CountDistinct => {
+                                    WindowFunction::Agg(AggFunction::CountDistinct)
+                                }
+                                protobuf::AggFunction::SumDistinct => {
+                                    WindowFunction::Agg(AggFunction::SumDistinct)
+                                }
+                                protobuf::AggFunction::AvgDistinct => {
+                                    WindowFunction::Agg(AggFunction::AvgDistinct)
+                                }
+                                protobuf::AggFunction::BitAnd => {
+                                    WindowFunction::Agg(AggFunction::BitAnd)
+                                }
+                                protobuf::AggFunction::BitOr => {
+                                    WindowFunction::Agg(AggFunction::BitOr)
+                                }
+                                protobuf::AggFunction::BitXor => {
+                                    WindowFunction::Agg(AggFunction::BitXor)
+                                }
+                                protobuf::AggFunction::MaxBy => {
+                                    WindowFunction::Agg(AggFunction::MaxBy)
+                                }
+                                protobuf::AggFunction::MinBy => {
+                                    WindowFunction::Agg(AggFunction::MinBy)
+                                }
+                                protobuf::AggFunction::Last => {
+                                    WindowFunction::Agg(AggFunction::Last)
+                                }
+                                protobuf::AggFunction::LastIgnoresNull => {
+                                    WindowFunction::Agg(AggFunction::LastIgnoresNull)
+                                }
                             },
                         };
-                        Ok::<_, Self::Error>(WindowExpr::new(window_func, children, field))
+                        let frame = match &w.frame {
+                            Some(frame) => frame.try_into()?,
+                            None => WindowFrame::default(),
+                        };
+                        Ok::<_, Self::Error>(WindowExpr::new(window_func, children, field, frame))
                     })
                     .collect::<Result<Vec<_>, _>>()?;
 
@@ -997,6 +1193,15 @@ fn try_parse_physical_expr(
                 let key = convert_required!(e.key)?;
                 Arc::new(GetMapValueExpr::new(expr, key))
             }
+            ExprType::BloomFilterMightContainExpr(e) => {
+                let bloom_filter_expr =
+                    try_parse_physical_expr_box_required(&e.bloom_filter_expr, input_schema)?;
+                let value_expr = try_parse_physical_expr_box_required(&e.value_expr, input_schema)?;
+                Arc::new(BloomFilterMightContainExpr::new(
+                    bloom_filter_expr,
+                    value_expr,
+                ))
+            }
             ExprType::StringStartsWithExpr(e) => {
                 let expr = try_parse_physical_expr_box_required(&e.expr, input_schema)?;
                 Arc::new(StringStartsWithExpr::new(expr, e.prefix.clone()))
@@ -1063,27 +1268,127 @@ fn try_parse_physical_expr_box_required(
     }
 }
 
-pub fn parse_protobuf_hash_partitioning(
+pub fn parse_protobuf_partitioning(
     input: Arc<dyn ExecutionPlan>,
-    partitioning: Option<&protobuf::PhysicalHashRepartition>,
+    partitioning: Option<&protobuf::PhysicalRepartition>,
 ) -> Result<Option<Partitioning>, PlanSerDeError> {
-    match partitioning {
-        Some(hash_part) => {
+    let Some(partitioning) = partitioning else {
+        return Ok(None);
+    };
+    let input_schema = input.schema();
+    let repartition_type = partitioning
+        .repartition_type
+        .as_ref()
+        .ok_or_else(|| proto_error("Missing required field in protobuf: repartition_type"))?;
+
+    Ok(Some(match repartition_type {
+        RepartitionType::SingleRepartition(_) => Partitioning::SinglePartitioning,
+        RepartitionType::HashRepartition(hash_part) => {
             let expr = hash_part
                 .hash_expr
                 .iter()
                 .map(|e| {
-                    try_parse_physical_expr(e, &input.schema())
-                        .and_then(|e| Ok(bind(e, &input.schema())?))
+                    try_parse_physical_expr(e, &input_schema)
+                        .and_then(|e| Ok(bind(e, &input_schema)?))
                 })
                 .collect::<Result<Vec<Arc<dyn PhysicalExpr>>, _>>()?;
+            Partitioning::HashPartitioning(expr, hash_part.partition_count.try_into().unwrap())
+        }
+        RepartitionType::RoundRobinRepartition(round_robin_part) => {
+            Partitioning::RoundRobinPartitioning(
+                round_robin_part.partition_count.try_into().unwrap(),
+            )
+        }
+        RepartitionType::RangeRepartition(range_part) => {
+            let sort_exprs = range_part
+                .sort_expr
+                .iter()
+                .map(|sort_expr| {
+                    let expr = sort_expr.expr.as_ref().ok_or_else(|| {
+                        proto_error("Unexpected empty range partitioning expression")
+                    })?;
+                    Ok(PhysicalSortExpr {
+                        expr: bind(try_parse_physical_expr(expr, &input_schema)?, &input_schema)?,
+                        options: SortOptions {
+                            descending: !sort_expr.asc,
+                            nulls_first: sort_expr.nulls_first,
+                        },
+                    })
+                })
+                .collect::<Result<Vec<_>, PlanSerDeError>>()?;
 
-            Ok(Some(Partitioning::Hash(
-                expr,
-                hash_part.partition_count.try_into().unwrap(),
-            )))
+            // bounds are transposed into one array for each sort expr, casted to
+            // the key type in case of literal types not exactly matching
+            let bounds = sort_exprs
+                .iter()
+                .enumerate()
+                .map(|(i, sort_expr)| {
+                    let values = range_part
+                        .bound
+                        .iter()
+                        .map(|bound| {
+                            bound
+                                .value
+                                .get(i)
+                                .ok_or_else(|| proto_error("Range bound has too few values"))?
+                                .try_into()
+                        })
+                        .collect::<Result<Vec<ScalarValue>, PlanSerDeError>>()?;
+                    let data_type = sort_expr.expr.data_type(&input_schema)?;
+                    let array = if values.is_empty() {
+                        new_empty_array(&data_type)
+                    } else {
+                        ScalarValue::iter_to_array(values)?
+                    };
+                    Ok(cast(&array, &data_type)?)
+                })
+                .collect::<Result<Vec<_>, PlanSerDeError>>()?;
+
+            let bounds = Arc::new(RangeBounds::try_new(&sort_exprs, &bounds)?);
+            Partitioning::RangePartitioning(
+                sort_exprs,
+                range_part.partition_count.try_into().unwrap(),
+                bounds,
+            )
         }
-        None => Ok(None),
+    }))
+}
+
+impl TryFrom<&protobuf::WindowFrame> for WindowFrame {
+    type Error = PlanSerDeError;
+
+    fn try_from(frame: &protobuf::WindowFrame) -> Result<Self, Self::Error> {
+        let units = match frame.units() {
+            protobuf::WindowFrameUnits::Rows => WindowFrameUnits::Rows,
+            protobuf::WindowFrameUnits::Range => WindowFrameUnits::Range,
+        };
+        Ok(WindowFrame::try_new(
+            units,
+            convert_required!(frame.start)?,
+            convert_required!(frame.end)?,
+        )?)
+    }
+}
+
+impl TryFrom<&protobuf::WindowFrameBound> for WindowFrameBound {
+    type Error = PlanSerDeError;
+
+    fn try_from(bound: &protobuf::WindowFrameBound) -> Result<Self, Self::Error> {
+        Ok(match bound.bound_type() {
+            protobuf::WindowFrameBoundType::UnboundedPreceding => {
+                WindowFrameBound::UnboundedPreceding
+            }
+            protobuf::WindowFrameBoundType::Preceding => {
+                WindowFrameBound::Preceding(convert_required!(bound.offset)?)
+            }
+            protobuf::WindowFrameBoundType::CurrentRow => WindowFrameBound::CurrentRow,
+            protobuf::WindowFrameBoundType::Following => {
+                WindowFrameBound::Following(convert_required!(bound.offset)?)
+            }
+            protobuf::WindowFrameBoundType::UnboundedFollowing => {
+                WindowFrameBound::UnboundedFollowing
+            }
+        })
     }
 }
 
@@ -1224,3 +1529,83 @@ impl TryInto<FileScanConfig> for &protobuf::FileScanExecConf {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use datafusion::physical_plan::displayable;
+
+    use super::*;
+    use crate::protobuf::{
+        arrow_type::ArrowTypeEnum, AggExecNode, ArrowType, EmptyMessage, EmptyPartitionsExecNode,
+        Field, PhysicalAggExprNode, PhysicalColumn, PhysicalExprNode, PhysicalPlanNode,
+        PhysicalSortExprNode,
+    };
+
+    #[test]
+    fn test_agg_expr_with_order_by() -> Result<(), PlanSerDeError> {
+        let field = |name: &str, arrow_type_enum: ArrowTypeEnum| Field {
+            name: name.to_string(),
+            arrow_type: Some(ArrowType {
+                arrow_type_enum: Some(arrow_type_enum),
+            }),
+            nullable: true,
+            children: vec![],
+        };
+        let column = |name: &str, index: u32| PhysicalExprNode {
+            expr_type: Some(ExprType::Column(PhysicalColumn {
+                name: name.to_string(),
+                index,
+            })),
+        };
+
+        // collect_list(v) with within-group ordering by k desc, as converted from spark
+        let input = PhysicalPlanNode {
+            physical_plan_type: Some(PhysicalPlanType::EmptyPartitions(EmptyPartitionsExecNode {
+                schema: Some(protobuf::Schema {
+                    columns: vec![
+                        field("v", ArrowTypeEnum::Utf8(EmptyMessage {})),
+                        field("k", ArrowTypeEnum::Int32(EmptyMessage {})),
+                    ],
+                }),
+                num_partitions: 1,
+            })),
+        };
+        let agg_expr = PhysicalExprNode {
+            expr_type: Some(ExprType::AggExpr(
+                PhysicalAggExprNode {
+                    agg_function: protobuf::AggFunction::CollectList as i32,
+                    children: vec![column("v", 0)],
+                    order_by: vec![PhysicalSortExprNode {
+                        expr: Some(column("k", 1).into()),
+                        asc: false,
+                        nulls_first: false,
+                    }],
+                }
+                .into(),
+            )),
+        };
+        let plan_node = PhysicalPlanNode {
+            physical_plan_type: Some(PhysicalPlanType::Agg(
+                AggExecNode {
+                    input: Some(input.into()),
+                    exec_mode: protobuf::AggExecMode::HashAgg as i32,
+                    grouping_expr: vec![],
+                    agg_expr: vec![agg_expr],
+                    mode: vec![protobuf::AggMode::Partial as i32],
+                    grouping_expr_name: vec![],
+                    agg_expr_name: vec!["collected".to_string()],
+                    initial_input_buffer_offset: 0,
+                    supports_partial_skipping: false,
+                }
+                .into(),
+            )),
+        };
+
+        let plan: Arc<dyn ExecutionPlan> = (&plan_node).try_into()?;
+        let displayed = displayable(plan.as_ref()).one_line().to_string();
+        assert!(displayed.contains("CollectList("), "{displayed}");
+        assert!(displayed.contains("ORDER BY"), "{displayed}");
+        assert!(displayed.contains("descending: true"), "{displayed}");
+        Ok(())
+    }
+}
@@ -15,10 +15,8 @@
 use std::sync::Arc;
 
 use arrow::datatypes::{DataType, Field, Fields, IntervalUnit, Schema, TimeUnit};
-use datafusion::{
-    common::JoinSide, logical_expr::Operator, prelude::JoinType, scalar::ScalarValue,
-};
-use datafusion_ext_plans::agg::AggFunction;
+use datafusion::{common::JoinSide, logical_expr::Operator, scalar::ScalarValue};
+use datafusion_ext_plans::{agg::AggFunction, joins::JoinType};
 
 use crate::error::PlanSerDeError;
 
@@ -111,6 +109,8 @@ impl From<protobuf::JoinType> for JoinType {
             protobuf::JoinType::Full => JoinType::Full,
             protobuf::JoinType::Semi => JoinType::LeftSemi,
             protobuf::JoinType::Anti => JoinType::LeftAnti,
+            protobuf::JoinType::Existence => JoinType::Existence,
+            protobuf::JoinType::NullAwareAnti => JoinType::NullAwareAnti,
         }
     }
 }
@@ -136,6 +136,26 @@ impl From<protobuf::AggFunction> for AggFunction {
             protobuf::AggFunction::CollectSet => AggFunction::CollectSet,
             protobuf::AggFunction::First => AggFunction::First,
             protobuf::AggFunction::FirstIgnoresNull => AggFunction::FirstIgnoresNull,
+            protobuf::AggFunction::StddevSamp => AggFunction::StddevSamp,
+            protobuf::AggFunction::StddevPop => AggFunction::StddevPop,
+            protobuf::AggFunction::VarSamp => AggFunction::VarSamp,
+            protobuf::AggFunction::VarPop => AggFunction::VarPop,
+            protobuf::AggFunction::CovarSamp => AggFunction::CovarSamp,
+            protobuf::AggFunction::CovarPop => AggFunction::CovarPop,
+            protobuf::AggFunction::Corr => AggFunction::Corr,
+            protobuf::AggFunction::ApproxCountDistinct => AggFunction::ApproxCountDistinct,
+            protobuf::AggFunction::ApproxPercentile => AggFunction::ApproxPercentile,
+            protobuf::AggFunction::BloomFilter => AggFunction::BloomFilter,
+            protobuf::AggFunction::CountDistinct => AggFunction::CountDistinct,
+            protobuf::AggFunction::SumDistinct => AggFunction::SumDistinct,
+            protobuf::AggFunction::AvgDistinct => AggFunction::AvgDistinct,
+            protobuf::AggFunction::BitAnd => AggFunction::BitAnd,
+            protobuf::AggFunction::BitOr => AggFunction::BitOr,
+            protobuf::AggFunction::BitXor => AggFunction::BitXor,
+            protobuf::AggFunction::MaxBy => AggFunction::MaxBy,
+            protobuf::AggFunction::MinBy => AggFunction::MinBy,
+            protobuf::AggFunction::Last => AggFunction::Last,
+            protobuf::AggFunction::LastIgnoresNull => AggFunction::LastIgnoresNull,
         }
     }
 }
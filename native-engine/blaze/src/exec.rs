@@ -31,9 +31,11 @@ use datafusion::{
     prelude::{SessionConfig, SessionContext},
 };
 use datafusion_ext_commons::df_execution_err;
-use datafusion_ext_plans::memmgr::MemManager;
+use datafusion_ext_plans::{
+    joins::join_hash_map::release_cached_join_hash_map, memmgr::MemManager,
+};
 use jni::{
-    objects::{JClass, JObject},
+    objects::{JClass, JObject, JString},
     JNIEnv,
 };
 use once_cell::sync::OnceCell;
@@ -140,3 +142,20 @@ pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_finalizeNative(
     let runtime = unsafe { Box::from_raw(raw_ptr as usize as *mut NativeExecutionRuntime) };
     runtime.finalize();
 }
+
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "system" fn Java_org_apache_spark_sql_blaze_JniBridge_releaseCachedJoinHashMap(
+    env: JNIEnv,
+    _: JClass,
+    cached_id: JString,
+) {
+    handle_unwinded_scope(|| -> Result<()> {
+        let cached_id: String = env
+            .get_string(cached_id)
+            .or_else(|err| df_execution_err!("cannot get cached id: {err:?}"))?
+            .into();
+        release_cached_join_hash_map(&cached_id);
+        Ok(())
+    })
+}
@@ -27,6 +27,8 @@ pub struct RadixTournamentTree<T> {
     cur_rdx: usize,
     values: UncheckedIndex<Vec<T>>,
     entries: UncheckedIndex<Vec<usize>>,
+    tails: UncheckedIndex<Vec<usize>>,
+    unsorted: UncheckedIndex<Vec<bool>>,
     node_nexts: UncheckedIndex<Vec<usize>>,
 }
 
@@ -43,6 +45,8 @@ impl<T: KeyForRadixTournamentTree> RadixTournamentTree<T> {
                 cur_rdx: 0,
                 values: unchecked_index::unchecked_index(values),
                 entries: unchecked_index::unchecked_index(vec![usize::MAX; num_keys]),
+                tails: unchecked_index::unchecked_index(vec![usize::MAX; num_keys]),
+                unsorted: unchecked_index::unchecked_index(vec![false; num_keys]),
                 node_nexts: unchecked_index::unchecked_index(vec![usize::MAX; num_values]),
             }
         };
@@ -75,9 +79,14 @@ impl<T: KeyForRadixTournamentTree> RadixTournamentTree<T> {
 
     fn init_tree(&mut self) {
         let mut min_rdx = usize::MAX;
-        for (i, v) in self.values.iter().enumerate() {
+        // insert in reversed order, so values of the same bucket are linked in
+        // index order
+        for (i, v) in self.values.iter().enumerate().rev() {
             let rdx = v.rdx();
             if rdx < self.num_keys {
+                if self.entries[rdx] == usize::MAX {
+                    self.tails[rdx] = i;
+                }
                 self.node_nexts[i] = self.entries[rdx];
                 self.entries[rdx] = i;
             }
@@ -96,11 +105,23 @@ impl<T: KeyForRadixTournamentTree> RadixTournamentTree<T> {
             if new_rdx > old_rdx {
                 // unlink from old bucket
                 self.entries[old_rdx] = self.node_nexts[i];
+                if self.entries[old_rdx] == usize::MAX {
+                    self.tails[old_rdx] = usize::MAX;
+                }
 
-                // link to new bucket
+                // append to the tail of new bucket. values of the same bucket
+                // are sorted by index when the bucket is reached, so equal keys
+                // are always popped in a stable order
                 if new_rdx < self.num_keys {
-                    self.node_nexts[i] = self.entries[new_rdx];
-                    self.entries[new_rdx] = i;
+                    let tail = self.tails[new_rdx];
+                    self.node_nexts[i] = usize::MAX;
+                    if tail == usize::MAX {
+                        self.entries[new_rdx] = i;
+                    } else {
+                        self.node_nexts[tail] = i;
+                        self.unsorted[new_rdx] |= tail > i;
+                    }
+                    self.tails[new_rdx] = i;
                 }
 
                 // forward cur_rdx if current bucket is exhausted
@@ -108,10 +129,31 @@ impl<T: KeyForRadixTournamentTree> RadixTournamentTree<T> {
                 while next_rdx < self.num_keys && self.entries[next_rdx] == usize::MAX {
                     next_rdx += 1;
                 }
+                if next_rdx < self.num_keys && self.unsorted[next_rdx] {
+                    self.sort_bucket(next_rdx);
+                }
                 self.cur_rdx = next_rdx;
             }
         }
     }
+
+    fn sort_bucket(&mut self, rdx: usize) {
+        let mut indices = vec![];
+        let mut next = self.entries[rdx];
+        while next != usize::MAX {
+            indices.push(next);
+            next = self.node_nexts[next];
+        }
+        indices.sort_unstable();
+
+        for (&i, &next) in indices.iter().zip(indices.iter().skip(1)) {
+            self.node_nexts[i] = next;
+        }
+        self.node_nexts[indices[indices.len() - 1]] = usize::MAX;
+        self.entries[rdx] = indices[0];
+        self.tails[rdx] = indices[indices.len() - 1];
+        self.unsorted[rdx] = false;
+    }
 }
 
 /// A PeekMut structure to the loser tree, used to get smallest value and auto
@@ -216,4 +258,59 @@ mod test {
             assert_eq!(actual, expected);
         }
     }
+
+    #[test]
+    fn test_stable() {
+        struct Cursor {
+            row_idx: usize,
+            values: Vec<u64>,
+        }
+        impl KeyForRadixTournamentTree for Cursor {
+            fn rdx(&self) -> usize {
+                self.values.get(self.row_idx).cloned().unwrap_or(u64::MAX) as usize
+            }
+        }
+
+        let nodes = vec![vec![1, 3, 5], vec![1, 2, 5], vec![0, 3, 5], vec![2, 3, 4]];
+        let mut loser_tree = RadixTournamentTree::new(
+            nodes
+                .into_iter()
+                .map(|values| Cursor { row_idx: 0, values })
+                .collect_vec(),
+            10,
+        );
+
+        // values of equal keys are popped in index order
+        let mut actual = vec![];
+        loop {
+            let idx = loser_tree
+                .values()
+                .iter()
+                .position(|c| std::ptr::eq(c, &*loser_tree.peek()));
+            let mut min = loser_tree.peek_mut();
+            if let Some(v) = min.values.get(min.row_idx) {
+                actual.push((*v, idx.unwrap()));
+                min.row_idx += 1;
+            } else {
+                break;
+            }
+        }
+        assert_eq!(
+            actual,
+            vec![
+                (0, 2),
+                (1, 0),
+                (1, 1),
+                (2, 1),
+                (2, 3),
+                (3, 0),
+                (3, 2),
+                (3, 3),
+                (4, 3),
+                (5, 0),
+                (5, 1),
+                (5, 2),
+            ]
+        );
+    }
 }
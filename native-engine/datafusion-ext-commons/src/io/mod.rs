@@ -21,7 +21,7 @@ use arrow::{
 };
 pub use batch_serde::{read_array, read_data_type, write_array, write_data_type};
 use datafusion::common::{cast::as_struct_array, Result};
-pub use scalar_serde::{read_scalar, write_scalar};
+pub use scalar_serde::{read_scalar, write_array_value, write_scalar};
 
 mod batch_serde;
 mod scalar_serde;
@@ -18,7 +18,7 @@ use std::{
 };
 
 use arrow::{
-    array::{AsArray, StructArray},
+    array::{Array, AsArray, StructArray},
     datatypes::*,
 };
 use datafusion::{common::Result, parquet::data_type::AsBytes, scalar::ScalarValue};
@@ -98,6 +98,88 @@ pub fn write_scalar<W: Write>(value: &ScalarValue, nullable: bool, output: &mut
     Ok(())
 }
 
+/// writes a value of an array in the same format as write_scalar(), without
+/// creating an intermediate ScalarValue
+pub fn write_array_value<W: Write>(
+    array: &dyn Array,
+    row_idx: usize,
+    nullable: bool,
+    output: &mut W,
+) -> Result<()> {
+    assert!(nullable || array.is_valid(row_idx));
+
+    macro_rules! write_prim {
+        ($v:expr) => {{
+            if nullable {
+                if array.is_valid(row_idx) {
+                    write_u8(1, output)?;
+                    output.write_all(&$v.to_ne_bytes())?;
+                } else {
+                    write_u8(0, output)?;
+                }
+            } else {
+                output.write_all(&$v.to_ne_bytes())?;
+            }
+        }};
+    }
+    macro_rules! write_bytes {
+        ($v:expr) => {{
+            if array.is_valid(row_idx) {
+                let v: &[u8] = $v;
+                write_len(v.len() + 1, output)?;
+                output.write_all(v)?;
+            } else {
+                write_len(0, output)?;
+            }
+        }};
+    }
+
+    match array.data_type() {
+        DataType::Boolean => write_prim!((array.as_boolean().value(row_idx) as i8)),
+        DataType::Int8 => write_prim!(array.as_primitive::<Int8Type>().value(row_idx)),
+        DataType::Int16 => write_prim!(array.as_primitive::<Int16Type>().value(row_idx)),
+        DataType::Int32 => write_prim!(array.as_primitive::<Int32Type>().value(row_idx)),
+        DataType::Int64 => write_prim!(array.as_primitive::<Int64Type>().value(row_idx)),
+        DataType::UInt8 => write_prim!(array.as_primitive::<UInt8Type>().value(row_idx)),
+        DataType::UInt16 => write_prim!(array.as_primitive::<UInt16Type>().value(row_idx)),
+        DataType::UInt32 => write_prim!(array.as_primitive::<UInt32Type>().value(row_idx)),
+        DataType::UInt64 => write_prim!(array.as_primitive::<UInt64Type>().value(row_idx)),
+        DataType::Float32 => write_prim!(array.as_primitive::<Float32Type>().value(row_idx)),
+        DataType::Float64 => write_prim!(array.as_primitive::<Float64Type>().value(row_idx)),
+        DataType::Decimal128(..) => {
+            write_prim!(array.as_primitive::<Decimal128Type>().value(row_idx))
+        }
+        DataType::Date32 => write_prim!(array.as_primitive::<Date32Type>().value(row_idx)),
+        DataType::Date64 => write_prim!(array.as_primitive::<Date64Type>().value(row_idx)),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            write_prim!(array.as_primitive::<TimestampSecondType>().value(row_idx))
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            write_prim!(array
+                .as_primitive::<TimestampMillisecondType>()
+                .value(row_idx))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            write_prim!(array
+                .as_primitive::<TimestampMicrosecondType>()
+                .value(row_idx))
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            write_prim!(array
+                .as_primitive::<TimestampNanosecondType>()
+                .value(row_idx))
+        }
+        DataType::Utf8 => write_bytes!(array.as_string::<i32>().value(row_idx).as_bytes()),
+        DataType::Binary => write_bytes!(array.as_binary::<i32>().value(row_idx)),
+        _ => write_scalar(
+            &ScalarValue::try_from_array(array, row_idx)?,
+            nullable,
+            output,
+        )?,
+    }
+    Ok(())
+}
+
 pub fn read_scalar<R: Read>(
     input: &mut R,
     data_type: &DataType,
@@ -196,12 +278,13 @@ pub fn read_scalar<R: Read>(
 
 #[cfg(test)]
 mod test {
-    use std::io::Cursor;
+    use std::{io::Cursor, sync::Arc};
 
+    use arrow::array::*;
     use arrow_schema::DataType;
     use datafusion::common::{Result, ScalarValue};
 
-    use crate::io::{read_scalar, write_scalar};
+    use crate::io::{read_scalar, write_array_value, write_scalar};
 
     #[test]
     fn test() -> Result<()> {
@@ -241,4 +324,46 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_write_array_value() -> Result<()> {
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(BooleanArray::from(vec![Some(true), None, Some(false)])),
+            Arc::new(Int32Array::from(vec![Some(1), None, Some(-3)])),
+            Arc::new(Float64Array::from(vec![Some(-0.0), None, Some(f64::NAN)])),
+            Arc::new(
+                Decimal128Array::from(vec![Some(i128::MAX), None, Some(-1)])
+                    .with_precision_and_scale(38, 10)?,
+            ),
+            Arc::new(TimestampMicrosecondArray::from(vec![
+                Some(1),
+                None,
+                Some(2),
+            ])),
+            Arc::new(StringArray::from(vec![Some("Wooden"), None, Some("")])),
+            Arc::new(BinaryArray::from(vec![
+                Some(&b"Slash"[..]),
+                None,
+                Some(&b""[..]),
+            ])),
+        ];
+
+        // values are written the same as write_scalar()
+        for array in &arrays {
+            for row_idx in 0..array.len() {
+                let scalar = ScalarValue::try_from_array(array, row_idx)?;
+                for nullable in [true, false] {
+                    if !nullable && scalar.is_null() {
+                        continue;
+                    }
+                    let mut expected = vec![];
+                    let mut buf = vec![];
+                    write_scalar(&scalar, nullable, &mut expected)?;
+                    write_array_value(array, row_idx, nullable, &mut buf)?;
+                    assert_eq!(buf, expected, "{scalar:?}");
+                }
+            }
+        }
+        Ok(())
+    }
 }
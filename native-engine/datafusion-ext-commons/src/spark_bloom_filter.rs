@@ -0,0 +1,181 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! spark compatible bloom filter, operating directly on the serialized form
+//! of spark's `BloomFilterImpl`:
+//!   version: i32, num_hash_functions: i32, num_words: i32, words: i64 * n
+//! all in big-endian.
+
+use datafusion::common::Result;
+
+use crate::{df_execution_err, spark_hash::spark_compatible_murmur3_hash};
+
+const VERSION_V1: i32 = 1;
+const HEADER_LEN: usize = 12;
+
+/// creates an empty serialized bloom filter, same as spark's
+/// `BloomFilter.create(expectedNumItems, numBits)`
+pub fn create(expected_num_items: i64, num_bits: i64) -> Result<Vec<u8>> {
+    if expected_num_items <= 0 || num_bits <= 0 {
+        return df_execution_err!(
+            "bloom filter: expected_num_items and num_bits must be positive, \
+             got {expected_num_items} and {num_bits}"
+        );
+    }
+    let num_hash_functions = optimal_num_of_hash_functions(expected_num_items, num_bits);
+    let num_words = (num_bits as f64 / 64.0).ceil() as usize;
+    if num_words > i32::MAX as usize {
+        return df_execution_err!("bloom filter: cannot allocate enough space for {num_bits} bits");
+    }
+
+    let mut bytes = vec![0u8; HEADER_LEN + num_words * 8];
+    bytes[0..4].copy_from_slice(&VERSION_V1.to_be_bytes());
+    bytes[4..8].copy_from_slice(&num_hash_functions.to_be_bytes());
+    bytes[8..12].copy_from_slice(&(num_words as i32).to_be_bytes());
+    Ok(bytes)
+}
+
+/// checks the serialized bloom filter is valid
+pub fn validate(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < HEADER_LEN {
+        return df_execution_err!("bloom filter: invalid length: {}", bytes.len());
+    }
+    let version = read_i32(bytes, 0);
+    if version != VERSION_V1 {
+        return df_execution_err!("bloom filter: unexpected version: {version}");
+    }
+    let num_words = read_i32(bytes, 8);
+    if num_words <= 0 || bytes.len() != HEADER_LEN + num_words as usize * 8 {
+        return df_execution_err!("bloom filter: invalid number of words: {num_words}");
+    }
+    Ok(())
+}
+
+pub fn put_long(bytes: &mut [u8], item: i64) {
+    let bit_size = bit_size(bytes);
+    for index in bit_indices(bytes, item) {
+        let index = index % bit_size;
+        let offset = HEADER_LEN + (index >> 6) as usize * 8;
+        let word = read_u64(bytes, offset) | (1 << (index & 63));
+        bytes[offset..][..8].copy_from_slice(&word.to_be_bytes());
+    }
+}
+
+pub fn might_contain_long(bytes: &[u8], item: i64) -> bool {
+    let bit_size = bit_size(bytes);
+    bit_indices(bytes, item).all(|index| {
+        let index = index % bit_size;
+        let offset = HEADER_LEN + (index >> 6) as usize * 8;
+        read_u64(bytes, offset) & (1 << (index & 63)) != 0
+    })
+}
+
+/// merges other bloom filter into this one, same as spark's
+/// `BloomFilter.mergeInPlace()`
+pub fn merge(bytes: &mut [u8], other: &[u8]) -> Result<()> {
+    if bytes.len() != other.len() || read_i32(bytes, 4) != read_i32(other, 4) {
+        return df_execution_err!("bloom filter: cannot merge incompatible bloom filters");
+    }
+    for offset in (HEADER_LEN..bytes.len()).step_by(8) {
+        let word = read_u64(bytes, offset) | read_u64(other, offset);
+        bytes[offset..][..8].copy_from_slice(&word.to_be_bytes());
+    }
+    Ok(())
+}
+
+/// number of set bits
+pub fn cardinality(bytes: &[u8]) -> usize {
+    (HEADER_LEN..bytes.len())
+        .step_by(8)
+        .map(|offset| read_u64(bytes, offset).count_ones() as usize)
+        .sum()
+}
+
+fn optimal_num_of_hash_functions(n: i64, m: i64) -> i32 {
+    // same as java's Math.round()
+    ((m as f64 / n as f64 * 2f64.ln() + 0.5).floor() as i32).max(1)
+}
+
+fn bit_size(bytes: &[u8]) -> i64 {
+    read_i32(bytes, 8) as i64 * 64
+}
+
+fn bit_indices(bytes: &[u8], item: i64) -> impl Iterator<Item = i64> {
+    let num_hash_functions = read_i32(bytes, 4);
+    let h1 = spark_compatible_murmur3_hash(item.to_le_bytes(), 0) as i32;
+    let h2 = spark_compatible_murmur3_hash(item.to_le_bytes(), h1 as u32) as i32;
+    (1..=num_hash_functions).map(move |i| {
+        let combined_hash = h1.wrapping_add(i.wrapping_mul(h2));
+        // flip all the bits if it's negative (guaranteed positive number)
+        if combined_hash < 0 {
+            !combined_hash as i64
+        } else {
+            combined_hash as i64
+        }
+    })
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+    i32::from_be_bytes(bytes[offset..][..4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(bytes[offset..][..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() -> Result<()> {
+        let mut bloom_filter = create(1000, 8192)?;
+        validate(&bloom_filter)?;
+        assert_eq!(read_i32(&bloom_filter, 4), 6); // round(8192 / 1000 * ln(2))
+        assert_eq!(bloom_filter.len(), HEADER_LEN + 8192 / 8);
+        assert_eq!(cardinality(&bloom_filter), 0);
+
+        for i in (0..2000).step_by(2) {
+            put_long(&mut bloom_filter, i);
+        }
+        assert!(cardinality(&bloom_filter) > 0);
+        assert!((0..2000)
+            .step_by(2)
+            .all(|i| might_contain_long(&bloom_filter, i)));
+
+        let false_positives = (1..2000)
+            .step_by(2)
+            .filter(|&i| might_contain_long(&bloom_filter, i))
+            .count();
+        assert!(false_positives < 50, "false positives: {false_positives}");
+
+        // merged bloom filter contains items of both sides
+        let mut other = create(1000, 8192)?;
+        put_long(&mut other, 1);
+        merge(&mut bloom_filter, &other)?;
+        assert!(might_contain_long(&bloom_filter, 1));
+        assert!(merge(&mut bloom_filter, &create(1000, 4096)?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bloom_filter_invalid() {
+        let mut bytes = vec![0u8; HEADER_LEN];
+        bytes[0..4].copy_from_slice(&VERSION_V1.to_be_bytes());
+        bytes[4..8].copy_from_slice(&1i32.to_be_bytes());
+        assert!(validate(&bytes).is_err()); // zero words
+        assert!(validate(&bytes[..8]).is_err());
+        assert!(create(1000, 0).is_err());
+    }
+}
@@ -28,7 +28,7 @@ use datafusion::error::Result;
 use crate::df_execution_err;
 
 #[inline]
-fn spark_compatible_murmur3_hash<T: AsRef<[u8]>>(data: T, seed: u32) -> u32 {
+pub fn spark_compatible_murmur3_hash<T: AsRef<[u8]>>(data: T, seed: u32) -> u32 {
     #[inline]
     fn mix_k1(mut k1: i32) -> i32 {
         k1 *= 0xcc9e2d51u32 as i32;
@@ -402,6 +402,203 @@ fn hash_one(col: &ArrayRef, idx: usize, hash: &mut u32) -> Result<()> {
     Ok(())
 }
 
+#[inline]
+pub fn spark_compatible_xxhash64<T: AsRef<[u8]>>(data: T, seed: u64) -> u64 {
+    const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+    const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+    const PRIME64_3: u64 = 0x165667B19E3779F9;
+    const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+    const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+    #[inline]
+    fn round(acc: u64, input: u64) -> u64 {
+        acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+            .rotate_left(31)
+            .wrapping_mul(PRIME64_1)
+    }
+
+    #[inline]
+    fn merge_round(hash: u64, acc: u64) -> u64 {
+        (hash ^ round(0, acc))
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(PRIME64_4)
+    }
+
+    #[inline]
+    fn read_u64(data: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(data[offset..][..8].try_into().unwrap())
+    }
+
+    #[inline]
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..][..4].try_into().unwrap())
+    }
+
+    let data = data.as_ref();
+    let len = data.len();
+    let mut offset = 0;
+    let mut hash = if len >= 32 {
+        let mut v1 = seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2);
+        let mut v2 = seed.wrapping_add(PRIME64_2);
+        let mut v3 = seed;
+        let mut v4 = seed.wrapping_sub(PRIME64_1);
+        while offset + 32 <= len {
+            v1 = round(v1, read_u64(data, offset));
+            v2 = round(v2, read_u64(data, offset + 8));
+            v3 = round(v3, read_u64(data, offset + 16));
+            v4 = round(v4, read_u64(data, offset + 24));
+            offset += 32;
+        }
+        let mut hash = v1
+            .rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18));
+        hash = merge_round(hash, v1);
+        hash = merge_round(hash, v2);
+        hash = merge_round(hash, v3);
+        hash = merge_round(hash, v4);
+        hash
+    } else {
+        seed.wrapping_add(PRIME64_5)
+    };
+    hash = hash.wrapping_add(len as u64);
+
+    while offset + 8 <= len {
+        hash ^= round(0, read_u64(data, offset));
+        hash = hash
+            .rotate_left(27)
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(PRIME64_4);
+        offset += 8;
+    }
+    if offset + 4 <= len {
+        hash ^= (read_u32(data, offset) as u64).wrapping_mul(PRIME64_1);
+        hash = hash
+            .rotate_left(23)
+            .wrapping_mul(PRIME64_2)
+            .wrapping_add(PRIME64_3);
+        offset += 4;
+    }
+    while offset < len {
+        hash ^= (data[offset] as u64).wrapping_mul(PRIME64_5);
+        hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        offset += 1;
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIME64_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIME64_3);
+    hash ^= hash >> 32;
+    hash
+}
+
+#[test]
+fn test_xxhash64() {
+    let hashes = ["", "a", "abc", "Nobody inspects the spammish repetition"]
+        .into_iter()
+        .map(|s| spark_compatible_xxhash64(s.as_bytes(), 0))
+        .collect::<Vec<_>>();
+    let expected = vec![
+        0xef46db3751d8e999,
+        0xd24ec4f1a98c6e5b,
+        0x44bc2cf5ad770999,
+        0xfbcea83c8a378bf1,
+    ];
+    assert_eq!(hashes, expected);
+    assert_eq!(
+        spark_compatible_xxhash64([], 2654435761),
+        0xac75fda2929b17ef
+    );
+}
+
+/// Creates spark compatible xxhash64 values for every row, nulls are skipped
+/// like `create_hashes`.
+pub fn create_xxhash64_hashes(arrays: &[ArrayRef], hashes_buffer: &mut [u64]) -> Result<()> {
+    for col in arrays {
+        xxhash64_array(col, hashes_buffer)?;
+    }
+    Ok(())
+}
+
+fn xxhash64_array(array: &ArrayRef, hashes_buffer: &mut [u64]) -> Result<()> {
+    macro_rules! hash_array_with {
+        ($array_type:ident, $v:ident => $bytes:expr) => {{
+            let array = array.as_any().downcast_ref::<$array_type>().unwrap();
+            for (i, hash) in hashes_buffer.iter_mut().enumerate() {
+                if array.is_valid(i) {
+                    let $v = array.value(i);
+                    *hash = spark_compatible_xxhash64($bytes, *hash);
+                }
+            }
+        }};
+    }
+
+    match array.data_type() {
+        DataType::Null => {}
+        DataType::Boolean => hash_array_with!(BooleanArray, v => (v as i32).to_le_bytes()),
+        DataType::Int8 => hash_array_with!(Int8Array, v => (v as i32).to_le_bytes()),
+        DataType::Int16 => hash_array_with!(Int16Array, v => (v as i32).to_le_bytes()),
+        DataType::Int32 => hash_array_with!(Int32Array, v => v.to_le_bytes()),
+        DataType::Int64 => hash_array_with!(Int64Array, v => v.to_le_bytes()),
+        // NaNs are canonicalized like java's floatToIntBits/doubleToLongBits
+        DataType::Float32 => hash_array_with!(Float32Array, v => {
+            (if v.is_nan() { f32::NAN } else { v }).to_bits().to_le_bytes()
+        }),
+        DataType::Float64 => hash_array_with!(Float64Array, v => {
+            (if v.is_nan() { f64::NAN } else { v }).to_bits().to_le_bytes()
+        }),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            hash_array_with!(TimestampSecondArray, v => v.to_le_bytes())
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            hash_array_with!(TimestampMillisecondArray, v => v.to_le_bytes())
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            hash_array_with!(TimestampMicrosecondArray, v => v.to_le_bytes())
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            hash_array_with!(TimestampNanosecondArray, v => v.to_le_bytes())
+        }
+        DataType::Date32 => hash_array_with!(Date32Array, v => v.to_le_bytes()),
+        DataType::Date64 => hash_array_with!(Date64Array, v => v.to_le_bytes()),
+        DataType::Binary => hash_array_with!(BinaryArray, v => v),
+        DataType::LargeBinary => hash_array_with!(LargeBinaryArray, v => v),
+        DataType::Utf8 => hash_array_with!(StringArray, v => v.as_bytes()),
+        DataType::LargeUtf8 => hash_array_with!(LargeStringArray, v => v.as_bytes()),
+        // spark hashes decimals as unscaled longs if they fit in 18 digits,
+        // otherwise as bytes of BigInteger.toByteArray()
+        &DataType::Decimal128(precision, _) if precision <= 18 => {
+            hash_array_with!(Decimal128Array, v => (v as i64).to_le_bytes())
+        }
+        DataType::Decimal128(..) => {
+            hash_array_with!(Decimal128Array, v => java_big_integer_bytes(v))
+        }
+        other => df_execution_err!("Unsupported data type in xxhash64 hasher: {other}")?,
+    }
+    Ok(())
+}
+
+/// minimal big-endian two's complement representation, the same as
+/// java's BigInteger.toByteArray()
+fn java_big_integer_bytes(v: i128) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        let redundant = match bytes[start] {
+            0x00 => bytes[start + 1] & 0x80 == 0,
+            0xff => bytes[start + 1] & 0x80 != 0,
+            _ => false,
+        };
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
 pub fn pmod(hash: u32, n: usize) -> usize {
     let hash = hash as i32;
     let n = n as i32;
@@ -0,0 +1,216 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{Array, BooleanArray, Int64Array},
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    logical_expr::ColumnarValue,
+    physical_plan::PhysicalExpr,
+};
+use datafusion_ext_commons::{df_execution_err, spark_bloom_filter};
+use once_cell::sync::OnceCell;
+
+use crate::down_cast_any_ref;
+
+/// spark's `BloomFilterMightContain`. the bloom filter expression is always
+/// foldable (typically a scalar subquery over `BloomFilterAggregate`), so it
+/// is evaluated only once and cached.
+pub struct BloomFilterMightContainExpr {
+    bloom_filter_expr: Arc<dyn PhysicalExpr>,
+    value_expr: Arc<dyn PhysicalExpr>,
+    bloom_filter: OnceCell<Option<Arc<Vec<u8>>>>,
+}
+
+impl BloomFilterMightContainExpr {
+    pub fn new(
+        bloom_filter_expr: Arc<dyn PhysicalExpr>,
+        value_expr: Arc<dyn PhysicalExpr>,
+    ) -> Self {
+        Self {
+            bloom_filter_expr,
+            value_expr,
+            bloom_filter: OnceCell::new(),
+        }
+    }
+
+    fn bloom_filter(&self, batch: &RecordBatch) -> Result<Option<Arc<Vec<u8>>>> {
+        self.bloom_filter
+            .get_or_try_init(|| match self.bloom_filter_expr.evaluate(batch)? {
+                ColumnarValue::Scalar(ScalarValue::Binary(Some(bloom_filter))) => {
+                    spark_bloom_filter::validate(&bloom_filter)?;
+                    Ok(Some(Arc::new(bloom_filter)))
+                }
+                ColumnarValue::Scalar(ScalarValue::Binary(None)) => Ok(None),
+                other => df_execution_err!("might_contain: invalid bloom filter: {other:?}"),
+            })
+            .cloned()
+    }
+}
+
+impl PartialEq<dyn Any> for BloomFilterMightContainExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.bloom_filter_expr.eq(&x.bloom_filter_expr) && self.value_expr.eq(&x.value_expr)
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl Debug for BloomFilterMightContainExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MightContain({:?}, {:?})",
+            self.bloom_filter_expr, self.value_expr
+        )
+    }
+}
+
+impl Display for BloomFilterMightContainExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MightContain({}, {})",
+            self.bloom_filter_expr, self.value_expr
+        )
+    }
+}
+
+impl PhysicalExpr for BloomFilterMightContainExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn nullable(&self, _input_schema: &Schema) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        // null bloom filter always produces null
+        let bloom_filter = match self.bloom_filter(batch)? {
+            Some(bloom_filter) => bloom_filter,
+            None => return Ok(ColumnarValue::Scalar(ScalarValue::Boolean(None))),
+        };
+
+        match self.value_expr.evaluate(batch)? {
+            ColumnarValue::Array(array) => {
+                let values = match array.as_any().downcast_ref::<Int64Array>() {
+                    Some(values) => values,
+                    None => df_execution_err!(
+                        "might_contain: expect int64 values, got {}",
+                        array.data_type()
+                    )?,
+                };
+                let ret_array = Arc::new(BooleanArray::from_iter(values.iter().map(|value| {
+                    value.map(|value| spark_bloom_filter::might_contain_long(&bloom_filter, value))
+                })));
+                Ok(ColumnarValue::Array(ret_array))
+            }
+            ColumnarValue::Scalar(ScalarValue::Int64(value)) => {
+                let ret =
+                    value.map(|value| spark_bloom_filter::might_contain_long(&bloom_filter, value));
+                Ok(ColumnarValue::Scalar(ScalarValue::Boolean(ret)))
+            }
+            value => df_execution_err!("might_contain: invalid value: {value:?}")?,
+        }
+    }
+
+    fn children(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.bloom_filter_expr.clone(), self.value_expr.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            children[1].clone(),
+        )))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.bloom_filter_expr.hash(&mut s);
+        self.value_expr.hash(&mut s);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Array, ArrayRef, BooleanArray, Int64Array},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_expr::{expressions as phys_expr, PhysicalExpr},
+    };
+    use datafusion_ext_commons::spark_bloom_filter;
+
+    use crate::bloom_filter_might_contain::BloomFilterMightContainExpr;
+
+    #[test]
+    fn test_might_contain() -> Result<()> {
+        let mut bloom_filter = spark_bloom_filter::create(100, 1024)?;
+        spark_bloom_filter::put_long(&mut bloom_filter, 1);
+        spark_bloom_filter::put_long(&mut bloom_filter, 3);
+
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![Some(1), Some(2), Some(3), None]));
+        let schema = Arc::new(Schema::new(vec![Field::new("col", DataType::Int64, true)]));
+        let batch = RecordBatch::try_new(schema, vec![values])?;
+
+        let expr = Arc::new(BloomFilterMightContainExpr::new(
+            phys_expr::lit(ScalarValue::Binary(Some(bloom_filter))),
+            phys_expr::col("col", &batch.schema())?,
+        ));
+        let ret = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(false),
+            Some(true),
+            None,
+        ]));
+        assert_eq!(&ret, &expected);
+
+        // null bloom filter
+        let expr = Arc::new(BloomFilterMightContainExpr::new(
+            phys_expr::lit(ScalarValue::Binary(None)),
+            phys_expr::col("col", &batch.schema())?,
+        ));
+        let ret = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+        assert_eq!(ret.null_count(), batch.num_rows());
+        Ok(())
+    }
+}
@@ -26,6 +26,7 @@ mod spark_murmur3_hash;
 mod spark_null_if;
 mod spark_strings;
 mod spark_unscaled_value;
+mod spark_xxhash64;
 
 pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementation> {
     Ok(match name {
@@ -36,6 +37,7 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "MakeDecimal" => Arc::new(spark_make_decimal::spark_make_decimal),
         "CheckOverflow" => Arc::new(spark_check_overflow::spark_check_overflow),
         "Murmur3Hash" => Arc::new(spark_murmur3_hash::spark_murmur3_hash),
+        "XxHash64" => Arc::new(spark_xxhash64::spark_xxhash64),
         "GetJsonObject" => Arc::new(spark_get_json_object::spark_get_json_object),
         "GetParsedJsonObject" => Arc::new(spark_get_json_object::spark_get_parsed_json_object),
         "ParseJson" => Arc::new(spark_get_json_object::spark_parse_json),
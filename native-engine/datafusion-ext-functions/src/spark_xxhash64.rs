@@ -0,0 +1,97 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::array::*;
+use datafusion::{common::Result, physical_plan::ColumnarValue};
+use datafusion_ext_commons::spark_hash::create_xxhash64_hashes;
+
+/// implements org.apache.spark.sql.catalyst.expressions.XxHash64
+pub fn spark_xxhash64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let len = args
+        .iter()
+        .map(|arg| match arg {
+            ColumnarValue::Array(array) => array.len(),
+            ColumnarValue::Scalar(_) => 1,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let arrays = args
+        .iter()
+        .map(|arg| {
+            Ok(match arg {
+                ColumnarValue::Array(array) => array.clone(),
+                ColumnarValue::Scalar(scalar) => scalar.to_array_of_size(len)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // use identical seed as spark's xxhash64 function
+    let spark_xxhash64_default_seed = 42u64;
+    let mut hash_buffer = vec![spark_xxhash64_default_seed; len];
+    create_xxhash64_hashes(&arrays, &mut hash_buffer)?;
+
+    Ok(ColumnarValue::Array(Arc::new(
+        Int64Array::from_iter_values(hash_buffer.into_iter().map(|hash| hash as i64)),
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, sync::Arc};
+
+    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use datafusion::logical_expr::ColumnarValue;
+    use datafusion_ext_commons::spark_hash::spark_compatible_xxhash64;
+
+    use crate::spark_xxhash64::spark_xxhash64;
+
+    #[test]
+    fn test_xxhash64_int64() -> Result<(), Box<dyn Error>> {
+        let result = spark_xxhash64(&vec![ColumnarValue::Array(Arc::new(Int64Array::from(
+            vec![Some(1), Some(-1), None],
+        )))])?
+        .into_array(3)?;
+
+        // nulls are skipped and get the seed
+        let expected = Int64Array::from(vec![
+            Some(spark_compatible_xxhash64(1i64.to_le_bytes(), 42) as i64),
+            Some(spark_compatible_xxhash64((-1i64).to_le_bytes(), 42) as i64),
+            Some(42),
+        ]);
+        let expected: ArrayRef = Arc::new(expected);
+
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_xxhash64_string() -> Result<(), Box<dyn Error>> {
+        let result = spark_xxhash64(&vec![ColumnarValue::Array(Arc::new(
+            StringArray::from_iter_values(["hello", ""]),
+        ))])?
+        .into_array(2)?;
+
+        let expected = Int64Array::from(vec![
+            Some(spark_compatible_xxhash64("hello", 42) as i64),
+            Some(spark_compatible_xxhash64("", 42) as i64),
+        ]);
+        let expected: ArrayRef = Arc::new(expected);
+
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+}
@@ -18,14 +18,17 @@ use std::{
     mem::{size_of, size_of_val},
 };
 
-use arrow::datatypes::DataType;
+use arrow::{array::Array, datatypes::DataType};
 use datafusion::{
     common::{Result, ScalarValue},
     parquet::data_type::AsBytes,
 };
 use datafusion_ext_commons::{
     df_execution_err, downcast_any,
-    io::{read_bytes_slice, read_len, read_scalar, read_u8, write_len, write_scalar, write_u8},
+    io::{
+        read_bytes_slice, read_len, read_scalar, read_u8, write_array_value, write_len,
+        write_scalar, write_u8,
+    },
     slim_bytes::SlimBytes,
 };
 use hashbrown::raw::RawTable;
@@ -220,7 +223,11 @@ pub trait AccumStateRow {
     fn set_fixed_valid(&mut self, addr: AccumStateValAddr, valid: bool) {
         let idx = addr.fixed_valid_idx();
         let fixed_len = self.fixed().len();
-        self.fixed_mut()[fixed_len - 1 - idx / 8] |= (valid as u8) << (idx % 8);
+        if valid {
+            self.fixed_mut()[fixed_len - 1 - idx / 8] |= 1 << (idx % 8);
+        } else {
+            self.fixed_mut()[fixed_len - 1 - idx / 8] &= !(1 << (idx % 8));
+        }
     }
 
     fn fixed_value<T: Sized + Copy>(&self, addr: AccumStateValAddr) -> T {
@@ -459,7 +466,8 @@ pub fn create_dyn_loaders_from_initial_value(values: &[AccumInitialValue]) -> Re
                     n => {
                         let data_len = n - 1;
                         let raw = read_bytes_slice(&mut r.0, data_len)?.into_vec();
-                        Some(Box::new(AggDynList { raw }))
+                        let num_items = read_len(&mut r.0)?;
+                        Some(Box::new(AggDynList { raw, num_items }))
                     }
                 })
             }),
@@ -471,7 +479,7 @@ pub fn create_dyn_loaders_from_initial_value(values: &[AccumInitialValue]) -> Re
                         let raw = read_bytes_slice(&mut r.0, data_len)?.into_vec();
                         let num_items = read_len(&mut r.0)?;
 
-                        let list = AggDynList { raw };
+                        let list = AggDynList { raw, num_items };
                         let mut internal_set = if num_items <= 4 {
                             InternalSet::Small(SmallVec::new())
                         } else {
@@ -585,6 +593,7 @@ pub fn create_dyn_savers_from_initial_value(values: &[AccumInitialValue]) -> Res
                             .or_else(|_| df_execution_err!("error downcasting to AggDynList"))?;
                         write_len(list.raw.len() + 1, &mut w.0)?;
                         w.0.write_all(&list.raw)?;
+                        write_len(list.num_items, &mut w.0)?;
                     } else {
                         write_len(0, &mut w.0)?;
                     }
@@ -770,15 +779,26 @@ impl AggDynValue for AggDynStr {
 #[derive(Clone, Default)]
 pub struct AggDynList {
     pub raw: Vec<u8>,
+    pub num_items: usize,
 }
 
 impl AggDynList {
     pub fn append(&mut self, value: &ScalarValue, nullable: bool) {
         write_scalar(&value, nullable, &mut self.raw).unwrap();
+        self.num_items += 1;
     }
 
     pub fn merge(&mut self, other: &mut Self) {
         self.raw.extend(std::mem::take(&mut other.raw));
+        self.num_items += std::mem::take(&mut other.num_items);
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_items == 0
     }
 
     pub fn into_values(self, dt: DataType, nullable: bool) -> impl Iterator<Item = ScalarValue> {
@@ -858,24 +878,32 @@ impl InternalSet {
     }
 
     fn insert(&mut self, list: &mut AggDynList, raw_value: &[u8]) {
+        let pos = list.raw.len();
+        list.raw.extend_from_slice(raw_value);
+        self.insert_appended(list, pos);
+    }
+
+    /// inserts the value appended to the end of list at `pos`, the value is
+    /// truncated from list if it already exists
+    fn insert_appended(&mut self, list: &mut AggDynList, pos: usize) {
         if let Self::Small(s) = self {
             if s.len() == s.inline_size() {
                 self.convert_to_huge(list);
             }
         }
 
-        match self {
+        let new_pos_len = (pos as u32, (list.raw.len() - pos) as u32);
+        let found = match self {
             InternalSet::Small(s) => {
-                for &mut pos_len in &mut *s {
-                    if list.ref_raw(pos_len) == raw_value {
-                        return;
-                    }
+                let raw_value = list.ref_raw(new_pos_len);
+                let found = s.iter().any(|&pos_len| list.ref_raw(pos_len) == raw_value);
+                if !found {
+                    s.push(new_pos_len);
                 }
-                let new_pos_len = (list.raw.len() as u32, raw_value.len() as u32);
-                list.raw.extend_from_slice(raw_value);
-                s.push(new_pos_len);
+                found
             }
             InternalSet::Huge(s) => {
+                let raw_value = list.ref_raw(new_pos_len);
                 let hash = gx_hash::<AGG_DYN_SET_HASH_SEED>(raw_value);
                 match s.find_or_find_insert_slot(
                     hash,
@@ -884,17 +912,21 @@ impl InternalSet {
                     },
                     |&pos_len| gx_hash::<AGG_DYN_SET_HASH_SEED>(list.ref_raw(pos_len)),
                 ) {
-                    Ok(_found) => {}
+                    Ok(_found) => true,
                     Err(slot) => {
-                        let new_pos_len = (list.raw.len() as u32, raw_value.len() as u32);
-                        list.raw.extend_from_slice(&raw_value);
                         unsafe {
                             // safety: call unsafe `insert_in_slot` method
                             s.insert_in_slot(hash, slot, new_pos_len);
                         }
+                        false
                     }
                 }
             }
+        };
+        if found {
+            list.raw.truncate(pos);
+        } else {
+            list.num_items += 1;
         }
     }
 
@@ -923,8 +955,30 @@ impl AggDynSet {
         self.append_raw(&raw_value);
     }
 
+    /// appends a value of an array, the value is written into the set in place
+    /// without creating an intermediate ScalarValue
+    pub fn append_array_value(
+        &mut self,
+        array: &dyn Array,
+        row_idx: usize,
+        nullable: bool,
+    ) -> Result<()> {
+        let pos = self.list.raw.len();
+        write_array_value(array, row_idx, nullable, &mut self.list.raw)?;
+        self.set.insert_appended(&mut self.list, pos);
+        Ok(())
+    }
+
     pub fn merge(&mut self, other: &mut Self) {
+        self.merge_with_limit(other, usize::MAX);
+    }
+
+    /// merges values of the other set, stops once `limit` values are reached
+    pub fn merge_with_limit(&mut self, other: &mut Self, limit: usize) {
         for pos_len in std::mem::take(&mut other.set).into_iter() {
+            if self.len() >= limit {
+                break;
+            }
             self.append_raw(other.ref_raw(pos_len));
         }
     }
@@ -933,6 +987,14 @@ impl AggDynSet {
         self.list.into_values(dt, nullable)
     }
 
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.len() == 0
+    }
+
     fn append_raw(&mut self, raw_value: &[u8]) {
         let self_set = unsafe {
             // safety: bypass borrow checking
@@ -1006,7 +1068,10 @@ impl AccumStateValAddr {
 mod test {
     use std::{collections::HashSet, io::Cursor};
 
-    use arrow::datatypes::DataType;
+    use arrow::{
+        array::{Array, Int32Array},
+        datatypes::DataType,
+    };
     use datafusion::common::{Result, ScalarValue};
     use datafusion_ext_commons::downcast_any;
 
@@ -1054,6 +1119,7 @@ mod test {
             .downcast::<AggDynSet>()
             .unwrap();
         drop(load_reader);
+        assert_eq!(dyn_set.len(), 6);
 
         let actual_set: HashSet<ScalarValue> = dyn_set.into_values(DataType::Utf8, false).collect();
         assert_eq!(actual_set.len(), 6);
@@ -1065,6 +1131,31 @@ mod test {
         assert!(actual_set.contains(&ScalarValue::from("Candy")));
     }
 
+    #[test]
+    fn test_dyn_set_append_array_value() -> Result<()> {
+        let array = Int32Array::from(vec![Some(1), Some(2), None, Some(1), Some(3), Some(2)]);
+        let mut dyn_set = AggDynSet::default();
+        let mut expected_set = AggDynSet::default();
+        for row_idx in (0..array.len()).filter(|&i| array.is_valid(i)) {
+            dyn_set.append_array_value(&array, row_idx, false)?;
+            expected_set.append(&ScalarValue::try_from_array(&array, row_idx)?, false);
+        }
+        // exceeds the inline size of small set
+        for i in 0..100 {
+            dyn_set.append_array_value(&Int32Array::from(vec![i % 10]), 0, false)?;
+            expected_set.append(&ScalarValue::from(i % 10), false);
+        }
+        assert_eq!(dyn_set.len(), 10);
+        assert_eq!(dyn_set.list.raw, expected_set.list.raw);
+
+        let values = dyn_set
+            .into_values(DataType::Int32, false)
+            .collect::<Vec<_>>();
+        let expected_values = [1, 2, 3, 0, 4, 5, 6, 7, 8, 9].map(ScalarValue::from);
+        assert_eq!(values, expected_values);
+        Ok(())
+    }
+
     #[test]
     fn test_acc() {
         let data_types = vec![
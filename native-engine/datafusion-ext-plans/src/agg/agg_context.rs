@@ -457,6 +457,12 @@ impl AggContext {
         Ok(())
     }
 
+    pub fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        for agg in &self.aggs {
+            agg.agg.increase_acc_mem_used(acc);
+        }
+    }
+
     pub fn acc_dyn_mem_used(&self) -> usize {
         self.aggs
             .iter()
@@ -343,6 +343,10 @@ impl AggTable {
             // merge records of current bucket
             while min_cursor.cur_bucket_idx == current_bucket_idx {
                 let (key, mut acc) = min_cursor.next_record()?;
+
+                // spilled accs are not tracked yet, count them in so that
+                // groups growing from merging are visible to the memory check
+                self.agg_ctx.increase_acc_mem_used(&mut acc.as_mut());
                 let hash = gx_hash::<GX_HASH_SEED_POST_MERGING>(&key);
                 match hashing.map.find_or_find_insert_slot(
                     hash,
@@ -778,7 +782,16 @@ impl MergingData {
                 })
             })
             .collect::<Vec<_>>();
-        radix_sort_u16_ranged_by(&mut sorted, NUM_SPILL_BUCKETS, |v| v.2);
+        let bucket_counts = radix_sort_u16_ranged_by(&mut sorted, NUM_SPILL_BUCKETS, |v| v.2);
+
+        // radix sorting is unstable, restore the input order of records in each
+        // bucket so that order-sensitive aggregates (first/last) are merged
+        // correctly
+        let mut beg = 0;
+        for bucket_count in bucket_counts {
+            sorted[beg..][..bucket_count].sort_unstable_by_key(|v| (v.0, v.1));
+            beg += bucket_count;
+        }
 
         // store serialized records
         // let acc_store = acc_store.lock();
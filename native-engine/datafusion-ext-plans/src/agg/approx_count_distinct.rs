@@ -0,0 +1,542 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{
+    df_execution_err, downcast_any, slim_bytes::SlimBytes, spark_hash::create_xxhash64_hashes,
+};
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynValue,
+        RefAccumStateRow,
+    },
+    hll_bias_data::{BIAS_DATA, RAW_ESTIMATE_DATA},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// seed of spark's `XxHash64Function` used by `HyperLogLogPlusPlus`
+const HASH_SEED: u64 = 42;
+
+/// approx_count_distinct with spark's `HyperLogLogPlusPlus` algorithm. the
+/// registers are stored in a binary acc value with the same word layout as
+/// spark's aggregation buffer.
+pub struct AggApproxCountDistinct {
+    child: Arc<dyn PhysicalExpr>,
+    relative_sd: f64,
+    hll: HyperLogLogPlusPlus,
+    data_type: DataType,
+    accums_initial: [AccumInitialValue; 1],
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggApproxCountDistinct {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggApproxCountDistinct {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggApproxCountDistinct {
+    pub fn try_new(child: Arc<dyn PhysicalExpr>, relative_sd: f64) -> Result<Self> {
+        Ok(Self {
+            child,
+            relative_sd,
+            hll: HyperLogLogPlusPlus::try_new(relative_sd)?,
+            data_type: DataType::Int64,
+            accums_initial: [AccumInitialValue::Scalar(ScalarValue::Binary(None))],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn registers_mut<'a>(&self, acc: &'a mut RefAccumStateRow) -> Result<&'a mut [u8]> {
+        let w = acc.dyn_value_mut(self.accum_state_val_addr);
+        if w.is_none() {
+            let new = AggDynBinary::new(SlimBytes::from(vec![0u8; self.hll.num_bytes()]));
+            self.add_mem_used(new.mem_size());
+            *w = Some(Box::new(new));
+        }
+        let registers = downcast_any!(w.as_mut().unwrap(), mut AggDynBinary)?;
+        Ok(&mut *registers.value)
+    }
+
+    fn evaluate(&self, acc: &mut RefAccumStateRow) -> Result<i64> {
+        Ok(match acc.dyn_value(self.accum_state_val_addr) {
+            Some(w) => self.hll.query(downcast_any!(w, AggDynBinary)?.value()),
+            None => 0,
+        })
+    }
+}
+
+impl Debug for AggApproxCountDistinct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ApproxCountDistinct({:?}, {})",
+            self.child, self.relative_sd
+        )
+    }
+}
+
+impl Agg for AggApproxCountDistinct {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(exprs[0].clone(), self.relative_sd)?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        false
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        // replace inputs with their hashes, null inputs are not counted.
+        // like spark, -0.0 is normalized to 0.0 before hashing
+        let input: ArrayRef = match partial_inputs[0].data_type() {
+            DataType::Float32 => Arc::new(
+                partial_inputs[0]
+                    .as_primitive::<Float32Type>()
+                    .unary::<_, Float32Type>(|v| if v == 0.0 { 0.0 } else { v }),
+            ),
+            DataType::Float64 => Arc::new(
+                partial_inputs[0]
+                    .as_primitive::<Float64Type>()
+                    .unary::<_, Float64Type>(|v| if v == 0.0 { 0.0 } else { v }),
+            ),
+            _ => partial_inputs[0].clone(),
+        };
+        let mut hashes = vec![HASH_SEED; input.len()];
+        create_xxhash64_hashes(&[input.clone()], &mut hashes)?;
+        Ok(vec![Arc::new(UInt64Array::new(
+            hashes.into(),
+            input.logical_nulls(),
+        ))])
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let hashes = downcast_any!(values[0], UInt64Array)?;
+        if hashes.is_valid(row_idx) {
+            self.hll
+                .update(self.registers_mut(acc)?, hashes.value(row_idx));
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let hashes = downcast_any!(values[0], UInt64Array)?;
+        if hashes.null_count() < hashes.len() {
+            let registers = self.registers_mut(acc)?;
+            for hash in hashes.iter().flatten() {
+                self.hll.update(registers, hash);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut AggDynBinary)?;
+                let v = downcast_any!(v, AggDynBinary)?;
+                if w.value().len() != v.value().len() {
+                    return df_execution_err!(
+                        "ApproxCountDistinct: cannot merge registers of different sizes"
+                    );
+                }
+                self.hll.merge(&mut *w.value, v.value());
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        Ok(ScalarValue::Int64(Some(self.evaluate(acc)?)))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter_mut()
+                .map(|acc| self.evaluate(acc))
+                .collect::<Result<Int64Array>>()?,
+        ))
+    }
+}
+
+const REGISTER_SIZE: usize = 6;
+const REGISTERS_PER_WORD: usize = u64::BITS as usize / REGISTER_SIZE;
+const REGISTER_WORD_MASK: u64 = (1 << REGISTER_SIZE) - 1;
+
+/// linear counting thresholds for p = 4..18, from the HLL++ paper
+const THRESHOLDS: [f64; 15] = [
+    10.0, 20.0, 40.0, 80.0, 220.0, 400.0, 900.0, 1800.0, 3100.0, 6500.0, 11500.0, 20000.0, 50000.0,
+    120000.0, 350000.0,
+];
+
+/// number of nearest neighbors used for interpolating the bias
+const K: usize = 6;
+
+/// port of spark's `HyperLogLogPlusPlusHelper`. registers are 6 bits wide
+/// and packed into little-endian 64-bit words, 10 registers per word.
+struct HyperLogLogPlusPlus {
+    p: usize,
+    idx_shift: usize,
+    w_padding: u64,
+    m: usize,
+    alpha_m2: f64,
+    num_words: usize,
+}
+
+impl HyperLogLogPlusPlus {
+    fn try_new(relative_sd: f64) -> Result<Self> {
+        let p = (2.0 * (1.106 / relative_sd).ln() / 2.0f64.ln()).ceil();
+        if !(4.0..=18.0).contains(&p) {
+            return df_execution_err!(
+                "HLL++ requires 4 to 18 bits for addressing, got {p} from relativeSD={relative_sd}"
+            );
+        }
+        let p = p as usize;
+        let m = 1usize << p;
+        let mf = m as f64;
+        let alpha_m2 = match p {
+            4 => 0.673 * mf * mf,
+            5 => 0.697 * mf * mf,
+            6 => 0.709 * mf * mf,
+            _ => (0.7213 / (1.0 + 1.079 / mf)) * mf * mf,
+        };
+        Ok(Self {
+            p,
+            idx_shift: u64::BITS as usize - p,
+            w_padding: 1 << (p - 1),
+            m,
+            alpha_m2,
+            num_words: m / REGISTERS_PER_WORD + 1,
+        })
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.num_words * 8
+    }
+
+    fn update(&self, registers: &mut [u8], hash: u64) {
+        let idx = (hash >> self.idx_shift) as usize;
+        let pw = ((hash << self.p) | self.w_padding).leading_zeros() as u64 + 1;
+
+        let word_idx = idx / REGISTERS_PER_WORD;
+        let word = get_word(registers, word_idx);
+        let shift = REGISTER_SIZE * (idx - word_idx * REGISTERS_PER_WORD);
+        let mask = REGISTER_WORD_MASK << shift;
+        if pw > (word & mask) >> shift {
+            set_word(registers, word_idx, (word & !mask) | (pw << shift));
+        }
+    }
+
+    fn merge(&self, registers: &mut [u8], other: &[u8]) {
+        for i in 0..self.num_words {
+            let word1 = get_word(registers, i);
+            let word2 = get_word(other, i);
+            let mut word = 0;
+            let mut mask = REGISTER_WORD_MASK;
+            for _ in 0..REGISTERS_PER_WORD {
+                word |= (word1 & mask).max(word2 & mask);
+                mask <<= REGISTER_SIZE;
+            }
+            set_word(registers, i, word);
+        }
+    }
+
+    fn query(&self, registers: &[u8]) -> i64 {
+        let mut z_inverse = 0.0;
+        let mut v = 0.0;
+        for idx in 0..self.m {
+            let word = get_word(registers, idx / REGISTERS_PER_WORD);
+            let shift = REGISTER_SIZE * (idx % REGISTERS_PER_WORD);
+            let m_idx = (word >> shift) & REGISTER_WORD_MASK;
+
+            // same as java's int shifting `1 << m_idx`
+            z_inverse += 1.0 / 1i32.wrapping_shl(m_idx as u32) as f64;
+            if m_idx == 0 {
+                v += 1.0;
+            }
+        }
+
+        let m = self.m as f64;
+        let e = self.alpha_m2 / z_inverse;
+
+        // apply bias correction for small raw estimates
+        let e_bias_corrected = if e < 5.0 * m {
+            e - self.estimate_bias(e)
+        } else {
+            e
+        };
+
+        let estimate = if v > 0.0 {
+            // use linear counting for small cardinality estimates
+            let h = m * (m / v).ln();
+            if h <= THRESHOLDS[self.p - 4] {
+                h
+            } else {
+                e_bias_corrected
+            }
+        } else {
+            e_bias_corrected
+        };
+
+        // same as java's Math.round()
+        (estimate + 0.5).floor() as i64
+    }
+
+    /// estimates the bias by averaging the biases of the K nearest raw
+    /// estimates in the empirical data
+    fn estimate_bias(&self, e: f64) -> f64 {
+        let estimates = RAW_ESTIMATE_DATA[self.p - 4];
+        let num_estimates = estimates.len();
+
+        // same as java's Arrays.binarySearch(), the data is not strictly sorted
+        // so the nearest index must be found in the same way as spark
+        let nearest_estimate_index = {
+            let (mut low, mut high) = (0, num_estimates as isize - 1);
+            loop {
+                if low > high {
+                    break low as usize;
+                }
+                let mid = (low + high) / 2;
+                let mid_val = estimates[mid as usize];
+                if mid_val < e {
+                    low = mid + 1;
+                } else if mid_val > e {
+                    high = mid - 1;
+                } else {
+                    break mid as usize;
+                }
+            }
+        };
+
+        // keep moving bounds as long as the (exclusive) high bound is closer
+        // to the estimate than the lower (inclusive) bound
+        let distance = |i: usize| (e - estimates[i]) * (e - estimates[i]);
+        let mut low = (nearest_estimate_index + 1).saturating_sub(K);
+        let mut high = (low + K).min(num_estimates);
+        while high < num_estimates && distance(high) < distance(low) {
+            low += 1;
+            high += 1;
+        }
+        let biases = &BIAS_DATA[self.p - 4][low..high];
+        biases.iter().sum::<f64>() / (high - low) as f64
+    }
+}
+
+fn get_word(registers: &[u8], word_idx: usize) -> u64 {
+    u64::from_le_bytes(registers[word_idx * 8..][..8].try_into().unwrap())
+}
+
+fn set_word(registers: &mut [u8], word_idx: usize, word: u64) {
+    registers[word_idx * 8..][..8].copy_from_slice(&word.to_le_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hll_layout() -> Result<()> {
+        // spark's default relativeSD=0.05 uses p=9
+        let hll = HyperLogLogPlusPlus::try_new(0.05)?;
+        assert_eq!(hll.p, 9);
+        assert_eq!(hll.num_words, 52);
+
+        // index 11 is the second register of the second word
+        let mut registers = vec![0u8; hll.num_bytes()];
+        let hash = (11u64 << 55) | (1u64 << 50);
+        hll.update(&mut registers, hash);
+        assert_eq!(get_word(&registers, 1), 5 << 6);
+
+        // registers keep the max value
+        hll.update(&mut registers, (11u64 << 55) | (1u64 << 54));
+        assert_eq!(get_word(&registers, 1), 5 << 6);
+
+        let mut other = vec![0u8; hll.num_bytes()];
+        hll.update(&mut other, (11u64 << 55) | (1u64 << 40));
+        hll.update(&mut other, 12u64 << 55);
+        hll.merge(&mut registers, &other);
+        assert_eq!(get_word(&registers, 1), (15 << 6) | (56 << 12));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hll_estimate() -> Result<()> {
+        let hll = HyperLogLogPlusPlus::try_new(0.05)?;
+        let mut registers1 = vec![0u8; hll.num_bytes()];
+        let mut registers2 = vec![0u8; hll.num_bytes()];
+        assert_eq!(hll.query(&registers1), 0);
+
+        for i in 0..100i64 {
+            let hash = datafusion_ext_commons::spark_hash::spark_compatible_xxhash64(
+                i.to_le_bytes(),
+                HASH_SEED,
+            );
+            hll.update(&mut registers1, hash);
+            hll.update(&mut registers2, hash);
+        }
+        let estimate = hll.query(&registers1);
+        assert_eq!(estimate, 103);
+
+        // merging duplicated values does not change the estimate
+        hll.merge(&mut registers1, &registers2);
+        assert_eq!(hll.query(&registers1), estimate);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hll_bias_correction() -> Result<()> {
+        let hll = HyperLogLogPlusPlus::try_new(0.05)?;
+        let assert_bias = |e: f64, expected: f64| {
+            let bias = hll.estimate_bias(e);
+            assert!((bias - expected).abs() < 1e-6, "bias of {e}: {bias}");
+        };
+        assert_bias(369.0, 352.0155333333334);
+        assert_bias(1000.0, 68.93603333333333);
+        assert_bias(1308.9692, 29.63550000000001);
+        assert_bias(3000.0, -7.445599999999918);
+
+        // raw estimate of 1000 values is about 1049, which is corrected to
+        // about 989 by bias correction
+        let mut registers = vec![0u8; hll.num_bytes()];
+        for i in 0..1000i64 {
+            let hash = datafusion_ext_commons::spark_hash::spark_compatible_xxhash64(
+                i.to_le_bytes(),
+                HASH_SEED,
+            );
+            hll.update(&mut registers, hash);
+        }
+        let estimate = hll.query(&registers);
+        assert_eq!(estimate, 989);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hll_spark_buffer() -> Result<()> {
+        // non-zero words of spark's aggregation buffer after updating longs 0..10
+        // and doubles [-0.0, 0.0, NaN, 1.5, -0.0, NaN], computed by a java
+        // transcription of spark's XxHash64Function and HyperLogLogPlusPlusHelper
+        let hll = HyperLogLogPlusPlus::try_new(0.05)?;
+        let non_zero_words = |registers: &[u8]| {
+            (0..hll.num_words)
+                .map(|i| (i, get_word(registers, i)))
+                .filter(|&(_, word)| word != 0)
+                .collect::<Vec<_>>()
+        };
+
+        let mut registers = vec![0u8; hll.num_bytes()];
+        let values = Arc::new(Int64Array::from_iter_values(0..10)) as ArrayRef;
+        let agg = AggApproxCountDistinct::try_new(
+            Arc::new(datafusion::physical_expr::expressions::Column::new("v", 0)),
+            0.05,
+        )?;
+        let hashes = agg.prepare_partial_args(&[values])?;
+        for hash in downcast_any!(hashes[0], UInt64Array)?.iter().flatten() {
+            hll.update(&mut registers, hash);
+        }
+        assert_eq!(
+            non_zero_words(&registers),
+            vec![
+                (1, 0x00000000000000c0),
+                (8, 0x0001000000000000),
+                (17, 0x0000000000040000),
+                (24, 0x0000000005000000),
+                (27, 0x0000000080000000),
+                (31, 0x0000040000000000),
+                (36, 0x0000003000000000),
+                (41, 0x00c0000000000000),
+                (43, 0x0000000000001000),
+                (45, 0x0003000000000000),
+            ],
+        );
+        assert_eq!(hll.query(&registers), 10);
+
+        let mut registers = vec![0u8; hll.num_bytes()];
+        let values = Arc::new(Float64Array::from(vec![
+            -0.0,
+            0.0,
+            f64::NAN,
+            1.5,
+            -0.0,
+            f64::NAN,
+        ])) as ArrayRef;
+        let hashes = agg.prepare_partial_args(&[values])?;
+        for hash in downcast_any!(hashes[0], UInt64Array)?.iter().flatten() {
+            hll.update(&mut registers, hash);
+        }
+        assert_eq!(
+            non_zero_words(&registers),
+            vec![
+                (21, 0x0000000001000000),
+                (36, 0x0000003000000000),
+                (42, 0x00000000c0000000),
+            ],
+        );
+        assert_eq!(hll.query(&registers), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{df_execution_err, downcast_any, slim_bytes::SlimBytes};
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynValue,
+        RefAccumStateRow,
+    },
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// percentile_approx with spark's `ApproximatePercentile` algorithm. the
+/// digest is stored in a binary acc value, see `PercentileDigest` for its
+/// layout.
+pub struct AggApproxPercentile {
+    child: Arc<dyn PhysicalExpr>,
+    arg_type: DataType,
+    data_type: DataType,
+    accuracy: i64,
+    percentages: Vec<f64>,
+    return_percentile_array: bool,
+    accums_initial: [AccumInitialValue; 1],
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggApproxPercentile {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggApproxPercentile {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggApproxPercentile {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        arg_type: DataType,
+        accuracy: i64,
+        percentages: Vec<f64>,
+        return_percentile_array: bool,
+    ) -> Result<Self> {
+        if accuracy <= 0 {
+            return df_execution_err!("ApproxPercentile: accuracy must be positive: {accuracy}");
+        }
+        if let Some(p) = percentages.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            return df_execution_err!("ApproxPercentile: percentage out of range: {p}");
+        }
+        if !return_percentile_array && percentages.len() != 1 {
+            return df_execution_err!("ApproxPercentile: expect exactly one percentage");
+        }
+        let data_type = if return_percentile_array {
+            DataType::List(Arc::new(Field::new("item", arg_type.clone(), true)))
+        } else {
+            arg_type.clone()
+        };
+        Ok(Self {
+            child,
+            arg_type,
+            data_type,
+            accuracy,
+            percentages,
+            return_percentile_array,
+            accums_initial: [AccumInitialValue::Scalar(ScalarValue::Binary(None))],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn relative_error(&self) -> f64 {
+        1.0 / self.accuracy as f64
+    }
+
+    fn add(&self, acc: &mut RefAccumStateRow, values: impl IntoIterator<Item = f64>) -> Result<()> {
+        let w = acc.dyn_value_mut(self.accum_state_val_addr);
+        if w.is_none() {
+            let new = AggDynBinary::new(PercentileDigest::new(self.relative_error()).encode(0));
+            self.add_mem_used(new.mem_size());
+            *w = Some(Box::new(new));
+        }
+        let digest = downcast_any!(w.as_mut().unwrap(), mut AggDynBinary)?;
+        for value in values {
+            if !PercentileDigest::try_add_in_place(&mut digest.value, value) {
+                self.sub_mem_used(digest.mem_size());
+                *digest =
+                    AggDynBinary::new(PercentileDigest::add_and_encode(digest.value(), value));
+                self.add_mem_used(digest.mem_size());
+            }
+        }
+        Ok(())
+    }
+
+    fn to_scalar(&self, value: f64) -> Result<ScalarValue> {
+        // same as spark, percentiles are truncated to integral types
+        Ok(match &self.arg_type {
+            DataType::Date32 => ScalarValue::Date32(Some(value as i32)),
+            DataType::Timestamp(TimeUnit::Microsecond, tz) => {
+                ScalarValue::TimestampMicrosecond(Some(value as i64), tz.clone())
+            }
+            other => ScalarValue::Float64(Some(value)).cast_to(other)?,
+        })
+    }
+}
+
+impl Debug for AggApproxPercentile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ApproxPercentile({:?}, {:?}, {})",
+            self.child, self.percentages, self.accuracy
+        )
+    }
+}
+
+impl Agg for AggApproxPercentile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.arg_type.clone(),
+            self.accuracy,
+            self.percentages.clone(),
+            self.return_percentile_array,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        let input = match partial_inputs[0].data_type() {
+            DataType::Date32 => {
+                datafusion_ext_commons::cast::cast(&partial_inputs[0], &DataType::Int32)?
+            }
+            DataType::Timestamp(..) => {
+                datafusion_ext_commons::cast::cast(&partial_inputs[0], &DataType::Int64)?
+            }
+            _ => partial_inputs[0].clone(),
+        };
+        Ok(vec![datafusion_ext_commons::cast::cast(
+            &input,
+            &DataType::Float64,
+        )?])
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let values = downcast_any!(values[0], Float64Array)?;
+        if values.is_valid(row_idx) {
+            self.add(acc, [values.value(row_idx)])?;
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let values = downcast_any!(values[0], Float64Array)?;
+        if values.null_count() < values.len() {
+            self.add(acc, values.iter().flatten())?;
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut AggDynBinary)?;
+                let v = downcast_any!(v, AggDynBinary)?;
+                let mut digest = PercentileDigest::decode(w.value());
+                digest.merge(PercentileDigest::decode(v.value()));
+                self.sub_mem_used(w.mem_size());
+                *w = AggDynBinary::new(digest.encode(0));
+                self.add_mem_used(w.mem_size());
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let percentiles = match std::mem::take(acc.dyn_value_mut(self.accum_state_val_addr)) {
+            Some(w) => {
+                self.sub_mem_used(w.mem_size());
+                let digest = downcast_any!(w, AggDynBinary)?;
+                PercentileDigest::decode(digest.value()).get_percentiles(&self.percentages)
+            }
+            None => vec![],
+        };
+        if percentiles.is_empty() {
+            return ScalarValue::try_from(&self.data_type);
+        }
+        let percentiles = percentiles
+            .into_iter()
+            .map(|value| self.to_scalar(value))
+            .collect::<Result<Vec<_>>>()?;
+
+        if self.return_percentile_array {
+            Ok(ScalarValue::List(ScalarValue::new_list(
+                &percentiles,
+                &self.arg_type,
+            )))
+        } else {
+            Ok(percentiles.into_iter().next().unwrap())
+        }
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let values: Vec<ScalarValue> = accs
+            .iter_mut()
+            .map(|acc| self.final_merge(acc))
+            .collect::<Result<_>>()?;
+
+        if values.is_empty() {
+            return Ok(new_empty_array(self.data_type()));
+        }
+        Ok(ScalarValue::iter_to_array(values)?)
+    }
+}
+
+const COMPRESS_THRESHOLD: usize = 10000;
+const HEAD_SIZE: usize = 50000;
+const MIN_HEAD_CAPACITY: usize = 16;
+
+const SUMMARIES_HEADER_LEN: usize = 20; // relative_error, count, num_samples
+const STATS_LEN: usize = 24; // value, g, delta
+const HEAD_HEADER_LEN: usize = 5; // compressed, head_len
+
+/// port of spark's `PercentileDigest`.
+///
+/// the binary layout starts with spark's serialized `PercentileDigest`
+/// (big-endian relative_error: f64, count: i64, num_samples: i32, and
+/// num_samples * (value: f64, g: i64, delta: i64)), followed by values not
+/// yet inserted into the summaries:
+///   compressed: u8, head_len: u32, head values: f64 * capacity
+///
+/// the head buffer grows by doubling, so adding values is amortized O(1).
+#[derive(Debug, Clone, PartialEq)]
+struct PercentileDigest {
+    summaries: QuantileSummaries,
+    compressed: bool,
+    head: Vec<f64>,
+}
+
+impl PercentileDigest {
+    fn new(relative_error: f64) -> Self {
+        Self {
+            summaries: QuantileSummaries {
+                relative_error,
+                count: 0,
+                sampled: vec![],
+            },
+            compressed: true,
+            head: vec![],
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let num_samples = read_i32(bytes, 16) as usize;
+        let sampled = (0..num_samples)
+            .map(|i| {
+                let offset = SUMMARIES_HEADER_LEN + i * STATS_LEN;
+                Stats {
+                    value: read_f64(bytes, offset),
+                    g: read_i64(bytes, offset + 8),
+                    delta: read_i64(bytes, offset + 16),
+                }
+            })
+            .collect();
+        let head_offset = SUMMARIES_HEADER_LEN + num_samples * STATS_LEN;
+        let head_len = read_u32(bytes, head_offset + 1) as usize;
+        Self {
+            summaries: QuantileSummaries {
+                relative_error: read_f64(bytes, 0),
+                count: read_i64(bytes, 8),
+                sampled,
+            },
+            compressed: bytes[head_offset] != 0,
+            head: (0..head_len)
+                .map(|i| read_f64(bytes, head_offset + HEAD_HEADER_LEN + i * 8))
+                .collect(),
+        }
+    }
+
+    fn encode(&self, head_capacity: usize) -> SlimBytes {
+        let summaries = &self.summaries;
+        let len = SUMMARIES_HEADER_LEN
+            + summaries.sampled.len() * STATS_LEN
+            + HEAD_HEADER_LEN
+            + head_capacity.max(self.head.len()) * 8;
+        let mut bytes = Vec::with_capacity(len);
+        bytes.extend_from_slice(&summaries.relative_error.to_be_bytes());
+        bytes.extend_from_slice(&summaries.count.to_be_bytes());
+        bytes.extend_from_slice(&(summaries.sampled.len() as i32).to_be_bytes());
+        for stats in &summaries.sampled {
+            bytes.extend_from_slice(&stats.value.to_be_bytes());
+            bytes.extend_from_slice(&stats.g.to_be_bytes());
+            bytes.extend_from_slice(&stats.delta.to_be_bytes());
+        }
+        bytes.push(self.compressed as u8);
+        bytes.extend_from_slice(&(self.head.len() as u32).to_be_bytes());
+        for &value in &self.head {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes.resize(len, 0);
+        SlimBytes::from(bytes)
+    }
+
+    /// appends the value into the head buffer if it has enough capacity and
+    /// no flushing is needed
+    fn try_add_in_place(bytes: &mut [u8], value: f64) -> bool {
+        let head_offset = SUMMARIES_HEADER_LEN + read_i32(bytes, 16) as usize * STATS_LEN;
+        let head_len = read_u32(bytes, head_offset + 1) as usize;
+        let head_capacity = (bytes.len() - head_offset - HEAD_HEADER_LEN) / 8;
+        if head_len >= head_capacity || head_len + 1 >= HEAD_SIZE {
+            return false;
+        }
+        let value_offset = head_offset + HEAD_HEADER_LEN + head_len * 8;
+        bytes[value_offset..][..8].copy_from_slice(&value.to_be_bytes());
+        bytes[head_offset] = 0;
+        bytes[head_offset + 1..][..4].copy_from_slice(&(head_len as u32 + 1).to_be_bytes());
+        true
+    }
+
+    /// slow path of adding a value, the head buffer is flushed or grown
+    fn add_and_encode(bytes: &[u8], value: f64) -> SlimBytes {
+        let head_offset = SUMMARIES_HEADER_LEN + read_i32(bytes, 16) as usize * STATS_LEN;
+        let head_capacity = (bytes.len() - head_offset - HEAD_HEADER_LEN) / 8;
+        let mut digest = Self::decode(bytes);
+        digest.add(value);
+
+        let new_head_capacity = if digest.head.len() <= head_capacity {
+            head_capacity
+        } else {
+            (head_capacity * 2).clamp(MIN_HEAD_CAPACITY, HEAD_SIZE)
+        };
+        digest.encode(new_head_capacity)
+    }
+
+    fn add(&mut self, value: f64) {
+        self.head.push(value);
+        self.compressed = false;
+        if self.head.len() >= HEAD_SIZE {
+            self.summaries.insert_head_buffer(&mut self.head);
+            if self.summaries.sampled.len() >= COMPRESS_THRESHOLD {
+                self.compress();
+            }
+        }
+    }
+
+    fn compress(&mut self) {
+        self.summaries.insert_head_buffer(&mut self.head);
+        self.summaries.compress();
+        self.compressed = true;
+    }
+
+    fn merge(&mut self, mut other: PercentileDigest) {
+        if !self.compressed {
+            self.compress();
+        }
+        if !other.compressed {
+            other.compress();
+        }
+        self.summaries.merge(&other.summaries);
+    }
+
+    fn get_percentiles(mut self, percentages: &[f64]) -> Vec<f64> {
+        if !self.compressed {
+            self.compress();
+        }
+        if self.summaries.count == 0 {
+            return vec![];
+        }
+        percentages
+            .iter()
+            .filter_map(|&percentage| self.summaries.query(percentage))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stats {
+    value: f64,
+    g: i64,
+    delta: i64,
+}
+
+/// port of spark's `QuantileSummaries`, an implementation of the
+/// Greenwald-Khanna algorithm.
+#[derive(Debug, Clone, PartialEq)]
+struct QuantileSummaries {
+    relative_error: f64,
+    count: i64,
+    sampled: Vec<Stats>,
+}
+
+impl QuantileSummaries {
+    fn insert_head_buffer(&mut self, head: &mut Vec<f64>) {
+        if head.is_empty() {
+            return;
+        }
+        head.sort_unstable_by(f64::total_cmp);
+
+        let mut new_samples = Vec::with_capacity(self.sampled.len() + head.len());
+        let mut sample_idx = 0;
+        for (ops_idx, &current_sample) in head.iter().enumerate() {
+            // add all the samples before the next observation
+            while sample_idx < self.sampled.len()
+                && self.sampled[sample_idx].value <= current_sample
+            {
+                new_samples.push(self.sampled[sample_idx]);
+                sample_idx += 1;
+            }
+
+            // the first and the last samples are exact
+            self.count += 1;
+            let delta = if new_samples.is_empty()
+                || (sample_idx == self.sampled.len() && ops_idx == head.len() - 1)
+            {
+                0
+            } else {
+                (2.0 * self.relative_error * self.count as f64).floor() as i64
+            };
+            new_samples.push(Stats {
+                value: current_sample,
+                g: 1,
+                delta,
+            });
+        }
+
+        // add all the remaining existing samples
+        new_samples.extend_from_slice(&self.sampled[sample_idx..]);
+        self.sampled = new_samples;
+        head.clear();
+    }
+
+    fn compress(&mut self) {
+        let merge_threshold = 2.0 * self.relative_error * self.count as f64;
+        self.sampled = compress_immut(&self.sampled, merge_threshold);
+    }
+
+    fn merge(&mut self, other: &QuantileSummaries) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = other.clone();
+            return;
+        }
+
+        // samples interleaving the other side suffer from the lack of
+        // precision of the other side, so their deltas are adjusted
+        let merged_relative_error = self.relative_error.max(other.relative_error);
+        let merged_count = self.count + other.count;
+        let additional_self_delta =
+            (2.0 * other.relative_error * other.count as f64).floor() as i64;
+        let additional_other_delta = (2.0 * self.relative_error * self.count as f64).floor() as i64;
+
+        let mut merged = Vec::with_capacity(self.sampled.len() + other.sampled.len());
+        let (mut self_idx, mut other_idx) = (0, 0);
+        while self_idx < self.sampled.len() && other_idx < other.sampled.len() {
+            let self_sample = self.sampled[self_idx];
+            let other_sample = other.sampled[other_idx];
+            let (next_sample, additional_delta) = if self_sample.value < other_sample.value {
+                self_idx += 1;
+                let delta = if other_idx > 0 {
+                    additional_self_delta
+                } else {
+                    0
+                };
+                (self_sample, delta)
+            } else {
+                other_idx += 1;
+                let delta = if self_idx > 0 {
+                    additional_other_delta
+                } else {
+                    0
+                };
+                (other_sample, delta)
+            };
+            merged.push(Stats {
+                delta: next_sample.delta + additional_delta,
+                ..next_sample
+            });
+        }
+        merged.extend_from_slice(&self.sampled[self_idx..]);
+        merged.extend_from_slice(&other.sampled[other_idx..]);
+
+        self.relative_error = merged_relative_error;
+        self.count = merged_count;
+        self.sampled = compress_immut(&merged, 2.0 * merged_relative_error * merged_count as f64);
+    }
+
+    fn query(&self, quantile: f64) -> Option<f64> {
+        let first = self.sampled.first()?;
+        let last = self.sampled.last()?;
+        if quantile <= self.relative_error {
+            return Some(first.value);
+        }
+        if quantile >= 1.0 - self.relative_error {
+            return Some(last.value);
+        }
+
+        let rank = (quantile * self.count as f64).ceil() as i64;
+        let target_error = self
+            .sampled
+            .iter()
+            .map(|stats| stats.delta + stats.g)
+            .max()
+            .unwrap_or_default()
+            / 2;
+        let mut min_rank = 0;
+        for stats in &self.sampled[..self.sampled.len() - 1] {
+            min_rank += stats.g;
+            let max_rank = min_rank + stats.delta;
+            if max_rank - target_error <= rank && rank <= min_rank + target_error {
+                return Some(stats.value);
+            }
+        }
+        Some(last.value)
+    }
+}
+
+fn compress_immut(samples: &[Stats], merge_threshold: f64) -> Vec<Stats> {
+    if samples.is_empty() {
+        return vec![];
+    }
+
+    // start from the last element, which is always part of the set. the head
+    // may be merged with the current element.
+    let mut res = vec![];
+    let mut head = samples[samples.len() - 1];
+    let mid_samples = if samples.len() > 2 {
+        &samples[1..samples.len() - 1]
+    } else {
+        &[]
+    };
+    for &sample in mid_samples.iter().rev() {
+        if ((sample.g + head.g + head.delta) as f64) < merge_threshold {
+            head.g += sample.g;
+        } else {
+            res.push(head);
+            head = sample;
+        }
+    }
+    res.push(head);
+
+    // add the minimum element if necessary
+    let curr_head = samples[0];
+    if curr_head.value <= head.value && samples.len() > 1 {
+        res.push(curr_head);
+    }
+    res.reverse();
+    res
+}
+
+fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_be_bytes(bytes[offset..][..8].try_into().unwrap())
+}
+
+fn read_i64(bytes: &[u8], offset: usize) -> i64 {
+    i64::from_be_bytes(bytes[offset..][..8].try_into().unwrap())
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+    i32::from_be_bytes(bytes[offset..][..4].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..][..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build_digest(values: impl IntoIterator<Item = f64>) -> SlimBytes {
+        let mut bytes = PercentileDigest::new(1.0 / 10000.0).encode(0);
+        for value in values {
+            if !PercentileDigest::try_add_in_place(&mut bytes, value) {
+                bytes = PercentileDigest::add_and_encode(&bytes, value);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_percentile_digest() {
+        let bytes = build_digest((1..=100).rev().map(|v| v as f64));
+        let digest = PercentileDigest::decode(&bytes);
+        assert!(!digest.compressed);
+        assert_eq!(digest.head.len(), 100);
+        assert_eq!(
+            digest.get_percentiles(&[0.0, 0.25, 0.5, 0.99, 1.0]),
+            vec![1.0, 25.0, 50.0, 99.0, 100.0],
+        );
+    }
+
+    #[test]
+    fn test_percentile_digest_merge() {
+        let mut digest1 = PercentileDigest::decode(&build_digest((1..=50).map(|v| v as f64)));
+        let digest2 = PercentileDigest::decode(&build_digest((51..=100).map(|v| v as f64)));
+        digest1.merge(digest2);
+
+        // merged digests are compressed and can be encoded/decoded
+        let digest = PercentileDigest::decode(&digest1.encode(0));
+        assert!(digest.compressed);
+        assert_eq!(digest, digest1);
+        assert_eq!(digest.summaries.count, 100);
+        assert_eq!(digest.get_percentiles(&[0.5, 0.75]), vec![50.0, 75.0]);
+    }
+
+    #[test]
+    fn test_percentile_digest_compress() {
+        // flushing and compressing happens when the head buffer is full
+        let num_values = HEAD_SIZE * 3;
+        let bytes = build_digest((0..num_values).map(|v| ((v * 7919) % num_values) as f64));
+        let digest = PercentileDigest::decode(&bytes);
+        assert_eq!(
+            digest.summaries.count + digest.head.len() as i64,
+            num_values as i64
+        );
+        assert!(digest.head.len() < HEAD_SIZE);
+
+        // error is bounded by relative_error * count
+        let max_error = num_values as f64 / 10000.0;
+        for (percentage, percentile) in [0.1, 0.5, 0.9]
+            .into_iter()
+            .zip(digest.get_percentiles(&[0.1, 0.5, 0.9]))
+        {
+            let expected = percentage * num_values as f64;
+            assert!((percentile - expected).abs() <= max_error + 1.0);
+        }
+    }
+}
@@ -38,6 +38,7 @@ use crate::agg::{
 pub struct AggAvg {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
+    fail_on_overflow: bool,
     agg_sum: AggSum,
     agg_count: AggCount,
     accums_initial: Vec<AccumInitialValue>,
@@ -47,10 +48,11 @@ pub struct AggAvg {
 
 impl WithAggBufAddrs for AggAvg {
     fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        let num_sum_accums = self.agg_sum.accums_initial().len();
         self.agg_sum
             .set_accum_state_val_addrs(accum_state_val_addrs);
         self.agg_count
-            .set_accum_state_val_addrs(&accum_state_val_addrs[1..]);
+            .set_accum_state_val_addrs(&accum_state_val_addrs[num_sum_accums..]);
     }
 }
 
@@ -61,8 +63,12 @@ impl WithMemTracking for AggAvg {
 }
 
 impl AggAvg {
-    pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
-        let agg_sum = AggSum::try_new(child.clone(), data_type.clone())?;
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        fail_on_overflow: bool,
+    ) -> Result<Self> {
+        let agg_sum = AggSum::try_new(child.clone(), data_type.clone(), fail_on_overflow)?;
         let agg_count = AggCount::try_new(child.clone(), DataType::Int64)?;
         let accums_initial = [agg_sum.accums_initial(), agg_count.accums_initial()].concat();
         let final_merger = get_final_merger(&data_type)?;
@@ -70,6 +76,7 @@ impl AggAvg {
         Ok(Self {
             child,
             data_type,
+            fail_on_overflow,
             agg_sum,
             agg_count,
             accums_initial,
@@ -98,6 +105,7 @@ impl Agg for AggAvg {
         Ok(Arc::new(Self::try_new(
             exprs[0].clone(),
             self.data_type.clone(),
+            self.fail_on_overflow,
         )?))
     }
 
@@ -183,6 +191,20 @@ impl Agg for AggAvg {
     }
 
     fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        if matches!(
+            self.data_type,
+            DataType::Interval(_) | DataType::Duration(_)
+        ) {
+            let avgs: Vec<ScalarValue> = accs
+                .iter_mut()
+                .map(|acc| self.final_merge(acc))
+                .collect::<Result<_>>()?;
+            if avgs.is_empty() {
+                return Ok(new_empty_array(&self.data_type));
+            }
+            return Ok(ScalarValue::iter_to_array(avgs)?);
+        }
+
         let sums = self.agg_sum.final_batch_merge(accs)?;
         let counts = self.agg_count.final_batch_merge(accs)?;
 
@@ -241,6 +263,19 @@ fn get_final_merger(dt: &DataType) -> Result<fn(ScalarValue, i64) -> ScalarValue
                 avg
             })
         }};
+        ($ty:ident,interval) => {{
+            Ok(|sum: ScalarValue, count: i64| {
+                let avg = match sum {
+                    ScalarValue::$ty(sum) => ScalarValue::$ty(if !count.is_zero() {
+                        sum.map(|sum| div_half_up(sum as i128, count as i128) as _)
+                    } else {
+                        None
+                    }),
+                    _ => unreachable!(),
+                };
+                avg
+            })
+        }};
     }
     match dt {
         DataType::Null => Ok(|_, _| ScalarValue::Null),
@@ -255,6 +290,21 @@ fn get_final_merger(dt: &DataType) -> Result<fn(ScalarValue, i64) -> ScalarValue
         DataType::UInt32 => get_fn!(UInt32, f64),
         DataType::UInt64 => get_fn!(UInt64, f64),
         DataType::Decimal128(..) => get_fn!(Decimal128),
+        DataType::Interval(IntervalUnit::YearMonth) => get_fn!(IntervalYearMonth, interval),
+        DataType::Duration(TimeUnit::Second) => get_fn!(DurationSecond, interval),
+        DataType::Duration(TimeUnit::Millisecond) => get_fn!(DurationMillisecond, interval),
+        DataType::Duration(TimeUnit::Microsecond) => get_fn!(DurationMicrosecond, interval),
+        DataType::Duration(TimeUnit::Nanosecond) => get_fn!(DurationNanosecond, interval),
         other => df_unimplemented_err!("unsupported data type in avg(): {other}"),
     }
 }
+
+/// divides with HALF_UP rounding, same as spark's interval division
+fn div_half_up(v: i128, n: i128) -> i128 {
+    let (quot, rem) = (v / n, v % n);
+    if rem.abs() * 2 >= n.abs() {
+        quot + v.signum() * n.signum()
+    } else {
+        quot
+    }
+}
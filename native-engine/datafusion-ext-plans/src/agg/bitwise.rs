@@ -0,0 +1,274 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    ops::{BitAnd, BitOr, BitXor},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    default_final_batch_merge_with_addr, default_final_merge_with_addr, Agg, WithAggBufAddrs,
+    WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+}
+
+impl BitwiseOp {
+    fn apply<T: BitwiseValue>(&self, v1: T, v2: T) -> T {
+        match self {
+            BitwiseOp::And => v1 & v2,
+            BitwiseOp::Or => v1 | v2,
+            BitwiseOp::Xor => v1 ^ v2,
+        }
+    }
+}
+
+trait BitwiseValue:
+    Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self>
+{
+}
+
+impl<T: Copy + BitAnd<Output = T> + BitOr<Output = T> + BitXor<Output = T>> BitwiseValue for T {}
+
+/// evaluates `$body` with `$T` bound to the native type of the data type
+macro_rules! with_bitwise_native_type {
+    ($dt:expr, $T:ident => $body:expr) => {{
+        match $dt {
+            DataType::Int8 => {
+                type $T = i8;
+                $body
+            }
+            DataType::Int16 => {
+                type $T = i16;
+                $body
+            }
+            DataType::Int32 => {
+                type $T = i32;
+                $body
+            }
+            DataType::Int64 => {
+                type $T = i64;
+                $body
+            }
+            other => df_execution_err!("bitwise aggregate: unsupported data type: {other}")?,
+        }
+    }};
+}
+
+/// evaluates `$body` with `$array` downcasted to its concrete array type
+macro_rules! with_bitwise_array {
+    ($array:expr, $a:ident => $body:expr) => {{
+        let array = &$array;
+        match array.data_type() {
+            DataType::Int8 => {
+                let $a = array.as_primitive::<Int8Type>();
+                $body
+            }
+            DataType::Int16 => {
+                let $a = array.as_primitive::<Int16Type>();
+                $body
+            }
+            DataType::Int32 => {
+                let $a = array.as_primitive::<Int32Type>();
+                $body
+            }
+            DataType::Int64 => {
+                let $a = array.as_primitive::<Int64Type>();
+                $body
+            }
+            other => df_execution_err!("bitwise aggregate: unsupported data type: {other}")?,
+        }
+    }};
+}
+
+/// bit_and/bit_or/bit_xor of integral values. the accumulator stays null
+/// until a non-null value is seen, the same as spark. bool_and/bool_or are
+/// not needed since spark rewrites them to min/max.
+pub struct AggBitwise {
+    child: Arc<dyn PhysicalExpr>,
+    op: BitwiseOp,
+    data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggBitwise {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggBitwise {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggBitwise {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        op: BitwiseOp,
+        data_type: DataType,
+    ) -> Result<Self> {
+        if !matches!(
+            data_type,
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64
+        ) {
+            return df_execution_err!("bitwise aggregate: unsupported data type: {data_type}");
+        }
+        let accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::try_from(
+            &data_type,
+        )?)];
+        Ok(Self {
+            child,
+            op,
+            data_type,
+            accums_initial,
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn update_value<T: BitwiseValue>(&self, acc: &mut RefAccumStateRow, v: T) {
+        let addr = self.accum_state_val_addr;
+        if acc.is_fixed_valid(addr) {
+            acc.update_fixed_value::<T>(addr, |w| self.op.apply(w, v));
+        } else {
+            acc.set_fixed_value::<T>(addr, v);
+            acc.set_fixed_valid(addr, true);
+        }
+    }
+}
+
+impl Debug for AggBitwise {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self.op {
+            BitwiseOp::And => "BitAnd",
+            BitwiseOp::Or => "BitOr",
+            BitwiseOp::Xor => "BitXor",
+        };
+        write!(f, "{}({:?})", name, self.child)
+    }
+}
+
+impl Agg for AggBitwise {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.op,
+            self.data_type.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        with_bitwise_array!(values[0], values => {
+            if values.is_valid(row_idx) {
+                self.update_value(acc, values.value(row_idx));
+            }
+        });
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        with_bitwise_array!(values[0], values => {
+            for (acc, value) in accs.iter_mut().zip(values.iter()) {
+                if let Some(value) = value {
+                    self.update_value(acc, value);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        with_bitwise_array!(values[0], values => {
+            let op = self.op;
+            if let Some(v) = values.iter().flatten().reduce(|v1, v2| op.apply(v1, v2)) {
+                self.update_value(acc, v);
+            }
+        });
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let addr = self.accum_state_val_addr;
+        if acc2.is_fixed_valid(addr) {
+            with_bitwise_native_type!(&self.data_type, T => {
+                self.update_value(acc1, acc2.fixed_value::<T>(addr));
+            });
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{downcast_any, slim_bytes::SlimBytes, spark_bloom_filter};
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynValue,
+        RefAccumStateRow,
+    },
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// spark's `BloomFilterAggregate`, used for building runtime filters. the
+/// bloom filter is stored in its serialized form so the output can be
+/// directly consumed by spark's `BloomFilterMightContain`.
+pub struct AggBloomFilter {
+    child: Arc<dyn PhysicalExpr>,
+    estimated_num_items: i64,
+    num_bits: i64,
+    data_type: DataType,
+    accums_initial: [AccumInitialValue; 1],
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggBloomFilter {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggBloomFilter {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggBloomFilter {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        estimated_num_items: i64,
+        num_bits: i64,
+    ) -> Result<Self> {
+        // check arguments in advance
+        spark_bloom_filter::create(estimated_num_items, 1)?;
+        spark_bloom_filter::create(1, num_bits)?;
+
+        Ok(Self {
+            child,
+            estimated_num_items,
+            num_bits,
+            data_type: DataType::Binary,
+            accums_initial: [AccumInitialValue::Scalar(ScalarValue::Binary(None))],
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn bloom_filter_mut<'a>(&self, acc: &'a mut RefAccumStateRow) -> Result<&'a mut [u8]> {
+        let w = acc.dyn_value_mut(self.accum_state_val_addr);
+        if w.is_none() {
+            let new = AggDynBinary::new(SlimBytes::from(spark_bloom_filter::create(
+                self.estimated_num_items,
+                self.num_bits,
+            )?));
+            self.add_mem_used(new.mem_size());
+            *w = Some(Box::new(new));
+        }
+        let bloom_filter = downcast_any!(w.as_mut().unwrap(), mut AggDynBinary)?;
+        Ok(&mut *bloom_filter.value)
+    }
+
+    fn evaluate(&self, acc: &mut RefAccumStateRow) -> Result<Option<Vec<u8>>> {
+        // like spark, returns null if no items were inserted
+        Ok(match acc.dyn_value(self.accum_state_val_addr) {
+            Some(w) => {
+                let bloom_filter = downcast_any!(w, AggDynBinary)?.value();
+                (spark_bloom_filter::cardinality(bloom_filter) > 0).then(|| bloom_filter.to_vec())
+            }
+            None => None,
+        })
+    }
+}
+
+impl Debug for AggBloomFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BloomFilter({:?}, {}, {})",
+            self.child, self.estimated_num_items, self.num_bits
+        )
+    }
+}
+
+impl Agg for AggBloomFilter {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.estimated_num_items,
+            self.num_bits,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        Ok(vec![datafusion_ext_commons::cast::cast(
+            &partial_inputs[0],
+            &DataType::Int64,
+        )?])
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let values = downcast_any!(values[0], Int64Array)?;
+        if values.is_valid(row_idx) {
+            spark_bloom_filter::put_long(self.bloom_filter_mut(acc)?, values.value(row_idx));
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let values = downcast_any!(values[0], Int64Array)?;
+        if values.null_count() < values.len() {
+            let bloom_filter = self.bloom_filter_mut(acc)?;
+            for value in values.iter().flatten() {
+                spark_bloom_filter::put_long(bloom_filter, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut AggDynBinary)?;
+                let v = downcast_any!(v, AggDynBinary)?;
+                spark_bloom_filter::merge(&mut *w.value, v.value())?;
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        Ok(ScalarValue::Binary(self.evaluate(acc)?))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter_mut()
+                .map(|acc| self.evaluate(acc))
+                .collect::<Result<BinaryArray>>()?,
+        ))
+    }
+}
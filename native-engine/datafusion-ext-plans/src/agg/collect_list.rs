@@ -15,15 +15,24 @@
 use std::{
     any::Any,
     fmt::{Debug, Formatter},
+    io::Cursor,
     sync::{atomic::AtomicUsize, Arc},
 };
 
-use arrow::{array::*, datatypes::*};
+use arrow::{
+    array::*,
+    datatypes::*,
+    row::{RowConverter, SortField},
+};
 use datafusion::{
     common::{Result, ScalarValue},
-    physical_expr::PhysicalExpr,
+    physical_expr::{PhysicalExpr, PhysicalSortExpr},
+};
+use datafusion_ext_commons::{
+    df_execution_err, downcast_any,
+    io::{read_bytes_slice, read_len, read_scalar, write_len},
 };
-use datafusion_ext_commons::{df_execution_err, downcast_any};
+use parking_lot::Mutex as SyncMutex;
 
 use crate::agg::{
     acc::{
@@ -37,6 +46,10 @@ pub struct AggCollectList {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
     arg_type: DataType,
+    max_elements: Option<usize>,
+    order_by: Vec<PhysicalSortExpr>,
+    order_by_types: Vec<DataType>,
+    order_by_row_converter: Arc<SyncMutex<RowConverter>>,
     accum_initial: [AccumInitialValue; 1],
     accum_state_val_addr: AccumStateValAddr,
     mem_used_tracker: AtomicUsize,
@@ -59,21 +72,129 @@ impl AggCollectList {
         child: Arc<dyn PhysicalExpr>,
         data_type: DataType,
         arg_type: DataType,
+        max_elements: Option<usize>,
     ) -> Result<Self> {
+        Self::try_new_sorted(child, data_type, arg_type, max_elements, vec![], vec![])
+    }
+
+    /// collect_list with a within-group ordering, like array_agg(v ORDER BY k).
+    /// each element is stored with its row-encoded sort key, elements are
+    /// sorted by the keys in final_merge().
+    pub fn try_new_sorted(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        arg_type: DataType,
+        max_elements: Option<usize>,
+        order_by: Vec<PhysicalSortExpr>,
+        order_by_types: Vec<DataType>,
+    ) -> Result<Self> {
+        let order_by_row_converter = Arc::new(SyncMutex::new(RowConverter::new(
+            order_by_types
+                .iter()
+                .zip(&order_by)
+                .map(|(dt, order)| SortField::new_with_options(dt.clone(), order.options))
+                .collect(),
+        )?));
         Ok(Self {
             child,
             data_type,
             accum_initial: [AccumInitialValue::DynList(arg_type.clone())],
             arg_type,
+            max_elements,
+            order_by,
+            order_by_types,
+            order_by_row_converter,
             accum_state_val_addr: AccumStateValAddr::default(),
             mem_used_tracker: AtomicUsize::new(0),
         })
     }
+
+    fn is_sorted(&self) -> bool {
+        !self.order_by.is_empty()
+    }
+
+    fn append_value(&self, list: &mut AggDynList, values: &[ArrayRef], i: usize) -> Result<()> {
+        if self.is_sorted() {
+            let sort_key = downcast_any!(values[1], BinaryArray)?.value(i);
+            write_len(sort_key.len(), &mut list.raw)?;
+            list.raw.extend_from_slice(sort_key);
+        }
+        list.append(&ScalarValue::try_from_array(&values[0], i)?, false);
+        Ok(())
+    }
+
+    fn is_full(&self, list: &AggDynList) -> bool {
+        // sorted lists cannot skip new elements, which may have smaller sort keys
+        !self.is_sorted() && self.max_elements.is_some_and(|max| list.len() >= max)
+    }
+
+    /// drops elements exceeding spark.blaze.collect.maxElements instead of
+    /// failing the task. unsorted lists keep the first elements, sorted lists
+    /// keep the elements with the smallest sort keys and are only truncated
+    /// once twice the limit is reached, to amortize the sorting cost.
+    fn truncate_to_max_elements(&self, list: &mut AggDynList) -> Result<()> {
+        let Some(max_elements) = self.max_elements else {
+            return Ok(());
+        };
+        if !self.is_sorted() {
+            if list.len() > max_elements {
+                let values = std::mem::take(list).into_values(self.arg_type.clone(), false);
+                for value in values.take(max_elements) {
+                    list.append(&value, false);
+                }
+            }
+            return Ok(());
+        }
+        if list.len() >= max_elements.saturating_mul(2) {
+            let sort_keys_and_values = self.sorted_keys_and_values(std::mem::take(list))?;
+            for (sort_key, value) in sort_keys_and_values.into_iter().take(max_elements) {
+                write_len(sort_key.len(), &mut list.raw)?;
+                list.raw.extend_from_slice(&sort_key);
+                list.append(&value, false);
+            }
+        }
+        Ok(())
+    }
+
+    fn sorted_keys_and_values(&self, list: AggDynList) -> Result<Vec<(Box<[u8]>, ScalarValue)>> {
+        // stable sorting, elements with equal keys are kept in input order
+        let mut cursor = Cursor::new(list.raw);
+        let mut sort_keys_and_values = Vec::with_capacity(list.num_items);
+        while (cursor.position() as usize) < cursor.get_ref().len() {
+            let sort_key_len = read_len(&mut cursor)?;
+            let sort_key = read_bytes_slice(&mut cursor, sort_key_len)?;
+            let value = read_scalar(&mut cursor, &self.arg_type, false)?;
+            sort_keys_and_values.push((sort_key, value));
+        }
+        sort_keys_and_values.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+        Ok(sort_keys_and_values)
+    }
+
+    fn into_values(&self, list: AggDynList) -> Result<Vec<ScalarValue>> {
+        if !self.is_sorted() {
+            return Ok(list.into_values(self.arg_type.clone(), false).collect());
+        }
+        let max_elements = self.max_elements.unwrap_or(usize::MAX);
+        Ok(self
+            .sorted_keys_and_values(list)?
+            .into_iter()
+            .take(max_elements)
+            .map(|(_, value)| value)
+            .collect())
+    }
 }
 
 impl Debug for AggCollectList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "CollectList({:?})", self.child)
+        if self.is_sorted() {
+            write!(
+                f,
+                "CollectList({:?} ORDER BY {:?})",
+                self.child, self.order_by
+            )
+        } else {
+            write!(f, "CollectList({:?})", self.child)
+        }
     }
 }
 
@@ -83,14 +204,34 @@ impl Agg for AggCollectList {
     }
 
     fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
-        vec![self.child.clone()]
+        [self.child.clone()]
+            .into_iter()
+            .chain(self.order_by.iter().map(|order| order.expr.clone()))
+            .collect()
     }
 
     fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
-        Ok(Arc::new(Self::try_new(
+        // ordering exprs are not necessary in final mode, keep the old ones if not
+        // provided
+        let order_by = self
+            .order_by
+            .iter()
+            .enumerate()
+            .map(|(i, order)| PhysicalSortExpr {
+                expr: exprs
+                    .get(i + 1)
+                    .cloned()
+                    .unwrap_or_else(|| order.expr.clone()),
+                options: order.options,
+            })
+            .collect();
+        Ok(Arc::new(Self::try_new_sorted(
             exprs[0].clone(),
             self.data_type.clone(),
             self.arg_type.clone(),
+            self.max_elements,
+            order_by,
+            self.order_by_types.clone(),
         )?))
     }
 
@@ -106,6 +247,22 @@ impl Agg for AggCollectList {
         &self.accum_initial
     }
 
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        if !self.is_sorted() {
+            return Ok(partial_inputs.to_vec());
+        }
+
+        // encode ordering columns into comparable binary sort keys
+        let rows = self
+            .order_by_row_converter
+            .lock()
+            .convert_columns(&partial_inputs[1..])?;
+        let sort_keys: ArrayRef = Arc::new(BinaryArray::from_iter_values(
+            rows.iter().map(|row| row.as_ref().to_vec()),
+        ));
+        Ok(vec![partial_inputs[0].clone(), sort_keys])
+    }
+
     fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
         if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
             self.add_mem_used(v.mem_size());
@@ -122,14 +279,19 @@ impl Agg for AggCollectList {
             match acc.dyn_value_mut(self.accum_state_val_addr) {
                 Some(dyn_list) => {
                     let list = downcast_any!(dyn_list, mut AggDynList)?;
+                    if self.is_full(list) {
+                        return Ok(());
+                    }
                     self.sub_mem_used(list.mem_size());
 
-                    list.append(&ScalarValue::try_from_array(&values[0], row_idx)?, false);
+                    self.append_value(list, values, row_idx)?;
+                    self.truncate_to_max_elements(list)?;
                     self.add_mem_used(list.mem_size());
                 }
                 w => {
                     let mut new_list = AggDynList::default();
-                    new_list.append(&ScalarValue::try_from_array(&values[0], row_idx)?, false);
+                    self.append_value(&mut new_list, values, row_idx)?;
+                    self.truncate_to_max_elements(&mut new_list)?;
                     self.add_mem_used(new_list.mem_size());
                     *w = Some(Box::new(new_list));
                 }
@@ -152,8 +314,9 @@ impl Agg for AggCollectList {
         self.sub_mem_used(list.mem_size());
 
         for i in 0..values[0].len() {
-            if values[0].is_valid(i) {
-                list.append(&ScalarValue::try_from_array(&values[0], i)?, false);
+            if values[0].is_valid(i) && !self.is_full(list) {
+                self.append_value(list, values, i)?;
+                self.truncate_to_max_elements(list)?;
             }
         }
         self.add_mem_used(list.mem_size());
@@ -176,6 +339,7 @@ impl Agg for AggCollectList {
                 self.sub_mem_used(v.mem_size());
 
                 w.merge(v);
+                self.truncate_to_max_elements(w)?;
                 self.add_mem_used(w.mem_size());
             }
             (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
@@ -195,9 +359,7 @@ impl Agg for AggCollectList {
                 self.sub_mem_used(list.mem_size());
 
                 Ok(ScalarValue::List(ScalarValue::new_list(
-                    &list
-                        .into_values(self.arg_type.clone(), false)
-                        .collect::<Vec<_>>(),
+                    &self.into_values(*list)?,
                     &self.arg_type,
                 )))
             }
@@ -37,6 +37,7 @@ pub struct AggCollectSet {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
     arg_type: DataType,
+    max_elements: Option<usize>,
     accum_initial: [AccumInitialValue; 1],
     accum_state_val_addr: AccumStateValAddr,
     mem_used_tracker: AtomicUsize,
@@ -59,16 +60,24 @@ impl AggCollectSet {
         child: Arc<dyn PhysicalExpr>,
         data_type: DataType,
         arg_type: DataType,
+        max_elements: Option<usize>,
     ) -> Result<Self> {
         Ok(Self {
             child,
             data_type,
             accum_initial: [AccumInitialValue::DynSet(arg_type.clone())],
             arg_type,
+            max_elements,
             accum_state_val_addr: AccumStateValAddr::default(),
             mem_used_tracker: AtomicUsize::new(0),
         })
     }
+
+    /// number of distinct elements kept in each set, elements exceeding
+    /// spark.blaze.collect.maxElements are dropped instead of failing the task
+    fn max_elements(&self) -> usize {
+        self.max_elements.unwrap_or(usize::MAX)
+    }
 }
 
 impl Debug for AggCollectSet {
@@ -91,6 +100,7 @@ impl Agg for AggCollectSet {
             exprs[0].clone(),
             self.data_type.clone(),
             self.arg_type.clone(),
+            self.max_elements,
         )?))
     }
 
@@ -122,6 +132,9 @@ impl Agg for AggCollectSet {
             match acc.dyn_value_mut(self.accum_state_val_addr) {
                 Some(dyn_set) => {
                     let set = downcast_any!(dyn_set, mut AggDynSet)?;
+                    if set.len() >= self.max_elements() {
+                        return Ok(());
+                    }
                     self.sub_mem_used(set.mem_size());
 
                     set.append(&ScalarValue::try_from_array(&values[0], row_idx)?, false);
@@ -152,7 +165,7 @@ impl Agg for AggCollectSet {
         self.sub_mem_used(set.mem_size());
 
         for i in 0..values[0].len() {
-            if values[0].is_valid(i) {
+            if values[0].is_valid(i) && set.len() < self.max_elements() {
                 set.append(&ScalarValue::try_from_array(&values[0], i)?, false);
             }
         }
@@ -174,7 +187,7 @@ impl Agg for AggCollectSet {
                 let v = downcast_any!(v, mut AggDynSet)?;
                 self.sub_mem_used(w.mem_size());
                 self.sub_mem_used(v.mem_size());
-                w.merge(v);
+                w.merge_with_limit(v, self.max_elements());
                 self.add_mem_used(w.mem_size());
             }
             (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
@@ -0,0 +1,324 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::downcast_any;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CovarianceType {
+    CovarSamp,
+    CovarPop,
+    Corr,
+}
+
+impl CovarianceType {
+    /// covariance: n, x_avg, y_avg, ck
+    /// corr: n, x_avg, y_avg, ck, x_mk, y_mk
+    fn num_accums(&self) -> usize {
+        match self {
+            CovarianceType::CovarSamp | CovarianceType::CovarPop => 4,
+            CovarianceType::Corr => 6,
+        }
+    }
+}
+
+/// covariance and pearson correlation coefficient, computed with the same
+/// algorithm as spark's `Covariance` and `PearsonCorrelation`, so that
+/// results are identical.
+pub struct AggCovariance {
+    x: Arc<dyn PhysicalExpr>,
+    y: Arc<dyn PhysicalExpr>,
+    covariance_type: CovarianceType,
+    null_on_divide_by_zero: bool,
+    data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addrs: Vec<AccumStateValAddr>,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggCovariance {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addrs =
+            accum_state_val_addrs[..self.covariance_type.num_accums()].to_vec();
+    }
+}
+
+impl WithMemTracking for AggCovariance {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggCovariance {
+    pub fn try_new(
+        x: Arc<dyn PhysicalExpr>,
+        y: Arc<dyn PhysicalExpr>,
+        covariance_type: CovarianceType,
+        null_on_divide_by_zero: bool,
+    ) -> Result<Self> {
+        let num_accums = covariance_type.num_accums();
+        let accums_initial =
+            vec![AccumInitialValue::Scalar(ScalarValue::Float64(Some(0.0))); num_accums];
+        Ok(Self {
+            x,
+            y,
+            covariance_type,
+            null_on_divide_by_zero,
+            data_type: DataType::Float64,
+            accums_initial,
+            accum_state_val_addrs: vec![AccumStateValAddr::default(); num_accums],
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn load_state(&self, acc: &RefAccumStateRow) -> CoMomentState {
+        let addrs = &self.accum_state_val_addrs;
+        let is_corr = self.covariance_type == CovarianceType::Corr;
+        CoMomentState {
+            n: acc.fixed_value(addrs[0]),
+            x_avg: acc.fixed_value(addrs[1]),
+            y_avg: acc.fixed_value(addrs[2]),
+            ck: acc.fixed_value(addrs[3]),
+            x_mk: if is_corr {
+                acc.fixed_value(addrs[4])
+            } else {
+                0.0
+            },
+            y_mk: if is_corr {
+                acc.fixed_value(addrs[5])
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn save_state(&self, acc: &mut RefAccumStateRow, state: CoMomentState) {
+        let addrs = &self.accum_state_val_addrs;
+        acc.set_fixed_value(addrs[0], state.n);
+        acc.set_fixed_value(addrs[1], state.x_avg);
+        acc.set_fixed_value(addrs[2], state.y_avg);
+        acc.set_fixed_value(addrs[3], state.ck);
+        if self.covariance_type == CovarianceType::Corr {
+            acc.set_fixed_value(addrs[4], state.x_mk);
+            acc.set_fixed_value(addrs[5], state.y_mk);
+        }
+    }
+
+    fn update(&self, state: &mut CoMomentState, x: f64, y: f64) {
+        match self.covariance_type {
+            CovarianceType::CovarSamp | CovarianceType::CovarPop => state.update_covariance(x, y),
+            CovarianceType::Corr => state.update_corr(x, y),
+        }
+    }
+
+    fn evaluate(&self, state: CoMomentState) -> Option<f64> {
+        let CoMomentState { n, ck, .. } = state;
+        if n == 0.0 {
+            return None;
+        }
+        if n == 1.0 && self.covariance_type != CovarianceType::CovarPop {
+            return (!self.null_on_divide_by_zero).then_some(f64::NAN);
+        }
+        Some(match self.covariance_type {
+            CovarianceType::CovarSamp => ck / (n - 1.0),
+            CovarianceType::CovarPop => ck / n,
+            CovarianceType::Corr => ck / (state.x_mk * state.y_mk).sqrt(),
+        })
+    }
+}
+
+impl Debug for AggCovariance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?}, {:?})", self.covariance_type, self.x, self.y)
+    }
+}
+
+impl Agg for AggCovariance {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.x.clone(), self.y.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            exprs[1].clone(),
+            self.covariance_type,
+            self.null_on_divide_by_zero,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        partial_inputs
+            .iter()
+            .map(|input| datafusion_ext_commons::cast::cast(input, &DataType::Float64))
+            .collect()
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let xs = downcast_any!(values[0], Float64Array)?;
+        let ys = downcast_any!(values[1], Float64Array)?;
+        if xs.is_valid(row_idx) && ys.is_valid(row_idx) {
+            let mut state = self.load_state(acc);
+            self.update(&mut state, xs.value(row_idx), ys.value(row_idx));
+            self.save_state(acc, state);
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let xs = downcast_any!(values[0], Float64Array)?;
+        let ys = downcast_any!(values[1], Float64Array)?;
+        for (acc, (x, y)) in accs.iter_mut().zip(xs.iter().zip(ys.iter())) {
+            if let (Some(x), Some(y)) = (x, y) {
+                let mut state = self.load_state(acc);
+                self.update(&mut state, x, y);
+                self.save_state(acc, state);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let xs = downcast_any!(values[0], Float64Array)?;
+        let ys = downcast_any!(values[1], Float64Array)?;
+        let mut state = self.load_state(acc);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            if let (Some(x), Some(y)) = (x, y) {
+                self.update(&mut state, x, y);
+            }
+        }
+        self.save_state(acc, state);
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let mut state = self.load_state(acc1);
+        state.merge(self.load_state(acc2));
+        self.save_state(acc1, state);
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.evaluate(self.load_state(acc))))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter()
+                .map(|acc| self.evaluate(self.load_state(acc)))
+                .collect::<Float64Array>(),
+        ))
+    }
+}
+
+/// x_mk and y_mk are only used by corr
+#[derive(Clone, Copy)]
+struct CoMomentState {
+    n: f64,
+    x_avg: f64,
+    y_avg: f64,
+    ck: f64,
+    x_mk: f64,
+    y_mk: f64,
+}
+
+impl CoMomentState {
+    fn update_covariance(&mut self, x: f64, y: f64) {
+        let n = self.n + 1.0;
+        let dx = x - self.x_avg;
+        let dy = y - self.y_avg;
+        let dy_n = dy / n;
+        self.n = n;
+        self.x_avg += dx / n;
+        self.y_avg += dy_n;
+        self.ck += dx * (y - self.y_avg);
+    }
+
+    fn update_corr(&mut self, x: f64, y: f64) {
+        let n = self.n + 1.0;
+        let dx = x - self.x_avg;
+        let dx_n = dx / n;
+        let dy = y - self.y_avg;
+        let dy_n = dy / n;
+        self.n = n;
+        self.x_avg += dx_n;
+        self.y_avg += dy_n;
+        self.ck += dx * (y - self.y_avg);
+        self.x_mk += dx * (x - self.x_avg);
+        self.y_mk += dy * (y - self.y_avg);
+    }
+
+    fn merge(&mut self, other: CoMomentState) {
+        let (n1, n2) = (self.n, other.n);
+        let n = n1 + n2;
+        let dx = other.x_avg - self.x_avg;
+        let dx_n = if n == 0.0 { 0.0 } else { dx / n };
+        let dy = other.y_avg - self.y_avg;
+        let dy_n = if n == 0.0 { 0.0 } else { dy / n };
+        self.n = n;
+        self.x_avg += dx_n * n2;
+        self.y_avg += dy_n * n2;
+        self.ck = self.ck + other.ck + dx * dy_n * n1 * n2;
+        self.x_mk = self.x_mk + other.x_mk + dx * dx_n * n1 * n2;
+        self.y_mk = self.y_mk + other.y_mk + dy * dy_n * n1 * n2;
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::{expressions::Column, PhysicalExpr},
+};
+use datafusion_ext_commons::{df_execution_err, downcast_any};
+
+use crate::agg::{
+    acc::{
+        create_acc_from_initial_value, AccumInitialValue, AccumStateRow, AccumStateValAddr,
+        AggDynSet, AggDynValue, OwnedAccumStateRow, RefAccumStateRow,
+    },
+    create_agg, Agg, AggFunction, WithAggBufAddrs, WithMemTracking,
+};
+
+/// count/sum/avg(DISTINCT ...) without spark's expand rewriting. distinct
+/// values of each group are collected into a set, the inner aggregate is
+/// then evaluated on these values in final_merge().
+pub struct AggDistinct {
+    child: Arc<dyn PhysicalExpr>,
+    arg_type: DataType,
+    inner_agg_function: AggFunction,
+    inner_args: Vec<Arc<dyn PhysicalExpr>>,
+    inner: Arc<dyn Agg>,
+    inner_input_schema: SchemaRef,
+    inner_initial_acc: OwnedAccumStateRow,
+    accum_initial: [AccumInitialValue; 1],
+    accum_state_val_addr: AccumStateValAddr,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggDistinct {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggDistinct {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggDistinct {
+    /// `inner_args` are extra literal arguments of the inner aggregate, like
+    /// failOnError of sum/avg
+    pub fn try_new(
+        inner_agg_function: AggFunction,
+        child: Arc<dyn PhysicalExpr>,
+        arg_type: DataType,
+        inner_args: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Self> {
+        // the inner aggregate reads distinct values from a single column batch
+        let inner_input_schema = Arc::new(Schema::new(vec![Field::new(
+            "distinct",
+            arg_type.clone(),
+            true,
+        )]));
+        let inner_child: Arc<dyn PhysicalExpr> = Arc::new(Column::new("distinct", 0));
+        let inner_children = [vec![inner_child], inner_args.clone()].concat();
+        let mut inner = create_agg(inner_agg_function, &inner_children, &inner_input_schema)?;
+        let (inner_initial_acc, inner_accum_state_val_addrs) =
+            create_acc_from_initial_value(inner.accums_initial())?;
+        match Arc::get_mut(&mut inner) {
+            Some(inner) => inner.set_accum_state_val_addrs(&inner_accum_state_val_addrs),
+            None => df_execution_err!("AggDistinct: inner aggregate is shared")?,
+        }
+
+        Ok(Self {
+            child,
+            accum_initial: [AccumInitialValue::DynSet(arg_type.clone())],
+            arg_type,
+            inner_agg_function,
+            inner_args,
+            inner,
+            inner_input_schema,
+            inner_initial_acc,
+            accum_state_val_addr: AccumStateValAddr::default(),
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn get_or_create_set<'a>(&self, acc: &'a mut RefAccumStateRow) -> Result<&'a mut AggDynSet> {
+        let dyn_set = match acc.dyn_value_mut(self.accum_state_val_addr) {
+            Some(dyn_set) => dyn_set,
+            w => {
+                let new_set = AggDynSet::default();
+                self.add_mem_used(new_set.mem_size());
+                w.insert(Box::new(new_set))
+            }
+        };
+        downcast_any!(dyn_set, mut AggDynSet)
+    }
+
+    fn evaluate(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let values = match std::mem::take(acc.dyn_value_mut(self.accum_state_val_addr)) {
+            Some(w) => {
+                self.sub_mem_used(w.mem_size());
+                w.as_any_boxed()
+                    .downcast::<AggDynSet>()
+                    .or_else(|_| df_execution_err!("error downcasting to AggDynSet"))?
+                    .into_values(self.arg_type.clone(), false)
+                    .collect::<Vec<_>>()
+            }
+            None => vec![],
+        };
+
+        let mut inner_acc = self.inner_initial_acc.clone();
+        let mut inner_acc = inner_acc.as_mut();
+        if !values.is_empty() {
+            let num_rows = values.len();
+            let batch = RecordBatch::try_new(
+                self.inner_input_schema.clone(),
+                vec![ScalarValue::iter_to_array(values)?],
+            )?;
+            let inner_inputs = self
+                .inner
+                .exprs()
+                .iter()
+                .map(|expr| expr.evaluate(&batch)?.into_array(num_rows))
+                .collect::<Result<Vec<_>>>()?;
+            let inner_inputs = self.inner.prepare_partial_args(&inner_inputs)?;
+            self.inner
+                .partial_update_all(&mut inner_acc, &inner_inputs)?;
+        }
+        self.inner.final_merge(&mut inner_acc)
+    }
+}
+
+impl Debug for AggDistinct {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}Distinct({:?})", self.inner_agg_function, self.child)
+    }
+}
+
+impl Agg for AggDistinct {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            self.inner_agg_function,
+            exprs[0].clone(),
+            self.arg_type.clone(),
+            self.inner_args.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        self.inner.data_type()
+    }
+
+    fn nullable(&self) -> bool {
+        self.inner.nullable()
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accum_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        // like spark, -0.0 and NaNs are normalized before deduplicating
+        let input: ArrayRef = match partial_inputs[0].data_type() {
+            DataType::Float32 => Arc::new(
+                partial_inputs[0]
+                    .as_primitive::<Float32Type>()
+                    .unary::<_, Float32Type>(|v| match v {
+                        v if v.is_nan() => f32::NAN,
+                        v if v == 0.0 => 0.0,
+                        v => v,
+                    }),
+            ),
+            DataType::Float64 => Arc::new(
+                partial_inputs[0]
+                    .as_primitive::<Float64Type>()
+                    .unary::<_, Float64Type>(|v| match v {
+                        v if v.is_nan() => f64::NAN,
+                        v if v == 0.0 => 0.0,
+                        v => v,
+                    }),
+            ),
+            _ => partial_inputs[0].clone(),
+        };
+        Ok(vec![input])
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        if values[0].is_valid(row_idx) {
+            let set = self.get_or_create_set(acc)?;
+            self.sub_mem_used(set.mem_size());
+            set.append_array_value(&values[0], row_idx, false)?;
+            self.add_mem_used(set.mem_size());
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let set = self.get_or_create_set(acc)?;
+        self.sub_mem_used(set.mem_size());
+        for row_idx in 0..values[0].len() {
+            if values[0].is_valid(row_idx) {
+                set.append_array_value(&values[0], row_idx, false)?;
+            }
+        }
+        self.add_mem_used(set.mem_size());
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut RefAccumStateRow,
+        merging_acc: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        match (
+            acc.dyn_value_mut(self.accum_state_val_addr),
+            merging_acc.dyn_value_mut(self.accum_state_val_addr),
+        ) {
+            (Some(w), Some(v)) => {
+                let w = downcast_any!(w, mut AggDynSet)?;
+                let v = downcast_any!(v, mut AggDynSet)?;
+                self.sub_mem_used(w.mem_size());
+                self.sub_mem_used(v.mem_size());
+                w.merge(v);
+                self.add_mem_used(w.mem_size());
+            }
+            (w_none, v @ Some(_)) => *w_none = std::mem::take(v),
+            (_, None) => {}
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        self.evaluate(acc)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let values: Vec<ScalarValue> = accs
+            .iter_mut()
+            .map(|acc| self.evaluate(acc))
+            .collect::<Result<_>>()?;
+
+        if values.is_empty() {
+            return Ok(new_empty_array(self.data_type()));
+        }
+        Ok(ScalarValue::iter_to_array(values)?)
+    }
+}
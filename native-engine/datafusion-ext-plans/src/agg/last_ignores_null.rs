@@ -0,0 +1,273 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::downcast_any;
+use paste::paste;
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynScalar, AggDynStr,
+        AggDynValue, RefAccumStateRow,
+    },
+    default_final_batch_merge_with_addr, default_final_merge_with_addr, Agg, WithAggBufAddrs,
+    WithMemTracking,
+};
+
+pub struct AggLastIgnoresNull {
+    child: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr: AccumStateValAddr,
+    partial_updater: fn(&AggLastIgnoresNull, &mut RefAccumStateRow, &ArrayRef, usize),
+    partial_buf_merger: fn(&AggLastIgnoresNull, &mut RefAccumStateRow, &mut RefAccumStateRow),
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggLastIgnoresNull {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr = accum_state_val_addrs[0];
+    }
+}
+
+impl WithMemTracking for AggLastIgnoresNull {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggLastIgnoresNull {
+    pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
+        let accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::try_from(
+            &data_type,
+        )?)];
+        let partial_updater = get_partial_updater(&data_type)?;
+        let partial_buf_merger = get_partial_buf_merger(&data_type)?;
+        Ok(Self {
+            child,
+            data_type,
+            accums_initial,
+            accum_state_val_addr: AccumStateValAddr::default(),
+            partial_updater,
+            partial_buf_merger,
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl Debug for AggLastIgnoresNull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LastIgnoresNull({:?})", self.child)
+    }
+}
+
+impl Agg for AggLastIgnoresNull {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.data_type.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if self.data_type.is_primitive()
+            || matches!(self.data_type, DataType::Null | DataType::Boolean)
+        {
+            return;
+        }
+        if let Some(v) = acc.dyn_value(self.accum_state_val_addr) {
+            self.add_mem_used(v.mem_size());
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let partial_updater = self.partial_updater;
+        let value = &values[0];
+        partial_updater(self, acc, value, row_idx);
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let partial_updater = self.partial_updater;
+        let value = &values[0];
+
+        if let Some(i) = (0..value.len()).rev().find(|&i| value.is_valid(i)) {
+            partial_updater(self, acc, value, i);
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let partial_buf_merger = self.partial_buf_merger;
+        partial_buf_merger(self, acc1, acc2);
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)
+    }
+}
+
+fn get_partial_updater(
+    dt: &DataType,
+) -> Result<fn(&AggLastIgnoresNull, &mut RefAccumStateRow, &ArrayRef, usize)> {
+    macro_rules! fn_fixed {
+        ($ty:ident) => {{
+            Ok(|this, acc, v, i| {
+                if v.is_valid(i) {
+                    let value = v.as_any().downcast_ref::<paste! {[<$ty Array>]}>().unwrap();
+                    acc.set_fixed_value(this.accum_state_val_addr, value.value(i));
+                    acc.set_fixed_valid(this.accum_state_val_addr, true);
+                }
+            })
+        }};
+    }
+    macro_rules! fn_dyn {
+        ($v:ident, $i:ident => $new:expr) => {{
+            Ok(|this, acc, $v: &ArrayRef, $i: usize| {
+                if $v.is_valid($i) {
+                    let w = acc.dyn_value_mut(this.accum_state_val_addr);
+                    if let Some(old) = w.as_ref() {
+                        this.sub_mem_used(old.mem_size());
+                    }
+                    let new = $new;
+                    this.add_mem_used(new.mem_size());
+                    *w = Some(Box::new(new));
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _, _| ()),
+        DataType::Boolean => fn_fixed!(Boolean),
+        DataType::Float32 => fn_fixed!(Float32),
+        DataType::Float64 => fn_fixed!(Float64),
+        DataType::Int8 => fn_fixed!(Int8),
+        DataType::Int16 => fn_fixed!(Int16),
+        DataType::Int32 => fn_fixed!(Int32),
+        DataType::Int64 => fn_fixed!(Int64),
+        DataType::UInt8 => fn_fixed!(UInt8),
+        DataType::UInt16 => fn_fixed!(UInt16),
+        DataType::UInt32 => fn_fixed!(UInt32),
+        DataType::UInt64 => fn_fixed!(UInt64),
+        DataType::Date32 => fn_fixed!(Date32),
+        DataType::Date64 => fn_fixed!(Date64),
+        DataType::Timestamp(TimeUnit::Second, _) => fn_fixed!(TimestampSecond),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => fn_fixed!(TimestampMillisecond),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => fn_fixed!(TimestampMicrosecond),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => fn_fixed!(TimestampNanosecond),
+        DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Utf8 => fn_dyn!(v, i => {
+            AggDynStr::from_str(downcast_any!(v, StringArray).unwrap().value(i))
+        }),
+        DataType::Binary => fn_dyn!(v, i => {
+            AggDynBinary::from_slice(downcast_any!(v, BinaryArray).unwrap().value(i))
+        }),
+        _other => fn_dyn!(v, i => {
+            AggDynScalar::new(
+                ScalarValue::try_from_array(v, i)
+                    .expect("LastIgnoresNull::partial_update error creating ScalarValue"),
+            )
+        }),
+    }
+}
+
+fn get_partial_buf_merger(
+    dt: &DataType,
+) -> Result<fn(&AggLastIgnoresNull, &mut RefAccumStateRow, &mut RefAccumStateRow)> {
+    // acc2 comes after acc1, so it always wins if valid
+
+    macro_rules! fn_fixed {
+        ($ty:ty) => {{
+            Ok(|this, acc1, acc2| {
+                if acc2.is_fixed_valid(this.accum_state_val_addr) {
+                    acc1.set_fixed_value(
+                        this.accum_state_val_addr,
+                        acc2.fixed_value::<$ty>(this.accum_state_val_addr),
+                    );
+                    acc1.set_fixed_valid(this.accum_state_val_addr, true);
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Boolean => fn_fixed!(bool),
+        DataType::Float32 => fn_fixed!(f32),
+        DataType::Float64 => fn_fixed!(f64),
+        DataType::Int8 => fn_fixed!(i8),
+        DataType::Int16 => fn_fixed!(i16),
+        DataType::Int32 | DataType::Date32 => fn_fixed!(i32),
+        DataType::Int64 | DataType::Date64 | DataType::Timestamp(..) => fn_fixed!(i64),
+        DataType::UInt8 => fn_fixed!(u8),
+        DataType::UInt16 => fn_fixed!(u16),
+        DataType::UInt32 => fn_fixed!(u32),
+        DataType::UInt64 => fn_fixed!(u64),
+        DataType::Decimal128(..) => fn_fixed!(i128),
+        DataType::Utf8 | DataType::Binary | _ => Ok(|this, acc1, acc2| {
+            let w = acc1.dyn_value_mut(this.accum_state_val_addr);
+            let v = acc2.dyn_value_mut(this.accum_state_val_addr);
+            if v.is_some() {
+                if let Some(old) = w.as_ref() {
+                    this.sub_mem_used(old.mem_size()); // old value will be
+                                                       // dropped
+                }
+                *w = std::mem::take(v);
+            }
+        }),
+    }
+}
@@ -0,0 +1,516 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    cmp::Ordering,
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::downcast_any;
+use paste::paste;
+
+use crate::agg::{
+    acc::{
+        AccumInitialValue, AccumStateRow, AccumStateValAddr, AggDynBinary, AggDynScalar, AggDynStr,
+        AggDynValue, RefAccumStateRow,
+    },
+    default_final_batch_merge_with_addr, default_final_merge_with_addr,
+    maxmin::{AggMaxMinParams, AggMaxParams, AggMinParams},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+pub type AggMaxBy = AggMaxMinBy<AggMaxParams>;
+pub type AggMinBy = AggMaxMinBy<AggMinParams>;
+
+/// max_by(value, key)/min_by(value, key). the accumulator holds both the
+/// ordering key and the value. the same as spark, rows with null keys are
+/// ignored, the later row wins on ties and NaN keys are larger than any other
+/// values.
+pub struct AggMaxMinBy<P: AggMaxMinParams> {
+    value: Arc<dyn PhysicalExpr>,
+    key: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    key_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr_value: AccumStateValAddr,
+    accum_state_val_addr_key: AccumStateValAddr,
+    key_updater: fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &ArrayRef, usize) -> bool,
+    key_merger: fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &mut RefAccumStateRow) -> bool,
+    value_updater: fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &ArrayRef, usize),
+    value_merger: fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &mut RefAccumStateRow),
+    mem_used_tracker: AtomicUsize,
+    _phantom: PhantomData<P>,
+}
+
+impl<P: AggMaxMinParams> WithAggBufAddrs for AggMaxMinBy<P> {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr_value = accum_state_val_addrs[0];
+        self.accum_state_val_addr_key = accum_state_val_addrs[1];
+    }
+}
+
+impl<P: AggMaxMinParams> WithMemTracking for AggMaxMinBy<P> {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl<P: AggMaxMinParams> AggMaxMinBy<P> {
+    pub fn try_new(
+        value: Arc<dyn PhysicalExpr>,
+        key: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        key_type: DataType,
+    ) -> Result<Self> {
+        let accums_initial = vec![
+            AccumInitialValue::Scalar(ScalarValue::try_from(&data_type)?),
+            AccumInitialValue::Scalar(ScalarValue::try_from(&key_type)?),
+        ];
+        let key_updater = get_key_updater::<P>(&key_type)?;
+        let key_merger = get_key_merger::<P>(&key_type)?;
+        let value_updater = get_value_updater::<P>(&data_type)?;
+        let value_merger = get_value_merger::<P>(&data_type)?;
+        Ok(Self {
+            value,
+            key,
+            data_type,
+            key_type,
+            accums_initial,
+            accum_state_val_addr_value: AccumStateValAddr::default(),
+            accum_state_val_addr_key: AccumStateValAddr::default(),
+            key_updater,
+            key_merger,
+            value_updater,
+            value_merger,
+            mem_used_tracker: AtomicUsize::new(0),
+            _phantom: Default::default(),
+        })
+    }
+
+    fn release_dyn(&self, acc: &mut RefAccumStateRow, addr: AccumStateValAddr) {
+        if let Some(v) = std::mem::take(acc.dyn_value_mut(addr)) {
+            self.sub_mem_used(v.mem_size());
+        }
+    }
+}
+
+impl<P: AggMaxMinParams> Debug for AggMaxMinBy<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_by({:?}, {:?})", P::NAME, self.value, self.key)
+    }
+}
+
+impl<P: AggMaxMinParams> Agg for AggMaxMinBy<P> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.value.clone(), self.key.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            exprs[1].clone(),
+            self.data_type.clone(),
+            self.key_type.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, acc: &mut RefAccumStateRow) {
+        if !is_fixed_type(&self.data_type) {
+            if let Some(v) = acc.dyn_value(self.accum_state_val_addr_value) {
+                self.add_mem_used(v.mem_size());
+            }
+        }
+        if !is_fixed_type(&self.key_type) {
+            if let Some(v) = acc.dyn_value(self.accum_state_val_addr_key) {
+                self.add_mem_used(v.mem_size());
+            }
+        }
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let key_updater = self.key_updater;
+        if key_updater(self, acc, &values[1], row_idx) {
+            let value_updater = self.value_updater;
+            value_updater(self, acc, &values[0], row_idx);
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        for row_idx in 0..values[1].len() {
+            self.partial_update(acc, values, row_idx)?;
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let key_merger = self.key_merger;
+        if key_merger(self, acc1, acc2) {
+            let value_merger = self.value_merger;
+            value_merger(self, acc1, acc2);
+        } else if !is_fixed_type(&self.data_type) {
+            self.release_dyn(acc2, self.accum_state_val_addr_value); // acc2 will be dropped
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        if !is_fixed_type(&self.key_type) {
+            self.release_dyn(acc, self.accum_state_val_addr_key);
+        }
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr_value)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        if !is_fixed_type(&self.key_type) {
+            for acc in accs.iter_mut() {
+                self.release_dyn(acc, self.accum_state_val_addr_key);
+            }
+        }
+        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr_value)
+    }
+}
+
+/// data types stored in the fixed part of accumulator, see
+/// `create_acc_from_initial_value()`
+fn is_fixed_type(dt: &DataType) -> bool {
+    matches!(
+        dt,
+        DataType::Null
+            | DataType::Boolean
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Decimal128(..)
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Timestamp(..)
+    )
+}
+
+/// compares keys in spark's ordering, in which NaN is larger than any other
+/// values and equal to itself
+fn cmp_key<T: PartialOrd + ?Sized>(k1: &T, k2: &T) -> Ordering {
+    k1.partial_cmp(k2).unwrap_or_else(|| {
+        let k1_is_nan = k1.partial_cmp(k1).is_none();
+        let k2_is_nan = k2.partial_cmp(k2).is_none();
+        k1_is_nan.cmp(&k2_is_nan)
+    })
+}
+
+/// returns true if the new key replaces the current one, which is also true
+/// on ties, so that the later row wins
+fn is_new_key_better<P: AggMaxMinParams>(ord: Ordering) -> bool {
+    ord != P::ORD.reverse()
+}
+
+fn update_fixed_key<P: AggMaxMinParams, T: Copy + PartialOrd>(
+    acc: &mut RefAccumStateRow,
+    addr: AccumStateValAddr,
+    k: T,
+) -> bool {
+    if acc.is_fixed_valid(addr) && !is_new_key_better::<P>(cmp_key(&k, &acc.fixed_value::<T>(addr)))
+    {
+        return false;
+    }
+    acc.set_fixed_value(addr, k);
+    acc.set_fixed_valid(addr, true);
+    true
+}
+
+/// returns true if the key of row i is better than or equal to the current one,
+/// in which case the key is updated and the value should be updated as well
+fn get_key_updater<P: AggMaxMinParams>(
+    dt: &DataType,
+) -> Result<fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &ArrayRef, usize) -> bool> {
+    macro_rules! fn_fixed {
+        ($ty:ident) => {{
+            Ok(|this, acc, k, i| {
+                type TArray = paste! {[<$ty Array>]};
+                let key = k.as_any().downcast_ref::<TArray>().unwrap();
+                key.is_valid(i)
+                    && update_fixed_key::<P, _>(acc, this.accum_state_val_addr_key, key.value(i))
+            })
+        }};
+    }
+    macro_rules! fn_dyn {
+        ($k:ident, $i:ident => $get:expr, $dyn_ty:ident, $new:expr) => {{
+            Ok(|this, acc, $k: &ArrayRef, $i: usize| {
+                if !$k.is_valid($i) {
+                    return false;
+                }
+                let key = $get;
+                match acc.dyn_value_mut(this.accum_state_val_addr_key) {
+                    Some(w) => {
+                        let w = downcast_any!(w.as_mut(), mut $dyn_ty).unwrap();
+                        if !is_new_key_better::<P>(cmp_key(key, w.value())) {
+                            return false;
+                        }
+                        this.sub_mem_used(w.mem_size());
+                        *w = $new(key);
+                        this.add_mem_used(w.mem_size());
+                    }
+                    w @ None => {
+                        let new = $new(key);
+                        this.add_mem_used(new.mem_size());
+                        *w = Some(Box::new(new));
+                    }
+                }
+                true
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _, _| false),
+        DataType::Boolean => fn_fixed!(Boolean),
+        DataType::Float32 => fn_fixed!(Float32),
+        DataType::Float64 => fn_fixed!(Float64),
+        DataType::Int8 => fn_fixed!(Int8),
+        DataType::Int16 => fn_fixed!(Int16),
+        DataType::Int32 => fn_fixed!(Int32),
+        DataType::Int64 => fn_fixed!(Int64),
+        DataType::UInt8 => fn_fixed!(UInt8),
+        DataType::UInt16 => fn_fixed!(UInt16),
+        DataType::UInt32 => fn_fixed!(UInt32),
+        DataType::UInt64 => fn_fixed!(UInt64),
+        DataType::Date32 => fn_fixed!(Date32),
+        DataType::Date64 => fn_fixed!(Date64),
+        DataType::Timestamp(TimeUnit::Second, _) => fn_fixed!(TimestampSecond),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => fn_fixed!(TimestampMillisecond),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => fn_fixed!(TimestampMicrosecond),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => fn_fixed!(TimestampNanosecond),
+        DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Utf8 => fn_dyn!(
+            k, i => downcast_any!(k, StringArray).unwrap().value(i),
+            AggDynStr,
+            AggDynStr::from_str
+        ),
+        DataType::Binary => fn_dyn!(
+            k, i => downcast_any!(k, BinaryArray).unwrap().value(i),
+            AggDynBinary,
+            AggDynBinary::from_slice
+        ),
+        _other => fn_dyn!(
+            k, i => &ScalarValue::try_from_array(k, i)
+                .expect("MaxMinBy::partial_update error creating ScalarValue"),
+            AggDynScalar,
+            |key: &ScalarValue| AggDynScalar::new(key.clone())
+        ),
+    }
+}
+
+/// merges key of acc2 into acc1, returns true if acc2's key is better or
+/// equal, in which case the value should be merged as well
+fn get_key_merger<P: AggMaxMinParams>(
+    dt: &DataType,
+) -> Result<fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &mut RefAccumStateRow) -> bool> {
+    macro_rules! fn_fixed {
+        ($ty:ty) => {{
+            Ok(|this, acc1, acc2| {
+                let addr = this.accum_state_val_addr_key;
+                acc2.is_fixed_valid(addr)
+                    && update_fixed_key::<P, _>(acc1, addr, acc2.fixed_value::<$ty>(addr))
+            })
+        }};
+    }
+    macro_rules! fn_dyn {
+        ($dyn_ty:ident) => {{
+            Ok(|this, acc1, acc2| {
+                let addr = this.accum_state_val_addr_key;
+                match (acc1.dyn_value_mut(addr), acc2.dyn_value_mut(addr)) {
+                    (Some(w), Some(v)) => {
+                        let better = is_new_key_better::<P>(cmp_key(
+                            downcast_any!(v, $dyn_ty).unwrap().value(),
+                            downcast_any!(w, $dyn_ty).unwrap().value(),
+                        ));
+                        if better {
+                            std::mem::swap(w, v);
+                        }
+                        this.sub_mem_used(v.mem_size()); // v will be dropped
+                        better
+                    }
+                    (w @ None, v @ Some(_)) => {
+                        *w = std::mem::take(v);
+                        true
+                    }
+                    (_, None) => false,
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _| false),
+        DataType::Boolean => fn_fixed!(bool),
+        DataType::Float32 => fn_fixed!(f32),
+        DataType::Float64 => fn_fixed!(f64),
+        DataType::Int8 => fn_fixed!(i8),
+        DataType::Int16 => fn_fixed!(i16),
+        DataType::Int32 | DataType::Date32 => fn_fixed!(i32),
+        DataType::Int64 | DataType::Date64 | DataType::Timestamp(..) => fn_fixed!(i64),
+        DataType::UInt8 => fn_fixed!(u8),
+        DataType::UInt16 => fn_fixed!(u16),
+        DataType::UInt32 => fn_fixed!(u32),
+        DataType::UInt64 => fn_fixed!(u64),
+        DataType::Decimal128(..) => fn_fixed!(i128),
+        DataType::Utf8 => fn_dyn!(AggDynStr),
+        DataType::Binary => fn_dyn!(AggDynBinary),
+        _other => fn_dyn!(AggDynScalar),
+    }
+}
+
+/// replaces the value with row i, which may be null
+fn get_value_updater<P: AggMaxMinParams>(
+    dt: &DataType,
+) -> Result<fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &ArrayRef, usize)> {
+    macro_rules! fn_fixed {
+        ($ty:ident) => {{
+            Ok(|this, acc, v, i| {
+                type TArray = paste! {[<$ty Array>]};
+                let addr = this.accum_state_val_addr_value;
+                if v.is_valid(i) {
+                    let value = v.as_any().downcast_ref::<TArray>().unwrap();
+                    acc.set_fixed_value(addr, value.value(i));
+                    acc.set_fixed_valid(addr, true);
+                } else {
+                    acc.set_fixed_valid(addr, false);
+                }
+            })
+        }};
+    }
+    macro_rules! fn_dyn {
+        ($v:ident, $i:ident => $get:expr, $new:expr) => {{
+            Ok(|this, acc, $v: &ArrayRef, $i: usize| {
+                this.release_dyn(acc, this.accum_state_val_addr_value);
+                if $v.is_valid($i) {
+                    let new = $new($get);
+                    this.add_mem_used(new.mem_size());
+                    *acc.dyn_value_mut(this.accum_state_val_addr_value) = Some(Box::new(new));
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _, _| ()),
+        DataType::Boolean => fn_fixed!(Boolean),
+        DataType::Float32 => fn_fixed!(Float32),
+        DataType::Float64 => fn_fixed!(Float64),
+        DataType::Int8 => fn_fixed!(Int8),
+        DataType::Int16 => fn_fixed!(Int16),
+        DataType::Int32 => fn_fixed!(Int32),
+        DataType::Int64 => fn_fixed!(Int64),
+        DataType::UInt8 => fn_fixed!(UInt8),
+        DataType::UInt16 => fn_fixed!(UInt16),
+        DataType::UInt32 => fn_fixed!(UInt32),
+        DataType::UInt64 => fn_fixed!(UInt64),
+        DataType::Date32 => fn_fixed!(Date32),
+        DataType::Date64 => fn_fixed!(Date64),
+        DataType::Timestamp(TimeUnit::Second, _) => fn_fixed!(TimestampSecond),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => fn_fixed!(TimestampMillisecond),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => fn_fixed!(TimestampMicrosecond),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => fn_fixed!(TimestampNanosecond),
+        DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Utf8 => fn_dyn!(
+            v, i => downcast_any!(v, StringArray).unwrap().value(i),
+            AggDynStr::from_str
+        ),
+        DataType::Binary => fn_dyn!(
+            v, i => downcast_any!(v, BinaryArray).unwrap().value(i),
+            AggDynBinary::from_slice
+        ),
+        _other => fn_dyn!(
+            v, i => ScalarValue::try_from_array(v, i)
+                .expect("MaxMinBy::partial_update error creating ScalarValue"),
+            AggDynScalar::new
+        ),
+    }
+}
+
+/// replaces the value of acc1 with acc2's
+fn get_value_merger<P: AggMaxMinParams>(
+    dt: &DataType,
+) -> Result<fn(&AggMaxMinBy<P>, &mut RefAccumStateRow, &mut RefAccumStateRow)> {
+    macro_rules! fn_fixed {
+        ($ty:ty) => {{
+            Ok(|this, acc1, acc2| {
+                let addr = this.accum_state_val_addr_value;
+                if acc2.is_fixed_valid(addr) {
+                    acc1.set_fixed_value(addr, acc2.fixed_value::<$ty>(addr));
+                    acc1.set_fixed_valid(addr, true);
+                } else {
+                    acc1.set_fixed_valid(addr, false);
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Boolean => fn_fixed!(bool),
+        DataType::Float32 => fn_fixed!(f32),
+        DataType::Float64 => fn_fixed!(f64),
+        DataType::Int8 => fn_fixed!(i8),
+        DataType::Int16 => fn_fixed!(i16),
+        DataType::Int32 | DataType::Date32 => fn_fixed!(i32),
+        DataType::Int64 | DataType::Date64 | DataType::Timestamp(..) => fn_fixed!(i64),
+        DataType::UInt8 => fn_fixed!(u8),
+        DataType::UInt16 => fn_fixed!(u16),
+        DataType::UInt32 => fn_fixed!(u32),
+        DataType::UInt64 => fn_fixed!(u64),
+        DataType::Decimal128(..) => fn_fixed!(i128),
+        _other => Ok(|this, acc1, acc2| {
+            let addr = this.accum_state_val_addr_value;
+            this.release_dyn(acc1, addr);
+            *acc1.dyn_value_mut(addr) = std::mem::take(acc2.dyn_value_mut(addr));
+        }),
+    }
+}
@@ -15,14 +15,25 @@
 pub mod acc;
 pub mod agg_context;
 pub mod agg_table;
+pub mod approx_count_distinct;
+pub mod approx_percentile;
 pub mod avg;
+pub mod bitwise;
+pub mod bloom_filter;
 pub mod collect_list;
 pub mod collect_set;
 pub mod count;
+pub mod covariance;
+pub mod distinct;
 pub mod first;
 pub mod first_ignores_null;
+mod hll_bias_data;
+pub mod last;
+pub mod last_ignores_null;
 pub mod maxmin;
+pub mod maxmin_by;
 pub mod sum;
+pub mod variance;
 
 use std::{
     any::Any,
@@ -34,10 +45,10 @@ use std::{
 };
 
 use arrow::{array::*, datatypes::*};
+use blaze_jni_bridge::{conf, conf::IntConf, is_jni_bridge_inited};
 use datafusion::{
     common::{Result, ScalarValue},
-    logical_expr::aggregate_function,
-    physical_expr::PhysicalExpr,
+    physical_expr::{expressions::Literal, PhysicalExpr, PhysicalSortExpr},
 };
 use datafusion_ext_commons::df_execution_err;
 use datafusion_ext_exprs::cast::TryCastExpr;
@@ -87,6 +98,26 @@ pub enum AggFunction {
     FirstIgnoresNull,
     CollectList,
     CollectSet,
+    StddevSamp,
+    StddevPop,
+    VarSamp,
+    VarPop,
+    CovarSamp,
+    CovarPop,
+    Corr,
+    ApproxCountDistinct,
+    ApproxPercentile,
+    BloomFilter,
+    CountDistinct,
+    SumDistinct,
+    AvgDistinct,
+    BitAnd,
+    BitOr,
+    BitXor,
+    MaxBy,
+    MinBy,
+    Last,
+    LastIgnoresNull,
 }
 
 #[derive(Debug, Clone)]
@@ -198,24 +229,20 @@ pub fn create_agg(
         }
         AggFunction::Sum => {
             let arg_type = children[0].data_type(input_schema)?;
-            let return_type = aggregate_function::AggregateFunction::return_type(
-                &aggregate_function::AggregateFunction::Sum,
-                &[arg_type],
-            )?;
+            let return_type = get_sum_avg_return_type(&agg_function, &arg_type)?;
             Arc::new(sum::AggSum::try_new(
                 Arc::new(TryCastExpr::new(children[0].clone(), return_type.clone())),
                 return_type,
+                get_fail_on_overflow(children)?,
             )?)
         }
         AggFunction::Avg => {
             let arg_type = children[0].data_type(input_schema)?;
-            let return_type = aggregate_function::AggregateFunction::return_type(
-                &aggregate_function::AggregateFunction::Avg,
-                &[arg_type],
-            )?;
+            let return_type = get_sum_avg_return_type(&agg_function, &arg_type)?;
             Arc::new(avg::AggAvg::try_new(
                 Arc::new(TryCastExpr::new(children[0].clone(), return_type.clone())),
                 return_type,
+                get_fail_on_overflow(children)?,
             )?)
         }
         AggFunction::Max => {
@@ -244,6 +271,7 @@ pub fn create_agg(
                 children[0].clone(),
                 return_type,
                 arg_type,
+                get_collect_max_elements()?,
             )?)
         }
         AggFunction::CollectSet => {
@@ -253,8 +281,247 @@ pub fn create_agg(
                 children[0].clone(),
                 return_type,
                 arg_type,
+                get_collect_max_elements()?,
             )?)
         }
+        AggFunction::StddevSamp
+        | AggFunction::StddevPop
+        | AggFunction::VarSamp
+        | AggFunction::VarPop => {
+            let variance_type = match agg_function {
+                AggFunction::StddevSamp => variance::VarianceType::StddevSamp,
+                AggFunction::StddevPop => variance::VarianceType::StddevPop,
+                AggFunction::VarSamp => variance::VarianceType::VarSamp,
+                _ => variance::VarianceType::VarPop,
+            };
+            Arc::new(variance::AggVariance::try_new(
+                children[0].clone(),
+                variance_type,
+                get_null_on_divide_by_zero(&children[1])?,
+            )?)
+        }
+        AggFunction::CovarSamp | AggFunction::CovarPop | AggFunction::Corr => {
+            let covariance_type = match agg_function {
+                AggFunction::CovarSamp => covariance::CovarianceType::CovarSamp,
+                AggFunction::CovarPop => covariance::CovarianceType::CovarPop,
+                _ => covariance::CovarianceType::Corr,
+            };
+            Arc::new(covariance::AggCovariance::try_new(
+                children[0].clone(),
+                children[1].clone(),
+                covariance_type,
+                get_null_on_divide_by_zero(&children[2])?,
+            )?)
+        }
+        AggFunction::ApproxCountDistinct => {
+            let relative_sd = match children[1]
+                .as_any()
+                .downcast_ref::<Literal>()
+                .map(|literal| literal.value())
+            {
+                Some(ScalarValue::Float64(Some(relative_sd))) => *relative_sd,
+                _ => df_execution_err!("expect float64 literal of relativeSD, got: {children:?}")?,
+            };
+            Arc::new(approx_count_distinct::AggApproxCountDistinct::try_new(
+                children[0].clone(),
+                relative_sd,
+            )?)
+        }
+        AggFunction::ApproxPercentile => {
+            // children: child, accuracy, returnPercentileArray, percentages...
+            let literal_value = |child: &Arc<dyn PhysicalExpr>| {
+                child
+                    .as_any()
+                    .downcast_ref::<Literal>()
+                    .map(|literal| literal.value().clone())
+            };
+            let accuracy = match literal_value(&children[1]) {
+                Some(ScalarValue::Int64(Some(accuracy))) => accuracy,
+                _ => df_execution_err!("expect int64 literal of accuracy, got: {children:?}")?,
+            };
+            let return_percentile_array = match literal_value(&children[2]) {
+                Some(ScalarValue::Boolean(Some(return_percentile_array))) => {
+                    return_percentile_array
+                }
+                _ => df_execution_err!(
+                    "expect boolean literal of returnPercentileArray, got: {children:?}"
+                )?,
+            };
+            let percentages = children[3..]
+                .iter()
+                .map(|child| match literal_value(child) {
+                    Some(ScalarValue::Float64(Some(percentage))) => Ok(percentage),
+                    _ => df_execution_err!("expect float64 literal of percentage, got: {child:?}"),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(approx_percentile::AggApproxPercentile::try_new(
+                children[0].clone(),
+                children[0].data_type(input_schema)?,
+                accuracy,
+                percentages,
+                return_percentile_array,
+            )?)
+        }
+        AggFunction::BloomFilter => {
+            // children: child, estimatedNumItems, numBits
+            let literal_value = |child: &Arc<dyn PhysicalExpr>, name: &str| match child
+                .as_any()
+                .downcast_ref::<Literal>()
+                .map(|literal| literal.value())
+            {
+                Some(ScalarValue::Int64(Some(value))) => Ok(*value),
+                _ => df_execution_err!("expect int64 literal of {name}, got: {child:?}"),
+            };
+            Arc::new(bloom_filter::AggBloomFilter::try_new(
+                children[0].clone(),
+                literal_value(&children[1], "estimatedNumItems")?,
+                literal_value(&children[2], "numBits")?,
+            )?)
+        }
+        AggFunction::CountDistinct | AggFunction::SumDistinct | AggFunction::AvgDistinct => {
+            let inner_agg_function = match agg_function {
+                AggFunction::CountDistinct => AggFunction::Count,
+                AggFunction::SumDistinct => AggFunction::Sum,
+                _ => AggFunction::Avg,
+            };
+            Arc::new(distinct::AggDistinct::try_new(
+                inner_agg_function,
+                children[0].clone(),
+                children[0].data_type(input_schema)?,
+                children[1..].to_vec(),
+            )?)
+        }
+        AggFunction::BitAnd | AggFunction::BitOr | AggFunction::BitXor => {
+            let op = match agg_function {
+                AggFunction::BitAnd => bitwise::BitwiseOp::And,
+                AggFunction::BitOr => bitwise::BitwiseOp::Or,
+                _ => bitwise::BitwiseOp::Xor,
+            };
+            let dt = children[0].data_type(input_schema)?;
+            Arc::new(bitwise::AggBitwise::try_new(children[0].clone(), op, dt)?)
+        }
+        AggFunction::MaxBy | AggFunction::MinBy => {
+            // children: value, ordering key
+            let value = children[0].clone();
+            let key = children[1].clone();
+            let value_type = value.data_type(input_schema)?;
+            let key_type = key.data_type(input_schema)?;
+            match agg_function {
+                AggFunction::MaxBy => Arc::new(maxmin_by::AggMaxBy::try_new(
+                    value, key, value_type, key_type,
+                )?),
+                _ => Arc::new(maxmin_by::AggMinBy::try_new(
+                    value, key, value_type, key_type,
+                )?),
+            }
+        }
+        AggFunction::Last => {
+            let dt = children[0].data_type(input_schema)?;
+            Arc::new(last::AggLast::try_new(children[0].clone(), dt)?)
+        }
+        AggFunction::LastIgnoresNull => {
+            let dt = children[0].data_type(input_schema)?;
+            Arc::new(last_ignores_null::AggLastIgnoresNull::try_new(
+                children[0].clone(),
+                dt,
+            )?)
+        }
+    })
+}
+
+/// creates an aggregate with a within-group ordering, currently only
+/// collect_list (array_agg) is supported.
+pub fn create_sorted_agg(
+    agg_function: AggFunction,
+    children: &[Arc<dyn PhysicalExpr>],
+    order_by: Vec<PhysicalSortExpr>,
+    input_schema: &SchemaRef,
+) -> Result<Arc<dyn Agg>> {
+    if order_by.is_empty() {
+        return create_agg(agg_function, children, input_schema);
+    }
+    Ok(match agg_function {
+        AggFunction::CollectList => {
+            let arg_type = children[0].data_type(input_schema)?;
+            let return_type = DataType::List(Arc::new(Field::new("item", arg_type.clone(), true)));
+            let order_by_types = order_by
+                .iter()
+                .map(|order| order.expr.data_type(input_schema))
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(collect_list::AggCollectList::try_new_sorted(
+                children[0].clone(),
+                return_type,
+                arg_type,
+                get_collect_max_elements()?,
+                order_by,
+                order_by_types,
+            )?)
+        }
+        other => df_execution_err!("aggregate function {other:?} does not support ordering")?,
+    })
+}
+
+fn get_collect_max_elements() -> Result<Option<usize>> {
+    if !is_jni_bridge_inited() {
+        return Ok(None);
+    }
+    let max_elements = conf::COLLECT_MAX_ELEMENTS.value()?;
+    Ok((max_elements >= 0).then_some(max_elements as usize))
+}
+
+/// statistical aggregates take spark's `nullOnDivideByZero` as the last
+/// child, which decides whether null or NaN is returned when dividing by zero
+fn get_null_on_divide_by_zero(child: &Arc<dyn PhysicalExpr>) -> Result<bool> {
+    match child
+        .as_any()
+        .downcast_ref::<Literal>()
+        .map(|literal| literal.value())
+    {
+        Some(ScalarValue::Boolean(Some(null_on_divide_by_zero))) => Ok(*null_on_divide_by_zero),
+        _ => df_execution_err!("expect boolean literal of nullOnDivideByZero, got: {child:?}"),
+    }
+}
+
+/// sum/avg take spark's `failOnError` (ansi mode of the plan) as an optional
+/// second child, which decides whether overflow raises an error
+fn get_fail_on_overflow(children: &[Arc<dyn PhysicalExpr>]) -> Result<bool> {
+    let Some(child) = children.get(1) else {
+        return Ok(false);
+    };
+    match child
+        .as_any()
+        .downcast_ref::<Literal>()
+        .map(|literal| literal.value())
+    {
+        Some(ScalarValue::Boolean(Some(fail_on_overflow))) => Ok(*fail_on_overflow),
+        _ => df_execution_err!("expect boolean literal of failOnError, got: {child:?}"),
+    }
+}
+
+/// widens the input of sum/avg like spark: integers to int64 (float64 for
+/// avg), floats to float64 and decimals to a wider decimal bounded by the max
+/// precision. ansi intervals keep their own type.
+fn get_sum_avg_return_type(agg_function: &AggFunction, arg_type: &DataType) -> Result<DataType> {
+    let is_avg = matches!(agg_function, AggFunction::Avg);
+    Ok(match arg_type {
+        DataType::Interval(IntervalUnit::YearMonth) | DataType::Duration(_) => arg_type.clone(),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 if !is_avg => {
+            DataType::Int64
+        }
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Float32
+        | DataType::Float64 => DataType::Float64,
+        &DataType::Decimal128(prec, scale) if !is_avg => {
+            DataType::Decimal128((prec + 10).min(DECIMAL128_MAX_PRECISION), scale)
+        }
+        &DataType::Decimal128(prec, scale) => DataType::Decimal128(
+            (prec + 4).min(DECIMAL128_MAX_PRECISION),
+            (scale + 4).min(DECIMAL128_MAX_SCALE),
+        ),
+        other => df_execution_err!("unsupported data type in {agg_function:?}: {other}")?,
     })
 }
 
@@ -15,7 +15,6 @@
 use std::{
     any::Any,
     fmt::{Debug, Formatter},
-    ops::Add,
     sync::{atomic::AtomicUsize, Arc},
 };
 
@@ -24,7 +23,7 @@ use datafusion::{
     common::{Result, ScalarValue},
     physical_expr::PhysicalExpr,
 };
-use datafusion_ext_commons::df_unimplemented_err;
+use datafusion_ext_commons::{df_execution_err, df_unimplemented_err};
 use paste::paste;
 
 use crate::agg::{
@@ -33,20 +32,38 @@ use crate::agg::{
     WithMemTracking,
 };
 
+/// how sum() behaves when the result overflows, same as spark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverflowMode {
+    /// integral sums wrap around in non-ansi mode
+    Wrap,
+    /// decimal sums become null in non-ansi mode
+    Null,
+    /// ansi mode and interval sums raise ARITHMETIC_OVERFLOW
+    Error,
+}
+
 pub struct AggSum {
     child: Arc<dyn PhysicalExpr>,
     data_type: DataType,
+    fail_on_overflow: bool,
+    overflow_mode: OverflowMode,
+    decimal_max: i128,
     accums_initial: Vec<AccumInitialValue>,
     accum_state_val_addr: AccumStateValAddr,
-    partial_updater: fn(&Self, &mut RefAccumStateRow, &ArrayRef, usize),
-    partial_batch_updater: fn(&Self, &mut [RefAccumStateRow], &ArrayRef),
-    partial_buf_merger: fn(&Self, &mut RefAccumStateRow, &mut RefAccumStateRow),
+    accum_state_val_addr_overflowed: AccumStateValAddr,
+    partial_updater: fn(&Self, &mut RefAccumStateRow, &ArrayRef, usize) -> Result<()>,
+    partial_batch_updater: fn(&Self, &mut [RefAccumStateRow], &ArrayRef) -> Result<()>,
+    partial_buf_merger: fn(&Self, &mut RefAccumStateRow, &mut RefAccumStateRow) -> Result<()>,
     mem_used_tracker: AtomicUsize,
 }
 
 impl WithAggBufAddrs for AggSum {
     fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
         self.accum_state_val_addr = accum_state_val_addrs[0];
+        if self.overflow_mode == OverflowMode::Null {
+            self.accum_state_val_addr_overflowed = accum_state_val_addrs[1];
+        }
     }
 }
 
@@ -57,24 +74,105 @@ impl WithMemTracking for AggSum {
 }
 
 impl AggSum {
-    pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
-        let accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::try_from(
-            &data_type,
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        fail_on_overflow: bool,
+    ) -> Result<Self> {
+        let (overflow_mode, decimal_max) = match &data_type {
+            DataType::Decimal128(prec, _) => {
+                let decimal_max = 10i128.pow(*prec as u32) - 1;
+                if fail_on_overflow {
+                    (OverflowMode::Error, decimal_max)
+                } else {
+                    (OverflowMode::Null, decimal_max)
+                }
+            }
+            DataType::Interval(_) | DataType::Duration(_) => (OverflowMode::Error, i128::MAX),
+            _ if fail_on_overflow => (OverflowMode::Error, i128::MAX),
+            _ => (OverflowMode::Wrap, i128::MAX),
+        };
+
+        // intervals are summed in their native integral representations
+        let mut accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::try_from(
+            match &data_type {
+                DataType::Interval(IntervalUnit::YearMonth) => &DataType::Int32,
+                DataType::Duration(_) => &DataType::Int64,
+                other => other,
+            },
         )?)];
+        if overflow_mode == OverflowMode::Null {
+            accums_initial.push(AccumInitialValue::Scalar(ScalarValue::Null)); // overflowed
+        }
+
         let partial_updater = get_partial_updater(&data_type)?;
         let partial_batch_updater = get_partial_batch_updater(&data_type)?;
         let partial_buf_merger = get_partial_buf_merger(&data_type)?;
         Ok(Self {
             child,
             data_type,
+            fail_on_overflow,
+            overflow_mode,
+            decimal_max,
             accums_initial,
             accum_state_val_addr: AccumStateValAddr::default(),
+            accum_state_val_addr_overflowed: AccumStateValAddr::default(),
             partial_updater,
             partial_batch_updater,
             partial_buf_merger,
             mem_used_tracker: AtomicUsize::new(0),
         })
     }
+
+    fn is_overflowed(&self, acc: &RefAccumStateRow) -> bool {
+        self.overflow_mode == OverflowMode::Null
+            && acc.is_fixed_valid(self.accum_state_val_addr_overflowed)
+    }
+
+    fn set_overflowed(&self, acc: &mut RefAccumStateRow) {
+        acc.set_fixed_valid(self.accum_state_val_addr, false);
+        acc.set_fixed_valid(self.accum_state_val_addr_overflowed, true);
+    }
+
+    fn update_value<T: SumValue>(&self, acc: &mut RefAccumStateRow, v: T) -> Result<()> {
+        let addr = self.accum_state_val_addr;
+        if !acc.is_fixed_valid(addr) {
+            if !self.is_overflowed(acc) {
+                acc.set_fixed_value::<T>(addr, v);
+                acc.set_fixed_valid(addr, true);
+            }
+            return Ok(());
+        }
+
+        let w = acc.fixed_value::<T>(addr);
+        let sum = match self.overflow_mode {
+            OverflowMode::Wrap => Some(w.wrapping_sum(v)),
+            _ => w
+                .checked_sum(v)
+                .filter(|sum| sum.within_decimal_max(self.decimal_max)),
+        };
+        match sum {
+            Some(sum) => acc.set_fixed_value::<T>(addr, sum),
+            None if self.overflow_mode == OverflowMode::Null => self.set_overflowed(acc),
+            None => return self.overflow_err(),
+        }
+        Ok(())
+    }
+
+    fn overflow_err(&self) -> Result<()> {
+        let ansi_hint = " If necessary set \"spark.sql.ansi.enabled\" to \"false\" \
+            to bypass this error.";
+        match &self.data_type {
+            DataType::Interval(_) | DataType::Duration(_) => {
+                df_execution_err!("[ARITHMETIC_OVERFLOW] interval overflow in sum().")
+            }
+            DataType::Decimal128(prec, scale) => df_execution_err!(
+                "[ARITHMETIC_OVERFLOW] decimal overflow in sum(), result cannot be represented \
+                    as Decimal({prec}, {scale}).{ansi_hint}"
+            ),
+            _ => df_execution_err!("[ARITHMETIC_OVERFLOW] long overflow in sum().{ansi_hint}"),
+        }
+    }
 }
 
 impl Debug for AggSum {
@@ -96,6 +194,7 @@ impl Agg for AggSum {
         Ok(Arc::new(Self::try_new(
             exprs[0].clone(),
             self.data_type.clone(),
+            self.fail_on_overflow,
         )?))
     }
 
@@ -130,8 +229,7 @@ impl Agg for AggSum {
         row_idx: usize,
     ) -> Result<()> {
         let partial_updater = self.partial_updater;
-        partial_updater(self, acc, &values[0], row_idx);
-        Ok(())
+        partial_updater(self, acc, &values[0], row_idx)
     }
 
     fn partial_batch_update(
@@ -140,8 +238,7 @@ impl Agg for AggSum {
         values: &[ArrayRef],
     ) -> Result<()> {
         let partial_batch_updater = self.partial_batch_updater;
-        partial_batch_updater(self, accs, &values[0]);
-        Ok(())
+        partial_batch_updater(self, accs, &values[0])
     }
 
     fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
@@ -149,8 +246,14 @@ impl Agg for AggSum {
             ($ty:ident) => {{
                 type TArray = paste! {[<$ty Array>]};
                 let value = values[0].as_any().downcast_ref::<TArray>().unwrap();
-                if let Some(sum) = arrow::compute::sum(value) {
-                    partial_update_prim(acc, self.accum_state_val_addr, sum);
+                if self.overflow_mode == OverflowMode::Wrap {
+                    if let Some(sum) = arrow::compute::sum(value) {
+                        self.update_value(acc, sum)?;
+                    }
+                } else {
+                    for v in value.iter().flatten() {
+                        self.update_value(acc, v)?;
+                    }
                 }
             }};
         }
@@ -167,6 +270,11 @@ impl Agg for AggSum {
             DataType::UInt32 => handle!(UInt32),
             DataType::UInt64 => handle!(UInt64),
             DataType::Decimal128(..) => handle!(Decimal128),
+            DataType::Interval(IntervalUnit::YearMonth) => handle!(IntervalYearMonth),
+            DataType::Duration(TimeUnit::Second) => handle!(DurationSecond),
+            DataType::Duration(TimeUnit::Millisecond) => handle!(DurationMillisecond),
+            DataType::Duration(TimeUnit::Microsecond) => handle!(DurationMicrosecond),
+            DataType::Duration(TimeUnit::Nanosecond) => handle!(DurationNanosecond),
             other => df_unimplemented_err!("unsupported data type in sum(): {other}")?,
         }
         Ok(())
@@ -178,8 +286,7 @@ impl Agg for AggSum {
         acc2: &mut RefAccumStateRow,
     ) -> Result<()> {
         let partial_buf_merger = self.partial_buf_merger;
-        partial_buf_merger(self, acc1, acc2);
-        Ok(())
+        partial_buf_merger(self, acc1, acc2)
     }
 
     fn partial_batch_merge(
@@ -189,49 +296,130 @@ impl Agg for AggSum {
     ) -> Result<()> {
         let partial_buf_merger = self.partial_buf_merger;
         for (acc, merging_acc) in accs.iter_mut().zip(merging_accs) {
-            partial_buf_merger(self, acc, merging_acc);
+            partial_buf_merger(self, acc, merging_acc)?;
         }
         Ok(())
     }
 
     fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
-        default_final_merge_with_addr(self, acc, self.accum_state_val_addr)
+        let addr = self.accum_state_val_addr;
+        macro_rules! handle_interval {
+            ($ty:ident) => {{
+                let v = if acc.is_fixed_valid(addr) {
+                    Some(acc.fixed_value(addr))
+                } else {
+                    None
+                };
+                Ok(ScalarValue::$ty(v))
+            }};
+        }
+        match &self.data_type {
+            DataType::Interval(IntervalUnit::YearMonth) => handle_interval!(IntervalYearMonth),
+            DataType::Duration(TimeUnit::Second) => handle_interval!(DurationSecond),
+            DataType::Duration(TimeUnit::Millisecond) => handle_interval!(DurationMillisecond),
+            DataType::Duration(TimeUnit::Microsecond) => handle_interval!(DurationMicrosecond),
+            DataType::Duration(TimeUnit::Nanosecond) => handle_interval!(DurationNanosecond),
+            _ => default_final_merge_with_addr(self, acc, addr),
+        }
     }
 
     fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
-        default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr)
+        let addr = self.accum_state_val_addr;
+        macro_rules! handle_interval {
+            ($ty:ident) => {{
+                type TType = paste! {[<$ty Type>]};
+                type TNative = <TType as ArrowPrimitiveType>::Native;
+                let array: PrimitiveArray<TType> = accs
+                    .iter()
+                    .map(|acc| {
+                        acc.is_fixed_valid(addr)
+                            .then(|| acc.fixed_value::<TNative>(addr))
+                    })
+                    .collect();
+                Ok(Arc::new(array))
+            }};
+        }
+        match &self.data_type {
+            DataType::Interval(IntervalUnit::YearMonth) => handle_interval!(IntervalYearMonth),
+            DataType::Duration(TimeUnit::Second) => handle_interval!(DurationSecond),
+            DataType::Duration(TimeUnit::Millisecond) => handle_interval!(DurationMillisecond),
+            DataType::Duration(TimeUnit::Microsecond) => handle_interval!(DurationMicrosecond),
+            DataType::Duration(TimeUnit::Nanosecond) => handle_interval!(DurationNanosecond),
+            _ => default_final_batch_merge_with_addr(self, accs, addr),
+        }
     }
 }
 
-fn partial_update_prim<T: Copy + Add<Output = T>>(
-    acc: &mut RefAccumStateRow,
-    addr: AccumStateValAddr,
-    v: T,
-) {
-    if acc.is_fixed_valid(addr) {
-        acc.update_fixed_value::<T>(addr, |w| w + v);
-    } else {
-        acc.set_fixed_value::<T>(addr, v);
-        acc.set_fixed_valid(addr, true);
+/// native values that can be summed with or without overflow checking
+trait SumValue: Copy {
+    fn wrapping_sum(self, other: Self) -> Self;
+    fn checked_sum(self, other: Self) -> Option<Self>;
+    fn within_decimal_max(self, _decimal_max: i128) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_sum_value_for_integer {
+    ($($ty:ty),*) => {$(
+        impl SumValue for $ty {
+            fn wrapping_sum(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            fn checked_sum(self, other: Self) -> Option<Self> {
+                self.checked_add(other)
+            }
+        }
+    )*};
+}
+impl_sum_value_for_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+macro_rules! impl_sum_value_for_float {
+    ($($ty:ty),*) => {$(
+        impl SumValue for $ty {
+            fn wrapping_sum(self, other: Self) -> Self {
+                self + other
+            }
+
+            fn checked_sum(self, other: Self) -> Option<Self> {
+                Some(self + other)
+            }
+        }
+    )*};
+}
+impl_sum_value_for_float!(f32, f64);
+
+impl SumValue for i128 {
+    fn wrapping_sum(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    fn checked_sum(self, other: Self) -> Option<Self> {
+        self.checked_add(other)
+    }
+
+    fn within_decimal_max(self, decimal_max: i128) -> bool {
+        (-decimal_max..=decimal_max).contains(&self)
     }
 }
 
 fn get_partial_updater(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut RefAccumStateRow, &ArrayRef, usize)> {
+) -> Result<fn(&AggSum, &mut RefAccumStateRow, &ArrayRef, usize) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, acc, v, i| {
                 type TArray = paste! {[<$ty Array>]};
                 let value = v.as_any().downcast_ref::<TArray>().unwrap();
                 if value.is_valid(i) {
-                    partial_update_prim(acc, this.accum_state_val_addr, value.value(i));
+                    this.update_value(acc, value.value(i))?;
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _, _| ()),
+        DataType::Null => Ok(|_, _, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
@@ -243,13 +431,18 @@ fn get_partial_updater(
         DataType::UInt32 => fn_fixed!(UInt32),
         DataType::UInt64 => fn_fixed!(UInt64),
         DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Interval(IntervalUnit::YearMonth) => fn_fixed!(IntervalYearMonth),
+        DataType::Duration(TimeUnit::Second) => fn_fixed!(DurationSecond),
+        DataType::Duration(TimeUnit::Millisecond) => fn_fixed!(DurationMillisecond),
+        DataType::Duration(TimeUnit::Microsecond) => fn_fixed!(DurationMicrosecond),
+        DataType::Duration(TimeUnit::Nanosecond) => fn_fixed!(DurationNanosecond),
         other => df_unimplemented_err!("unsupported data type in sum(): {other}"),
     }
 }
 
 fn get_partial_batch_updater(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut [RefAccumStateRow], &ArrayRef)> {
+) -> Result<fn(&AggSum, &mut [RefAccumStateRow], &ArrayRef) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, accs, v| {
@@ -257,14 +450,15 @@ fn get_partial_batch_updater(
                 let value = v.as_any().downcast_ref::<TArray>().unwrap();
                 for (acc, value) in accs.iter_mut().zip(value.iter()) {
                     if let Some(value) = value {
-                        partial_update_prim(acc, this.accum_state_val_addr, value);
+                        this.update_value(acc, value)?;
                     }
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Null => Ok(|_, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
@@ -276,27 +470,35 @@ fn get_partial_batch_updater(
         DataType::UInt32 => fn_fixed!(UInt32),
         DataType::UInt64 => fn_fixed!(UInt64),
         DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Interval(IntervalUnit::YearMonth) => fn_fixed!(IntervalYearMonth),
+        DataType::Duration(TimeUnit::Second) => fn_fixed!(DurationSecond),
+        DataType::Duration(TimeUnit::Millisecond) => fn_fixed!(DurationMillisecond),
+        DataType::Duration(TimeUnit::Microsecond) => fn_fixed!(DurationMicrosecond),
+        DataType::Duration(TimeUnit::Nanosecond) => fn_fixed!(DurationNanosecond),
         other => df_unimplemented_err!("unsupported data type in sum(): {other}"),
     }
 }
 
 fn get_partial_buf_merger(
     dt: &DataType,
-) -> Result<fn(&AggSum, &mut RefAccumStateRow, &mut RefAccumStateRow)> {
+) -> Result<fn(&AggSum, &mut RefAccumStateRow, &mut RefAccumStateRow) -> Result<()>> {
     macro_rules! fn_fixed {
         ($ty:ident) => {{
             Ok(|this, acc1, acc2| {
                 type TType = paste! {[<$ty Type>]};
                 type TNative = <TType as ArrowPrimitiveType>::Native;
-                if acc2.is_fixed_valid(this.accum_state_val_addr) {
+                if this.is_overflowed(acc2) {
+                    this.set_overflowed(acc1);
+                } else if acc2.is_fixed_valid(this.accum_state_val_addr) {
                     let v = acc2.fixed_value::<TNative>(this.accum_state_val_addr);
-                    partial_update_prim(acc1, this.accum_state_val_addr, v);
+                    this.update_value(acc1, v)?;
                 }
+                Ok(())
             })
         }};
     }
     match dt {
-        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Null => Ok(|_, _, _| Ok(())),
         DataType::Float32 => fn_fixed!(Float32),
         DataType::Float64 => fn_fixed!(Float64),
         DataType::Int8 => fn_fixed!(Int8),
@@ -308,6 +510,175 @@ fn get_partial_buf_merger(
         DataType::UInt32 => fn_fixed!(UInt32),
         DataType::UInt64 => fn_fixed!(UInt64),
         DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        DataType::Interval(IntervalUnit::YearMonth) => fn_fixed!(IntervalYearMonth),
+        DataType::Duration(TimeUnit::Second) => fn_fixed!(DurationSecond),
+        DataType::Duration(TimeUnit::Millisecond) => fn_fixed!(DurationMillisecond),
+        DataType::Duration(TimeUnit::Microsecond) => fn_fixed!(DurationMicrosecond),
+        DataType::Duration(TimeUnit::Nanosecond) => fn_fixed!(DurationNanosecond),
         other => df_unimplemented_err!("unsupported data type in sum(): {other}"),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use datafusion::physical_expr::expressions::{Column, Literal};
+
+    use super::*;
+    use crate::agg::{acc::create_acc_from_initial_value, create_agg, AggFunction};
+
+    fn sum_all(
+        data_type: DataType,
+        fail_on_overflow: bool,
+        values: ArrayRef,
+    ) -> Result<ScalarValue> {
+        let mut agg = AggSum::try_new(Arc::new(Column::new("v", 0)), data_type, fail_on_overflow)?;
+        let (mut acc1, addrs) = create_acc_from_initial_value(agg.accums_initial())?;
+        let (mut acc2, _) = create_acc_from_initial_value(agg.accums_initial())?;
+        agg.set_accum_state_val_addrs(&addrs);
+
+        // update the first half row by row and the second half in batch, then merge
+        let values = agg.prepare_partial_args(&[values])?.remove(0);
+        let mid = values.len() / 2;
+        for i in 0..mid {
+            agg.partial_update(&mut acc1.as_mut(), &[values.clone()], i)?;
+        }
+        agg.partial_update_all(&mut acc2.as_mut(), &[values.slice(mid, values.len() - mid)])?;
+        agg.partial_merge(&mut acc1.as_mut(), &mut acc2.as_mut())?;
+        agg.final_merge(&mut acc1.as_mut())
+    }
+
+    #[test]
+    fn test_sum_int64_overflow() -> Result<()> {
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![i64::MAX, 1, 1, 1]));
+
+        // wraps around in non-ansi mode
+        let sum = sum_all(DataType::Int64, false, values.clone())?;
+        assert_eq!(sum, ScalarValue::Int64(Some(i64::MIN + 2)));
+
+        // raises ARITHMETIC_OVERFLOW in ansi mode
+        let err = sum_all(DataType::Int64, true, values).unwrap_err();
+        assert!(err.to_string().contains("[ARITHMETIC_OVERFLOW]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_decimal_overflow() -> Result<()> {
+        let max = 10i128.pow(20) - 1;
+        let decimal_type = DataType::Decimal128(20, 2);
+        let values: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![Some(max), Some(1), Some(-2), None])
+                .with_precision_and_scale(20, 2)?,
+        );
+
+        // overflowed sum is null in non-ansi mode, even if later values bring it back
+        let sum = sum_all(decimal_type.clone(), false, values.clone())?;
+        assert_eq!(sum, ScalarValue::Decimal128(None, 20, 2));
+
+        // raises ARITHMETIC_OVERFLOW in ansi mode
+        let err = sum_all(decimal_type.clone(), true, values).unwrap_err();
+        assert!(err.to_string().contains("[ARITHMETIC_OVERFLOW]"));
+
+        // sums not exceeding the precision are not affected
+        let values: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![Some(max), Some(-2), Some(1), None])
+                .with_precision_and_scale(20, 2)?,
+        );
+        let sum = sum_all(decimal_type, false, values)?;
+        assert_eq!(sum, ScalarValue::Decimal128(Some(max - 1), 20, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_interval() -> Result<()> {
+        let values: ArrayRef = Arc::new(IntervalYearMonthArray::from(vec![
+            Some(12),
+            None,
+            Some(3),
+            Some(-1),
+        ]));
+        let sum = sum_all(DataType::Interval(IntervalUnit::YearMonth), false, values)?;
+        assert_eq!(sum, ScalarValue::IntervalYearMonth(Some(14)));
+
+        let values: ArrayRef = Arc::new(DurationMicrosecondArray::from(vec![
+            Some(1_000_000),
+            Some(2_500_000),
+            None,
+            Some(500_000),
+        ]));
+        let sum = sum_all(DataType::Duration(TimeUnit::Microsecond), false, values)?;
+        assert_eq!(sum, ScalarValue::DurationMicrosecond(Some(4_000_000)));
+
+        // interval overflow always fails, regardless of ansi mode
+        let values: ArrayRef = Arc::new(DurationMicrosecondArray::from(vec![i64::MAX, 1]));
+        let err = sum_all(DataType::Duration(TimeUnit::Microsecond), false, values).unwrap_err();
+        assert!(err.to_string().contains("[ARITHMETIC_OVERFLOW]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_avg_widening() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, true),
+            Field::new("f", DataType::Float32, true),
+            Field::new("d", DataType::Decimal128(20, 2), true),
+            Field::new("m", DataType::Decimal128(36, 36), true),
+        ]));
+        let return_type = |agg_function: AggFunction, col: &str| -> Result<DataType> {
+            let child = Arc::new(Column::new_with_schema(col, &schema)?);
+            Ok(create_agg(agg_function, &[child], &schema)?
+                .data_type()
+                .clone())
+        };
+        assert_eq!(return_type(AggFunction::Sum, "i")?, DataType::Int64);
+        assert_eq!(return_type(AggFunction::Avg, "i")?, DataType::Float64);
+        assert_eq!(return_type(AggFunction::Sum, "f")?, DataType::Float64);
+        assert_eq!(return_type(AggFunction::Avg, "f")?, DataType::Float64);
+        assert_eq!(
+            return_type(AggFunction::Sum, "d")?,
+            DataType::Decimal128(30, 2)
+        );
+        assert_eq!(
+            return_type(AggFunction::Avg, "d")?,
+            DataType::Decimal128(24, 6)
+        );
+        assert_eq!(
+            return_type(AggFunction::Sum, "m")?,
+            DataType::Decimal128(38, 36)
+        );
+        assert_eq!(
+            return_type(AggFunction::Avg, "m")?,
+            DataType::Decimal128(38, 38)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_fail_on_error_child() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, true)]));
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![i64::MAX, 1]));
+        let sum_with_ansi = |ansi: Option<bool>| -> Result<ScalarValue> {
+            let mut children: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("v", 0))];
+            if let Some(ansi) = ansi {
+                children.push(Arc::new(Literal::new(ScalarValue::Boolean(Some(ansi)))));
+            }
+            let mut agg = create_agg(AggFunction::Sum, &children, &schema)?;
+            let (mut acc, addrs) = create_acc_from_initial_value(agg.accums_initial())?;
+            Arc::get_mut(&mut agg)
+                .expect("agg is not shared")
+                .set_accum_state_val_addrs(&addrs);
+            let values = agg.prepare_partial_args(&[values.clone()])?;
+            agg.partial_update_all(&mut acc.as_mut(), &values)?;
+            agg.final_merge(&mut acc.as_mut())
+        };
+
+        // failOnError defaults to false if not given by the plan
+        assert_eq!(sum_with_ansi(None)?, ScalarValue::Int64(Some(i64::MIN)));
+        assert_eq!(
+            sum_with_ansi(Some(false))?,
+            ScalarValue::Int64(Some(i64::MIN))
+        );
+        let err = sum_with_ansi(Some(true)).unwrap_err();
+        assert!(err.to_string().contains("[ARITHMETIC_OVERFLOW]"));
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::downcast_any;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarianceType {
+    VarSamp,
+    VarPop,
+    StddevSamp,
+    StddevPop,
+}
+
+/// variance and standard deviation, computed with the same central moment
+/// algorithm as spark's `CentralMomentAgg`, so that results are identical.
+pub struct AggVariance {
+    child: Arc<dyn PhysicalExpr>,
+    variance_type: VarianceType,
+    null_on_divide_by_zero: bool,
+    data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addrs: Vec<AccumStateValAddr>, // n, avg, m2
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggVariance {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addrs = accum_state_val_addrs[..3].to_vec();
+    }
+}
+
+impl WithMemTracking for AggVariance {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggVariance {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        variance_type: VarianceType,
+        null_on_divide_by_zero: bool,
+    ) -> Result<Self> {
+        let accums_initial = vec![AccumInitialValue::Scalar(ScalarValue::Float64(Some(0.0))); 3];
+        Ok(Self {
+            child,
+            variance_type,
+            null_on_divide_by_zero,
+            data_type: DataType::Float64,
+            accums_initial,
+            accum_state_val_addrs: vec![AccumStateValAddr::default(); 3],
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn load_state(&self, acc: &RefAccumStateRow) -> MomentState {
+        let addrs = &self.accum_state_val_addrs;
+        MomentState {
+            n: acc.fixed_value(addrs[0]),
+            avg: acc.fixed_value(addrs[1]),
+            m2: acc.fixed_value(addrs[2]),
+        }
+    }
+
+    fn save_state(&self, acc: &mut RefAccumStateRow, state: MomentState) {
+        let addrs = &self.accum_state_val_addrs;
+        acc.set_fixed_value(addrs[0], state.n);
+        acc.set_fixed_value(addrs[1], state.avg);
+        acc.set_fixed_value(addrs[2], state.m2);
+    }
+
+    fn evaluate(&self, state: MomentState) -> Option<f64> {
+        let MomentState { n, m2, .. } = state;
+        if n == 0.0 {
+            return None;
+        }
+        let variance = match self.variance_type {
+            VarianceType::VarPop | VarianceType::StddevPop => m2 / n,
+            VarianceType::VarSamp | VarianceType::StddevSamp => {
+                if n == 1.0 {
+                    return (!self.null_on_divide_by_zero).then_some(f64::NAN);
+                }
+                m2 / (n - 1.0)
+            }
+        };
+        Some(match self.variance_type {
+            VarianceType::StddevSamp | VarianceType::StddevPop => variance.sqrt(),
+            VarianceType::VarSamp | VarianceType::VarPop => variance,
+        })
+    }
+}
+
+impl Debug for AggVariance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}({:?})", self.variance_type, self.child)
+    }
+}
+
+impl Agg for AggVariance {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.variance_type,
+            self.null_on_divide_by_zero,
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        Ok(vec![datafusion_ext_commons::cast::cast(
+            &partial_inputs[0],
+            &DataType::Float64,
+        )?])
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let values = downcast_any!(values[0], Float64Array)?;
+        if values.is_valid(row_idx) {
+            let mut state = self.load_state(acc);
+            state.update(values.value(row_idx));
+            self.save_state(acc, state);
+        }
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let values = downcast_any!(values[0], Float64Array)?;
+        for (acc, value) in accs.iter_mut().zip(values.iter()) {
+            if let Some(value) = value {
+                let mut state = self.load_state(acc);
+                state.update(value);
+                self.save_state(acc, state);
+            }
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        let values = downcast_any!(values[0], Float64Array)?;
+        let mut state = self.load_state(acc);
+        for value in values.iter().flatten() {
+            state.update(value);
+        }
+        self.save_state(acc, state);
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let mut state = self.load_state(acc1);
+        state.merge(self.load_state(acc2));
+        self.save_state(acc1, state);
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.evaluate(self.load_state(acc))))
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        Ok(Arc::new(
+            accs.iter()
+                .map(|acc| self.evaluate(self.load_state(acc)))
+                .collect::<Float64Array>(),
+        ))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct MomentState {
+    n: f64,
+    avg: f64,
+    m2: f64,
+}
+
+impl MomentState {
+    fn update(&mut self, value: f64) {
+        let n = self.n + 1.0;
+        let delta = value - self.avg;
+        let delta_n = delta / n;
+        self.n = n;
+        self.avg += delta_n;
+        self.m2 += delta * (delta - delta_n);
+    }
+
+    fn merge(&mut self, other: MomentState) {
+        let (n1, n2) = (self.n, other.n);
+        let n = n1 + n2;
+        let delta = other.avg - self.avg;
+        let delta_n = if n == 0.0 { 0.0 } else { delta / n };
+        self.n = n;
+        self.avg += delta_n * n2;
+        self.m2 = self.m2 + other.m2 + delta * delta_n * n1 * n2;
+    }
+}
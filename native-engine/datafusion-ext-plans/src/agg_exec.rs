@@ -375,22 +375,30 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::Int32Array,
-        datatypes::{DataType, Field, Schema},
+        array::{
+            ArrayRef, AsArray, Decimal128Array, Float64Array, Int32Array, Int64Array, StringArray,
+        },
+        compute::SortOptions,
+        datatypes::{DataType, Field, Int32Type, Int64Type, Schema, SchemaRef},
         record_batch::RecordBatch,
     };
     use datafusion::{
         assert_batches_sorted_eq,
         common::{Result, ScalarValue},
-        physical_expr::{expressions as phys_expr, expressions::Column},
+        physical_expr::{
+            expressions as phys_expr, expressions::Column, PhysicalExpr, PhysicalSortExpr,
+        },
         physical_plan::{common, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
+    use datafusion_ext_commons::spark_bloom_filter;
 
     use crate::{
         agg::{
-            create_agg,
-            AggExecMode::HashAgg,
+            collect_list::AggCollectList,
+            collect_set::AggCollectSet,
+            create_agg, create_sorted_agg, Agg,
+            AggExecMode::{self, HashAgg, SortAgg},
             AggExpr, AggFunction,
             AggMode::{Final, Partial},
             GroupingExpr,
@@ -451,6 +459,80 @@ mod test {
         Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap())
     }
 
+    /// runs aggregates grouped by the first column, in partial and then final
+    /// mode, returns output batches of the final aggregate
+    async fn run_partial_final(
+        schema: &SchemaRef,
+        batches: Vec<RecordBatch>,
+        aggs: Vec<(&str, AggFunction, Vec<Arc<dyn PhysicalExpr>>)>,
+    ) -> Result<Vec<RecordBatch>> {
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function, children)| {
+                Ok((name, create_agg(agg_function, &children, schema)?))
+            })
+            .collect::<Result<_>>()?;
+        run_partial_final_aggs(HashAgg, schema, batches, aggs).await
+    }
+
+    async fn run_partial_final_aggs(
+        exec_mode: AggExecMode,
+        schema: &SchemaRef,
+        batches: Vec<RecordBatch>,
+        aggs: Vec<(&str, Arc<dyn Agg>)>,
+    ) -> Result<Vec<RecordBatch>> {
+        let input = Arc::new(MemoryExec::try_new(&[batches], schema.clone(), None)?);
+        let grouping_exprs = vec![GroupingExpr {
+            field_name: schema.field(0).name().clone(),
+            expr: Arc::new(Column::new(schema.field(0).name(), 0)),
+        }];
+        let aggs_agg_expr = aggs
+            .into_iter()
+            .map(|(name, agg)| AggExpr {
+                field_name: name.to_string(),
+                mode: Partial,
+                agg,
+            })
+            .collect::<Vec<_>>();
+
+        let agg_exec_partial = AggExec::try_new(
+            exec_mode,
+            grouping_exprs.clone(),
+            aggs_agg_expr.clone(),
+            0,
+            false,
+            input,
+        )?;
+        let agg_exec_final = AggExec::try_new(
+            exec_mode,
+            grouping_exprs,
+            aggs_agg_expr
+                .into_iter()
+                .map(|mut agg| {
+                    let null_exprs = agg
+                        .agg
+                        .exprs()
+                        .iter()
+                        .map(|_| {
+                            Arc::new(phys_expr::Literal::new(ScalarValue::Null))
+                                as Arc<dyn PhysicalExpr>
+                        })
+                        .collect();
+                    agg.agg = agg.agg.with_new_exprs(null_exprs)?;
+                    agg.mode = Final;
+                    Ok(agg)
+                })
+                .collect::<Result<_>>()?,
+            0,
+            false,
+            Arc::new(agg_exec_partial),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        common::collect(agg_exec_final.execute(0, task_ctx)?).await
+    }
+
     #[tokio::test]
     async fn test_agg() -> Result<()> {
         MemManager::init(10000);
@@ -632,4 +714,744 @@ mod test {
         assert_batches_sorted_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_agg_statistical() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("x", DataType::Int32, true),
+            Field::new("y", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 1, 1, 2, 3, 3])),
+                Arc::new(Int32Array::from(vec![
+                    Some(3),
+                    Some(1),
+                    Some(4),
+                    None,
+                    Some(1),
+                    Some(5),
+                    Some(5),
+                    None,
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![
+                    Some(2),
+                    Some(7),
+                    Some(1),
+                    Some(3),
+                    Some(8),
+                    Some(2),
+                    Some(1),
+                    Some(1),
+                    None,
+                ])),
+            ],
+        )?;
+        let x = phys_expr::col("x", &schema)?;
+        let y = phys_expr::col("y", &schema)?;
+        let null_on_divide_by_zero = |v: bool| -> Arc<dyn PhysicalExpr> {
+            Arc::new(phys_expr::Literal::new(ScalarValue::Boolean(Some(v))))
+        };
+        let aggs = [
+            ("var_samp", AggFunction::VarSamp, vec![x.clone()], true),
+            ("var_pop", AggFunction::VarPop, vec![x.clone()], true),
+            (
+                "stddev_samp",
+                AggFunction::StddevSamp,
+                vec![x.clone()],
+                true,
+            ),
+            ("stddev_pop", AggFunction::StddevPop, vec![x.clone()], true),
+            (
+                "covar_samp",
+                AggFunction::CovarSamp,
+                vec![x.clone(), y.clone()],
+                true,
+            ),
+            (
+                "covar_pop",
+                AggFunction::CovarPop,
+                vec![x.clone(), y.clone()],
+                true,
+            ),
+            ("corr", AggFunction::Corr, vec![x.clone(), y.clone()], true),
+            (
+                "var_samp_legacy",
+                AggFunction::VarSamp,
+                vec![x.clone()],
+                false,
+            ),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function, mut children, null_on_div_by_zero)| {
+                children.push(null_on_divide_by_zero(null_on_div_by_zero));
+                (name, agg_function, children)
+            })
+            .collect();
+        let batches = run_partial_final(&schema, vec![batch], aggs).await?;
+        let expected = vec![
+            "+---+----------+---------+--------------------+------------+------------+-----------+---------------------+-----------------+",
+            "| g | var_samp | var_pop | stddev_samp        | stddev_pop | covar_samp | covar_pop | corr                | var_samp_legacy |",
+            "+---+----------+---------+--------------------+------------+------------+-----------+---------------------+-----------------+",
+            "| 1 | 3.2      | 2.56    | 1.7888543819998317 | 1.6        | -5.25      | -4.2      | -0.9057110466368399 | 3.2             |",
+            "| 2 |          | 0.0     |                    | 0.0        |            | 0.0       |                     | NaN             |",
+            "| 3 |          |         |                    |            |            |           |                     |                 |",
+            "+---+----------+---------+--------------------+------------+------------+-----------+---------------------+-----------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    /// runs approx_count_distinct(v) with spark's default relativeSD grouped
+    /// by g, returns sorted (g, estimate) pairs. expected estimates in tests
+    /// are computed by a java transcription of spark's XxHash64Function and
+    /// HyperLogLogPlusPlusHelper (p=9, linear counting threshold 400, bias
+    /// correction below 5m=2560)
+    async fn approx_count_distinct(batches: Vec<RecordBatch>) -> Result<Vec<(i32, i64)>> {
+        let schema = batches[0].schema();
+        let batches = run_partial_final(
+            &schema,
+            batches,
+            vec![(
+                "approx_count_distinct",
+                AggFunction::ApproxCountDistinct,
+                vec![
+                    phys_expr::col("v", &schema)?,
+                    Arc::new(phys_expr::Literal::new(ScalarValue::Float64(Some(0.05)))),
+                ],
+            )],
+        )
+        .await?;
+
+        let mut results = vec![];
+        for batch in &batches {
+            let gs = batch.column(0).as_primitive::<Int32Type>();
+            let vs = batch.column(1).as_primitive::<Int64Type>();
+            results.extend(gs.values().iter().copied().zip(vs.values().iter().copied()));
+        }
+        results.sort();
+        Ok(results)
+    }
+
+    fn build_batch_g_v(gs: Vec<i32>, vs: ArrayRef) -> Result<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", vs.data_type().clone(), true),
+        ]));
+        Ok(RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(gs)), vs],
+        )?)
+    }
+
+    #[tokio::test]
+    async fn test_agg_approx_count_distinct() -> Result<()> {
+        MemManager::init(10000);
+
+        // g=1: 200 distinct values, each appears in both batches
+        // g=2: all nulls
+        // g=3: one distinct value
+        let mut batches = vec![];
+        for _ in 0..2 {
+            let mut gs = vec![];
+            let mut vs = vec![];
+            for i in 0..200 {
+                gs.extend([1, 2, 3]);
+                vs.extend([Some(i), None, Some(7)]);
+            }
+            batches.push(build_batch_g_v(gs, Arc::new(Int64Array::from(vs)))?);
+        }
+        let results = approx_count_distinct(batches).await?;
+        assert_eq!(results, vec![(1, 195), (2, 0), (3, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_approx_count_distinct_thresholds() -> Result<()> {
+        MemManager::init(10000);
+
+        // g=1: linear counting (H=396.5), g=2: bias corrected (H=414.2),
+        // g=3: bias corrected (E=2533.0), g=4: raw estimate (E=2769.7)
+        let mut gs = vec![];
+        let mut vs = vec![];
+        for (g, n) in [(1, 400), (2, 420), (3, 2700), (4, 3000)] {
+            gs.extend(std::iter::repeat(g).take(n));
+            vs.extend(0..n as i64);
+        }
+        let batch = build_batch_g_v(gs, Arc::new(Int64Array::from(vs)))?;
+        let results = approx_count_distinct(vec![batch]).await?;
+        assert_eq!(results, vec![(1, 397), (2, 401), (3, 2540), (4, 2770)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_approx_count_distinct_types() -> Result<()> {
+        MemManager::init(10000);
+
+        // strings "s0", "s1", ...
+        let mut gs = vec![];
+        let mut vs = vec![];
+        for (g, n) in [(1, 500), (2, 3000)] {
+            gs.extend(std::iter::repeat(g).take(n));
+            vs.extend((0..n).map(|i| format!("s{i}")));
+        }
+        let batch = build_batch_g_v(gs, Arc::new(StringArray::from(vs)))?;
+        let results = approx_count_distinct(vec![batch]).await?;
+        assert_eq!(results, vec![(1, 479), (2, 2867)]);
+
+        // decimals with precision > 18 are hashed as BigInteger.toByteArray()
+        let vs =
+            Decimal128Array::from_iter_values((0..300i128).map(|i| (i - 150) * 10i128.pow(21) + i))
+                .with_precision_and_scale(38, 2)?;
+        let batch = build_batch_g_v(vec![1; 300], Arc::new(vs))?;
+        let results = approx_count_distinct(vec![batch]).await?;
+        assert_eq!(results, vec![(1, 291)]);
+
+        // -0.0 is counted as 0.0, NaN is counted once
+        let vs = Float64Array::from(vec![-0.0, 0.0, f64::NAN, 1.5, -0.0, f64::NAN]);
+        let batch = build_batch_g_v(vec![1; 6], Arc::new(vs))?;
+        let results = approx_count_distinct(vec![batch]).await?;
+        assert_eq!(results, vec![(1, 3)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_approx_percentile() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Int32, true),
+        ]));
+
+        // g=1: values 1..=100 split into two batches
+        // g=2: all nulls
+        let mut batches = vec![];
+        for range in [(1..=50), (51..=100)] {
+            let mut gs = vec![];
+            let mut vs = vec![];
+            for i in range.rev() {
+                gs.extend([1, 2]);
+                vs.extend([Some(i), None]);
+            }
+            batches.push(RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(gs)),
+                    Arc::new(Int32Array::from(vs)),
+                ],
+            )?);
+        }
+        let literal =
+            |v: ScalarValue| -> Arc<dyn PhysicalExpr> { Arc::new(phys_expr::Literal::new(v)) };
+        let aggs = [
+            ("median", false, vec![0.5]),
+            ("quartiles", true, vec![0.25, 0.5, 0.75]),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, return_percentile_array, percentages)| {
+                let mut children = vec![
+                    phys_expr::col("v", &schema)?,
+                    literal(ScalarValue::Int64(Some(10000))),
+                    literal(ScalarValue::Boolean(Some(return_percentile_array))),
+                ];
+                for percentage in percentages {
+                    children.push(literal(ScalarValue::Float64(Some(percentage))));
+                }
+                Ok((name, AggFunction::ApproxPercentile, children))
+            })
+            .collect::<Result<_>>()?;
+        let batches = run_partial_final(&schema, batches, aggs).await?;
+        let expected = vec![
+            "+---+--------+--------------+",
+            "| g | median | quartiles    |",
+            "+---+--------+--------------+",
+            "| 1 | 50     | [25, 50, 75] |",
+            "| 2 |        |              |",
+            "+---+--------+--------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_distinct() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Int32, true),
+        ]));
+
+        // g=1: duplicated values split into two batches
+        // g=2: all nulls
+        let batches = [
+            (
+                vec![1, 1, 2, 1, 2],
+                vec![Some(1), Some(2), None, Some(1), None],
+            ),
+            (vec![1, 2, 1, 1], vec![Some(3), None, Some(2), Some(3)]),
+        ]
+        .into_iter()
+        .map(|(gs, vs)| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(gs)),
+                    Arc::new(Int32Array::from(vs)),
+                ],
+            )
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+        let aggs = [
+            ("count_distinct", AggFunction::CountDistinct),
+            ("sum_distinct", AggFunction::SumDistinct),
+            ("avg_distinct", AggFunction::AvgDistinct),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function)| {
+                Ok((name, agg_function, vec![phys_expr::col("v", &schema)?]))
+            })
+            .collect::<Result<_>>()?;
+        let batches = run_partial_final(&schema, batches, aggs).await?;
+        let expected = vec![
+            "+---+----------------+--------------+--------------+",
+            "| g | count_distinct | sum_distinct | avg_distinct |",
+            "+---+----------------+--------------+--------------+",
+            "| 1 | 3              | 6            | 2.0          |",
+            "| 2 | 0              |              |              |",
+            "+---+----------------+--------------+--------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_bitwise() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("i", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2, 2, 3])),
+                Arc::new(Int32Array::from(vec![
+                    Some(3),
+                    Some(5),
+                    None,
+                    Some(12),
+                    Some(10),
+                    None,
+                ])),
+            ],
+        )?;
+        let aggs = [
+            ("bit_and", AggFunction::BitAnd, "i"),
+            ("bit_or", AggFunction::BitOr, "i"),
+            ("bit_xor", AggFunction::BitXor, "i"),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function, col)| {
+                Ok((name, agg_function, vec![phys_expr::col(col, &schema)?]))
+            })
+            .collect::<Result<_>>()?;
+        let batches = run_partial_final(&schema, vec![batch], aggs).await?;
+        let expected = vec![
+            "+---+---------+--------+---------+",
+            "| g | bit_and | bit_or | bit_xor |",
+            "+---+---------+--------+---------+",
+            "| 1 | 1       | 7      | 6       |",
+            "| 2 | 8       | 14     | 6       |",
+            "| 3 |         |        |         |",
+            "+---+---------+--------+---------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_maxmin_by() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+            Field::new("k", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 2, 2, 3])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    Some("c"),
+                    Some("d"),
+                    None,
+                    Some("e"),
+                    Some("f"),
+                ])),
+                Arc::new(Int64Array::from(vec![
+                    Some(1),
+                    Some(3),
+                    Some(2),
+                    None,
+                    Some(10),
+                    Some(5),
+                    None,
+                ])),
+            ],
+        )?;
+        let aggs = [
+            ("max_by_v_k", AggFunction::MaxBy, "v", "k"),
+            ("min_by_v_k", AggFunction::MinBy, "v", "k"),
+            ("max_by_k_v", AggFunction::MaxBy, "k", "v"),
+            ("min_by_k_v", AggFunction::MinBy, "k", "v"),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function, value, key)| {
+                let children = vec![
+                    phys_expr::col(value, &schema)?,
+                    phys_expr::col(key, &schema)?,
+                ];
+                Ok((name, agg_function, children))
+            })
+            .collect::<Result<_>>()?;
+        let batches = run_partial_final(&schema, vec![batch], aggs).await?;
+        let expected = vec![
+            "+---+------------+------------+------------+------------+",
+            "| g | max_by_v_k | min_by_v_k | max_by_k_v | min_by_k_v |",
+            "+---+------------+------------+------------+------------+",
+            "| 1 | b          | a          |            | 1          |",
+            "| 2 |            | e          | 5          | 5          |",
+            "| 3 |            |            |            |            |",
+            "+---+------------+------------+------------+------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_maxmin_by_ties_and_nan() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+            Field::new("k", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2, 2, 2, 3, 3])),
+                Arc::new(StringArray::from(vec![
+                    "a", "b", "c", "d", "e", "f", "g", "h",
+                ])),
+                Arc::new(Float64Array::from(vec![
+                    1.0,
+                    3.0,
+                    3.0,
+                    f64::NAN,
+                    2.0,
+                    f64::NAN,
+                    -0.0,
+                    0.0,
+                ])),
+                Arc::new(StringArray::from(vec![
+                    "x", "y", "y", "x", "x", "w", "z", "z",
+                ])),
+            ],
+        )?;
+        let aggs = [
+            ("max_by_v_k", AggFunction::MaxBy, "v", "k"),
+            ("min_by_v_k", AggFunction::MinBy, "v", "k"),
+            ("max_by_v_s", AggFunction::MaxBy, "v", "s"),
+            ("min_by_v_s", AggFunction::MinBy, "v", "s"),
+        ];
+        let aggs = aggs
+            .into_iter()
+            .map(|(name, agg_function, value, key)| {
+                let children = vec![
+                    phys_expr::col(value, &schema)?,
+                    phys_expr::col(key, &schema)?,
+                ];
+                Ok((name, agg_function, children))
+            })
+            .collect::<Result<_>>()?;
+        let batches = run_partial_final(&schema, vec![batch], aggs).await?;
+
+        // the later row wins on ties, NaN is the largest and -0.0 equals 0.0
+        let expected = vec![
+            "+---+------------+------------+------------+------------+",
+            "| g | max_by_v_k | min_by_v_k | max_by_v_s | min_by_v_s |",
+            "+---+------------+------------+------------+------------+",
+            "| 1 | c          | a          | c          | a          |",
+            "| 2 | f          | e          | e          | f          |",
+            "| 3 | h          | h          | h          | h          |",
+            "+---+------------+------------+------------+------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_last() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+            Field::new("i", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2, 2, 3])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    None,
+                    Some("c"),
+                    None,
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    None,
+                    Some(3),
+                    None,
+                    Some(5),
+                    None,
+                ])),
+            ],
+        )?;
+
+        // input is sorted by grouping key, so it also works in SortAgg mode
+        for exec_mode in [HashAgg, SortAgg] {
+            let aggs = [
+                ("last_v", AggFunction::Last, "v"),
+                ("last_ignores_null_v", AggFunction::LastIgnoresNull, "v"),
+                ("last_i", AggFunction::Last, "i"),
+                ("last_ignores_null_i", AggFunction::LastIgnoresNull, "i"),
+            ];
+            let aggs = aggs
+                .into_iter()
+                .map(|(name, agg_function, col)| {
+                    let agg = create_agg(agg_function, &[phys_expr::col(col, &schema)?], &schema)?;
+                    Ok((name, agg))
+                })
+                .collect::<Result<_>>()?;
+            let batches =
+                run_partial_final_aggs(exec_mode, &schema, vec![batch.clone()], aggs).await?;
+            let expected = vec![
+                "+---+--------+---------------------+--------+---------------------+",
+                "| g | last_v | last_ignores_null_v | last_i | last_ignores_null_i |",
+                "+---+--------+---------------------+--------+---------------------+",
+                "| 1 |        | b                   | 3      | 3                   |",
+                "| 2 |        | c                   | 5      | 5                   |",
+                "| 3 |        |                     |        |                     |",
+                "+---+--------+---------------------+--------+---------------------+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_bloom_filter() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Int64, true),
+        ]));
+
+        // g=1: even values in 0..200
+        // g=2: all nulls
+        let mut gs = vec![];
+        let mut vs = vec![];
+        for i in (0..200).step_by(2) {
+            gs.extend([1, 2]);
+            vs.extend([Some(i), None]);
+        }
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(gs)),
+                Arc::new(Int64Array::from(vs)),
+            ],
+        )?;
+        let literal =
+            |v: ScalarValue| -> Arc<dyn PhysicalExpr> { Arc::new(phys_expr::Literal::new(v)) };
+        let batches = run_partial_final(
+            &schema,
+            vec![batch],
+            vec![(
+                "bloom_filter",
+                AggFunction::BloomFilter,
+                vec![
+                    phys_expr::col("v", &schema)?,
+                    literal(ScalarValue::Int64(Some(100))),
+                    literal(ScalarValue::Int64(Some(4096))),
+                ],
+            )],
+        )
+        .await?;
+
+        let mut num_groups = 0;
+        for batch in &batches {
+            let gs = batch
+                .column(0)
+                .as_primitive::<arrow::datatypes::Int32Type>();
+            let bloom_filters = batch.column(1).as_binary::<i32>();
+            for (g, bloom_filter) in gs.values().iter().zip(bloom_filters) {
+                num_groups += 1;
+                match g {
+                    1 => {
+                        let bloom_filter = bloom_filter.expect("bloom filter should not be null");
+                        spark_bloom_filter::validate(bloom_filter)?;
+                        assert!((0..200)
+                            .step_by(2)
+                            .all(|i| spark_bloom_filter::might_contain_long(bloom_filter, i)));
+                    }
+                    _ => assert!(bloom_filter.is_none()),
+                }
+            }
+        }
+        assert_eq!(num_groups, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agg_collect_sorted_and_limited() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("g", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+            Field::new("k", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2, 2])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    Some("c"),
+                    Some("d"),
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![2, 3, 1, 5, 4])),
+            ],
+        )?;
+
+        let execute_aggs = |aggs: Vec<(&'static str, Arc<dyn Agg>)>| {
+            run_partial_final_aggs(HashAgg, &schema, vec![batch.clone()], aggs)
+        };
+        let sort_expr = |descending: bool| -> Result<PhysicalSortExpr> {
+            Ok(PhysicalSortExpr {
+                expr: phys_expr::col("k", &schema)?,
+                options: SortOptions {
+                    descending,
+                    nulls_first: false,
+                },
+            })
+        };
+
+        // collect_list ordered by k
+        let v = phys_expr::col("v", &schema)?;
+        let batches = execute_aggs(vec![
+            (
+                "collect_asc",
+                create_sorted_agg(
+                    AggFunction::CollectList,
+                    &[v.clone()],
+                    vec![sort_expr(false)?],
+                    &schema,
+                )?,
+            ),
+            (
+                "collect_desc",
+                create_sorted_agg(
+                    AggFunction::CollectList,
+                    &[v.clone()],
+                    vec![sort_expr(true)?],
+                    &schema,
+                )?,
+            ),
+        ])
+        .await?;
+        let expected = vec![
+            "+---+-------------+--------------+",
+            "| g | collect_asc | collect_desc |",
+            "+---+-------------+--------------+",
+            "| 1 | [c, a, b]   | [b, a, c]    |",
+            "| 2 | [d]         | [d]          |",
+            "+---+-------------+--------------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // elements exceeding the max elements limit are dropped, sorted lists keep
+        // the elements with the smallest sort keys
+        let list_type = DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)));
+        let sorted_limited = |descending: bool, max_elements: usize| -> Result<Arc<dyn Agg>> {
+            Ok(Arc::new(AggCollectList::try_new_sorted(
+                v.clone(),
+                list_type.clone(),
+                DataType::Utf8,
+                Some(max_elements),
+                vec![sort_expr(descending)?],
+                vec![DataType::Int32],
+            )?))
+        };
+        let batches = execute_aggs(vec![
+            (
+                "list",
+                Arc::new(AggCollectList::try_new(
+                    v.clone(),
+                    list_type.clone(),
+                    DataType::Utf8,
+                    Some(2),
+                )?),
+            ),
+            (
+                "set",
+                Arc::new(AggCollectSet::try_new(
+                    v.clone(),
+                    list_type.clone(),
+                    DataType::Utf8,
+                    Some(2),
+                )?),
+            ),
+            ("asc1", sorted_limited(false, 1)?),
+            ("asc2", sorted_limited(false, 2)?),
+            ("desc1", sorted_limited(true, 1)?),
+        ])
+        .await?;
+        let expected = vec![
+            "+---+--------+--------+------+--------+-------+",
+            "| g | list   | set    | asc1 | asc2   | desc1 |",
+            "+---+--------+--------+------+--------+-------+",
+            "| 1 | [a, b] | [a, b] | [c]  | [c, a] | [b]   |",
+            "| 2 | [d]    | [d]    | [d]  | [d]    | [d]   |",
+            "+---+--------+--------+------+--------+-------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
 }
@@ -20,33 +20,41 @@ use std::{
     time::Duration,
 };
 
-use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use arrow::{compute::concat_batches, datatypes::SchemaRef, record_batch::RecordBatch};
 use blaze_jni_bridge::{
     conf,
     conf::{BooleanConf, IntConf},
 };
 use datafusion::{
-    common::{Result, Statistics},
+    common::{JoinSide, Result, Statistics},
     execution::context::TaskContext,
-    logical_expr::JoinType,
-    physical_expr::PhysicalSortExpr,
+    physical_expr::{PhysicalExpr, PhysicalSortExpr},
     physical_plan::{
         expressions::Column,
-        joins::{
-            utils::{build_join_schema, check_join_is_valid, JoinFilter, JoinOn},
-            HashJoinExec, PartitionMode,
-        },
+        joins::utils::{check_join_is_valid, JoinFilter, JoinOn},
         memory::MemoryStream,
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
         stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::{df_execution_err, downcast_any};
+use datafusion_ext_commons::{
+    df_unimplemented_err, downcast_any, streams::coalesce_stream::CoalesceInput,
+};
 use futures::{stream::once, StreamExt, TryStreamExt};
 use parking_lot::Mutex;
 
-use crate::{sort_exec::SortExec, sort_merge_join_exec::SortMergeJoinExec};
+use crate::{
+    common::output::TaskOutputter,
+    joins::{
+        build_join_schema, check_null_aware_anti_join,
+        hash_join::{execute_hash_join, HashJoinParams},
+        join_hash_map::{cached_join_hash_map_slot, JoinHashMap},
+        JoinType, RelativeJoinType,
+    },
+    sort_exec::SortExec,
+    sort_merge_join_exec::SortMergeJoinExec,
+};
 
 #[derive(Debug)]
 pub struct BroadcastJoinExec {
@@ -60,6 +68,10 @@ pub struct BroadcastJoinExec {
     join_type: JoinType,
     /// Optional filter before outputting
     join_filter: Option<JoinFilter>,
+    /// Which side is broadcasted and used as the build side
+    broadcast_side: JoinSide,
+    /// Optional id for sharing the build side hash map across tasks
+    cached_build_hash_map_id: Option<String>,
     /// The schema once the join is applied
     schema: SchemaRef,
     /// Execution metrics
@@ -73,21 +85,34 @@ impl BroadcastJoinExec {
         on: JoinOn,
         join_type: JoinType,
         join_filter: Option<JoinFilter>,
+        broadcast_side: JoinSide,
+        cached_build_hash_map_id: Option<String>,
     ) -> Result<Self> {
-        if matches!(
-            join_type,
-            JoinType::LeftSemi | JoinType::LeftAnti | JoinType::RightSemi | JoinType::RightAnti,
-        ) {
-            if join_filter.is_some() {
-                df_execution_err!("Semi/Anti join with filter is not supported yet")?;
-            }
-        }
-
         let left_schema = left.schema();
         let right_schema = right.schema();
 
         check_join_is_valid(&left_schema, &right_schema, &on)?;
-        let schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type).0);
+        if join_type == JoinType::NullAwareAnti {
+            // null-aware anti join needs the whole right side to decide
+            // whether a left row is output
+            check_null_aware_anti_join(&on, &join_filter)?;
+            if broadcast_side != JoinSide::Right {
+                return df_unimplemented_err!(
+                    "BroadcastJoin with null-aware anti join requires broadcasting right side"
+                );
+            }
+        }
+        if cached_build_hash_map_id.is_some()
+            && RelativeJoinType::try_new(join_type, broadcast_side)?.tracks_build_matched()
+        {
+            // matched flags of build side rows are tracked per task, so the
+            // unmatched rows cannot be decided with a hash map shared by tasks
+            return df_unimplemented_err!(
+                "BroadcastJoin with shared build side hash map does not support join type \
+                 {join_type:?} with {broadcast_side:?} side broadcasted"
+            );
+        }
+        let schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type));
 
         Ok(Self {
             left,
@@ -95,6 +120,8 @@ impl BroadcastJoinExec {
             on,
             join_type,
             join_filter,
+            broadcast_side,
+            cached_build_hash_map_id,
             schema,
             metrics: ExecutionPlanMetricsSet::new(),
         })
@@ -111,7 +138,10 @@ impl ExecutionPlan for BroadcastJoinExec {
     }
 
     fn output_partitioning(&self) -> Partitioning {
-        self.right.output_partitioning()
+        match self.broadcast_side {
+            JoinSide::Left => self.right.output_partitioning(),
+            JoinSide::Right => self.left.output_partitioning(),
+        }
     }
 
     fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
@@ -132,6 +162,8 @@ impl ExecutionPlan for BroadcastJoinExec {
             self.on.iter().cloned().collect(),
             self.join_type,
             self.join_filter.clone(),
+            self.broadcast_side,
+            self.cached_build_hash_map_id.clone(),
         )?))
     }
 
@@ -148,6 +180,9 @@ impl ExecutionPlan for BroadcastJoinExec {
             self.on.clone(),
             self.join_type,
             self.join_filter.clone(),
+            self.broadcast_side,
+            self.cached_build_hash_map_id.clone(),
+            self.schema(),
             BaselineMetrics::new(&self.metrics, partition),
         );
 
@@ -168,7 +203,7 @@ impl ExecutionPlan for BroadcastJoinExec {
 
 impl DisplayAs for BroadcastJoinExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "BroadcastJoin")
+        write!(f, "BroadcastJoin [{:?}]", self.broadcast_side)
     }
 }
 
@@ -180,109 +215,76 @@ async fn execute_broadcast_join(
     on: JoinOn,
     join_type: JoinType,
     join_filter: Option<JoinFilter>,
+    broadcast_side: JoinSide,
+    cached_build_hash_map_id: Option<String>,
+    schema: SchemaRef,
     metrics: BaselineMetrics,
 ) -> Result<SendableRecordBatchStream> {
-    let enabled_fallback_to_smj = conf::BHJ_FALLBACKS_TO_SMJ_ENABLE.value()?;
-    let bhj_num_rows_limit = conf::BHJ_FALLBACKS_TO_SMJ_ROWS_THRESHOLD.value()? as usize;
-    let bhj_mem_size_limit = conf::BHJ_FALLBACKS_TO_SMJ_MEM_THRESHOLD.value()? as usize;
-
-    // if broadcasted size is small enough, use hash join
-    // otherwise use sort-merge join
-    #[derive(Debug)]
-    enum JoinMode {
-        Hash,
-        SortMerge,
-    }
-    let mut join_mode = JoinMode::Hash;
-
-    let left_schema = left.schema();
-    let mut left = left;
-
-    if enabled_fallback_to_smj {
-        let mut left_stream = left.execute(0, context.clone())?.fuse();
-        let mut left_cached: Vec<RecordBatch> = vec![];
-        let mut left_num_rows = 0;
-        let mut left_mem_size = 0;
-
-        // read and cache batches from broadcasted side until reached limits
-        while let Some(batch) = left_stream.next().await.transpose()? {
-            left_num_rows += batch.num_rows();
-            left_mem_size += batch.get_array_memory_size();
-            left_cached.push(batch);
-            if left_num_rows > bhj_num_rows_limit || left_mem_size > bhj_mem_size_limit {
-                join_mode = JoinMode::SortMerge;
-                break;
+    let left_keys: Vec<Arc<dyn PhysicalExpr>> = on.iter().map(|(l, _)| l.clone()).collect();
+    let right_keys: Vec<Arc<dyn PhysicalExpr>> = on.iter().map(|(_, r)| r.clone()).collect();
+    let (build, build_keys, probe, probe_keys) = match broadcast_side {
+        JoinSide::Left => (left, left_keys, right, right_keys),
+        JoinSide::Right => (right, right_keys, left, left_keys),
+    };
+
+    // hash map of the broadcasted side is shared by all tasks in the executor
+    // if cached id is specified
+    let join_mode = match &cached_build_hash_map_id {
+        Some(cached_id) => {
+            let slot = cached_join_hash_map_slot(cached_id);
+            let mut slot = slot.lock().await;
+            match slot.as_ref() {
+                Some(join_hash_map) => JoinMode::Hash(join_hash_map.clone()),
+                None => {
+                    let join_mode =
+                        collect_broadcasted(build, &build_keys, context.clone(), &probe).await?;
+                    if let JoinMode::Hash(join_hash_map) = &join_mode {
+                        *slot = Some(join_hash_map.clone());
+                    }
+                    join_mode
+                }
             }
         }
-
-        // convert left cached and rest batches into execution plan
-        let left_cached_stream: SendableRecordBatchStream = Box::pin(MemoryStream::try_new(
-            left_cached,
-            left_schema.clone(),
-            None,
-        )?);
-        let left_rest_stream: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
-            left_schema.clone(),
-            left_stream,
-        ));
-        let left_stream: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
-            left_schema.clone(),
-            left_cached_stream.chain(left_rest_stream),
-        ));
-        left = Arc::new(RecordBatchStreamsWrapperExec {
-            schema: left_schema.clone(),
-            stream: Mutex::new(Some(left_stream)),
-            output_partitioning: right.output_partitioning(),
-        });
-    }
+        None => collect_broadcasted(build, &build_keys, context.clone(), &probe).await?,
+    };
 
     match join_mode {
-        JoinMode::Hash => {
-            let join = Arc::new(HashJoinExec::try_new(
-                left.clone(),
-                right.clone(),
-                on,
+        JoinMode::Hash(join_hash_map) => {
+            log::info!(
+                "BroadcastJoin is using hash join mode, num build side rows: {}",
+                join_hash_map.num_rows(),
+            );
+            let probe_stream = context.coalesce_with_default_batch_size(
+                probe.execute(partition, context.clone())?,
+                &metrics,
+            )?;
+            let join_params = HashJoinParams {
+                join_type,
+                build_side: broadcast_side,
+                probe_keys,
                 join_filter,
-                &join_type,
-                PartitionMode::CollectLeft,
-                false,
-            )?);
-            log::info!("BroadcastJoin is using hash join mode: {:?}", &join);
-
-            let join_schema = join.schema();
-            let completed = join
-                .execute(partition, context)?
-                .chain(futures::stream::poll_fn(move |_| {
-                    // update metrics
-                    let join_metrics = join.metrics().unwrap();
-                    metrics.record_output(join_metrics.output_rows().unwrap_or(0));
-                    metrics.elapsed_compute().add_duration(Duration::from_nanos(
-                        [
-                            join_metrics
-                                .sum_by_name("build_time")
-                                .map(|v| v.as_usize() as u64),
-                            join_metrics
-                                .sum_by_name("join_time")
-                                .map(|v| v.as_usize() as u64),
-                        ]
-                        .into_iter()
-                        .flatten()
-                        .sum(),
-                    ));
-                    Poll::Ready(None)
-                }));
-            Ok(Box::pin(RecordBatchStreamAdapter::new(
-                join_schema,
-                completed,
-            )))
+                probe_schema: probe.schema(),
+                output_schema: schema.clone(),
+            };
+            let metrics_cloned = metrics.clone();
+            let output = context.output_with_sender("BroadcastJoin", schema, move |sender| {
+                execute_hash_join(
+                    probe_stream,
+                    join_hash_map,
+                    join_params,
+                    metrics_cloned,
+                    sender,
+                )
+            })?;
+            Ok(context.coalesce_with_default_batch_size(output, &metrics)?)
         }
-        JoinMode::SortMerge => {
-            let sort_exprs: Vec<PhysicalSortExpr> = on
+        JoinMode::SortMerge(build) => {
+            let sort_exprs: Vec<PhysicalSortExpr> = probe_keys
                 .iter()
-                .map(|(_col_left, col_right)| PhysicalSortExpr {
+                .map(|col_probe| PhysicalSortExpr {
                     expr: Arc::new(Column::new(
                         "",
-                        downcast_any!(col_right, Column)
+                        downcast_any!(col_probe, Column)
                             .expect("requires column")
                             .index(),
                     )),
@@ -290,10 +292,15 @@ async fn execute_broadcast_join(
                 })
                 .collect();
 
-            let right_sorted = Arc::new(SortExec::new(right, sort_exprs.clone(), None));
+            let probe_sorted = Arc::new(SortExec::new(probe, sort_exprs.clone(), None));
+            let (left, right): (Arc<dyn ExecutionPlan>, Arc<dyn ExecutionPlan>) =
+                match broadcast_side {
+                    JoinSide::Left => (build, probe_sorted.clone()),
+                    JoinSide::Right => (probe_sorted.clone(), build),
+                };
             let join = Arc::new(SortMergeJoinExec::try_new(
-                left.clone(),
-                right_sorted.clone(),
+                left,
+                right,
                 on,
                 join_type,
                 join_filter,
@@ -306,12 +313,12 @@ async fn execute_broadcast_join(
                 .execute(partition, context)?
                 .chain(futures::stream::poll_fn(move |_| {
                     // update metrics
-                    let right_sorted_metrics = right_sorted.metrics().unwrap();
+                    let probe_sorted_metrics = probe_sorted.metrics().unwrap();
                     let join_metrics = join.metrics().unwrap();
                     metrics.record_output(join_metrics.output_rows().unwrap_or(0));
                     metrics.elapsed_compute().add_duration(Duration::from_nanos(
                         [
-                            right_sorted_metrics.elapsed_compute(),
+                            probe_sorted_metrics.elapsed_compute(),
                             join_metrics.elapsed_compute(),
                         ]
                         .into_iter()
@@ -328,6 +335,69 @@ async fn execute_broadcast_join(
     }
 }
 
+// if broadcasted size is small enough, use hash join
+// otherwise use sort-merge join
+enum JoinMode {
+    Hash(Arc<JoinHashMap>),
+    SortMerge(Arc<dyn ExecutionPlan>),
+}
+
+/// reads all batches from the broadcasted side and builds the hash map,
+/// falls back to sort-merge join if the broadcasted side exceeds the limits
+async fn collect_broadcasted(
+    build: Arc<dyn ExecutionPlan>,
+    build_keys: &[Arc<dyn PhysicalExpr>],
+    context: Arc<TaskContext>,
+    probe: &Arc<dyn ExecutionPlan>,
+) -> Result<JoinMode> {
+    let enabled_fallback_to_smj = conf::BHJ_FALLBACKS_TO_SMJ_ENABLE.value()?;
+    let bhj_num_rows_limit = conf::BHJ_FALLBACKS_TO_SMJ_ROWS_THRESHOLD.value()? as usize;
+    let bhj_mem_size_limit = conf::BHJ_FALLBACKS_TO_SMJ_MEM_THRESHOLD.value()? as usize;
+
+    let build_schema = build.schema();
+    let mut build_stream = build.execute(0, context.clone())?.fuse();
+    let mut build_cached: Vec<RecordBatch> = vec![];
+    let mut build_num_rows = 0;
+    let mut build_mem_size = 0;
+
+    // read and cache batches from broadcasted side until reached limits
+    while let Some(batch) = build_stream.next().await.transpose()? {
+        build_num_rows += batch.num_rows();
+        build_mem_size += batch.get_array_memory_size();
+        build_cached.push(batch);
+
+        if enabled_fallback_to_smj
+            && (build_num_rows > bhj_num_rows_limit || build_mem_size > bhj_mem_size_limit)
+        {
+            // convert build cached and rest batches into execution plan
+            let build_cached_stream: SendableRecordBatchStream = Box::pin(MemoryStream::try_new(
+                build_cached,
+                build_schema.clone(),
+                None,
+            )?);
+            let build_rest_stream: SendableRecordBatchStream = Box::pin(
+                RecordBatchStreamAdapter::new(build_schema.clone(), build_stream),
+            );
+            let build_stream: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
+                build_schema.clone(),
+                build_cached_stream.chain(build_rest_stream),
+            ));
+            return Ok(JoinMode::SortMerge(Arc::new(
+                RecordBatchStreamsWrapperExec {
+                    schema: build_schema.clone(),
+                    stream: Mutex::new(Some(build_stream)),
+                    output_partitioning: probe.output_partitioning(),
+                },
+            )));
+        }
+    }
+
+    let data_batch = concat_batches(&build_schema, &build_cached)?;
+    let join_hash_map =
+        JoinHashMap::try_new("BroadcastJoinHashMap".to_string(), data_batch, build_keys).await?;
+    Ok(JoinMode::Hash(join_hash_map))
+}
+
 pub struct RecordBatchStreamsWrapperExec {
     pub schema: SchemaRef,
     pub stream: Mutex<Option<SendableRecordBatchStream>>,
@@ -390,3 +460,54 @@ impl ExecutionPlan for RecordBatchStreamsWrapperExec {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        common::JoinSide,
+        physical_expr::expressions::Column,
+        physical_plan::{memory::MemoryExec, ExecutionPlan},
+    };
+
+    use crate::{broadcast_join_exec::BroadcastJoinExec, joins::JoinType};
+
+    fn build_table_i32(name: &str, values: Vec<i32>) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new(name, DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))]).unwrap();
+        Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap())
+    }
+
+    #[test]
+    fn test_shared_hash_map_join_types() {
+        let try_new_join = |join_type: JoinType, broadcast_side: JoinSide| {
+            BroadcastJoinExec::try_new(
+                build_table_i32("a", vec![1, 2, 3]),
+                build_table_i32("b", vec![2, 3, 4]),
+                vec![(Arc::new(Column::new("a", 0)), Arc::new(Column::new("b", 0)))],
+                join_type,
+                None,
+                broadcast_side,
+                Some("test_shared_hash_map_join_types".to_string()),
+            )
+        };
+
+        // matched flags of probe side rows are tracked per task
+        assert!(try_new_join(JoinType::Inner, JoinSide::Right).is_ok());
+        assert!(try_new_join(JoinType::Left, JoinSide::Right).is_ok());
+        assert!(try_new_join(JoinType::Right, JoinSide::Left).is_ok());
+        assert!(try_new_join(JoinType::LeftSemi, JoinSide::Right).is_ok());
+        assert!(try_new_join(JoinType::LeftAnti, JoinSide::Right).is_ok());
+        assert!(try_new_join(JoinType::Existence, JoinSide::Right).is_ok());
+
+        // matched flags of build side rows cannot be tracked with a shared hash map
+        assert!(try_new_join(JoinType::Left, JoinSide::Left).is_err());
+        assert!(try_new_join(JoinType::Full, JoinSide::Right).is_err());
+        assert!(try_new_join(JoinType::LeftSemi, JoinSide::Left).is_err());
+        assert!(try_new_join(JoinType::LeftAnti, JoinSide::Left).is_err());
+        assert!(try_new_join(JoinType::Existence, JoinSide::Left).is_err());
+    }
+}
@@ -15,26 +15,31 @@
 use std::{any::Any, fmt::Formatter, sync::Arc};
 
 use arrow::datatypes::SchemaRef;
+use blaze_jni_bridge::{conf, conf::BooleanConf, is_jni_bridge_inited};
 use datafusion::{
-    common::{JoinType, Result, Statistics},
+    common::{JoinSide, Result, Statistics},
     execution::{SendableRecordBatchStream, TaskContext},
     physical_expr::{Partitioning, PhysicalSortExpr},
     physical_plan::{
-        joins::{
-            utils::{build_join_schema, check_join_is_valid, JoinFilter},
-            NestedLoopJoinExec,
-        },
-        memory::MemoryExec,
+        joins::utils::{check_join_is_valid, JoinFilter},
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
         stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionPlan,
     },
 };
-use datafusion_ext_commons::batch_size;
+use datafusion_ext_commons::streams::coalesce_stream::CoalesceInput;
 use futures::{stream::once, StreamExt, TryStreamExt};
-use parking_lot::Mutex;
 
-use crate::broadcast_join_exec::RecordBatchStreamsWrapperExec;
+use crate::{
+    common::output::TaskOutputter,
+    joins::{
+        build_join_schema,
+        nested_loop_join::{
+            execute_nested_loop_join, NestedLoopJoinBuildSide, NestedLoopJoinParams,
+        },
+        JoinType,
+    },
+};
 
 #[derive(Debug)]
 pub struct BroadcastNestedLoopJoinExec {
@@ -56,7 +61,7 @@ impl BroadcastNestedLoopJoinExec {
         let left_schema = left.schema();
         let right_schema = right.schema();
         check_join_is_valid(&left_schema, &right_schema, &[])?;
-        let (schema, _column_indices) = build_join_schema(&left_schema, &right_schema, &join_type);
+        let schema = build_join_schema(&left_schema, &right_schema, &join_type);
 
         Ok(Self {
             left,
@@ -126,6 +131,7 @@ impl ExecutionPlan for BroadcastNestedLoopJoinExec {
                 self.right.clone(),
                 self.join_type,
                 self.filter.clone(),
+                self.schema(),
                 self.metrics.clone(),
             ))
             .try_flatten(),
@@ -149,99 +155,52 @@ async fn execute_join(
     right: Arc<dyn ExecutionPlan>,
     join_type: JoinType,
     filter: Option<JoinFilter>,
+    schema: SchemaRef,
     metrics: ExecutionPlanMetricsSet,
 ) -> Result<SendableRecordBatchStream> {
-    // inner side
-    let mut inner_stream = if left_is_build_side(join_type) {
-        left.execute(partition, context.clone())?
-    } else {
-        right.execute(partition, context.clone())?
-    };
-    let inner_schema = inner_stream.schema();
-    let mut inner_batches = vec![];
-    while let Some(batch) = inner_stream.next().await.transpose()? {
-        inner_batches.push(batch);
-    }
-
-    let inner_batch_max_num_rows = inner_batches
-        .iter()
-        .map(|batch| batch.num_rows())
-        .max()
-        .unwrap_or(0);
-    let inner_batch_max_mem_size = inner_batches
-        .iter()
-        .map(|batch| batch.get_array_memory_size())
-        .max()
-        .unwrap_or(0);
-
-    let target_output_num_rows = batch_size();
-    let target_output_mem_size = 1 << 26; // 64MB
-    let inner_exec: Arc<dyn ExecutionPlan> =
-        Arc::new(MemoryExec::try_new(&[inner_batches], inner_schema, None)?);
-
-    // outer side
-    let (outer_schema, outer_partitioning, outer_stream) = if left_is_build_side(join_type) {
-        (
-            right.schema(),
-            right.output_partitioning(),
-            right.execute(partition, context.clone())?,
-        )
-    } else {
-        (
-            left.schema(),
-            left.output_partitioning(),
-            left.execute(partition, context.clone())?,
-        )
-    };
-    let chunked_outer_stream = Box::pin(RecordBatchStreamAdapter::new(
-        outer_schema.clone(),
-        outer_stream.flat_map(move |batch_result| match batch_result {
-            Ok(batch) => {
-                let batch_num_rows = batch.num_rows();
-                let batch_mem_size = batch.get_array_memory_size();
-                let output_num_rows = batch_num_rows * inner_batch_max_num_rows;
-                let output_mem_size = batch_num_rows * inner_batch_max_mem_size
-                    + batch_mem_size * inner_batch_max_num_rows;
-                let chunk_count = std::cmp::min(
-                    (output_num_rows / target_output_num_rows).max(1),
-                    (output_mem_size / target_output_mem_size).max(1),
-                );
-                let chunk_len = (batch_num_rows / chunk_count).max(1);
-
-                let mut chunks = vec![];
-                for beg in (0..batch.num_rows()).step_by(chunk_len) {
-                    chunks.push(Ok(batch.slice(beg, chunk_len.min(batch.num_rows() - beg))));
-                }
-                futures::stream::iter(chunks)
-            }
-            Err(err) => futures::stream::iter(vec![Err(err)]),
-        }),
-    ));
-    let outer_exec: Arc<dyn ExecutionPlan> = Arc::new(RecordBatchStreamsWrapperExec {
-        schema: outer_schema,
-        stream: Mutex::new(Some(chunked_outer_stream)),
-        output_partitioning: outer_partitioning,
-    });
-
-    // join with datafusion's builtin NestedLoopJoinExec
-    let nlj = if left_is_build_side(join_type) {
-        NestedLoopJoinExec::try_new(inner_exec, outer_exec, filter, &join_type)?
+    let baseline_metrics = BaselineMetrics::new(&metrics, partition);
+    let (build_side, build, probe) = if left_is_build_side(join_type) {
+        (JoinSide::Left, left, right)
     } else {
-        NestedLoopJoinExec::try_new(outer_exec, inner_exec, filter, &join_type)?
+        (JoinSide::Right, right, left)
     };
-    let joined = nlj.execute(partition, context)?;
 
-    let baseline_metrics = BaselineMetrics::new(&metrics, partition);
-    let output_stream = Box::pin(RecordBatchStreamAdapter::new(
-        joined.schema(),
-        joined.map(move |batch_result| {
-            if let Ok(batch) = &batch_result {
-                baseline_metrics.record_output(batch.num_rows());
-            }
-            batch_result
-        }),
-    ));
-    Ok(output_stream)
+    // collect all rows of the build side, which may be spilled if the
+    // build side is too large
+    let build_schema = build.schema();
+    let build_buffer = NestedLoopJoinBuildSide::try_new(build_schema.clone(), partition, &metrics)?;
+    let mut build_stream = context.coalesce_with_default_batch_size(
+        build.execute(partition, context.clone())?,
+        &baseline_metrics,
+    )?;
+    while let Some(batch) = build_stream.next().await.transpose()? {
+        build_buffer.insert_batch(batch).await?;
+    }
+
+    let probe_stream = context.coalesce_with_default_batch_size(
+        probe.execute(partition, context.clone())?,
+        &baseline_metrics,
+    )?;
+    let join_params = NestedLoopJoinParams {
+        join_type,
+        build_side,
+        join_filter: filter,
+        build_schema,
+        probe_schema: probe.schema(),
+        output_schema: schema.clone(),
+        range_join_enabled: is_jni_bridge_inited() && conf::RANGE_JOIN_ENABLE.value()?,
+    };
+    let metrics_cloned = baseline_metrics.clone();
+    let output = context.output_with_sender("BroadcastNestedLoopJoin", schema, move |sender| {
+        execute_nested_loop_join(
+            probe_stream,
+            build_buffer,
+            join_params,
+            metrics_cloned,
+            sender,
+        )
+    })?;
+    Ok(context.coalesce_with_default_batch_size(output, &baseline_metrics)?)
 }
 
 fn left_is_build_side(join_type: JoinType) -> bool {
@@ -250,3 +209,210 @@ fn left_is_build_side(join_type: JoinType) -> bool {
         JoinType::Right | JoinType::RightSemi | JoinType::RightAnti | JoinType::Full
     )
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result},
+        logical_expr::Operator,
+        physical_expr::expressions::{BinaryExpr, Column},
+        physical_plan::{
+            common,
+            joins::utils::{ColumnIndex, JoinFilter},
+            memory::MemoryExec,
+            ExecutionPlan,
+        },
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        broadcast_nested_loop_join_exec::BroadcastNestedLoopJoinExec, joins::JoinType,
+        memmgr::MemManager,
+    };
+
+    fn build_table_i32(
+        a: (&str, &Vec<Option<i32>>),
+        b: (&str, &Vec<Option<i32>>),
+    ) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(a.0, DataType::Int32, true),
+            Field::new(b.0, DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(a.1.clone())),
+                Arc::new(Int32Array::from(b.1.clone())),
+            ],
+        )
+        .unwrap();
+        Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap())
+    }
+
+    /// join filter: left.b1 < right.b2
+    fn lt_filter() -> JoinFilter {
+        JoinFilter::new(
+            Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("b1", 0)),
+                Operator::Lt,
+                Arc::new(Column::new("b2", 1)),
+            )),
+            vec![
+                ColumnIndex {
+                    index: 1,
+                    side: JoinSide::Left,
+                },
+                ColumnIndex {
+                    index: 1,
+                    side: JoinSide::Right,
+                },
+            ],
+            Schema::new(vec![
+                Field::new("b1", DataType::Int32, true),
+                Field::new("b2", DataType::Int32, true),
+            ]),
+        )
+    }
+
+    async fn bnlj_collect(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+    ) -> Result<Vec<RecordBatch>> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let join = BroadcastNestedLoopJoinExec::try_new(left, right, join_type, Some(lt_filter()))?;
+        let stream = join.execute(0, task_ctx)?;
+        common::collect(stream).await
+    }
+
+    #[tokio::test]
+    async fn test_bnlj() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![Some(1), Some(2), Some(3)]),
+            ("b1", &vec![Some(10), Some(20), Some(30)]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![Some(100), Some(200)]),
+            ("b2", &vec![Some(15), Some(25)]),
+        );
+
+        let batches = bnlj_collect(left.clone(), right.clone(), JoinType::Inner).await?;
+        let expected = vec![
+            "+----+----+-----+----+",
+            "| a1 | b1 | a2  | b2 |",
+            "+----+----+-----+----+",
+            "| 1  | 10 | 100 | 15 |",
+            "| 1  | 10 | 200 | 25 |",
+            "| 2  | 20 | 200 | 25 |",
+            "+----+----+-----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches = bnlj_collect(left.clone(), right.clone(), JoinType::Full).await?;
+        let expected = vec![
+            "+----+----+-----+----+",
+            "| a1 | b1 | a2  | b2 |",
+            "+----+----+-----+----+",
+            "| 1  | 10 | 100 | 15 |",
+            "| 1  | 10 | 200 | 25 |",
+            "| 2  | 20 | 200 | 25 |",
+            "| 3  | 30 |     |    |",
+            "+----+----+-----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches = bnlj_collect(left.clone(), right.clone(), JoinType::LeftAnti).await?;
+        let expected = vec![
+            "+----+----+",
+            "| a1 | b1 |",
+            "+----+----+",
+            "| 3  | 30 |",
+            "+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches = bnlj_collect(left.clone(), right.clone(), JoinType::RightSemi).await?;
+        let expected = vec![
+            "+-----+----+",
+            "| a2  | b2 |",
+            "+-----+----+",
+            "| 100 | 15 |",
+            "| 200 | 25 |",
+            "+-----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bnlj_existence() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![Some(1), Some(2), Some(3), Some(4)]),
+            ("b1", &vec![Some(10), Some(20), Some(30), None]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![Some(100), Some(200)]),
+            ("b2", &vec![Some(15), Some(25)]),
+        );
+
+        let batches = bnlj_collect(left, right, JoinType::Existence).await?;
+        let expected = vec![
+            "+----+----+--------+",
+            "| a1 | b1 | exists |",
+            "+----+----+--------+",
+            "| 1  | 10 | true   |",
+            "| 2  | 20 | true   |",
+            "| 3  | 30 | false  |",
+            "| 4  |    | false  |",
+            "+----+----+--------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bnlj_null_aware_anti() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![Some(1), Some(2), Some(3), Some(4)]),
+            ("b1", &vec![Some(10), Some(20), Some(30), None]),
+        );
+
+        // null filter results are treated as matched
+        let right = build_table_i32(("a2", &vec![Some(100)]), ("b2", &vec![Some(25)]));
+        let batches = bnlj_collect(left.clone(), right, JoinType::NullAwareAnti).await?;
+        let expected = vec![
+            "+----+----+",
+            "| a1 | b1 |",
+            "+----+----+",
+            "| 3  | 30 |",
+            "+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let right = build_table_i32(("a2", &vec![Some(100)]), ("b2", &vec![None]));
+        let batches = bnlj_collect(left.clone(), right, JoinType::NullAwareAnti).await?;
+        assert!(batches.iter().all(|batch| batch.num_rows() == 0));
+
+        // all rows are output if right side is empty
+        let right = build_table_i32(("a2", &vec![]), ("b2", &vec![]));
+        let batches = bnlj_collect(left.clone(), right, JoinType::NullAwareAnti).await?;
+        let expected = vec![
+            "+----+----+",
+            "| a1 | b1 |",
+            "+----+----+",
+            "| 1  | 10 |",
+            "| 2  | 20 |",
+            "| 3  | 30 |",
+            "| 4  |    |",
+            "+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+}
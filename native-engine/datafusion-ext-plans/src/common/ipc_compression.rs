@@ -18,32 +18,128 @@
 use std::io::{BufReader, Cursor, Read, Take, Write};
 
 use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
+use blaze_jni_bridge::{
+    conf,
+    conf::{IntConf, StringConf},
+    is_jni_bridge_inited,
+};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use datafusion::common::Result;
 use datafusion_ext_commons::{
     df_execution_err,
     io::{read_one_batch, write_one_batch},
 };
+use once_cell::sync::OnceCell;
 
 pub const DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE: usize = 4194304;
-const ZSTD_LEVEL: i32 = 1;
+const DEFAULT_ZSTD_LEVEL: i32 = 1;
+
+/// compression codec of ipc blocks. the codec is recorded in the header of
+/// each block, so blocks written with different codecs can be read together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoCompressionCodec {
+    None,
+    Lz4,
+    Zstd(i32),
+    Snappy,
+}
+
+impl IoCompressionCodec {
+    /// creates codec by short name or spark's codec class name, like
+    /// `zstd` or `org.apache.spark.io.ZStdCompressionCodec`
+    pub fn try_new(name: &str, zstd_level: i32) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "lz4" | "org.apache.spark.io.lz4compressioncodec" => Ok(Self::Lz4),
+            "zstd" | "org.apache.spark.io.zstdcompressioncodec" => Ok(Self::Zstd(zstd_level)),
+            "snappy" | "org.apache.spark.io.snappycompressioncodec" => Ok(Self::Snappy),
+            _ => df_execution_err!(
+                "unsupported io compression codec: {name}, \
+                 supported codecs: none, lz4, zstd, snappy"
+            ),
+        }
+    }
+
+    /// codec configured by spark.blaze.shuffle.compression.codec and
+    /// spark.blaze.shuffle.compression.zstd.level
+    pub fn configured() -> Result<Self> {
+        static CACHED_CODEC: OnceCell<IoCompressionCodec> = OnceCell::new();
+        CACHED_CODEC
+            .get_or_try_init(|| {
+                if is_jni_bridge_inited() {
+                    Self::try_new(
+                        &conf::SHUFFLE_COMPRESSION_CODEC.value()?,
+                        conf::SHUFFLE_COMPRESSION_ZSTD_LEVEL.value()?,
+                    )
+                } else {
+                    Ok(Self::Zstd(DEFAULT_ZSTD_LEVEL)) // for testing
+                }
+            })
+            .copied()
+    }
+
+    fn id(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Lz4 => 1,
+            Self::Zstd(_) => 2,
+            Self::Snappy => 3,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Lz4),
+            2 => Ok(Self::Zstd(DEFAULT_ZSTD_LEVEL)), // level is not needed by decoder
+            3 => Ok(Self::Snappy),
+            other => df_execution_err!("unknown io compression codec id: {other}"),
+        }
+    }
+}
+
+/// size of uncompressed data in a block before it is compressed and flushed,
+/// configured by spark.blaze.shuffle.compression.targetBufSize
+fn target_buf_size() -> Result<usize> {
+    static CACHED_TARGET_BUF_SIZE: OnceCell<usize> = OnceCell::new();
+    CACHED_TARGET_BUF_SIZE
+        .get_or_try_init(|| {
+            if is_jni_bridge_inited() {
+                Ok(conf::SHUFFLE_COMPRESSION_TARGET_BUF_SIZE.value()? as usize)
+            } else {
+                Ok(DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE) // for testing
+            }
+        })
+        .copied()
+}
 
 pub struct IpcCompressionWriter<W: Write> {
     output: W,
-    compressed: bool,
+    codec: IoCompressionCodec,
     buf: Box<dyn CompressibleBlockWriter>,
     buf_empty: bool,
+    target_buf_size: usize,
 }
 unsafe impl<W: Write> Send for IpcCompressionWriter<W> {}
 
 impl<W: Write> IpcCompressionWriter<W> {
-    pub fn new(output: W, compressed: bool) -> Self {
-        Self {
+    pub fn try_new(output: W, compressed: bool) -> Result<Self> {
+        let codec = if compressed {
+            IoCompressionCodec::configured()?
+        } else {
+            IoCompressionCodec::None
+        };
+        Self::try_new_with_codec(output, codec)
+    }
+
+    pub fn try_new_with_codec(output: W, codec: IoCompressionCodec) -> Result<Self> {
+        Ok(Self {
             output,
-            compressed,
-            buf: create_block_writer(compressed),
+            codec,
+            buf: create_block_writer(codec),
             buf_empty: true,
-        }
+            target_buf_size: target_buf_size()?,
+        })
     }
 
     /// Write a batch, returning uncompressed bytes size
@@ -54,7 +150,7 @@ impl<W: Write> IpcCompressionWriter<W> {
         self.buf_empty = false;
         drop(batch_buf);
 
-        if self.buf.buf_len() as f64 >= DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE as f64 * 0.9 {
+        if self.buf.buf_len() as f64 >= self.target_buf_size as f64 * 0.9 {
             self.flush()?;
         }
         Ok(uncompressed_bytes_size)
@@ -63,7 +159,7 @@ impl<W: Write> IpcCompressionWriter<W> {
     pub fn flush(&mut self) -> Result<()> {
         if !self.buf_empty {
             // finish current buf and open next
-            let next_buf = create_block_writer(self.compressed);
+            let next_buf = create_block_writer(self.codec);
             let block_data = std::mem::replace(&mut self.buf, next_buf).finish()?;
             self.output.write_all(&block_data)?;
             self.output.flush()?;
@@ -72,6 +168,10 @@ impl<W: Write> IpcCompressionWriter<W> {
         Ok(())
     }
 
+    pub fn inner(&self) -> &W {
+        &self.output
+    }
+
     pub fn finish_into_inner(mut self) -> Result<W> {
         self.flush()?;
         Ok(self.output)
@@ -134,28 +234,41 @@ impl<R: Read> IpcCompressionReader<R> {
     }
 }
 
+// block header: 1 byte of codec id and 4 bytes of block length
+const HEADER_LEN: usize = 5;
+
 #[derive(Clone, Copy)]
 struct Header {
-    compressed: bool,
+    codec: IoCompressionCodec,
     block_len: usize,
 }
 
 impl Header {
-    fn new(compressed: bool, block_len: usize) -> Self {
-        Self {
-            compressed,
-            block_len,
-        }
+    fn new(codec: IoCompressionCodec, block_len: usize) -> Self {
+        Self { codec, block_len }
     }
 
-    fn from_u32(value: u32) -> Self {
-        let compressed = (value & 0x8000_0000) > 0;
-        let block_len = (value & 0x7fff_ffff) as usize;
-        Self::new(compressed, block_len)
+    fn read_from<R: Read>(mut input: R) -> Result<Option<Self>> {
+        let codec_id = match input.read_u8() {
+            Ok(codec_id) => codec_id,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(err) => {
+                return df_execution_err!("{err}");
+            }
+        };
+        let codec = IoCompressionCodec::from_id(codec_id)?;
+        let block_len = input.read_u32::<LittleEndian>()? as usize;
+        Ok(Some(Self::new(codec, block_len)))
     }
 
-    fn to_u32(&self) -> u32 {
-        (self.compressed as u32) << 31 | (self.block_len as u32)
+    // fills the header into the reserved space at the beginning of block data
+    fn fill_block(&self, block_data: &mut [u8]) -> Result<()> {
+        let mut header_buf = &mut block_data[0..HEADER_LEN];
+        header_buf.write_u8(self.codec.id())?;
+        header_buf.write_u32::<LittleEndian>(self.block_len as u32)?;
+        Ok(())
     }
 }
 
@@ -164,79 +277,60 @@ trait CompressibleBlockWriter: Write {
     fn finish(self: Box<Self>) -> Result<Vec<u8>>;
 }
 
-struct ZstdWriter(zstd::Encoder<'static, Vec<u8>>);
-
-impl ZstdWriter {
-    fn new() -> Self {
-        Self(zstd::Encoder::new(vec![0u8; 4], ZSTD_LEVEL).expect("error creating zstd encoder"))
-    }
+/// writes compressed data into a buffer with reserved space for block header
+struct BlockWriter<E: Write> {
+    codec: IoCompressionCodec,
+    encoder: E,
+    get_buf: fn(&E) -> &Vec<u8>,
+    finish_encoder: fn(E) -> Result<Vec<u8>>,
 }
 
-impl Write for ZstdWriter {
+impl<E: Write> Write for BlockWriter<E> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
+        self.encoder.write(buf)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
+        self.encoder.flush()
     }
 }
 
-impl CompressibleBlockWriter for ZstdWriter {
+impl<E: Write> CompressibleBlockWriter for BlockWriter<E> {
     fn buf_len(&self) -> usize {
-        self.0.get_ref().len()
+        (self.get_buf)(&self.encoder).len()
     }
 
     fn finish(self: Box<Self>) -> Result<Vec<u8>> {
-        let mut block_data = self.0.finish()?;
-        let header = Header::new(true, block_data.len() - 4);
-        block_data[0..4]
-            .as_mut()
-            .write_u32::<LittleEndian>(header.to_u32())?;
+        let mut block_data = (self.finish_encoder)(self.encoder)?;
+        let header = Header::new(self.codec, block_data.len() - HEADER_LEN);
+        header.fill_block(&mut block_data)?;
         Ok(block_data)
     }
 }
 
-struct UncompressedWriter(Vec<u8>);
-
-impl UncompressedWriter {
-    fn new() -> Self {
-        Self(vec![0u8; 4])
-    }
+trait CompressibleBlockReader<R: Read>: Read {
+    fn finish_into_inner(self: Box<Self>) -> Result<R>;
 }
 
-impl Write for UncompressedWriter {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
+impl<R: Read> CompressibleBlockReader<R> for zstd::Decoder<'_, BufReader<Take<R>>> {
+    fn finish_into_inner(self: Box<Self>) -> Result<R> {
+        let mut r = self.finish().into_inner();
+        std::io::copy(&mut r, &mut std::io::sink())?; // skip to end
+        Ok(r.into_inner())
     }
 }
 
-impl CompressibleBlockWriter for UncompressedWriter {
-    fn buf_len(&self) -> usize {
-        self.0.len()
-    }
-
-    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
-        let mut block_data = self.0;
-        let header = Header::new(false, block_data.len() - 4);
-        block_data[0..4]
-            .as_mut()
-            .write_u32::<LittleEndian>(header.to_u32())?;
-        Ok(block_data)
+impl<R: Read> CompressibleBlockReader<R> for lz4_flex::frame::FrameDecoder<Take<R>> {
+    fn finish_into_inner(self: Box<Self>) -> Result<R> {
+        let mut r = self.into_inner();
+        std::io::copy(&mut r, &mut std::io::sink())?; // skip to end
+        Ok(r.into_inner())
     }
 }
 
-trait CompressibleBlockReader<R: Read>: Read {
-    fn finish_into_inner(self: Box<Self>) -> Result<R>;
-}
-
-impl<R: Read> CompressibleBlockReader<R> for zstd::Decoder<'_, BufReader<Take<R>>> {
+impl<R: Read> CompressibleBlockReader<R> for snap::read::FrameDecoder<Take<R>> {
     fn finish_into_inner(self: Box<Self>) -> Result<R> {
-        let mut r = self.finish().into_inner();
+        let mut r = self.into_inner();
         std::io::copy(&mut r, &mut std::io::sink())?; // skip to end
         Ok(r.into_inner())
     }
@@ -248,32 +342,127 @@ impl<R: Read> CompressibleBlockReader<R> for Take<R> {
     }
 }
 
-fn create_block_writer(compressed: bool) -> Box<dyn CompressibleBlockWriter> {
-    if compressed {
-        Box::new(ZstdWriter::new())
-    } else {
-        Box::new(UncompressedWriter::new())
+fn create_block_writer(codec: IoCompressionCodec) -> Box<dyn CompressibleBlockWriter> {
+    let header_placeholder = vec![0u8; HEADER_LEN];
+    match codec {
+        IoCompressionCodec::None => Box::new(BlockWriter {
+            codec,
+            encoder: header_placeholder,
+            get_buf: |e| e,
+            finish_encoder: |e| Ok(e),
+        }),
+        IoCompressionCodec::Lz4 => Box::new(BlockWriter {
+            codec,
+            encoder: lz4_flex::frame::FrameEncoder::new(header_placeholder),
+            get_buf: |e| e.get_ref(),
+            finish_encoder: |e| e.finish().or_else(|err| df_execution_err!("{err}")),
+        }),
+        IoCompressionCodec::Zstd(level) => Box::new(BlockWriter {
+            codec,
+            encoder: zstd::Encoder::new(header_placeholder, level)
+                .expect("error creating zstd encoder"),
+            get_buf: |e| e.get_ref(),
+            finish_encoder: |e| Ok(e.finish()?),
+        }),
+        IoCompressionCodec::Snappy => Box::new(BlockWriter {
+            codec,
+            encoder: snap::write::FrameEncoder::new(header_placeholder),
+            get_buf: |e| e.get_ref(),
+            finish_encoder: |e| e.into_inner().or_else(|err| df_execution_err!("{err}")),
+        }),
     }
 }
 
 fn create_block_reader<R: Read + 'static>(
     mut input: R,
 ) -> Result<Option<Box<dyn CompressibleBlockReader<R>>>> {
-    let header = match input.read_u32::<LittleEndian>() {
-        Ok(value) => Header::from_u32(value),
-        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
-            return Ok(None);
-        }
-        Err(err) => {
-            return df_execution_err!("{err}");
-        }
+    let header = match Header::read_from(&mut input)? {
+        Some(header) => header,
+        None => return Ok(None),
     };
 
     let taken = input.take(header.block_len as u64);
-    if !header.compressed {
-        return Ok(Some(Box::new(taken)));
+    Ok(Some(match header.codec {
+        IoCompressionCodec::None => Box::new(taken),
+        IoCompressionCodec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(taken)),
+        IoCompressionCodec::Zstd(_) => {
+            Box::new(zstd::Decoder::new(taken).expect("error creating zstd decoder"))
+        }
+        IoCompressionCodec::Snappy => Box::new(snap::read::FrameDecoder::new(taken)),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, StringArray},
+        record_batch::RecordBatch,
+    };
+    use datafusion::common::Result;
+
+    use crate::common::ipc_compression::{
+        IoCompressionCodec, IpcCompressionReader, IpcCompressionWriter,
+    };
+
+    #[test]
+    fn test_io_compression_codec_names() -> Result<()> {
+        assert_eq!(
+            IoCompressionCodec::try_new("none", 1)?,
+            IoCompressionCodec::None
+        );
+        assert_eq!(
+            IoCompressionCodec::try_new("LZ4", 1)?,
+            IoCompressionCodec::Lz4
+        );
+        assert_eq!(
+            IoCompressionCodec::try_new("zstd", 3)?,
+            IoCompressionCodec::Zstd(3)
+        );
+        assert_eq!(
+            IoCompressionCodec::try_new("snappy", 1)?,
+            IoCompressionCodec::Snappy
+        );
+        assert_eq!(
+            IoCompressionCodec::try_new("org.apache.spark.io.ZStdCompressionCodec", 3)?,
+            IoCompressionCodec::Zstd(3)
+        );
+        assert!(IoCompressionCodec::try_new("lzf", 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_compression_mixed_codecs() -> Result<()> {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("a", Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef),
+            (
+                "b",
+                Arc::new(StringArray::from(vec!["x", "yy", "zzz"])) as ArrayRef,
+            ),
+        ])?;
+        let codecs = [
+            IoCompressionCodec::None,
+            IoCompressionCodec::Lz4,
+            IoCompressionCodec::Zstd(3),
+            IoCompressionCodec::Snappy,
+        ];
+
+        // blocks written with different codecs are concatenated into one output
+        let mut output = vec![];
+        for codec in codecs {
+            let mut writer = IpcCompressionWriter::try_new_with_codec(&mut output, codec)?;
+            writer.write_batch(batch.clone())?;
+            writer.write_batch(batch.slice(1, 2))?;
+            writer.finish_into_inner()?;
+        }
+
+        let mut reader = IpcCompressionReader::new(Cursor::new(output), batch.schema());
+        for _ in codecs {
+            assert_eq!(reader.read_batch()?, Some(batch.clone()));
+            assert_eq!(reader.read_batch()?, Some(batch.slice(1, 2)));
+        }
+        assert_eq!(reader.read_batch()?, None);
+        Ok(())
     }
-    Ok(Some(Box::new(
-        zstd::Decoder::new(taken).expect("error creating ztd decoder"),
-    )))
 }
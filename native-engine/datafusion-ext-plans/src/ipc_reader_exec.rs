@@ -16,7 +16,7 @@ use std::{
     any::Any,
     fmt::{Debug, Formatter},
     fs::File,
-    io::{BufReader, Read, Seek, SeekFrom},
+    io::{BufReader, Read, Seek, SeekFrom, Take},
     sync::Arc,
 };
 
@@ -45,7 +45,10 @@ use futures::{stream::once, TryStreamExt};
 use jni::objects::{GlobalRef, JObject};
 use parking_lot::Mutex;
 
-use crate::common::{ipc_compression::IpcCompressionReader, output::TaskOutputter};
+use crate::{
+    common::{ipc_compression::IpcCompressionReader, output::TaskOutputter},
+    shuffle::checksum::{verify_checksum, ChecksumVerifyingReader, ShuffleChecksumAlgorithm},
+};
 
 #[derive(Debug, Clone)]
 pub struct IpcReaderExec {
@@ -180,22 +183,40 @@ pub async fn read_ipc(
             .or_else(|err| df_execution_err!("{err}"))??;
 
             // get ipc reader
-            let reader = Arc::new(Mutex::new(match next {
-                Some((segment_classname, segment)) => {
-                    if segment_classname == "org.apache.spark.storage.FileSegment" {
-                        get_file_segment_reader(schema.clone(), segment.as_obj())?
-                    } else {
-                        get_channel_reader(schema.clone(), segment.as_obj())?
+            let (reader, checksummed_segment) = match next {
+                Some((segment_classname, segment)) => match segment_classname.as_str() {
+                    "org.apache.spark.storage.FileSegment" => (
+                        get_file_segment_reader(schema.clone(), segment.as_obj())?,
+                        None,
+                    ),
+                    "org.apache.spark.sql.execution.blaze.shuffle.BlazeChecksummedFileSegment" => {
+                        let checksummed_segment =
+                            Arc::new(ChecksummedFileSegment::try_new(segment.as_obj())?);
+                        (
+                            checksummed_segment.get_reader(schema.clone())?,
+                            Some(checksummed_segment),
+                        )
                     }
-                }
+                    _ => (get_channel_reader(schema.clone(), segment.as_obj())?, None),
+                },
                 None => break,
-            }));
+            };
+            let reader = Arc::new(Mutex::new(reader));
 
             while let Some(batch) = {
                 let reader_cloned = reader.clone();
-                tokio::task::spawn_blocking(move || reader_cloned.clone().lock().read_batch())
-                    .await
-                    .or_else(|err| df_execution_err!("{err}"))??
+                let checksummed_segment = checksummed_segment.clone();
+                tokio::task::spawn_blocking(move || {
+                    reader_cloned.lock().read_batch().or_else(|err| {
+                        // report corrupted block instead of the decoding error
+                        if let Some(checksummed_segment) = checksummed_segment {
+                            checksummed_segment.diagnose()?;
+                        }
+                        Err(err)
+                    })
+                })
+                .await
+                .or_else(|err| df_execution_err!("{err}"))??
             } {
                 size_counter.add(batch.get_array_mem_size());
                 baseline_metrics.record_output(batch.num_rows());
@@ -223,18 +244,80 @@ fn get_file_segment_reader(
     schema: SchemaRef,
     file_segment: JObject,
 ) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+    let (path, offset, length) = get_file_segment(file_segment)?;
+    Ok(IpcCompressionReader::new(
+        Box::new(BufReader::with_capacity(
+            65536,
+            open_file_segment(&path, offset, length)?,
+        )),
+        schema,
+    ))
+}
+
+fn get_file_segment(file_segment: JObject) -> Result<(String, u64, u64)> {
     let file = jni_call!(SparkFileSegment(file_segment).file() -> JObject)?;
     let path = jni_call!(JavaFile(file.as_obj()).getPath() -> JObject)?;
     let path = jni_get_string!(path.as_obj().into())?;
     let offset = jni_call!(SparkFileSegment(file_segment).offset() -> i64)?;
     let length = jni_call!(SparkFileSegment(file_segment).length() -> i64)?;
+    Ok((path, offset as u64, length as u64))
+}
 
+fn open_file_segment(path: &str, offset: u64, length: u64) -> Result<Take<File>> {
     let mut file = File::open(path)?;
-    file.seek(SeekFrom::Start(offset as u64))?;
-    Ok(IpcCompressionReader::new(
-        Box::new(BufReader::with_capacity(65536, file.take(length as u64))),
-        schema,
-    ))
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file.take(length))
+}
+
+/// local shuffle block with the checksum recorded by shuffle writer
+struct ChecksummedFileSegment {
+    block_id: String,
+    path: String,
+    offset: u64,
+    length: u64,
+    checksum: i64,
+    algorithm: ShuffleChecksumAlgorithm,
+}
+
+impl ChecksummedFileSegment {
+    fn try_new(segment: JObject) -> Result<Self> {
+        let block_id = jni_call!(BlazeChecksummedFileSegment(segment).blockId() -> JObject)?;
+        let block_id = jni_get_string!(block_id.as_obj().into())?;
+        let file_segment =
+            jni_call!(BlazeChecksummedFileSegment(segment).fileSegment() -> JObject)?;
+        let (path, offset, length) = get_file_segment(file_segment.as_obj())?;
+        let checksum = jni_call!(BlazeChecksummedFileSegment(segment).checksum() -> i64)?;
+        Ok(Self {
+            block_id,
+            path,
+            offset,
+            length,
+            checksum,
+            algorithm: ShuffleChecksumAlgorithm::configured()?,
+        })
+    }
+
+    // checksum is verified after all data of the segment is read
+    fn get_reader(&self, schema: SchemaRef) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+        let input = ChecksumVerifyingReader::new(
+            open_file_segment(&self.path, self.offset, self.length)?,
+            self.block_id.clone(),
+            self.algorithm,
+            self.checksum,
+        );
+        Ok(IpcCompressionReader::new(
+            Box::new(BufReader::with_capacity(65536, input)),
+            schema,
+        ))
+    }
+
+    // recomputes checksum of the whole segment, called when the segment
+    // cannot be decoded
+    fn diagnose(&self) -> Result<()> {
+        let input = open_file_segment(&self.path, self.offset, self.length)?;
+        let actual = self.algorithm.compute(input)?;
+        verify_checksum(&self.block_id, self.algorithm, self.checksum, actual)
+    }
 }
 
 struct ReadableByteChannelReader {
@@ -149,7 +149,7 @@ pub async fn write_ipc(
             }
         }
 
-        let mut writer = IpcCompressionWriter::new(IpcConsumerWrite(ipc_consumer), true);
+        let mut writer = IpcCompressionWriter::try_new(IpcConsumerWrite(ipc_consumer), true)?;
         while let Some(batch) = input.next().await.transpose()? {
             let _timer = metrics.elapsed_compute().timer();
             let num_rows = batch.num_rows();
@@ -0,0 +1,867 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        as_boolean_array, new_null_array, Array, ArrayRef, AsArray, BooleanArray, UInt32Array,
+    },
+    compute::{filter, filter_record_batch, prep_null_mask_filter, take},
+    datatypes::{SchemaRef, UInt32Type},
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use datafusion::{
+    common::{JoinSide, Result},
+    physical_expr::PhysicalExpr,
+    physical_plan::{
+        joins::utils::JoinFilter, metrics::BaselineMetrics, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::batch_size;
+use futures::StreamExt;
+
+use crate::{
+    common::output::WrappedRecordBatchSender,
+    joins::{join_hash_map::JoinHashMap, JoinType, RelativeJoinType},
+};
+
+pub struct HashJoinParams {
+    pub join_type: JoinType,
+    pub build_side: JoinSide,
+    pub probe_keys: Vec<Arc<dyn PhysicalExpr>>,
+    pub join_filter: Option<JoinFilter>,
+    pub probe_schema: SchemaRef,
+    pub output_schema: SchemaRef,
+}
+
+/// joins batches from the probe side with the hash map of the build side.
+/// unmatched rows of the build side are output after the probe side is
+/// exhausted.
+pub async fn execute_hash_join(
+    mut probe: SendableRecordBatchStream,
+    join_hash_map: Arc<JoinHashMap>,
+    join_params: HashJoinParams,
+    metrics: BaselineMetrics,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    let mut joiner = HashJoiner::try_new(join_hash_map, join_params)?;
+
+    while let Some(batch) = probe.next().await.transpose()? {
+        let elapsed_time = metrics.elapsed_compute().clone();
+        let mut timer = elapsed_time.timer();
+
+        for output_batch in joiner.join_probe_batch(&batch)? {
+            metrics.record_output(output_batch.num_rows());
+            sender.send(Ok(output_batch), Some(&mut timer)).await;
+        }
+    }
+
+    let elapsed_time = metrics.elapsed_compute().clone();
+    let mut timer = elapsed_time.timer();
+    for output_batch in joiner.finish()? {
+        metrics.record_output(output_batch.num_rows());
+        sender.send(Ok(output_batch), Some(&mut timer)).await;
+    }
+    Ok(())
+}
+
+struct HashJoiner {
+    join_hash_map: Arc<JoinHashMap>,
+    join_params: HashJoinParams,
+    join_type: RelativeJoinType,
+    build_matched: Vec<bool>,
+    batch_size: usize,
+}
+
+impl HashJoiner {
+    fn try_new(join_hash_map: Arc<JoinHashMap>, join_params: HashJoinParams) -> Result<Self> {
+        let join_type = RelativeJoinType::try_new(join_params.join_type, join_params.build_side)?;
+        let build_matched = if join_type.tracks_build_matched() {
+            vec![false; join_hash_map.num_rows()]
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            join_hash_map,
+            join_params,
+            join_type,
+            build_matched,
+            batch_size: batch_size(),
+        })
+    }
+
+    fn join_probe_batch(&mut self, probe_batch: &RecordBatch) -> Result<Vec<RecordBatch>> {
+        let num_rows = probe_batch.num_rows();
+
+        // null-aware anti join: all probe rows are output if the build side is
+        // empty, and nothing is output if the build side contains null keys
+        if self.join_type == RelativeJoinType::ProbeNullAwareAnti {
+            if self.join_hash_map.num_rows() == 0 {
+                return Ok(vec![self.output_probe_only(probe_batch.clone())?]);
+            }
+            if self.join_hash_map.has_null_keys() {
+                return Ok(vec![]);
+            }
+        }
+
+        let key_cols = self
+            .join_params
+            .probe_keys
+            .iter()
+            .map(|expr| {
+                expr.evaluate(probe_batch)
+                    .and_then(|v| v.into_array(num_rows))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let join_hash_map = self.join_hash_map.clone();
+        let (hashes, key_rows) = join_hash_map.create_probe_keys(&key_cols)?;
+        let has_filter = self.join_params.join_filter.is_some();
+
+        let mut probe_matched = vec![false; num_rows];
+        let mut build_indices = Vec::with_capacity(self.batch_size);
+        let mut probe_indices = Vec::with_capacity(self.batch_size);
+        let mut output_batches = vec![];
+
+        for probe_idx in 0..num_rows {
+            if key_cols.iter().any(|col| col.is_null(probe_idx)) {
+                continue;
+            }
+            for build_idx in join_hash_map.lookup(hashes[probe_idx], key_rows.row(probe_idx)) {
+                // without filter, semi/anti joins only need the matched flags
+                if !has_filter {
+                    match self.join_type {
+                        RelativeJoinType::ProbeSemi
+                        | RelativeJoinType::ProbeAnti
+                        | RelativeJoinType::ProbeExistence
+                        | RelativeJoinType::ProbeNullAwareAnti => {
+                            probe_matched[probe_idx] = true;
+                            break;
+                        }
+                        RelativeJoinType::BuildSemi
+                        | RelativeJoinType::BuildAnti
+                        | RelativeJoinType::BuildExistence => {
+                            self.build_matched[build_idx as usize] = true;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                build_indices.push(build_idx);
+                probe_indices.push(probe_idx as u32);
+                if build_indices.len() >= self.batch_size {
+                    self.flush_pairs(
+                        probe_batch,
+                        &mut build_indices,
+                        &mut probe_indices,
+                        &mut probe_matched,
+                        &mut output_batches,
+                    )?;
+                }
+            }
+        }
+        if !build_indices.is_empty() {
+            self.flush_pairs(
+                probe_batch,
+                &mut build_indices,
+                &mut probe_indices,
+                &mut probe_matched,
+                &mut output_batches,
+            )?;
+        }
+
+        match self.join_type {
+            RelativeJoinType::ProbeOuter | RelativeJoinType::Full => {
+                let unmatched = UInt32Array::from_iter_values(
+                    (0..num_rows as u32).filter(|&i| !probe_matched[i as usize]),
+                );
+                if !unmatched.is_empty() {
+                    output_batches.push(self.output_joined(
+                        None,
+                        Some((probe_batch, &unmatched)),
+                        unmatched.len(),
+                    )?);
+                }
+            }
+            RelativeJoinType::ProbeSemi
+            | RelativeJoinType::ProbeAnti
+            | RelativeJoinType::ProbeNullAwareAnti => {
+                let is_semi = self.join_type == RelativeJoinType::ProbeSemi;
+                let selected = BooleanArray::from(
+                    probe_matched
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &matched)| {
+                            // probe rows with null keys never match, and are
+                            // excluded by null-aware anti join
+                            if self.join_type == RelativeJoinType::ProbeNullAwareAnti {
+                                return !matched && key_cols.iter().all(|col| col.is_valid(i));
+                            }
+                            matched == is_semi
+                        })
+                        .collect::<Vec<_>>(),
+                );
+                let selected_batch = filter_record_batch(probe_batch, &selected)?;
+                if selected_batch.num_rows() > 0 {
+                    output_batches.push(self.output_probe_only(selected_batch)?);
+                }
+            }
+            RelativeJoinType::ProbeExistence => {
+                let exists: ArrayRef = Arc::new(BooleanArray::from(probe_matched));
+                output_batches.push(RecordBatch::try_new_with_options(
+                    self.join_params.output_schema.clone(),
+                    [probe_batch.columns(), &[exists]].concat(),
+                    &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+                )?);
+            }
+            _ => {}
+        }
+        Ok(output_batches)
+    }
+
+    /// outputs build side rows depending on whether they are matched, called
+    /// after the probe side is exhausted
+    fn finish(&mut self) -> Result<Vec<RecordBatch>> {
+        if self.join_type == RelativeJoinType::BuildExistence {
+            let mut output_batches = vec![];
+            let num_build_rows = self.build_matched.len();
+            for start in (0..num_build_rows).step_by(self.batch_size) {
+                let len = self.batch_size.min(num_build_rows - start);
+                let indices = UInt32Array::from_iter_values(start as u32..(start + len) as u32);
+                let exists: ArrayRef = Arc::new(BooleanArray::from(
+                    self.build_matched[start..start + len].to_vec(),
+                ));
+                output_batches.push(RecordBatch::try_new_with_options(
+                    self.join_params.output_schema.clone(),
+                    [self.build_cols(Some(&indices), len)?, vec![exists]].concat(),
+                    &RecordBatchOptions::new().with_row_count(Some(len)),
+                )?);
+            }
+            return Ok(output_batches);
+        }
+
+        let output_matched = match self.join_type {
+            RelativeJoinType::BuildSemi => true,
+            RelativeJoinType::BuildAnti | RelativeJoinType::BuildOuter | RelativeJoinType::Full => {
+                false
+            }
+            _ => return Ok(vec![]),
+        };
+        let indices = self
+            .build_matched
+            .iter()
+            .enumerate()
+            .filter(|(_, &matched)| matched == output_matched)
+            .map(|(i, _)| i as u32)
+            .collect::<Vec<_>>();
+
+        let mut output_batches = vec![];
+        for chunk in indices.chunks(self.batch_size) {
+            let chunk = UInt32Array::from(chunk.to_vec());
+            output_batches.push(match self.join_type {
+                RelativeJoinType::BuildSemi | RelativeJoinType::BuildAnti => {
+                    RecordBatch::try_new_with_options(
+                        self.join_params.output_schema.clone(),
+                        self.build_cols(Some(&chunk), chunk.len())?,
+                        &RecordBatchOptions::new().with_row_count(Some(chunk.len())),
+                    )?
+                }
+                _ => self.output_joined(Some(&chunk), None, chunk.len())?,
+            });
+        }
+        Ok(output_batches)
+    }
+
+    fn flush_pairs(
+        &mut self,
+        probe_batch: &RecordBatch,
+        build_indices: &mut Vec<u32>,
+        probe_indices: &mut Vec<u32>,
+        probe_matched: &mut [bool],
+        output_batches: &mut Vec<RecordBatch>,
+    ) -> Result<()> {
+        let mut build_indices = UInt32Array::from(std::mem::take(build_indices));
+        let mut probe_indices = UInt32Array::from(std::mem::take(probe_indices));
+
+        if let Some(join_filter) = &self.join_params.join_filter {
+            let selected =
+                self.eval_join_filter(join_filter, probe_batch, &build_indices, &probe_indices)?;
+            build_indices = filter(&build_indices, &selected)?
+                .as_primitive::<UInt32Type>()
+                .clone();
+            probe_indices = filter(&probe_indices, &selected)?
+                .as_primitive::<UInt32Type>()
+                .clone();
+        }
+
+        for (&build_idx, &probe_idx) in build_indices.values().iter().zip(probe_indices.values()) {
+            probe_matched[probe_idx as usize] = true;
+            if !self.build_matched.is_empty() {
+                self.build_matched[build_idx as usize] = true;
+            }
+        }
+
+        if self.join_type.outputs_pairs() && !build_indices.is_empty() {
+            output_batches.push(self.output_joined(
+                Some(&build_indices),
+                Some((probe_batch, &probe_indices)),
+                build_indices.len(),
+            )?);
+        }
+        Ok(())
+    }
+
+    /// evaluates join filter on the given pairs, null results are treated as
+    /// false
+    fn eval_join_filter(
+        &self,
+        join_filter: &JoinFilter,
+        probe_batch: &RecordBatch,
+        build_indices: &UInt32Array,
+        probe_indices: &UInt32Array,
+    ) -> Result<BooleanArray> {
+        let build_batch = self.join_hash_map.data_batch();
+        let build_left = matches!(self.join_params.build_side, JoinSide::Left);
+        let intermediate_columns = join_filter
+            .column_indices()
+            .iter()
+            .map(|ci| {
+                let from_build = build_left == matches!(ci.side, JoinSide::Left);
+                Ok(if from_build {
+                    take(build_batch.column(ci.index), build_indices, None)?
+                } else {
+                    take(probe_batch.column(ci.index), probe_indices, None)?
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let intermediate_batch = RecordBatch::try_new_with_options(
+            Arc::new(join_filter.schema().clone()),
+            intermediate_columns,
+            &RecordBatchOptions::new().with_row_count(Some(build_indices.len())),
+        )?;
+
+        let filtered_array = join_filter
+            .expression()
+            .evaluate(&intermediate_batch)?
+            .into_array(intermediate_batch.num_rows())?;
+        let filtered = as_boolean_array(&filtered_array);
+        Ok(if filtered.null_count() > 0 {
+            prep_null_mask_filter(filtered)
+        } else {
+            filtered.clone()
+        })
+    }
+
+    fn output_probe_only(&self, probe_batch: RecordBatch) -> Result<RecordBatch> {
+        Ok(RecordBatch::try_new_with_options(
+            self.join_params.output_schema.clone(),
+            probe_batch.columns().to_vec(),
+            &RecordBatchOptions::new().with_row_count(Some(probe_batch.num_rows())),
+        )?)
+    }
+
+    fn build_cols(&self, indices: Option<&UInt32Array>, num_rows: usize) -> Result<Vec<ArrayRef>> {
+        let build_batch = self.join_hash_map.data_batch();
+        match indices {
+            Some(indices) => build_batch
+                .columns()
+                .iter()
+                .map(|col| Ok(take(col, indices, None)?))
+                .collect(),
+            None => Ok(build_batch
+                .columns()
+                .iter()
+                .map(|col| new_null_array(col.data_type(), num_rows))
+                .collect()),
+        }
+    }
+
+    fn probe_cols(
+        &self,
+        probe: Option<(&RecordBatch, &UInt32Array)>,
+        num_rows: usize,
+    ) -> Result<Vec<ArrayRef>> {
+        match probe {
+            Some((probe_batch, indices)) => probe_batch
+                .columns()
+                .iter()
+                .map(|col| Ok(take(col, indices, None)?))
+                .collect(),
+            None => Ok(self
+                .join_params
+                .probe_schema
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), num_rows))
+                .collect()),
+        }
+    }
+
+    /// outputs joined rows, build side or probe side columns are nulls if
+    /// indices are not specified
+    fn output_joined(
+        &self,
+        build_indices: Option<&UInt32Array>,
+        probe: Option<(&RecordBatch, &UInt32Array)>,
+        num_rows: usize,
+    ) -> Result<RecordBatch> {
+        let build_cols = self.build_cols(build_indices, num_rows)?;
+        let probe_cols = self.probe_cols(probe, num_rows)?;
+        let output_cols = match self.join_params.build_side {
+            JoinSide::Left => [build_cols, probe_cols].concat(),
+            JoinSide::Right => [probe_cols, build_cols].concat(),
+        };
+        Ok(RecordBatch::try_new_with_options(
+            self.join_params.output_schema.clone(),
+            output_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result, ScalarValue},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            PhysicalExpr,
+        },
+        physical_plan::{
+            common,
+            joins::utils::{ColumnIndex, JoinFilter},
+            memory::MemoryStream,
+            metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+            SendableRecordBatchStream,
+        },
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        common::output::TaskOutputter,
+        joins::{
+            build_join_schema,
+            hash_join::{execute_hash_join, HashJoinParams},
+            join_hash_map::JoinHashMap,
+            JoinType,
+        },
+        memmgr::MemManager,
+    };
+
+    fn build_table_i32(
+        a: (&str, &Vec<i32>),
+        b: (&str, &Vec<i32>),
+        c: (&str, &Vec<i32>),
+    ) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new(a.0, DataType::Int32, false),
+            Field::new(b.0, DataType::Int32, false),
+            Field::new(c.0, DataType::Int32, false),
+        ]);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(a.1.clone())),
+                Arc::new(Int32Array::from(b.1.clone())),
+                Arc::new(Int32Array::from(c.1.clone())),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn hash_join_collect(
+        left: RecordBatch,
+        right: RecordBatch,
+        join_type: JoinType,
+        join_filter: Option<JoinFilter>,
+    ) -> Result<Vec<RecordBatch>> {
+        hash_join_collect_with_build_side(left, right, join_type, join_filter, JoinSide::Left).await
+    }
+
+    async fn hash_join_collect_with_build_side(
+        left: RecordBatch,
+        right: RecordBatch,
+        join_type: JoinType,
+        join_filter: Option<JoinFilter>,
+        build_side: JoinSide,
+    ) -> Result<Vec<RecordBatch>> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output_schema = Arc::new(build_join_schema(
+            &left.schema(),
+            &right.schema(),
+            &join_type,
+        ));
+        let (build, probe) = match build_side {
+            JoinSide::Left => (left, right),
+            JoinSide::Right => (right, left),
+        };
+        let build_key = Column::new(build.schema().field(1).name(), 1);
+        let probe_key = Column::new(probe.schema().field(1).name(), 1);
+        let probe_schema = probe.schema();
+        let probe: SendableRecordBatchStream = Box::pin(MemoryStream::try_new(
+            vec![probe],
+            probe_schema.clone(),
+            None,
+        )?);
+
+        let join_hash_map = JoinHashMap::try_new(
+            "test".to_string(),
+            build,
+            &[Arc::new(build_key) as Arc<dyn PhysicalExpr>],
+        )
+        .await?;
+        let join_params = HashJoinParams {
+            join_type,
+            build_side,
+            probe_keys: vec![Arc::new(probe_key)],
+            join_filter,
+            probe_schema,
+            output_schema: output_schema.clone(),
+        };
+        let metrics = BaselineMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        let output = task_ctx.output_with_sender("HashJoin", output_schema, move |sender| {
+            execute_hash_join(probe, join_hash_map, join_params, metrics, sender)
+        })?;
+        common::collect(output).await
+    }
+
+    #[tokio::test]
+    async fn test_hash_join() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![1, 2, 3, 4]),
+            ("b1", &vec![4, 5, 5, 7]),
+            ("c1", &vec![7, 8, 9, 0]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![10, 20, 30]),
+            ("b2", &vec![4, 5, 6]),
+            ("c2", &vec![70, 80, 90]),
+        );
+
+        let batches = hash_join_collect(left.clone(), right.clone(), JoinType::Inner, None).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "| 1  | 4  | 7  | 10 | 4  | 70 |",
+            "| 2  | 5  | 8  | 20 | 5  | 80 |",
+            "| 3  | 5  | 9  | 20 | 5  | 80 |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches = hash_join_collect(left.clone(), right.clone(), JoinType::Full, None).await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "|    |    |    | 30 | 6  | 90 |",
+            "| 1  | 4  | 7  | 10 | 4  | 70 |",
+            "| 2  | 5  | 8  | 20 | 5  | 80 |",
+            "| 3  | 5  | 9  | 20 | 5  | 80 |",
+            "| 4  | 7  | 0  |    |    |    |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches =
+            hash_join_collect(left.clone(), right.clone(), JoinType::LeftAnti, None).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 4  | 7  | 0  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches =
+            hash_join_collect(left.clone(), right.clone(), JoinType::RightSemi, None).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a2 | b2 | c2 |",
+            "+----+----+----+",
+            "| 10 | 4  | 70 |",
+            "| 20 | 5  | 80 |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hash_join_with_filter() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![1, 2, 3, 4]),
+            ("b1", &vec![4, 5, 5, 7]),
+            ("c1", &vec![7, 8, 9, 0]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![10, 20, 30]),
+            ("b2", &vec![4, 5, 6]),
+            ("c2", &vec![70, 80, 90]),
+        );
+
+        // filter: c1 < 9
+        let filter_schema = Schema::new(vec![Field::new("c1", DataType::Int32, false)]);
+        let join_filter = JoinFilter::new(
+            Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("c1", 0)),
+                Operator::Lt,
+                Arc::new(Literal::new(ScalarValue::Int32(Some(9)))),
+            )),
+            vec![ColumnIndex {
+                index: 2,
+                side: JoinSide::Left,
+            }],
+            filter_schema,
+        );
+
+        let batches = hash_join_collect(
+            left.clone(),
+            right.clone(),
+            JoinType::Right,
+            Some(join_filter.clone()),
+        )
+        .await?;
+        let expected = vec![
+            "+----+----+----+----+----+----+",
+            "| a1 | b1 | c1 | a2 | b2 | c2 |",
+            "+----+----+----+----+----+----+",
+            "|    |    |    | 30 | 6  | 90 |",
+            "| 1  | 4  | 7  | 10 | 4  | 70 |",
+            "| 2  | 5  | 8  | 20 | 5  | 80 |",
+            "+----+----+----+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        let batches = hash_join_collect(left, right, JoinType::LeftSemi, Some(join_filter)).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 1  | 4  | 7  |",
+            "| 2  | 5  | 8  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hash_join_semi_anti_with_filter() -> Result<()> {
+        // same tables as sort-merge join tests, broadcasting either side
+        let left = build_table_i32(
+            ("a1", &vec![1, 2, 3, 4, 5]),
+            ("b1", &vec![4, 5, 5, 6, 7]),
+            ("c1", &vec![7, 8, 85, 95, 9]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b2", &vec![4, 5, 5, 6]),
+            ("c2", &vec![70, 10, 80, 90]),
+        );
+
+        // filter: c1 < c2
+        let filter_schema = Schema::new(vec![
+            Field::new("c1", DataType::Int32, false),
+            Field::new("c2", DataType::Int32, false),
+        ]);
+        let join_filter = JoinFilter::new(
+            Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("c1", 0)),
+                Operator::Lt,
+                Arc::new(Column::new("c2", 1)),
+            )),
+            vec![
+                ColumnIndex {
+                    index: 2,
+                    side: JoinSide::Left,
+                },
+                ColumnIndex {
+                    index: 2,
+                    side: JoinSide::Right,
+                },
+            ],
+            filter_schema,
+        );
+
+        let cases = [
+            (
+                JoinType::LeftSemi,
+                vec![
+                    "+----+----+----+",
+                    "| a1 | b1 | c1 |",
+                    "+----+----+----+",
+                    "| 1  | 4  | 7  |",
+                    "| 2  | 5  | 8  |",
+                    "+----+----+----+",
+                ],
+            ),
+            (
+                JoinType::LeftAnti,
+                vec![
+                    "+----+----+----+",
+                    "| a1 | b1 | c1 |",
+                    "+----+----+----+",
+                    "| 3  | 5  | 85 |",
+                    "| 4  | 6  | 95 |",
+                    "| 5  | 7  | 9  |",
+                    "+----+----+----+",
+                ],
+            ),
+            (
+                JoinType::RightSemi,
+                vec![
+                    "+----+----+----+",
+                    "| a2 | b2 | c2 |",
+                    "+----+----+----+",
+                    "| 10 | 4  | 70 |",
+                    "| 20 | 5  | 10 |",
+                    "| 30 | 5  | 80 |",
+                    "+----+----+----+",
+                ],
+            ),
+            (
+                JoinType::RightAnti,
+                vec![
+                    "+----+----+----+",
+                    "| a2 | b2 | c2 |",
+                    "+----+----+----+",
+                    "| 40 | 6  | 90 |",
+                    "+----+----+----+",
+                ],
+            ),
+        ];
+        for (join_type, expected) in cases {
+            for build_side in [JoinSide::Left, JoinSide::Right] {
+                let batches = hash_join_collect_with_build_side(
+                    left.clone(),
+                    right.clone(),
+                    join_type,
+                    Some(join_filter.clone()),
+                    build_side,
+                )
+                .await?;
+                assert_batches_sorted_eq!(expected, &batches);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hash_join_existence() -> Result<()> {
+        let left = build_table_i32(
+            ("a1", &vec![1, 2, 3, 4]),
+            ("b1", &vec![4, 5, 5, 7]),
+            ("c1", &vec![7, 8, 9, 0]),
+        );
+        let right = build_table_i32(
+            ("a2", &vec![10, 20, 30]),
+            ("b2", &vec![4, 5, 6]),
+            ("c2", &vec![70, 80, 90]),
+        );
+        let expected = vec![
+            "+----+----+----+--------+",
+            "| a1 | b1 | c1 | exists |",
+            "+----+----+----+--------+",
+            "| 1  | 4  | 7  | true   |",
+            "| 2  | 5  | 8  | true   |",
+            "| 3  | 5  | 9  | true   |",
+            "| 4  | 7  | 0  | false  |",
+            "+----+----+----+--------+",
+        ];
+        for build_side in [JoinSide::Left, JoinSide::Right] {
+            let batches = hash_join_collect_with_build_side(
+                left.clone(),
+                right.clone(),
+                JoinType::Existence,
+                None,
+                build_side,
+            )
+            .await?;
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hash_join_null_aware_anti() -> Result<()> {
+        let build_nullable_table = |a: (&str, Vec<i32>), b: (&str, Vec<Option<i32>>)| {
+            let schema = Schema::new(vec![
+                Field::new(a.0, DataType::Int32, false),
+                Field::new(b.0, DataType::Int32, true),
+            ]);
+            RecordBatch::try_new(
+                Arc::new(schema),
+                vec![
+                    Arc::new(Int32Array::from(a.1)),
+                    Arc::new(Int32Array::from(b.1)),
+                ],
+            )
+            .unwrap()
+        };
+        let left =
+            build_nullable_table(("a1", vec![1, 2, 3]), ("b1", vec![Some(4), None, Some(7)]));
+        let null_aware_anti_join = |right: RecordBatch| {
+            hash_join_collect_with_build_side(
+                left.clone(),
+                right,
+                JoinType::NullAwareAnti,
+                None,
+                JoinSide::Right,
+            )
+        };
+
+        // left rows with null keys are excluded
+        let right = build_nullable_table(("a2", vec![10, 20]), ("b2", vec![Some(4), Some(6)]));
+        let batches = null_aware_anti_join(right).await?;
+        let expected = vec![
+            "+----+----+",
+            "| a1 | b1 |",
+            "+----+----+",
+            "| 3  | 7  |",
+            "+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // nothing is output if right side contains null keys
+        let right = build_nullable_table(("a2", vec![10, 20]), ("b2", vec![Some(4), None]));
+        let batches = null_aware_anti_join(right).await?;
+        assert!(batches.iter().all(|batch| batch.num_rows() == 0));
+
+        // all rows are output if right side is empty
+        let right = build_nullable_table(("a2", vec![]), ("b2", vec![]));
+        let batches = null_aware_anti_join(right).await?;
+        let expected = vec![
+            "+----+----+",
+            "| a1 | b1 |",
+            "+----+----+",
+            "| 1  | 4  |",
+            "| 2  |    |",
+            "| 3  | 7  |",
+            "+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+}
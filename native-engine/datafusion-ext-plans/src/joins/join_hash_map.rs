@@ -0,0 +1,306 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Weak};
+
+use arrow::{
+    array::{Array, ArrayRef},
+    datatypes::DataType,
+    record_batch::RecordBatch,
+    row::{Row, RowConverter, Rows, SortField},
+};
+use async_trait::async_trait;
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::{array_size::ArraySize, cast::cast, spark_hash::create_hashes};
+use futures::lock::Mutex;
+use hashbrown::HashMap;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex as SyncMutex;
+
+use crate::memmgr::{MemConsumer, MemConsumerInfo, MemManager};
+
+/// an immutable hash map of the build side of hash joins. rows are looked up
+/// with spark-compatible murmur3 hashes of join keys, and hash collisions are
+/// resolved by comparing the join keys in row format.
+pub struct JoinHashMap {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    data_batch: RecordBatch,
+    key_data_types: Vec<DataType>,
+    key_row_converter: SyncMutex<RowConverter>,
+    key_rows: Rows,
+    map: HashMap<u32, (u32, u32)>, // hash => range of sorted_indices
+    sorted_indices: Vec<u32>,      // indices of non-null keys, sorted by hash
+}
+
+impl JoinHashMap {
+    pub async fn try_new(
+        name: String,
+        data_batch: RecordBatch,
+        key_exprs: &[Arc<dyn PhysicalExpr>],
+    ) -> Result<Arc<Self>> {
+        let key_cols = key_exprs
+            .iter()
+            .map(|expr| {
+                expr.evaluate(&data_batch)
+                    .and_then(|v| v.into_array(data_batch.num_rows()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let key_data_types = key_cols
+            .iter()
+            .map(|col| col.data_type().clone())
+            .collect::<Vec<_>>();
+        let mut key_row_converter = RowConverter::new(
+            key_data_types
+                .iter()
+                .map(|dt| SortField::new(dt.clone()))
+                .collect(),
+        )?;
+        let key_rows = key_row_converter.convert_columns(&key_cols)?;
+        let hashes = create_key_hashes(&key_cols, data_batch.num_rows())?;
+
+        // null keys never match, so they are excluded from the map
+        let mut sorted_indices = (0..data_batch.num_rows())
+            .filter(|&i| key_cols.iter().all(|col| col.is_valid(i)))
+            .map(|i| i as u32)
+            .collect::<Vec<_>>();
+        sorted_indices.sort_unstable_by_key(|&i| hashes[i as usize]);
+
+        let mut map = HashMap::new();
+        let mut start = 0;
+        while start < sorted_indices.len() {
+            let hash = hashes[sorted_indices[start] as usize];
+            let mut end = start + 1;
+            while end < sorted_indices.len() && hashes[sorted_indices[end] as usize] == hash {
+                end += 1;
+            }
+            map.insert(hash, (start as u32, end as u32));
+            start = end;
+        }
+        map.shrink_to_fit();
+
+        let join_hash_map = Arc::new(Self {
+            name,
+            mem_consumer_info: None,
+            data_batch,
+            key_data_types,
+            key_row_converter: SyncMutex::new(key_row_converter),
+            key_rows,
+            map,
+            sorted_indices,
+        });
+        MemManager::register_consumer(join_hash_map.clone(), false);
+        join_hash_map
+            .update_mem_used(join_hash_map.mem_size())
+            .await?;
+        Ok(join_hash_map)
+    }
+
+    pub fn data_batch(&self) -> &RecordBatch {
+        &self.data_batch
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.data_batch.num_rows()
+    }
+
+    /// returns whether any row of the build side has null keys
+    pub fn has_null_keys(&self) -> bool {
+        self.sorted_indices.len() < self.num_rows()
+    }
+
+    pub fn mem_size(&self) -> usize {
+        self.data_batch.get_array_mem_size()
+            + self.key_rows.size()
+            + self.sorted_indices.capacity() * std::mem::size_of::<u32>()
+            + self.map.capacity() * std::mem::size_of::<(u32, (u32, u32))>()
+    }
+
+    /// converts join keys of the probe side into hashes and rows comparable
+    /// with the build side keys, keys are casted to build side types if
+    /// necessary
+    pub fn create_probe_keys(&self, key_cols: &[ArrayRef]) -> Result<(Vec<u32>, Rows)> {
+        let num_rows = key_cols.first().map(|col| col.len()).unwrap_or(0);
+        let key_cols = key_cols
+            .iter()
+            .zip(&self.key_data_types)
+            .map(|(col, dt)| {
+                if col.data_type() != dt {
+                    return cast(col, dt);
+                }
+                Ok(col.clone())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let hashes = create_key_hashes(&key_cols, num_rows)?;
+        let rows = self.key_row_converter.lock().convert_columns(&key_cols)?;
+        Ok((hashes, rows))
+    }
+
+    /// returns indices of build side rows matching the given probe key
+    pub fn lookup<'a>(&'a self, hash: u32, key: Row<'a>) -> impl Iterator<Item = u32> + 'a {
+        let (start, end) = self.map.get(&hash).copied().unwrap_or_default();
+        self.sorted_indices[start as usize..end as usize]
+            .iter()
+            .copied()
+            .filter(move |&i| self.key_rows.row(i as usize) == key)
+    }
+}
+
+#[async_trait]
+impl MemConsumer for JoinHashMap {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+}
+
+impl Drop for JoinHashMap {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+fn create_key_hashes(key_cols: &[ArrayRef], num_rows: usize) -> Result<Vec<u32>> {
+    // use identical seed as spark hash partition
+    let mut hashes = vec![42; num_rows];
+    create_hashes(key_cols, &mut hashes)?;
+    Ok(hashes)
+}
+
+type CachedJoinHashMapSlot = Arc<Mutex<Option<Arc<JoinHashMap>>>>;
+
+static CACHED_JOIN_HASH_MAPS: OnceCell<SyncMutex<HashMap<String, CachedJoinHashMapSlot>>> =
+    OnceCell::new();
+
+/// returns the executor-wide cache slot of a join hash map, so that tasks
+/// joining with the same broadcasted data can share one hash map. the slot
+/// holds a strong reference, so the hash map is also reused by tasks running
+/// after the previous ones are finished. it is kept until released with
+/// `release_cached_join_hash_map()` on stage cleanup.
+pub fn cached_join_hash_map_slot(cached_id: &str) -> CachedJoinHashMapSlot {
+    let mut cached = CACHED_JOIN_HASH_MAPS.get_or_init(Default::default).lock();
+    cached.entry(cached_id.to_string()).or_default().clone()
+}
+
+/// releases a cached join hash map. tasks still using the hash map keep their
+/// own references, and the memory is freed after they are finished.
+pub fn release_cached_join_hash_map(cached_id: &str) {
+    if let Some(cached) = CACHED_JOIN_HASH_MAPS.get() {
+        if cached.lock().remove(cached_id).is_some() {
+            log::info!("released cached join hash map: {cached_id}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::physical_expr::{expressions::Column, PhysicalExpr};
+
+    use crate::{
+        joins::join_hash_map::{
+            cached_join_hash_map_slot, release_cached_join_hash_map, JoinHashMap,
+        },
+        memmgr::MemManager,
+    };
+
+    #[tokio::test]
+    async fn test_join_hash_map() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![
+                    Some(1),
+                    Some(2),
+                    Some(1),
+                    None,
+                    Some(3),
+                ])),
+                Arc::new(StringArray::from(vec!["x", "y", "x", "x", "z"])),
+            ],
+        )?;
+        let key_exprs: Vec<Arc<dyn PhysicalExpr>> =
+            vec![Arc::new(Column::new("a", 0)), Arc::new(Column::new("b", 1))];
+        let join_hash_map = JoinHashMap::try_new("test".to_string(), batch, &key_exprs).await?;
+
+        // probe keys are casted to build side key types
+        let probe_cols: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![Some(1), Some(3), Some(3), None])),
+            Arc::new(StringArray::from(vec!["x", "z", "x", "x"])),
+        ];
+        let (hashes, rows) = join_hash_map.create_probe_keys(&probe_cols)?;
+        let lookup = |i: usize| {
+            let mut matched = join_hash_map
+                .lookup(hashes[i], rows.row(i))
+                .collect::<Vec<_>>();
+            matched.sort();
+            matched
+        };
+        assert_eq!(lookup(0), vec![0, 2]);
+        assert_eq!(lookup(1), vec![4]);
+        assert_eq!(lookup(2), Vec::<u32>::new());
+        assert_eq!(lookup(3), Vec::<u32>::new());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_join_hash_map() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![1, 2, 3]))])?;
+        let key_exprs: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("a", 0))];
+
+        // first task builds the hash map and finishes
+        {
+            let slot = cached_join_hash_map_slot("test_cached");
+            let mut slot = slot.lock().await;
+            assert!(slot.is_none());
+            let join_hash_map =
+                JoinHashMap::try_new("test".to_string(), batch.clone(), &key_exprs).await?;
+            *slot = Some(join_hash_map);
+        }
+
+        // hash map is still cached for tasks running later
+        let cached = cached_join_hash_map_slot("test_cached")
+            .lock()
+            .await
+            .clone();
+        assert_eq!(cached.as_ref().map(|hash_map| hash_map.num_rows()), Some(3));
+
+        // released hash map is still usable by running tasks
+        release_cached_join_hash_map("test_cached");
+        assert!(cached_join_hash_map_slot("test_cached")
+            .lock()
+            .await
+            .is_none());
+        assert_eq!(cached.map(|hash_map| hash_map.num_rows()), Some(3));
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use datafusion::{
+    common::{JoinSide, Result},
+    logical_expr,
+    physical_plan::joins::utils::{JoinFilter, JoinOn},
+};
+use datafusion_ext_commons::df_unimplemented_err;
+
+pub mod hash_join;
+pub mod join_hash_map;
+pub mod nested_loop_join;
+pub mod range_join;
+
+/// join types supported by blaze joins, which extends datafusion's join types
+/// with spark-specific existence join and null-aware anti join
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+    LeftSemi,
+    RightSemi,
+    LeftAnti,
+    RightAnti,
+    /// outputs all left rows with an additional non-null boolean column
+    /// indicating whether each row has any matched right rows
+    Existence,
+    /// left anti join with the semantics of spark's `NOT IN` subquery: left
+    /// rows are output only if the join condition is false (not null) for all
+    /// right rows. so nothing is output if any right key is null, and left
+    /// rows with null keys are output only if the right side is empty
+    NullAwareAnti,
+}
+
+impl JoinType {
+    /// returns whether only left columns are output (excluding the exists
+    /// column of existence join)
+    pub fn outputs_left_only(&self) -> bool {
+        matches!(
+            self,
+            Self::LeftSemi | Self::LeftAnti | Self::Existence | Self::NullAwareAnti
+        )
+    }
+
+    /// returns whether only right columns are output
+    pub fn outputs_right_only(&self) -> bool {
+        matches!(self, Self::RightSemi | Self::RightAnti)
+    }
+}
+
+impl From<logical_expr::JoinType> for JoinType {
+    fn from(join_type: logical_expr::JoinType) -> Self {
+        match join_type {
+            logical_expr::JoinType::Inner => Self::Inner,
+            logical_expr::JoinType::Left => Self::Left,
+            logical_expr::JoinType::Right => Self::Right,
+            logical_expr::JoinType::Full => Self::Full,
+            logical_expr::JoinType::LeftSemi => Self::LeftSemi,
+            logical_expr::JoinType::RightSemi => Self::RightSemi,
+            logical_expr::JoinType::LeftAnti => Self::LeftAnti,
+            logical_expr::JoinType::RightAnti => Self::RightAnti,
+        }
+    }
+}
+
+/// creates output schema of joining left and right schemas
+pub fn build_join_schema(left: &Schema, right: &Schema, join_type: &JoinType) -> Schema {
+    let df_join_type = match join_type {
+        JoinType::Inner => logical_expr::JoinType::Inner,
+        JoinType::Left => logical_expr::JoinType::Left,
+        JoinType::Right => logical_expr::JoinType::Right,
+        JoinType::Full => logical_expr::JoinType::Full,
+        JoinType::LeftSemi => logical_expr::JoinType::LeftSemi,
+        JoinType::RightSemi => logical_expr::JoinType::RightSemi,
+        JoinType::LeftAnti | JoinType::NullAwareAnti => logical_expr::JoinType::LeftAnti,
+        JoinType::RightAnti => logical_expr::JoinType::RightAnti,
+        JoinType::Existence => {
+            let mut fields = left.fields().iter().cloned().collect::<Vec<_>>();
+            fields.push(Arc::new(Field::new("exists", DataType::Boolean, false)));
+            return Schema::new_with_metadata(fields, left.metadata().clone());
+        }
+    };
+    datafusion::physical_plan::joins::utils::build_join_schema(left, right, &df_join_type).0
+}
+
+/// null-aware anti join on keys requires spark's single-column `NOT IN`
+/// pattern, in which there is only one join key and no extra join filter
+pub fn check_null_aware_anti_join(on: &JoinOn, join_filter: &Option<JoinFilter>) -> Result<()> {
+    if on.len() != 1 || join_filter.is_some() {
+        return df_unimplemented_err!(
+            "null-aware anti join only supports single join key without join filter"
+        );
+    }
+    Ok(())
+}
+
+/// join types relative to the build side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RelativeJoinType {
+    Inner,
+    Full,
+    ProbeOuter,
+    BuildOuter,
+    ProbeSemi,
+    BuildSemi,
+    ProbeAnti,
+    BuildAnti,
+    ProbeExistence,
+    BuildExistence,
+    ProbeNullAwareAnti,
+}
+
+impl RelativeJoinType {
+    pub fn try_new(join_type: JoinType, build_side: JoinSide) -> Result<Self> {
+        let build_left = matches!(build_side, JoinSide::Left);
+        Ok(match (join_type, build_left) {
+            (JoinType::Inner, _) => Self::Inner,
+            (JoinType::Full, _) => Self::Full,
+            (JoinType::Left, true) | (JoinType::Right, false) => Self::BuildOuter,
+            (JoinType::Left, false) | (JoinType::Right, true) => Self::ProbeOuter,
+            (JoinType::LeftSemi, true) | (JoinType::RightSemi, false) => Self::BuildSemi,
+            (JoinType::LeftSemi, false) | (JoinType::RightSemi, true) => Self::ProbeSemi,
+            (JoinType::LeftAnti, true) | (JoinType::RightAnti, false) => Self::BuildAnti,
+            (JoinType::LeftAnti, false) | (JoinType::RightAnti, true) => Self::ProbeAnti,
+            (JoinType::Existence, true) => Self::BuildExistence,
+            (JoinType::Existence, false) => Self::ProbeExistence,
+            (JoinType::NullAwareAnti, false) => Self::ProbeNullAwareAnti,
+            (JoinType::NullAwareAnti, true) => {
+                return df_unimplemented_err!(
+                    "null-aware anti join does not support building left side"
+                );
+            }
+        })
+    }
+
+    /// returns whether joined pairs of build and probe rows are output
+    pub fn outputs_pairs(&self) -> bool {
+        matches!(
+            self,
+            Self::Inner | Self::Full | Self::ProbeOuter | Self::BuildOuter
+        )
+    }
+
+    /// returns whether matched flags of build side rows are needed
+    pub fn tracks_build_matched(&self) -> bool {
+        matches!(
+            self,
+            Self::Full
+                | Self::BuildOuter
+                | Self::BuildSemi
+                | Self::BuildAnti
+                | Self::BuildExistence
+        )
+    }
+}
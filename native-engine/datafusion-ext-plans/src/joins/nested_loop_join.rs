@@ -0,0 +1,1104 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::{Cursor, Write},
+    ops::Range,
+    sync::{Arc, Weak},
+};
+
+use arrow::{
+    array::{
+        as_boolean_array, new_null_array, Array, ArrayRef, AsArray, BooleanArray, UInt32Array,
+    },
+    compute::{filter, filter_record_batch, take},
+    datatypes::{SchemaRef, UInt32Type},
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use async_trait::async_trait;
+use datafusion::{
+    common::{JoinSide, Result},
+    physical_plan::{
+        joins::utils::JoinFilter,
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+        SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    batch_size, df_execution_err,
+    io::{read_one_batch, write_one_batch},
+};
+use futures::{lock::Mutex, StreamExt};
+
+use crate::{
+    common::output::WrappedRecordBatchSender,
+    joins::{range_join::RangeJoinCondition, JoinType, RelativeJoinType},
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{FileSpill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+};
+
+pub struct NestedLoopJoinParams {
+    pub join_type: JoinType,
+    pub build_side: JoinSide,
+    pub join_filter: Option<JoinFilter>,
+    pub build_schema: SchemaRef,
+    pub probe_schema: SchemaRef,
+    pub output_schema: SchemaRef,
+    pub range_join_enabled: bool,
+}
+
+/// joins batches from the probe side with every chunk of the build side.
+/// unmatched rows of the build side are output after the probe side is
+/// exhausted.
+pub async fn execute_nested_loop_join(
+    mut probe: SendableRecordBatchStream,
+    build_side: Arc<NestedLoopJoinBuildSide>,
+    join_params: NestedLoopJoinParams,
+    metrics: BaselineMetrics,
+    sender: Arc<WrappedRecordBatchSender>,
+) -> Result<()> {
+    let mut build_chunks = build_side.take_chunks().await?;
+    let mut joiner = NestedLoopJoiner::try_new(build_chunks.num_rows, join_params)?;
+    if let Some(range_condition) = &joiner.range_condition {
+        build_chunks.sort_chunks(range_condition)?;
+    }
+
+    while let Some(probe_batch) = probe.next().await.transpose()? {
+        let elapsed_time = metrics.elapsed_compute().clone();
+        let mut timer = elapsed_time.timer();
+        let num_probe_rows = probe_batch.num_rows();
+        let mut probe_matched = vec![false; num_probe_rows];
+
+        let mut build_offset = 0;
+        for chunk_idx in 0..build_chunks.num_chunks() {
+            if joiner.probe_side_determined(&probe_matched) {
+                break;
+            }
+            let build_chunk = build_chunks.read_chunk(chunk_idx)?;
+            let windows = joiner.probe_windows(&build_chunk, &probe_batch)?;
+
+            // join with a part of probe rows each time, so that the number of
+            // candidate pairs does not exceed batch size
+            let mut start = 0;
+            while start < num_probe_rows {
+                let mut end = start + 1;
+                let mut num_pairs = windows[start].len();
+                while end < num_probe_rows && num_pairs + windows[end].len() <= joiner.batch_size {
+                    num_pairs += windows[end].len();
+                    end += 1;
+                }
+                if num_pairs > 0 {
+                    for output_batch in joiner.join_build_chunk(
+                        &probe_batch,
+                        start..end,
+                        &windows,
+                        &build_chunk,
+                        build_offset,
+                        &mut probe_matched,
+                    )? {
+                        metrics.record_output(output_batch.num_rows());
+                        sender.send(Ok(output_batch), Some(&mut timer)).await;
+                    }
+                }
+                start = end;
+            }
+            build_offset += build_chunk.num_rows();
+        }
+
+        for output_batch in joiner.finish_probe_batch(&probe_batch, probe_matched)? {
+            metrics.record_output(output_batch.num_rows());
+            sender.send(Ok(output_batch), Some(&mut timer)).await;
+        }
+    }
+
+    let elapsed_time = metrics.elapsed_compute().clone();
+    let mut timer = elapsed_time.timer();
+    if joiner.join_type.tracks_build_matched() {
+        let mut build_offset = 0;
+        for chunk_idx in 0..build_chunks.num_chunks() {
+            let build_chunk = build_chunks.read_chunk(chunk_idx)?;
+            for output_batch in joiner.finish_build_chunk(&build_chunk, build_offset)? {
+                metrics.record_output(output_batch.num_rows());
+                sender.send(Ok(output_batch), Some(&mut timer)).await;
+            }
+            build_offset += build_chunk.num_rows();
+        }
+    }
+    Ok(())
+}
+
+/// collects all rows of the build side. collected batches are spilled under
+/// memory pressure, and then streamed back chunk by chunk while joining.
+pub struct NestedLoopJoinBuildSide {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    schema: SchemaRef,
+    data: Mutex<BuildSideData>,
+    spill_metrics: SpillMetrics,
+}
+
+#[derive(Default)]
+struct BuildSideData {
+    staging_batches: Vec<RecordBatch>,
+    staging_mem_used: usize,
+    spill: Option<FileSpill>,
+    spill_offsets: Vec<u64>, // offset of each spilled batch
+    num_rows: usize,
+}
+
+impl NestedLoopJoinBuildSide {
+    pub fn try_new(
+        schema: SchemaRef,
+        partition: usize,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Arc<Self>> {
+        let build_side = Arc::new(Self {
+            name: format!("NestedLoopJoinBuildSide[partition={}]", partition),
+            mem_consumer_info: None,
+            schema,
+            data: Default::default(),
+            spill_metrics: SpillMetrics::new(metrics, partition),
+        });
+        MemManager::register_consumer(build_side.clone(), true);
+        Ok(build_side)
+    }
+
+    pub async fn insert_batch(&self, batch: RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let mut data = self.data.lock().await;
+        data.num_rows += batch.num_rows();
+        data.staging_mem_used += batch.get_array_mem_size();
+        data.staging_batches.push(batch);
+        let mem_used = data.staging_mem_used;
+        drop(data);
+
+        self.update_mem_used(mem_used).await?;
+        Ok(())
+    }
+
+    /// finishes collecting and takes all chunks of the build side. if any
+    /// batch has been spilled, all in-memory batches are spilled too, so that
+    /// memory is released during joining.
+    async fn take_chunks(&self) -> Result<BuildChunks> {
+        self.set_spillable(false);
+        let mut data = self.data.lock().await;
+        if data.spill.is_none() {
+            return Ok(BuildChunks {
+                schema: self.schema.clone(),
+                num_rows: data.num_rows,
+                chunks: std::mem::take(&mut data.staging_batches)
+                    .into_iter()
+                    .map(BuildChunk::InMemory)
+                    .collect(),
+                spill: None,
+                spill_metrics: self.spill_metrics.clone(),
+            });
+        }
+
+        data.spill_staging_batches(&self.spill_metrics)?;
+        let chunks = std::mem::take(&mut data.spill_offsets)
+            .into_iter()
+            .map(BuildChunk::Spilled)
+            .collect();
+        let spill = data.spill.take();
+        let num_rows = data.num_rows;
+        drop(data);
+
+        self.update_mem_used(0).await?;
+        Ok(BuildChunks {
+            schema: self.schema.clone(),
+            num_rows,
+            chunks,
+            spill,
+            spill_metrics: self.spill_metrics.clone(),
+        })
+    }
+}
+
+impl BuildSideData {
+    fn spill_staging_batches(&mut self, spill_metrics: &SpillMetrics) -> Result<()> {
+        for batch in std::mem::take(&mut self.staging_batches) {
+            let spill = match &mut self.spill {
+                Some(spill) => spill,
+                None => self.spill.insert(FileSpill::try_new(spill_metrics)?),
+            };
+            self.spill_offsets.push(spill_one_batch(spill, &batch)?);
+        }
+        self.staging_mem_used = 0;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MemConsumer for NestedLoopJoinBuildSide {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<()> {
+        self.data
+            .lock()
+            .await
+            .spill_staging_batches(&self.spill_metrics)?;
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+impl Drop for NestedLoopJoinBuildSide {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+enum BuildChunk {
+    InMemory(RecordBatch),
+    Spilled(u64), // offset in the spill
+}
+
+struct BuildChunks {
+    schema: SchemaRef,
+    num_rows: usize,
+    chunks: Vec<BuildChunk>,
+    spill: Option<FileSpill>, // all spilled chunks, can be read for multiple times
+    spill_metrics: SpillMetrics,
+}
+
+impl BuildChunks {
+    fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// reads a chunk of the build side. spilled chunks are read from their
+    /// offsets, so they can be read again for every probe batch.
+    fn read_chunk(&self, idx: usize) -> Result<RecordBatch> {
+        match &self.chunks[idx] {
+            BuildChunk::InMemory(batch) => Ok(batch.clone()),
+            BuildChunk::Spilled(offset) => {
+                read_spilled_chunk(self.spill.as_ref(), *offset, &self.schema)
+            }
+        }
+    }
+
+    /// sorts every chunk by the build key of range condition. sorted spilled
+    /// chunks are written into a new spill.
+    fn sort_chunks(&mut self, range_condition: &RangeJoinCondition) -> Result<()> {
+        let mut sorted_spill = None;
+        for chunk in &mut self.chunks {
+            match chunk {
+                BuildChunk::InMemory(batch) => {
+                    *batch = range_condition.sort_build_chunk(batch)?;
+                }
+                BuildChunk::Spilled(offset) => {
+                    let batch = read_spilled_chunk(self.spill.as_ref(), *offset, &self.schema)?;
+                    let sorted = range_condition.sort_build_chunk(&batch)?;
+                    let sorted_spill = match &mut sorted_spill {
+                        Some(spill) => spill,
+                        None => sorted_spill.insert(FileSpill::try_new(&self.spill_metrics)?),
+                    };
+                    *offset = spill_one_batch(sorted_spill, &sorted)?;
+                }
+            }
+        }
+        if sorted_spill.is_some() {
+            self.spill = sorted_spill;
+        }
+        Ok(())
+    }
+}
+
+fn read_spilled_chunk(
+    spill: Option<&FileSpill>,
+    offset: u64,
+    schema: &SchemaRef,
+) -> Result<RecordBatch> {
+    let Some(spill) = spill else {
+        return df_execution_err!("NestedLoopJoin: missing spill");
+    };
+    match read_one_batch(&mut spill.get_compressed_reader_at(offset), schema)? {
+        Some(batch) => Ok(batch),
+        None => df_execution_err!("NestedLoopJoin: missing spilled chunk"),
+    }
+}
+
+/// appends a batch to the spill, returns the offset of the spilled chunk
+fn spill_one_batch(spill: &mut FileSpill, batch: &RecordBatch) -> Result<u64> {
+    let offset = spill.file_size();
+    let mut buf = vec![];
+    write_one_batch(batch, &mut Cursor::new(&mut buf))?;
+    let mut writer = spill.get_compressed_writer();
+    writer.write_all(&buf)?;
+    drop(writer);
+    Ok(offset)
+}
+
+struct NestedLoopJoiner {
+    join_params: NestedLoopJoinParams,
+    join_type: RelativeJoinType,
+    range_condition: Option<RangeJoinCondition>,
+    build_matched: Vec<bool>,
+    batch_size: usize,
+}
+
+impl NestedLoopJoiner {
+    fn try_new(num_build_rows: usize, join_params: NestedLoopJoinParams) -> Result<Self> {
+        let join_type = RelativeJoinType::try_new(join_params.join_type, join_params.build_side)?;
+        let build_matched = if join_type.tracks_build_matched() {
+            vec![false; num_build_rows]
+        } else {
+            vec![]
+        };
+
+        // null-aware anti join treats null results of filter as matched, so
+        // null bounds cannot be pruned
+        let range_condition = match &join_params.join_filter {
+            Some(join_filter)
+                if join_params.range_join_enabled
+                    && join_type != RelativeJoinType::ProbeNullAwareAnti =>
+            {
+                RangeJoinCondition::try_extract(
+                    join_filter,
+                    join_params.build_side,
+                    &join_params.build_schema,
+                    &join_params.probe_schema,
+                )?
+            }
+            _ => None,
+        };
+        Ok(Self {
+            join_params,
+            join_type,
+            range_condition,
+            build_matched,
+            batch_size: batch_size(),
+        })
+    }
+
+    /// returns whether the rest build chunks cannot change the output of the
+    /// given probe batch
+    fn probe_side_determined(&self, probe_matched: &[bool]) -> bool {
+        !self.join_type.outputs_pairs()
+            && !self.join_type.tracks_build_matched()
+            && probe_matched.iter().all(|&matched| matched)
+    }
+
+    /// returns the candidate build rows of the chunk for each probe row
+    fn probe_windows(
+        &self,
+        build_chunk: &RecordBatch,
+        probe_batch: &RecordBatch,
+    ) -> Result<Vec<Range<usize>>> {
+        match &self.range_condition {
+            Some(range_condition) => range_condition.probe_windows(build_chunk, probe_batch),
+            None => Ok(vec![0..build_chunk.num_rows(); probe_batch.num_rows()]),
+        }
+    }
+
+    /// joins the given range of probe rows with their candidate rows in a
+    /// chunk of the build side, matched flags are updated for both sides
+    fn join_build_chunk(
+        &mut self,
+        probe_batch: &RecordBatch,
+        probe_range: Range<usize>,
+        windows: &[Range<usize>],
+        build_chunk: &RecordBatch,
+        build_offset: usize,
+        probe_matched: &mut [bool],
+    ) -> Result<Vec<RecordBatch>> {
+        let num_build_rows = build_chunk.num_rows();
+        if probe_range.is_empty() || num_build_rows == 0 {
+            return Ok(vec![]);
+        }
+
+        // without filter, every pair is matched and semi/anti joins only
+        // need the matched flags
+        if self.join_params.join_filter.is_none() && !self.join_type.outputs_pairs() {
+            probe_matched[probe_range].fill(true);
+            if !self.build_matched.is_empty() {
+                self.build_matched[build_offset..build_offset + num_build_rows].fill(true);
+            }
+            return Ok(vec![]);
+        }
+
+        let num_pairs = probe_range.clone().map(|i| windows[i].len()).sum();
+        let mut build_indices = Vec::with_capacity(num_pairs);
+        let mut probe_indices = Vec::with_capacity(num_pairs);
+        for probe_idx in probe_range {
+            for build_idx in windows[probe_idx].clone() {
+                build_indices.push(build_idx as u32);
+                probe_indices.push(probe_idx as u32);
+            }
+        }
+        let build_indices = UInt32Array::from(build_indices);
+        let probe_indices = UInt32Array::from(probe_indices);
+        let selected = match &self.join_params.join_filter {
+            Some(join_filter) => self.eval_join_filter(
+                join_filter,
+                build_chunk,
+                &build_indices,
+                probe_batch,
+                &probe_indices,
+            )?,
+            None => BooleanArray::from(vec![true; build_indices.len()]),
+        };
+
+        for (i, (&build_idx, &probe_idx)) in build_indices
+            .values()
+            .iter()
+            .zip(probe_indices.values())
+            .enumerate()
+        {
+            if selected.value(i) {
+                probe_matched[probe_idx as usize] = true;
+                if !self.build_matched.is_empty() {
+                    self.build_matched[build_offset + build_idx as usize] = true;
+                }
+            }
+        }
+
+        if !self.join_type.outputs_pairs() {
+            return Ok(vec![]);
+        }
+        let build_indices = filter(&build_indices, &selected)?
+            .as_primitive::<UInt32Type>()
+            .clone();
+        let probe_indices = filter(&probe_indices, &selected)?
+            .as_primitive::<UInt32Type>()
+            .clone();
+        if build_indices.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.output_joined(
+            Some((build_chunk, &build_indices)),
+            Some((probe_batch, &probe_indices)),
+            build_indices.len(),
+        )?])
+    }
+
+    /// outputs probe side rows depending on whether they are matched, called
+    /// after the probe batch is joined with all build chunks
+    fn finish_probe_batch(
+        &self,
+        probe_batch: &RecordBatch,
+        probe_matched: Vec<bool>,
+    ) -> Result<Vec<RecordBatch>> {
+        let num_rows = probe_batch.num_rows();
+        match self.join_type {
+            RelativeJoinType::ProbeOuter | RelativeJoinType::Full => {
+                let unmatched = UInt32Array::from_iter_values(
+                    (0..num_rows as u32).filter(|&i| !probe_matched[i as usize]),
+                );
+                if unmatched.is_empty() {
+                    return Ok(vec![]);
+                }
+                Ok(vec![self.output_joined(
+                    None,
+                    Some((probe_batch, &unmatched)),
+                    unmatched.len(),
+                )?])
+            }
+            RelativeJoinType::ProbeSemi
+            | RelativeJoinType::ProbeAnti
+            | RelativeJoinType::ProbeNullAwareAnti => {
+                let is_semi = self.join_type == RelativeJoinType::ProbeSemi;
+                let selected = BooleanArray::from(
+                    probe_matched
+                        .iter()
+                        .map(|&matched| matched == is_semi)
+                        .collect::<Vec<_>>(),
+                );
+                let selected_batch = filter_record_batch(probe_batch, &selected)?;
+                if selected_batch.num_rows() == 0 {
+                    return Ok(vec![]);
+                }
+                Ok(vec![self.output_with_cols(
+                    selected_batch.columns().to_vec(),
+                    selected_batch.num_rows(),
+                )?])
+            }
+            RelativeJoinType::ProbeExistence => {
+                let exists: ArrayRef = Arc::new(BooleanArray::from(probe_matched));
+                Ok(vec![self.output_with_cols(
+                    [probe_batch.columns(), &[exists]].concat(),
+                    num_rows,
+                )?])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// outputs rows of a build chunk depending on whether they are matched,
+    /// called after the probe side is exhausted
+    fn finish_build_chunk(
+        &self,
+        build_chunk: &RecordBatch,
+        build_offset: usize,
+    ) -> Result<Vec<RecordBatch>> {
+        let num_rows = build_chunk.num_rows();
+        let build_matched = &self.build_matched[build_offset..build_offset + num_rows];
+        if self.join_type == RelativeJoinType::BuildExistence {
+            let exists: ArrayRef = Arc::new(BooleanArray::from(build_matched.to_vec()));
+            return Ok(vec![self.output_with_cols(
+                [build_chunk.columns(), &[exists]].concat(),
+                num_rows,
+            )?]);
+        }
+
+        let output_matched = match self.join_type {
+            RelativeJoinType::BuildSemi => true,
+            RelativeJoinType::BuildAnti | RelativeJoinType::BuildOuter | RelativeJoinType::Full => {
+                false
+            }
+            _ => return Ok(vec![]),
+        };
+        let indices = UInt32Array::from_iter_values(
+            (0..num_rows as u32).filter(|&i| build_matched[i as usize] == output_matched),
+        );
+        if indices.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![match self.join_type {
+            RelativeJoinType::BuildSemi | RelativeJoinType::BuildAnti => self.output_with_cols(
+                self.build_cols(Some((build_chunk, &indices)), indices.len())?,
+                indices.len(),
+            )?,
+            _ => self.output_joined(Some((build_chunk, &indices)), None, indices.len())?,
+        }])
+    }
+
+    /// evaluates join filter on the given pairs. null results are treated as
+    /// false, except null-aware anti join, in which a null result means the
+    /// probe row cannot be proven unmatched (spark's `NOT IN` semantics)
+    fn eval_join_filter(
+        &self,
+        join_filter: &JoinFilter,
+        build_chunk: &RecordBatch,
+        build_indices: &UInt32Array,
+        probe_batch: &RecordBatch,
+        probe_indices: &UInt32Array,
+    ) -> Result<BooleanArray> {
+        let build_left = matches!(self.join_params.build_side, JoinSide::Left);
+        let intermediate_columns = join_filter
+            .column_indices()
+            .iter()
+            .map(|ci| {
+                let from_build = build_left == matches!(ci.side, JoinSide::Left);
+                Ok(if from_build {
+                    take(build_chunk.column(ci.index), build_indices, None)?
+                } else {
+                    take(probe_batch.column(ci.index), probe_indices, None)?
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let intermediate_batch = RecordBatch::try_new_with_options(
+            Arc::new(join_filter.schema().clone()),
+            intermediate_columns,
+            &RecordBatchOptions::new().with_row_count(Some(build_indices.len())),
+        )?;
+
+        let filtered_array = join_filter
+            .expression()
+            .evaluate(&intermediate_batch)?
+            .into_array(intermediate_batch.num_rows())?;
+        let filtered = as_boolean_array(&filtered_array);
+        let null_as_matched = self.join_type == RelativeJoinType::ProbeNullAwareAnti;
+        Ok(filtered
+            .iter()
+            .map(|v| Some(v.unwrap_or(null_as_matched)))
+            .collect())
+    }
+
+    fn output_with_cols(&self, cols: Vec<ArrayRef>, num_rows: usize) -> Result<RecordBatch> {
+        Ok(RecordBatch::try_new_with_options(
+            self.join_params.output_schema.clone(),
+            cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?)
+    }
+
+    fn build_cols(
+        &self,
+        build: Option<(&RecordBatch, &UInt32Array)>,
+        num_rows: usize,
+    ) -> Result<Vec<ArrayRef>> {
+        match build {
+            Some((build_chunk, indices)) => build_chunk
+                .columns()
+                .iter()
+                .map(|col| Ok(take(col, indices, None)?))
+                .collect(),
+            None => Ok(self
+                .join_params
+                .build_schema
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), num_rows))
+                .collect()),
+        }
+    }
+
+    fn probe_cols(
+        &self,
+        probe: Option<(&RecordBatch, &UInt32Array)>,
+        num_rows: usize,
+    ) -> Result<Vec<ArrayRef>> {
+        match probe {
+            Some((probe_batch, indices)) => probe_batch
+                .columns()
+                .iter()
+                .map(|col| Ok(take(col, indices, None)?))
+                .collect(),
+            None => Ok(self
+                .join_params
+                .probe_schema
+                .fields()
+                .iter()
+                .map(|field| new_null_array(field.data_type(), num_rows))
+                .collect()),
+        }
+    }
+
+    /// outputs joined rows, build side or probe side columns are nulls if
+    /// not specified
+    fn output_joined(
+        &self,
+        build: Option<(&RecordBatch, &UInt32Array)>,
+        probe: Option<(&RecordBatch, &UInt32Array)>,
+        num_rows: usize,
+    ) -> Result<RecordBatch> {
+        let build_cols = self.build_cols(build, num_rows)?;
+        let probe_cols = self.probe_cols(probe, num_rows)?;
+        let output_cols = match self.join_params.build_side {
+            JoinSide::Left => [build_cols, probe_cols].concat(),
+            JoinSide::Right => [probe_cols, build_cols].concat(),
+        };
+        Ok(RecordBatch::try_new_with_options(
+            self.join_params.output_schema.clone(),
+            output_cols,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            PhysicalExpr,
+        },
+        physical_plan::{
+            common,
+            joins::utils::{ColumnIndex, JoinFilter},
+            memory::MemoryStream,
+            metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+        },
+        prelude::SessionContext,
+        scalar::ScalarValue,
+    };
+
+    use crate::{
+        common::output::TaskOutputter,
+        joins::{
+            build_join_schema,
+            nested_loop_join::{
+                execute_nested_loop_join, NestedLoopJoinBuildSide, NestedLoopJoinParams,
+            },
+            JoinType,
+        },
+        memmgr::{MemConsumer, MemManager},
+    };
+
+    fn build_batch(a: (&str, Vec<i32>), b: (&str, Vec<i32>)) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new(a.0, DataType::Int32, false),
+            Field::new(b.0, DataType::Int32, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(a.1)),
+                Arc::new(Int32Array::from(b.1)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn build_nullable_batch(
+        a: (&str, Vec<Option<i32>>),
+        b: (&str, Vec<Option<i32>>),
+        c: (&str, Vec<Option<i32>>),
+    ) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new(a.0, DataType::Int32, true),
+            Field::new(b.0, DataType::Int32, true),
+            Field::new(c.0, DataType::Int32, true),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(a.1)),
+                Arc::new(Int32Array::from(b.1)),
+                Arc::new(Int32Array::from(c.1)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn binary(
+        l: Arc<dyn PhysicalExpr>,
+        op: Operator,
+        r: Arc<dyn PhysicalExpr>,
+    ) -> Arc<dyn PhysicalExpr> {
+        Arc::new(BinaryExpr::new(l, op, r))
+    }
+
+    fn col(name: &str, index: usize) -> Arc<dyn PhysicalExpr> {
+        Arc::new(Column::new(name, index))
+    }
+
+    /// creates a join filter on columns (left.b, right.b)
+    fn b_filter(expression: Arc<dyn PhysicalExpr>, nullable: bool) -> JoinFilter {
+        JoinFilter::new(
+            expression,
+            vec![
+                ColumnIndex {
+                    index: 1,
+                    side: JoinSide::Left,
+                },
+                ColumnIndex {
+                    index: 1,
+                    side: JoinSide::Right,
+                },
+            ],
+            Schema::new(vec![
+                Field::new("b1", DataType::Int32, nullable),
+                Field::new("b2", DataType::Int32, nullable),
+            ]),
+        )
+    }
+
+    /// filter `b1 < b2`
+    fn lt_filter() -> JoinFilter {
+        b_filter(binary(col("b1", 0), Operator::Lt, col("b2", 1)), false)
+    }
+
+    /// joins left and right with the given filter, building the left side.
+    /// every left batch is spilled into a separate chunk if `spill` is set.
+    async fn nested_loop_join_collect(
+        left: Vec<RecordBatch>,
+        right: Vec<RecordBatch>,
+        join_type: JoinType,
+        join_filter: JoinFilter,
+        spill: bool,
+        range_join_enabled: bool,
+    ) -> Result<Vec<RecordBatch>> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let left_schema = left[0].schema();
+        let right_schema = right[0].schema();
+        let output_schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type));
+
+        let metrics = ExecutionPlanMetricsSet::new();
+        let build_side = NestedLoopJoinBuildSide::try_new(left_schema.clone(), 0, &metrics)?;
+        for batch in left {
+            build_side.insert_batch(batch).await?;
+            if spill {
+                build_side.spill().await?;
+            }
+        }
+        let probe = Box::pin(MemoryStream::try_new(right, right_schema.clone(), None)?);
+        let join_params = NestedLoopJoinParams {
+            join_type,
+            build_side: JoinSide::Left,
+            join_filter: Some(join_filter),
+            build_schema: left_schema,
+            probe_schema: right_schema,
+            output_schema: output_schema.clone(),
+            range_join_enabled,
+        };
+        let metrics = BaselineMetrics::new(&metrics, 0);
+        let output =
+            task_ctx.output_with_sender("NestedLoopJoin", output_schema, move |sender| {
+                execute_nested_loop_join(probe, build_side, join_params, metrics, sender)
+            })?;
+        common::collect(output).await
+    }
+
+    #[tokio::test]
+    async fn test_nested_loop_join_spilled() -> Result<()> {
+        let left = vec![
+            build_batch(("a1", vec![1, 2]), ("b1", vec![10, 20])),
+            build_batch(("a1", vec![3]), ("b1", vec![30])),
+            build_batch(("a1", vec![4, 5]), ("b1", vec![40, 50])),
+        ];
+        let right = vec![
+            build_batch(("a2", vec![100, 200]), ("b2", vec![15, 35])),
+            build_batch(("a2", vec![300]), ("b2", vec![5])),
+        ];
+
+        for (spill, range_join_enabled) in [(false, false), (true, false), (true, true)] {
+            let collect = |join_type| {
+                nested_loop_join_collect(
+                    left.clone(),
+                    right.clone(),
+                    join_type,
+                    lt_filter(),
+                    spill,
+                    range_join_enabled,
+                )
+            };
+            let batches = collect(JoinType::Full).await?;
+            let expected = vec![
+                "+----+----+-----+----+",
+                "| a1 | b1 | a2  | b2 |",
+                "+----+----+-----+----+",
+                "|    |    | 300 | 5  |",
+                "| 1  | 10 | 100 | 15 |",
+                "| 1  | 10 | 200 | 35 |",
+                "| 2  | 20 | 200 | 35 |",
+                "| 3  | 30 | 200 | 35 |",
+                "| 4  | 40 |     |    |",
+                "| 5  | 50 |     |    |",
+                "+----+----+-----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = collect(JoinType::LeftAnti).await?;
+            let expected = vec![
+                "+----+----+",
+                "| a1 | b1 |",
+                "+----+----+",
+                "| 4  | 40 |",
+                "| 5  | 50 |",
+                "+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = collect(JoinType::RightSemi).await?;
+            let expected = vec![
+                "+-----+----+",
+                "| a2  | b2 |",
+                "+-----+----+",
+                "| 100 | 15 |",
+                "| 200 | 35 |",
+                "+-----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nested_loop_join_band() -> Result<()> {
+        let left = vec![
+            build_nullable_batch(
+                ("a1", vec![Some(1), Some(2), Some(3)]),
+                ("b1", vec![Some(30), None, Some(10)]),
+                ("c1", vec![Some(0), Some(0), Some(0)]),
+            ),
+            build_nullable_batch(
+                ("a1", vec![Some(4), Some(5)]),
+                ("b1", vec![Some(22), Some(18)]),
+                ("c1", vec![Some(0), Some(0)]),
+            ),
+        ];
+        let right = vec![build_nullable_batch(
+            ("a2", vec![Some(100), Some(200), Some(300), Some(400)]),
+            ("b2", vec![Some(20), Some(12), None, Some(50)]),
+            ("c2", vec![Some(0), Some(0), Some(0), Some(0)]),
+        )];
+
+        // b2 - 5 <= b1 AND b1 <= b2 + 5
+        let five: Arc<dyn PhysicalExpr> = Arc::new(Literal::new(ScalarValue::Int32(Some(5))));
+        let band_filter = || {
+            b_filter(
+                binary(
+                    binary(
+                        binary(col("b2", 1), Operator::Minus, five.clone()),
+                        Operator::LtEq,
+                        col("b1", 0),
+                    ),
+                    Operator::And,
+                    binary(
+                        col("b1", 0),
+                        Operator::LtEq,
+                        binary(col("b2", 1), Operator::Plus, five.clone()),
+                    ),
+                ),
+                true,
+            )
+        };
+
+        for (spill, range_join_enabled) in [(false, false), (false, true), (true, true)] {
+            let collect = |join_type| {
+                nested_loop_join_collect(
+                    left.clone(),
+                    right.clone(),
+                    join_type,
+                    band_filter(),
+                    spill,
+                    range_join_enabled,
+                )
+            };
+            let batches = collect(JoinType::Full).await?;
+            let expected = vec![
+                "+----+----+----+-----+----+----+",
+                "| a1 | b1 | c1 | a2  | b2 | c2 |",
+                "+----+----+----+-----+----+----+",
+                "|    |    |    | 300 |    | 0  |",
+                "|    |    |    | 400 | 50 | 0  |",
+                "| 1  | 30 | 0  |     |    |    |",
+                "| 2  |    | 0  |     |    |    |",
+                "| 3  | 10 | 0  | 200 | 12 | 0  |",
+                "| 4  | 22 | 0  | 100 | 20 | 0  |",
+                "| 5  | 18 | 0  | 100 | 20 | 0  |",
+                "+----+----+----+-----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = collect(JoinType::RightAnti).await?;
+            let expected = vec![
+                "+-----+----+----+",
+                "| a2  | b2 | c2 |",
+                "+-----+----+----+",
+                "| 300 |    | 0  |",
+                "| 400 | 50 | 0  |",
+                "+-----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nested_loop_join_interval() -> Result<()> {
+        let left = vec![
+            build_nullable_batch(
+                ("a1", vec![Some(1), Some(2), Some(3)]),
+                ("b1", vec![Some(0), Some(5), Some(20)]),
+                ("c1", vec![Some(100), Some(8), Some(30)]),
+            ),
+            build_nullable_batch(
+                ("a1", vec![Some(4), Some(5), Some(6)]),
+                ("b1", vec![Some(10), None, Some(12)]),
+                ("c1", vec![Some(11), Some(50), None]),
+            ),
+        ];
+        let right = vec![
+            build_nullable_batch(
+                ("a2", vec![Some(100), Some(200), Some(300)]),
+                ("b2", vec![Some(7), Some(25), Some(11)]),
+                ("c2", vec![Some(0), Some(0), Some(0)]),
+            ),
+            build_nullable_batch(
+                ("a2", vec![Some(400), Some(500)]),
+                ("b2", vec![None, Some(200)]),
+                ("c2", vec![Some(0), Some(0)]),
+            ),
+        ];
+
+        // b1 <= b2 AND b2 <= c1
+        let interval_filter = || {
+            JoinFilter::new(
+                binary(
+                    binary(col("b1", 0), Operator::LtEq, col("b2", 2)),
+                    Operator::And,
+                    binary(col("b2", 2), Operator::LtEq, col("c1", 1)),
+                ),
+                vec![
+                    ColumnIndex {
+                        index: 1,
+                        side: JoinSide::Left,
+                    },
+                    ColumnIndex {
+                        index: 2,
+                        side: JoinSide::Left,
+                    },
+                    ColumnIndex {
+                        index: 1,
+                        side: JoinSide::Right,
+                    },
+                ],
+                Schema::new(vec![
+                    Field::new("b1", DataType::Int32, true),
+                    Field::new("c1", DataType::Int32, true),
+                    Field::new("b2", DataType::Int32, true),
+                ]),
+            )
+        };
+
+        for (spill, range_join_enabled) in [(false, false), (false, true), (true, true)] {
+            let collect = |join_type| {
+                nested_loop_join_collect(
+                    left.clone(),
+                    right.clone(),
+                    join_type,
+                    interval_filter(),
+                    spill,
+                    range_join_enabled,
+                )
+            };
+            let batches = collect(JoinType::Inner).await?;
+            let expected = vec![
+                "+----+----+-----+-----+----+----+",
+                "| a1 | b1 | c1  | a2  | b2 | c2 |",
+                "+----+----+-----+-----+----+----+",
+                "| 1  | 0  | 100 | 100 | 7  | 0  |",
+                "| 1  | 0  | 100 | 200 | 25 | 0  |",
+                "| 1  | 0  | 100 | 300 | 11 | 0  |",
+                "| 2  | 5  | 8   | 100 | 7  | 0  |",
+                "| 3  | 20 | 30  | 200 | 25 | 0  |",
+                "| 4  | 10 | 11  | 300 | 11 | 0  |",
+                "+----+----+-----+-----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+
+            let batches = collect(JoinType::LeftAnti).await?;
+            let expected = vec![
+                "+----+----+----+",
+                "| a1 | b1 | c1 |",
+                "+----+----+----+",
+                "| 5  |    | 50 |",
+                "| 6  | 12 |    |",
+                "+----+----+----+",
+            ];
+            assert_batches_sorted_eq!(expected, &batches);
+        }
+        Ok(())
+    }
+}
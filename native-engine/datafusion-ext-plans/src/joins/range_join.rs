@@ -0,0 +1,391 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{Array, ArrayRef},
+    compute::{sort_to_indices, take, SortOptions},
+    datatypes::{DataType, SchemaRef},
+    record_batch::{RecordBatch, RecordBatchOptions},
+    row::{RowConverter, Rows, SortField},
+};
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNode},
+        DataFusionError, JoinSide, Result,
+    },
+    logical_expr::Operator,
+    physical_expr::{
+        expressions::{BinaryExpr, CastExpr, Column, Literal, TryCastExpr},
+        utils::collect_columns,
+        PhysicalExpr,
+    },
+    physical_plan::joins::utils::JoinFilter,
+};
+use parking_lot::Mutex as SyncMutex;
+
+/// range conditions extracted from the filter of a nested loop join, used to
+/// prune candidate pairs. rows of each build chunk are sorted by the build
+/// key, so that a probe row only needs to be joined with a window of build
+/// rows, like `build_key BETWEEN probe_lower AND probe_upper`. an additional
+/// interval end can be specified for conditions like
+/// `build_start <= probe_point AND build_end >= probe_point`, in which the
+/// window starts from the first build row whose running max of end is not
+/// less than the probe point.
+///
+/// the windows are supersets of matched rows, the complete join filter is
+/// still evaluated on all candidate pairs.
+pub struct RangeJoinCondition {
+    build_key: Arc<dyn PhysicalExpr>,
+    probe_lower: Option<Arc<dyn PhysicalExpr>>,
+    probe_upper: Option<Arc<dyn PhysicalExpr>>,
+    interval_end: Option<IntervalEnd>,
+    key_row_converter: SyncMutex<RowConverter>,
+}
+
+/// `build_end >= probe_point`
+struct IntervalEnd {
+    build_end: Arc<dyn PhysicalExpr>,
+    probe_point: Arc<dyn PhysicalExpr>,
+    row_converter: SyncMutex<RowConverter>,
+}
+
+impl RangeJoinCondition {
+    /// extracts range conditions from the conjunctions of join filter,
+    /// returns None if no conditions can be used for pruning
+    pub fn try_extract(
+        join_filter: &JoinFilter,
+        build_side: JoinSide,
+        build_schema: &SchemaRef,
+        probe_schema: &SchemaRef,
+    ) -> Result<Option<Self>> {
+        // collect all bounds in the form of `build_expr >= probe_expr` or
+        // `build_expr <= probe_expr`
+        let mut bounds: Vec<RangeBound> = vec![];
+        for conjunct in split_conjunction(join_filter.expression()) {
+            let Some(binary) = conjunct.as_any().downcast_ref::<BinaryExpr>() else {
+                continue;
+            };
+            let is_lower = match binary.op() {
+                Operator::Gt | Operator::GtEq => true,
+                Operator::Lt | Operator::LtEq => false,
+                _ => continue,
+            };
+            let left = bind_to_side(binary.left(), join_filter, build_side);
+            let right = bind_to_side(binary.right(), join_filter, build_side);
+            let (build_expr, probe_expr, is_lower) = match (left, right) {
+                (Some((true, l)), Some((false, r))) => (l, r, is_lower),
+                (Some((false, l)), Some((true, r))) => (r, l, !is_lower),
+                _ => continue,
+            };
+
+            let build_data_type = build_expr.data_type(build_schema)?;
+            let probe_data_type = probe_expr.data_type(probe_schema)?;
+            if build_data_type != probe_data_type || !is_supported_type(&build_data_type) {
+                continue;
+            }
+            bounds.push(RangeBound {
+                build_expr,
+                probe_expr,
+                data_type: build_data_type,
+                is_lower,
+            });
+        }
+
+        // prefer a build key with both lower and upper bounds
+        let find_bound = |build_expr: &Arc<dyn PhysicalExpr>, is_lower: bool| {
+            bounds
+                .iter()
+                .find(|b| b.is_lower == is_lower && expr_eq(&b.build_expr, build_expr))
+        };
+        let key_bound = bounds
+            .iter()
+            .find(|b| find_bound(&b.build_expr, !b.is_lower).is_some())
+            .or_else(|| bounds.iter().find(|b| !b.is_lower))
+            .or_else(|| bounds.first());
+        let Some(key_bound) = key_bound else {
+            return Ok(None);
+        };
+        let build_key = key_bound.build_expr.clone();
+        let probe_lower = find_bound(&build_key, true).map(|b| b.probe_expr.clone());
+        let probe_upper = find_bound(&build_key, false).map(|b| b.probe_expr.clone());
+
+        // the interval end is used only when the window is bounded above
+        let interval_end = probe_upper
+            .as_ref()
+            .and_then(|_| {
+                bounds
+                    .iter()
+                    .find(|b| b.is_lower && !expr_eq(&b.build_expr, &build_key))
+            })
+            .map(|b| {
+                Ok::<_, DataFusionError>(IntervalEnd {
+                    build_end: b.build_expr.clone(),
+                    probe_point: b.probe_expr.clone(),
+                    row_converter: SyncMutex::new(RowConverter::new(vec![SortField::new(
+                        b.data_type.clone(),
+                    )])?),
+                })
+            })
+            .transpose()?;
+
+        Ok(Some(Self {
+            key_row_converter: SyncMutex::new(RowConverter::new(vec![
+                SortField::new_with_options(key_bound.data_type.clone(), key_sort_options()),
+            ])?),
+            build_key,
+            probe_lower,
+            probe_upper,
+            interval_end,
+        }))
+    }
+
+    /// sorts rows of a build chunk by the build key, with nulls last
+    pub fn sort_build_chunk(&self, build_chunk: &RecordBatch) -> Result<RecordBatch> {
+        let keys = evaluate(&self.build_key, build_chunk)?;
+        let indices = sort_to_indices(&keys, Some(key_sort_options()), None)?;
+        let sorted_cols = build_chunk
+            .columns()
+            .iter()
+            .map(|col| Ok(take(col, &indices, None)?))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new_with_options(
+            build_chunk.schema(),
+            sorted_cols,
+            &RecordBatchOptions::new().with_row_count(Some(build_chunk.num_rows())),
+        )?)
+    }
+
+    /// returns the window of candidate build rows for each probe row, the
+    /// build chunk must be sorted with `sort_build_chunk()`
+    pub fn probe_windows(
+        &self,
+        sorted_build_chunk: &RecordBatch,
+        probe_batch: &RecordBatch,
+    ) -> Result<Vec<Range<usize>>> {
+        // null keys are sorted last and never matched
+        let keys = evaluate(&self.build_key, sorted_build_chunk)?;
+        let num_valid_keys = keys.len() - keys.null_count();
+        let key_rows = self.key_row_converter.lock().convert_columns(&[keys])?;
+        let probe_lower_rows = self.convert_probe_keys(&self.probe_lower, probe_batch)?;
+        let probe_upper_rows = self.convert_probe_keys(&self.probe_upper, probe_batch)?;
+
+        // running max of interval ends, null ends are skipped since they are
+        // never matched
+        let interval_end = match &self.interval_end {
+            Some(interval_end) => {
+                let ends = evaluate(&interval_end.build_end, sorted_build_chunk)?;
+                let probe_points = evaluate(&interval_end.probe_point, probe_batch)?;
+                let mut end_row_converter = interval_end.row_converter.lock();
+                let end_rows = end_row_converter.convert_columns(&[ends.clone()])?;
+                let probe_point_rows =
+                    end_row_converter.convert_columns(&[probe_points.clone()])?;
+                drop(end_row_converter);
+
+                let mut max_end_indices = Vec::with_capacity(num_valid_keys);
+                let mut max_end_idx: Option<usize> = None;
+                for i in 0..num_valid_keys {
+                    if ends.is_valid(i)
+                        && max_end_idx.map_or(true, |max_i| end_rows.row(i) > end_rows.row(max_i))
+                    {
+                        max_end_idx = Some(i);
+                    }
+                    max_end_indices.push(max_end_idx);
+                }
+                Some((end_rows, max_end_indices, probe_points, probe_point_rows))
+            }
+            None => None,
+        };
+
+        let mut windows = Vec::with_capacity(probe_batch.num_rows());
+        for probe_idx in 0..probe_batch.num_rows() {
+            let mut start = 0;
+            let mut end = num_valid_keys;
+
+            // comparisons with null bounds are never true
+            if let Some((bounds, rows)) = &probe_lower_rows {
+                if bounds.is_null(probe_idx) {
+                    windows.push(0..0);
+                    continue;
+                }
+                let lower = rows.row(probe_idx);
+                start = start.max(partition_point(0..end, |i| key_rows.row(i) < lower));
+            }
+            if let Some((bounds, rows)) = &probe_upper_rows {
+                if bounds.is_null(probe_idx) {
+                    windows.push(0..0);
+                    continue;
+                }
+                let upper = rows.row(probe_idx);
+                end = end.min(partition_point(0..end, |i| key_rows.row(i) <= upper));
+            }
+            if let Some((end_rows, max_end_indices, probe_points, probe_point_rows)) = &interval_end
+            {
+                if probe_points.is_null(probe_idx) {
+                    windows.push(0..0);
+                    continue;
+                }
+                let point = probe_point_rows.row(probe_idx);
+                start = start.max(partition_point(0..end, |i| {
+                    max_end_indices[i].map_or(true, |max_i| end_rows.row(max_i) < point)
+                }));
+            }
+            windows.push(start..end.max(start));
+        }
+        Ok(windows)
+    }
+
+    fn convert_probe_keys(
+        &self,
+        probe_expr: &Option<Arc<dyn PhysicalExpr>>,
+        probe_batch: &RecordBatch,
+    ) -> Result<Option<(ArrayRef, Rows)>> {
+        match probe_expr {
+            Some(probe_expr) => {
+                let probe_keys = evaluate(probe_expr, probe_batch)?;
+                let rows = self
+                    .key_row_converter
+                    .lock()
+                    .convert_columns(&[probe_keys.clone()])?;
+                Ok(Some((probe_keys, rows)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct RangeBound {
+    build_expr: Arc<dyn PhysicalExpr>,
+    probe_expr: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    is_lower: bool, // build_expr >= probe_expr if true, otherwise <=
+}
+
+fn key_sort_options() -> SortOptions {
+    SortOptions {
+        descending: false,
+        nulls_first: false,
+    }
+}
+
+/// only types whose row format ordering is identical to the comparison
+/// semantics are supported, floats are excluded because of NaN and -0.0
+fn is_supported_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Decimal128(..)
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Timestamp(..)
+            | DataType::Utf8
+    )
+}
+
+/// only deterministic expressions which never fail are used for pruning,
+/// since they are evaluated on rows not paired by the original filter
+fn is_supported_expr(expr: &Arc<dyn PhysicalExpr>) -> bool {
+    let any = expr.as_any();
+    if any.is::<Column>() || any.is::<Literal>() {
+        return true;
+    }
+    if any.is::<CastExpr>() || any.is::<TryCastExpr>() {
+        return expr.children().iter().all(is_supported_expr);
+    }
+    if let Some(binary) = any.downcast_ref::<BinaryExpr>() {
+        return matches!(
+            binary.op(),
+            Operator::Plus | Operator::Minus | Operator::Multiply
+        ) && expr.children().iter().all(is_supported_expr);
+    }
+    false
+}
+
+/// rewrites an expression of the intermediate filter schema into one side.
+/// returns (is_build_side, expr) if the expression references columns from
+/// exactly one side.
+fn bind_to_side(
+    expr: &Arc<dyn PhysicalExpr>,
+    join_filter: &JoinFilter,
+    build_side: JoinSide,
+) -> Option<(bool, Arc<dyn PhysicalExpr>)> {
+    if !is_supported_expr(expr) {
+        return None;
+    }
+    let column_indices = join_filter.column_indices();
+    let mut sides = collect_columns(expr)
+        .into_iter()
+        .map(|col| column_indices[col.index()].side == build_side);
+    let is_build = sides.next()?;
+    if !sides.all(|side| side == is_build) {
+        return None;
+    }
+
+    let bound = expr
+        .clone()
+        .transform_down(&|node: Arc<dyn PhysicalExpr>| {
+            Ok(Transformed::Yes(
+                if let Some(col) = node.as_any().downcast_ref::<Column>() {
+                    let idx = column_indices[col.index()].index;
+                    Arc::new(Column::new(col.name(), idx))
+                } else {
+                    node
+                },
+            ))
+        })
+        .ok()?;
+    Some((is_build, bound))
+}
+
+fn split_conjunction(expr: &Arc<dyn PhysicalExpr>) -> Vec<&Arc<dyn PhysicalExpr>> {
+    match expr.as_any().downcast_ref::<BinaryExpr>() {
+        Some(binary) if binary.op() == &Operator::And => {
+            let mut conjuncts = split_conjunction(binary.left());
+            conjuncts.extend(split_conjunction(binary.right()));
+            conjuncts
+        }
+        _ => vec![expr],
+    }
+}
+
+fn expr_eq(a: &Arc<dyn PhysicalExpr>, b: &Arc<dyn PhysicalExpr>) -> bool {
+    a.as_ref().eq(b.as_any())
+}
+
+fn evaluate(expr: &Arc<dyn PhysicalExpr>, batch: &RecordBatch) -> Result<ArrayRef> {
+    expr.evaluate(batch)?.into_array(batch.num_rows())
+}
+
+/// returns the first index in range where the predicate is false, the
+/// predicate must be monotonic (true then false) in the range
+fn partition_point(range: Range<usize>, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut lo, mut hi) = (range.start, range.end);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
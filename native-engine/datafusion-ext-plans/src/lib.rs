@@ -29,6 +29,7 @@ pub mod generate;
 pub mod generate_exec;
 pub mod ipc_reader_exec;
 pub mod ipc_writer_exec;
+pub mod joins;
 pub mod limit_exec;
 pub mod memmgr;
 pub mod parquet_exec;
@@ -36,8 +37,9 @@ pub mod parquet_sink_exec;
 pub mod project_exec;
 pub mod rename_columns_exec;
 pub mod rss_shuffle_writer_exec;
-mod shuffle;
+pub mod shuffle;
 pub mod shuffle_writer_exec;
+pub mod shuffled_hash_join_exec;
 pub mod sort_exec;
 pub mod sort_merge_join_exec;
 pub mod window;
@@ -15,7 +15,7 @@
 use std::{
     any::Any,
     fs::File,
-    io::{BufReader, BufWriter, Cursor, Read, Seek, Write},
+    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
     sync::Arc,
     time::Duration,
 };
@@ -70,24 +70,28 @@ pub fn try_new_spill(spill_metrics: &SpillMetrics) -> Result<Box<dyn Spill>> {
 }
 
 /// A spill structure which write data to temporary files
-/// used in driver side
-struct FileSpill(File, SpillMetrics);
+/// used in driver side, or when the spilled data needs to be read for
+/// multiple times
+pub struct FileSpill(File, SpillMetrics);
 impl FileSpill {
-    fn try_new(spill_metrics: &SpillMetrics) -> Result<Self> {
+    pub fn try_new(spill_metrics: &SpillMetrics) -> Result<Self> {
         let file = tempfile::tempfile()?;
         Ok(Self(file, spill_metrics.clone()))
     }
-}
 
-impl Spill for FileSpill {
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// size of all data written into this spill
+    pub fn file_size(&self) -> u64 {
+        self.0.len()
     }
 
-    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
+    /// reads data starting from the specified offset, typically an offset
+    /// returned by file_size() before writing
+    pub fn get_buf_reader_at<'a>(&'a self, offset: u64) -> BufReader<Box<dyn Read + Send + 'a>> {
         let mut file_cloned = self.0.try_clone().expect("File.try_clone() returns error");
         file_cloned.sync_data().expect("error synchronizing data");
-        file_cloned.rewind().expect("error rewinding");
+        file_cloned
+            .seek(SeekFrom::Start(offset))
+            .expect("error seeking");
         BufReader::with_capacity(
             65536,
             Box::new(IoTimeReadWrapper(
@@ -97,8 +101,25 @@ impl Spill for FileSpill {
         )
     }
 
+    pub fn get_compressed_reader_at<'a>(&'a self, offset: u64) -> SpillCompressedReader<'a> {
+        lz4_flex::frame::FrameDecoder::new(self.get_buf_reader_at(offset))
+    }
+}
+
+impl Spill for FileSpill {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
+        self.get_buf_reader_at(0)
+    }
+
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
-        let file_cloned = self.0.try_clone().expect("File.try_clone() returns error");
+        // cloned files share the same cursor, which may have been moved by
+        // previous reading, so always append to the end
+        let mut file_cloned = self.0.try_clone().expect("File.try_clone() returns error");
+        file_cloned.seek(SeekFrom::End(0)).expect("error seeking");
         BufWriter::with_capacity(
             65536,
             Box::new(IoTimeWriteWrapper(
@@ -26,8 +26,7 @@ use datafusion::{
         expressions::PhysicalSortExpr,
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         stream::RecordBatchStreamAdapter,
-        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
-        Statistics,
+        DisplayAs, DisplayFormatType, ExecutionPlan, SendableRecordBatchStream, Statistics,
     },
 };
 use futures::{stream::once, TryStreamExt};
@@ -36,7 +35,7 @@ use crate::{
     memmgr::MemManager,
     shuffle::{
         rss_single_repartitioner::RssSingleShuffleRepartitioner,
-        rss_sort_repartitioner::RssSortShuffleRepartitioner, ShuffleRepartitioner,
+        rss_sort_repartitioner::RssSortShuffleRepartitioner, Partitioning, ShuffleRepartitioner,
     },
 };
 
@@ -59,7 +58,7 @@ impl DisplayAs for RssShuffleWriterExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "RssShuffleWriterExec: partitioning={:?}",
+            "RssShuffleWriterExec: partitioning={}",
             self.partitioning
         )
     }
@@ -77,8 +76,10 @@ impl ExecutionPlan for RssShuffleWriterExec {
         self.input.schema()
     }
 
-    fn output_partitioning(&self) -> Partitioning {
-        self.partitioning.clone()
+    fn output_partitioning(&self) -> datafusion::physical_plan::Partitioning {
+        datafusion::physical_plan::Partitioning::UnknownPartitioning(
+            self.partitioning.partition_count(),
+        )
     }
 
     fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
@@ -121,11 +122,11 @@ impl ExecutionPlan for RssShuffleWriterExec {
 
         let input = self.input.execute(partition, context.clone())?;
         let repartitioner: Arc<dyn ShuffleRepartitioner> = match &self.partitioning {
-            p if p.partition_count() == 1 => Arc::new(RssSingleShuffleRepartitioner::new(
+            p if p.partition_count() == 1 => Arc::new(RssSingleShuffleRepartitioner::try_new(
                 rss_partition_writer,
                 data_size_metric,
-            )),
-            Partitioning::Hash(..) => {
+            )?),
+            _ => {
                 let partitioner = Arc::new(RssSortShuffleRepartitioner::new(
                     partition,
                     rss_partition_writer,
@@ -135,7 +136,6 @@ impl ExecutionPlan for RssShuffleWriterExec {
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
-            p => unreachable!("unsupported partitioning: {:?}", p),
         };
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
@@ -18,7 +18,7 @@ use arrow::record_batch::RecordBatch;
 use blaze_jni_bridge::jni_call;
 use bytesize::ByteSize;
 use count_write::CountWrite;
-use datafusion::{common::Result, physical_plan::Partitioning};
+use datafusion::common::Result;
 use datafusion_ext_commons::{
     array_size::ArraySize,
     ds::rdx_tournament_tree::{KeyForRadixTournamentTree, RadixTournamentTree},
@@ -31,11 +31,18 @@ use crate::{
         batch_selection::interleave_batches, compute_suggested_batch_size_for_output,
         ipc_compression::IpcCompressionWriter, staging_mem_size_for_partial_sort,
     },
-    shuffle::{evaluate_hashes, evaluate_partition_ids, rss::RssWriter},
+    shuffle::{
+        checksum::{PartitionChecksumWriter, ShuffleChecksumAlgorithm},
+        evaluate_partition_ids,
+        rss::RssWriter,
+        split_index::ShuffleSplitIndex,
+        Partitioning,
+    },
 };
 
-#[derive(Default)]
 pub struct BufferedData {
+    partition_id: usize,
+    row_offset: usize,
     staging_batches: Vec<RecordBatch>,
     sorted_batches: Vec<RecordBatch>,
     sorted_partition_indices: Vec<Vec<u32>>,
@@ -45,6 +52,32 @@ pub struct BufferedData {
 }
 
 impl BufferedData {
+    pub fn new(partition_id: usize) -> Self {
+        Self {
+            partition_id,
+            row_offset: 0,
+            staging_batches: vec![],
+            sorted_batches: vec![],
+            sorted_partition_indices: vec![],
+            num_rows: 0,
+            staging_mem_used: 0,
+            sorted_mem_used: 0,
+        }
+    }
+
+    // takes all buffered data out, the remaining empty buffer continues with
+    // the row offset so that round-robin partitioning is not reset by spilling
+    pub fn drain(&mut self) -> Self {
+        let num_staging_rows = self
+            .staging_batches
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum::<usize>();
+        let mut remaining = Self::new(self.partition_id);
+        remaining.row_offset = self.row_offset + num_staging_rows;
+        std::mem::replace(self, remaining)
+    }
+
     pub fn add_batch(&mut self, batch: RecordBatch, partitioning: &Partitioning) -> Result<()> {
         self.num_rows += batch.num_rows();
         self.staging_mem_used += batch.get_array_mem_size();
@@ -65,8 +98,17 @@ impl BufferedData {
         let staging_batches = std::mem::take(&mut self.staging_batches);
         self.staging_mem_used = 0;
 
-        let (partition_indices, sorted_batch) =
-            sort_batches_by_partition_id(staging_batches, partitioning)?;
+        let num_staging_rows = staging_batches
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum::<usize>();
+        let (partition_indices, sorted_batch) = sort_batches_by_partition_id(
+            staging_batches,
+            partitioning,
+            self.partition_id,
+            self.row_offset,
+        )?;
+        self.row_offset += num_staging_rows;
 
         self.sorted_mem_used +=
             sorted_batch.get_array_mem_size() + partition_indices.len() * size_of::<u32>();
@@ -75,38 +117,64 @@ impl BufferedData {
         Ok(())
     }
 
-    // write buffered data to spill/target file, returns uncompressed size and
-    // offsets to each partition
+    // write buffered data to spill/target file, returns uncompressed size,
+    // offsets to each partition, checksums of each partition (empty if
+    // checksum algorithm is not specified) and blocks in each partition
     pub fn write<W: Write>(
         self,
-        mut w: W,
+        w: W,
         partitioning: &Partitioning,
-    ) -> Result<(usize, Vec<u64>)> {
-        if self.num_rows == 0 {
-            return Ok((0, vec![0; partitioning.partition_count() + 1]));
-        }
-        let mut offsets = vec![];
+        checksum_algorithm: Option<ShuffleChecksumAlgorithm>,
+    ) -> Result<(usize, Vec<u64>, Vec<i64>, ShuffleSplitIndex)> {
+        let num_partitions = partitioning.partition_count();
+        let mut w = PartitionChecksumWriter::new(w, checksum_algorithm);
+        let mut split_index = ShuffleSplitIndex::new(num_partitions);
+        let mut offsets = vec![0];
         let mut offset = 0;
-        let mut iter = self.into_sorted_batches(partitioning)?.peekable();
         let mut uncompressed_size = 0;
 
-        while let Some(&part_id) = iter.peek().map(|(part_id, _)| part_id) {
-            while offsets.len() <= part_id as usize {
-                offsets.push(offset); // fill offsets of empty partitions
-            }
+        if self.num_rows > 0 {
+            let mut iter = self.into_sorted_batches(partitioning)?.peekable();
+            while let Some(&part_id) = iter.peek().map(|(part_id, _)| part_id) {
+                while offsets.len() <= part_id as usize {
+                    offsets.push(offset); // fill offsets of empty partitions
+                    w.finish_partition();
+                }
 
-            // write all batches with this part id
-            let mut writer = IpcCompressionWriter::new(CountWrite::from(&mut w), true);
-            while matches!(iter.peek(), Some((id, _)) if *id == part_id) {
-                uncompressed_size += writer.write_batch(iter.next().unwrap().1)?;
+                // write all batches with this part id, a block is recorded into
+                // split index whenever the writer flushes
+                let mut writer = IpcCompressionWriter::try_new(CountWrite::from(&mut w), true)?;
+                let mut block_start = 0;
+                let mut block_rows = 0;
+                while matches!(iter.peek(), Some((id, _)) if *id == part_id) {
+                    let batch = iter.next().unwrap().1;
+                    block_rows += batch.num_rows() as u64;
+                    uncompressed_size += writer.write_batch(batch)?;
+
+                    let block_end = writer.inner().count();
+                    if block_end > block_start {
+                        split_index.add_block(
+                            part_id as usize,
+                            block_end - block_start,
+                            block_rows,
+                        );
+                        block_start = block_end;
+                        block_rows = 0;
+                    }
+                }
+                let block_end = writer.finish_into_inner()?.count();
+                split_index.add_block(part_id as usize, block_end - block_start, block_rows);
+
+                offset += block_end;
+                offsets.push(offset);
+                w.finish_partition();
             }
-            offset += writer.finish_into_inner()?.count();
-            offsets.push(offset);
         }
-        while offsets.len() <= partitioning.partition_count() {
+        while offsets.len() <= num_partitions {
             offsets.push(offset); // fill offsets of empty partitions
+            w.finish_partition();
         }
-        Ok((uncompressed_size, offsets))
+        Ok((uncompressed_size, offsets, w.into_inner().1, split_index))
     }
 
     // write buffered data to rss, returns uncompressed size
@@ -122,10 +190,10 @@ impl BufferedData {
         let mut uncompressed_size = 0;
 
         while let Some(&part_id) = iter.peek().map(|(part_id, _)| part_id) {
-            let mut writer = IpcCompressionWriter::new(
+            let mut writer = IpcCompressionWriter::try_new(
                 RssWriter::new(rss_partition_writer.clone(), part_id as usize),
                 true,
-            );
+            )?;
 
             // write all batches with this part id
             while matches!(iter.peek(), Some((id, _)) if *id == part_id) {
@@ -232,23 +300,30 @@ impl BufferedData {
 fn sort_batches_by_partition_id(
     batches: Vec<RecordBatch>,
     partitioning: &Partitioning,
+    partition_id: usize,
+    row_offset: usize,
 ) -> Result<(Vec<u32>, RecordBatch)> {
     let num_rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
     let num_partitions = partitioning.partition_count();
     let schema = batches[0].schema();
 
-    let mut indices = batches // partition_id, batch_idx, row_idx
-        .iter()
-        .enumerate()
-        .flat_map(|(batch_idx, batch)| {
-            let hashes = evaluate_hashes(partitioning, batch)
-                .expect(&format!("error evaluating hashes with {partitioning}"));
-            evaluate_partition_ids(&hashes, partitioning.partition_count())
+    let mut indices = vec![]; // partition_id, batch_idx, row_idx
+    let mut batch_row_offset = row_offset;
+    for (batch_idx, batch) in batches.iter().enumerate() {
+        let part_ids = evaluate_partition_ids(partitioning, batch, partition_id, batch_row_offset)
+            .map_err(|err| {
+                err.context(format!(
+                    "error evaluating partition ids with {partitioning}"
+                ))
+            })?;
+        indices.extend(
+            part_ids
                 .into_iter()
                 .enumerate()
-                .map(move |(row_idx, part_id)| (part_id, batch_idx as u32, row_idx as u32))
-        })
-        .collect::<Vec<_>>();
+                .map(|(row_idx, part_id)| (part_id, batch_idx as u32, row_idx as u32)),
+        );
+        batch_row_offset += batch.num_rows();
+    }
 
     // use quick sort if there are too many partitions or too few rows, otherwise
     // use radix sort
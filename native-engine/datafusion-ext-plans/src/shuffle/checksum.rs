@@ -0,0 +1,316 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+};
+
+use blaze_jni_bridge::{conf, conf::StringConf, is_jni_bridge_inited};
+use byteorder::{BigEndian, WriteBytesExt};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+use once_cell::sync::OnceCell;
+
+/// checksum algorithm of shuffle partitions, same as
+/// spark.shuffle.checksum.algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShuffleChecksumAlgorithm {
+    Adler32,
+    Crc32,
+}
+
+impl ShuffleChecksumAlgorithm {
+    pub fn try_new(name: &str) -> Result<Self> {
+        match name.to_uppercase().as_str() {
+            "ADLER32" => Ok(Self::Adler32),
+            "CRC32" => Ok(Self::Crc32),
+            other => df_execution_err!("unsupported shuffle checksum algorithm: {other}"),
+        }
+    }
+
+    /// algorithm configured by spark.blaze.shuffle.checksum.algorithm
+    pub fn configured() -> Result<Self> {
+        static CACHED_ALGORITHM: OnceCell<ShuffleChecksumAlgorithm> = OnceCell::new();
+        CACHED_ALGORITHM
+            .get_or_try_init(|| {
+                if is_jni_bridge_inited() {
+                    Self::try_new(&conf::SHUFFLE_CHECKSUM_ALGORITHM.value()?)
+                } else {
+                    Ok(Self::Adler32) // for testing
+                }
+            })
+            .copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Adler32 => "ADLER32",
+            Self::Crc32 => "CRC32",
+        }
+    }
+
+    /// computes checksum of all data in the input
+    pub fn compute<R: Read>(&self, mut input: R) -> Result<i64> {
+        let mut checksum = Checksum::new(*self);
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let read_len = input.read(&mut buf)?;
+            if read_len == 0 {
+                return Ok(checksum.value());
+            }
+            checksum.update(&buf[..read_len]);
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Checksum {
+    Adler32(adler::Adler32),
+    Crc32(crc32fast::Hasher),
+}
+
+impl Checksum {
+    fn new(algorithm: ShuffleChecksumAlgorithm) -> Self {
+        match algorithm {
+            ShuffleChecksumAlgorithm::Adler32 => Self::Adler32(adler::Adler32::new()),
+            ShuffleChecksumAlgorithm::Crc32 => Self::Crc32(crc32fast::Hasher::new()),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Adler32(adler32) => *adler32 = adler::Adler32::new(),
+            Self::Crc32(crc32) => crc32.reset(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Adler32(adler32) => adler32.write_slice(data),
+            Self::Crc32(crc32) => crc32.update(data),
+        }
+    }
+
+    // spark stores the unsigned 32-bit value of java.util.zip.Checksum as a long
+    fn value(&self) -> i64 {
+        match self {
+            Self::Adler32(adler32) => adler32.checksum() as i64,
+            Self::Crc32(crc32) => crc32.clone().finalize() as i64,
+        }
+    }
+}
+
+/// writer computing checksum of each partition while partitions are written
+/// sequentially, no checksums are computed if algorithm is not specified
+pub struct PartitionChecksumWriter<W: Write> {
+    inner: W,
+    checksum: Option<Checksum>,
+    checksums: Vec<i64>,
+}
+
+impl<W: Write> PartitionChecksumWriter<W> {
+    pub fn new(inner: W, algorithm: Option<ShuffleChecksumAlgorithm>) -> Self {
+        Self {
+            inner,
+            checksum: algorithm.map(Checksum::new),
+            checksums: vec![],
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// records checksum of all data written since last finished partition
+    pub fn finish_partition(&mut self) {
+        if let Some(checksum) = &mut self.checksum {
+            self.checksums.push(checksum.value());
+            checksum.reset();
+        }
+    }
+
+    pub fn into_inner(self) -> (W, Vec<i64>) {
+        (self.inner, self.checksums)
+    }
+}
+
+impl<W: Write> Write for PartitionChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(checksum) = &mut self.checksum {
+            checksum.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// writes checksums of partitions in spark's checksum file format (one
+/// big-endian long per partition)
+pub fn write_checksum_file(checksum_file: &str, checksums: &[i64]) -> Result<()> {
+    let mut output = BufWriter::new(File::create(checksum_file)?);
+    for &checksum in checksums {
+        output.write_i64::<BigEndian>(checksum)?;
+    }
+    output
+        .into_inner()
+        .or_else(|e| df_execution_err!("{e}"))?
+        .sync_data()?;
+    Ok(())
+}
+
+/// reader verifying checksum of a shuffle block after all data is read
+pub struct ChecksumVerifyingReader<R: Read> {
+    inner: R,
+    block_id: String,
+    algorithm: ShuffleChecksumAlgorithm,
+    expected: i64,
+    checksum: Checksum,
+    verified: bool,
+}
+
+impl<R: Read> ChecksumVerifyingReader<R> {
+    pub fn new(
+        inner: R,
+        block_id: String,
+        algorithm: ShuffleChecksumAlgorithm,
+        expected: i64,
+    ) -> Self {
+        Self {
+            inner,
+            block_id,
+            algorithm,
+            expected,
+            checksum: Checksum::new(algorithm),
+            verified: false,
+        }
+    }
+}
+
+impl<R: Read> Read for ChecksumVerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read_len = self.inner.read(buf)?;
+        if read_len > 0 {
+            self.checksum.update(&buf[..read_len]);
+        } else if !buf.is_empty() && !self.verified {
+            verify_checksum(
+                &self.block_id,
+                self.algorithm,
+                self.expected,
+                self.checksum.value(),
+            )
+            .map_err(std::io::Error::other)?;
+            self.verified = true;
+        }
+        Ok(read_len)
+    }
+}
+
+pub fn verify_checksum(
+    block_id: &str,
+    algorithm: ShuffleChecksumAlgorithm,
+    expected: i64,
+    actual: i64,
+) -> Result<()> {
+    if actual != expected {
+        return df_execution_err!(
+            "shuffle block {block_id} is corrupted: {} checksum mismatch, \
+                expected {expected}, actual {actual}",
+            algorithm.name(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Write};
+
+    use byteorder::{BigEndian, ReadBytesExt};
+    use datafusion::common::Result;
+
+    use crate::shuffle::checksum::{
+        write_checksum_file, ChecksumVerifyingReader, PartitionChecksumWriter,
+        ShuffleChecksumAlgorithm,
+    };
+
+    #[test]
+    fn test_checksum_values() -> Result<()> {
+        // same as java.util.zip.Adler32/CRC32
+        let data = b"The quick brown fox jumps over the lazy dog";
+        let adler32 = ShuffleChecksumAlgorithm::Adler32.compute(&data[..])?;
+        let crc32 = ShuffleChecksumAlgorithm::Crc32.compute(&data[..])?;
+        assert_eq!(adler32, 0x5BDC0FDA);
+        assert_eq!(crc32, 0x414FA339);
+
+        // empty partitions
+        assert_eq!(ShuffleChecksumAlgorithm::Adler32.compute(&b""[..])?, 1);
+        assert_eq!(ShuffleChecksumAlgorithm::Crc32.compute(&b""[..])?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_checksum_file() -> Result<()> {
+        let algorithm = ShuffleChecksumAlgorithm::Crc32;
+        let mut writer = PartitionChecksumWriter::new(vec![], Some(algorithm));
+        for partition_data in [&b"aaa"[..], b"", b"bbbb", b"cc"] {
+            writer.write_all(partition_data)?;
+            writer.finish_partition();
+        }
+        let (data, checksums) = writer.into_inner();
+        assert_eq!(data, b"aaabbbbcc");
+
+        let dir = tempfile::tempdir()?;
+        let checksum_file = dir.path().join("shuffle.checksum");
+        write_checksum_file(checksum_file.to_str().unwrap(), &checksums)?;
+
+        let mut input = Cursor::new(std::fs::read(&checksum_file)?);
+        for expected in [&b"aaa"[..], b"", b"bbbb", b"cc"] {
+            assert_eq!(input.read_i64::<BigEndian>()?, algorithm.compute(expected)?);
+        }
+        assert!(input.read_i64::<BigEndian>().is_err());
+
+        // no checksums are computed without algorithm
+        let mut writer = PartitionChecksumWriter::new(vec![], None);
+        writer.write_all(b"aaa")?;
+        writer.finish_partition();
+        assert!(writer.into_inner().1.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_verifying_reader() -> Result<()> {
+        let algorithm = ShuffleChecksumAlgorithm::Adler32;
+        let data = b"shuffle block data";
+        let checksum = algorithm.compute(&data[..])?;
+
+        let mut buf = vec![];
+        let mut reader =
+            ChecksumVerifyingReader::new(&data[..], "b0".to_string(), algorithm, checksum);
+        reader.read_to_end(&mut buf)?;
+        assert_eq!(buf, data);
+
+        let mut corrupted = data.to_vec();
+        corrupted[3] ^= 1;
+        let mut reader =
+            ChecksumVerifyingReader::new(&corrupted[..], "b1".to_string(), algorithm, checksum);
+        let err = reader.read_to_end(&mut vec![]).unwrap_err();
+        assert!(err.to_string().contains("shuffle block b1 is corrupted"));
+        Ok(())
+    }
+}
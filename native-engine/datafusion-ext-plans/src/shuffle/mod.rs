@@ -12,19 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::{
-    atomic::{AtomicUsize, Ordering::SeqCst},
-    Arc,
+use std::{
+    fmt::{Display, Formatter},
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
 };
 
-use arrow::{error::Result as ArrowResult, record_batch::RecordBatch};
+use arrow::{
+    array::{Array, ArrayRef},
+    record_batch::RecordBatch,
+    row::{RowConverter, Rows, SortField},
+};
 use async_trait::async_trait;
 use bytesize::ByteSize;
 use datafusion::{
     common::Result,
     error::DataFusionError,
     execution::context::TaskContext,
-    physical_plan::{metrics::BaselineMetrics, Partitioning, SendableRecordBatchStream},
+    physical_expr::{PhysicalExpr, PhysicalSortExpr},
+    physical_plan::{metrics::BaselineMetrics, SendableRecordBatchStream},
 };
 use datafusion_ext_commons::{
     array_size::ArraySize,
@@ -32,11 +40,16 @@ use datafusion_ext_commons::{
     streams::coalesce_stream::CoalesceInput,
 };
 use futures::StreamExt;
+use itertools::Itertools;
 
-use crate::{common::output::TaskOutputter, memmgr::spill::Spill};
+use crate::{
+    common::output::TaskOutputter, memmgr::spill::Spill, shuffle::split_index::ShuffleSplitIndex,
+};
 
+pub mod checksum;
 pub mod single_repartitioner;
 pub mod sort_repartitioner;
+pub mod split_index;
 
 mod buffered_data;
 mod rss;
@@ -103,31 +116,317 @@ impl dyn ShuffleRepartitioner {
 struct ShuffleSpill {
     spill: Box<dyn Spill>,
     offsets: Vec<u64>,
+    split_index: ShuffleSplitIndex,
+}
+
+/// partitioning schemes supported by shuffle writers
+#[derive(Debug, Clone)]
+pub enum Partitioning {
+    /// all rows are written into the only output partition
+    SinglePartitioning,
+    /// rows are distributed by spark-compatible murmur3 hashes of the exprs
+    HashPartitioning(Vec<Arc<dyn PhysicalExpr>>, usize),
+    /// rows are distributed one by one, starting from a position determined
+    /// by the map partition id
+    RoundRobinPartitioning(usize),
+    /// rows are distributed by comparing sort keys with the range bounds
+    /// sampled by spark
+    RangePartitioning(Vec<PhysicalSortExpr>, usize, Arc<RangeBounds>),
+}
+
+/// range bounds sampled by spark, converted into rows only once so that
+/// they can be compared with the sort keys of every input batch
+#[derive(Debug)]
+pub struct RangeBounds {
+    row_converter: RowConverter,
+    bound_rows: Rows,
+}
+
+impl RangeBounds {
+    /// creates range bounds from one array for each sort expr
+    pub fn try_new(sort_exprs: &[PhysicalSortExpr], bounds: &[ArrayRef]) -> Result<Self> {
+        let row_converter = RowConverter::new(
+            sort_exprs
+                .iter()
+                .zip(bounds)
+                .map(|(expr, bound)| {
+                    SortField::new_with_options(bound.data_type().clone(), expr.options)
+                })
+                .collect(),
+        )?;
+        let bound_rows = row_converter.convert_columns(bounds)?;
+        Ok(Self {
+            row_converter,
+            bound_rows,
+        })
+    }
+
+    pub fn num_bounds(&self) -> usize {
+        self.bound_rows.num_rows()
+    }
+}
+
+impl Partitioning {
+    pub fn partition_count(&self) -> usize {
+        match self {
+            Partitioning::SinglePartitioning => 1,
+            Partitioning::HashPartitioning(_, n) => *n,
+            Partitioning::RoundRobinPartitioning(n) => *n,
+            Partitioning::RangePartitioning(_, n, _) => *n,
+        }
+    }
+}
+
+impl Display for Partitioning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Partitioning::SinglePartitioning => write!(f, "SinglePartitioning"),
+            Partitioning::HashPartitioning(exprs, n) => {
+                let exprs = exprs.iter().map(|e| format!("{e}")).join(", ");
+                write!(f, "HashPartitioning([{exprs}], {n})")
+            }
+            Partitioning::RoundRobinPartitioning(n) => write!(f, "RoundRobinPartitioning({n})"),
+            Partitioning::RangePartitioning(sort_exprs, n, bounds) => {
+                let sort_exprs = sort_exprs.iter().map(|e| format!("{e}")).join(", ");
+                write!(
+                    f,
+                    "RangePartitioning([{sort_exprs}], {n}, num_bounds={})",
+                    bounds.num_bounds(),
+                )
+            }
+        }
+    }
 }
 
-fn evaluate_hashes(partitioning: &Partitioning, batch: &RecordBatch) -> ArrowResult<Vec<u32>> {
+/// evaluates output partition ids of all rows in the batch. row_offset is the
+/// position of the first row in the whole input of this map partition, which
+/// is needed by round-robin partitioning.
+fn evaluate_partition_ids(
+    partitioning: &Partitioning,
+    batch: &RecordBatch,
+    partition_id: usize,
+    row_offset: usize,
+) -> Result<Vec<u32>> {
+    let num_rows = batch.num_rows();
     match partitioning {
-        Partitioning::Hash(exprs, _) => {
+        Partitioning::SinglePartitioning => Ok(vec![0; num_rows]),
+        Partitioning::HashPartitioning(exprs, num_partitions) => {
             let mut hashes_buf = vec![];
             let arrays = exprs
                 .iter()
-                .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())?))
+                .map(|expr| Ok(expr.evaluate(batch)?.into_array(num_rows)?))
                 .collect::<Result<Vec<_>>>()?;
 
             // use identical seed as spark hash partition
-            hashes_buf.resize(arrays[0].len(), 42);
+            hashes_buf.resize(num_rows, 42);
 
             // compute hash array
             create_hashes(&arrays, &mut hashes_buf)?;
-            Ok(hashes_buf)
+            Ok(hashes_buf
+                .iter()
+                .map(|hash| pmod(*hash, *num_partitions) as u32)
+                .collect())
+        }
+        Partitioning::RoundRobinPartitioning(num_partitions) => {
+            // like spark, the position is increased before computing the partition
+            // id of each row. the result is deterministic as long as the input
+            // order is deterministic (rows are sorted before repartitioning if
+            // spark.sql.execution.sortBeforeRepartition is enabled), so that
+            // retried map tasks produce the same output
+            let start = round_robin_start_position(partition_id, *num_partitions) + row_offset;
+            Ok((0..num_rows)
+                .map(|i| ((start + i + 1) % num_partitions) as u32)
+                .collect())
+        }
+        Partitioning::RangePartitioning(sort_exprs, _, bounds) => {
+            let keys = sort_exprs
+                .iter()
+                .map(|expr| Ok(expr.expr.evaluate(batch)?.into_array(num_rows)?))
+                .collect::<Result<Vec<_>>>()?;
+            let bound_rows = &bounds.bound_rows;
+            let key_rows = bounds.row_converter.convert_columns(&keys)?;
+
+            // like spark, a row goes to the first partition whose upper bound is
+            // not less than its key
+            Ok(key_rows
+                .iter()
+                .map(|key| {
+                    let (mut lo, mut hi) = (0, bound_rows.num_rows());
+                    while lo < hi {
+                        let mid = (lo + hi) / 2;
+                        if bound_rows.row(mid) < key {
+                            lo = mid + 1;
+                        } else {
+                            hi = mid;
+                        }
+                    }
+                    lo as u32
+                })
+                .collect())
         }
-        _ => unreachable!("unsupported partitioning: {:?}", partitioning),
     }
 }
 
-fn evaluate_partition_ids(hashes: &[u32], num_partitions: usize) -> Vec<u32> {
-    hashes
-        .iter()
-        .map(|hash| pmod(*hash, num_partitions) as u32)
-        .collect()
+/// computes the starting position of round-robin partitioning, which is the
+/// same as `new Random(hashing.byteswap32(partitionId)).nextInt(numPartitions)`
+/// in spark
+fn round_robin_start_position(partition_id: usize, num_partitions: usize) -> usize {
+    const MULTIPLIER: i64 = 0x5DEECE66D;
+    const MASK: i64 = (1 << 48) - 1;
+
+    // scala.util.hashing.byteswap32
+    let byteswap32 = |v: i32| {
+        let hc = v.wrapping_mul(0x9e3775cdu32 as i32);
+        hc.swap_bytes().wrapping_mul(0x9e3775cdu32 as i32)
+    };
+
+    // java.util.Random.nextInt(bound)
+    let mut seed = (byteswap32(partition_id as i32) as i64 ^ MULTIPLIER) & MASK;
+    let mut next_i31 = || {
+        seed = seed.wrapping_mul(MULTIPLIER).wrapping_add(0xB) & MASK;
+        (seed >> 17) as i32
+    };
+    let bound = num_partitions as i32;
+    let m = bound - 1;
+    let mut r = next_i31();
+    if bound & m == 0 {
+        return ((bound as i64 * r as i64) >> 31) as usize;
+    }
+    let mut u = r;
+    loop {
+        r = u % bound;
+        if u.wrapping_sub(r).wrapping_add(m) >= 0 {
+            return r as usize;
+        }
+        u = next_i31();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, Int32Array},
+        compute::SortOptions,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::{expressions::Column, PhysicalSortExpr},
+    };
+
+    use crate::{
+        common::ipc_compression::IpcCompressionReader,
+        shuffle::{
+            buffered_data::BufferedData, evaluate_partition_ids, round_robin_start_position,
+            Partitioning, RangeBounds,
+        },
+    };
+
+    fn build_batch(values: Vec<Option<i32>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(values))]).unwrap()
+    }
+
+    #[test]
+    fn test_round_robin_start_position() {
+        // expected values are from spark:
+        // new Random(hashing.byteswap32(partitionId)).nextInt(numPartitions)
+        assert_eq!(round_robin_start_position(0, 10), 0);
+        assert_eq!(round_robin_start_position(1, 10), 2);
+        assert_eq!(round_robin_start_position(2, 10), 1);
+        assert_eq!(round_robin_start_position(0, 8), 5);
+        assert_eq!(round_robin_start_position(2, 8), 6);
+    }
+
+    #[test]
+    fn test_round_robin_partitioning() -> Result<()> {
+        let partitioning = Partitioning::RoundRobinPartitioning(10);
+        let batch = build_batch(vec![Some(1), None, Some(3), Some(4)]);
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 1, 0)?,
+            vec![3, 4, 5, 6],
+        );
+
+        // continues with the row offset
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 1, 8)?,
+            vec![1, 2, 3, 4],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_partitioning() -> Result<()> {
+        let sort_expr = |descending: bool| PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions {
+                descending,
+                nulls_first: true,
+            },
+        };
+        let batch = build_batch(vec![Some(1), None, Some(5), Some(10), Some(11), Some(3)]);
+
+        let bounds: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(vec![3, 10]))];
+        let bounds = Arc::new(RangeBounds::try_new(&[sort_expr(false)], &bounds)?);
+        let partitioning = Partitioning::RangePartitioning(vec![sort_expr(false)], 3, bounds);
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 0, 0)?,
+            vec![0, 0, 1, 1, 2, 0],
+        );
+
+        let bounds: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(vec![10, 3]))];
+        let bounds = Arc::new(RangeBounds::try_new(&[sort_expr(true)], &bounds)?);
+        let partitioning = Partitioning::RangePartitioning(vec![sort_expr(true)], 3, bounds);
+        assert_eq!(
+            evaluate_partition_ids(&partitioning, &batch, 0, 0)?,
+            vec![2, 0, 1, 0, 0, 1],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffered_data_split_index() -> Result<()> {
+        let partitioning = Partitioning::RoundRobinPartitioning(3);
+        let mut data = BufferedData::new(0);
+        for i in 0..10 {
+            data.add_batch(
+                build_batch((0..100).map(|v| Some(i * 100 + v)).collect()),
+                &partitioning,
+            )?;
+        }
+
+        let mut output = vec![];
+        let (_, offsets, _, split_index) = data.write(&mut output, &partitioning, None)?;
+        assert_eq!(split_index.num_partitions(), 3);
+
+        for partition_id in 0..3 {
+            let blocks = split_index.blocks(partition_id);
+            let blocks_len = blocks.iter().map(|block| block.len).sum::<u64>();
+            assert_eq!(
+                blocks_len,
+                offsets[partition_id + 1] - offsets[partition_id]
+            );
+
+            // each split range can be read separately
+            let mut num_rows = 0;
+            for (range, split_rows) in
+                split_index.split_partition(partition_id, offsets[partition_id], 2)
+            {
+                let input = Cursor::new(output[range.start as usize..range.end as usize].to_vec());
+                let mut reader = IpcCompressionReader::new(input, build_batch(vec![]).schema());
+                let mut read_rows = 0;
+                while let Some(batch) = reader.read_batch()? {
+                    read_rows += batch.num_rows() as u64;
+                }
+                assert_eq!(read_rows, split_rows);
+                num_rows += read_rows;
+            }
+            assert_eq!(num_rows, split_index.num_rows(partition_id));
+        }
+        assert_eq!((0..3).map(|i| split_index.num_rows(i)).sum::<u64>(), 1000);
+        Ok(())
+    }
 }
@@ -31,14 +31,14 @@ pub struct RssSingleShuffleRepartitioner {
 }
 
 impl RssSingleShuffleRepartitioner {
-    pub fn new(rss_partition_writer: GlobalRef, data_size_metric: Count) -> Self {
-        Self {
-            rss_partition_writer: Arc::new(Mutex::new(IpcCompressionWriter::new(
+    pub fn try_new(rss_partition_writer: GlobalRef, data_size_metric: Count) -> Result<Self> {
+        Ok(Self {
+            rss_partition_writer: Arc::new(Mutex::new(IpcCompressionWriter::try_new(
                 RssWriter::new(rss_partition_writer, 0),
                 true,
-            ))),
+            )?)),
             data_size_metric,
-        }
+        })
     }
 }
 
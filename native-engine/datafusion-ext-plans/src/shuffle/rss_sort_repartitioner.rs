@@ -16,17 +16,14 @@ use std::sync::Weak;
 
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
-use datafusion::{
-    common::Result,
-    physical_plan::{metrics::Count, Partitioning},
-};
+use datafusion::{common::Result, physical_plan::metrics::Count};
 use datafusion_ext_commons::df_execution_err;
 use futures::lock::Mutex;
 use jni::objects::GlobalRef;
 
 use crate::{
     memmgr::{MemConsumer, MemConsumerInfo, MemManager},
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner},
+    shuffle::{buffered_data::BufferedData, Partitioning, ShuffleRepartitioner},
 };
 
 pub struct RssSortShuffleRepartitioner {
@@ -48,7 +45,7 @@ impl RssSortShuffleRepartitioner {
         Self {
             name: format!("RssSortShufflePartitioner[partition={}]", partition_id),
             mem_consumer_info: None,
-            data: Mutex::default(),
+            data: Mutex::new(BufferedData::new(partition_id)),
             partitioning,
             rss: rss_partition_writer,
             data_size_metric,
@@ -73,7 +70,7 @@ impl MemConsumer for RssSortShuffleRepartitioner {
     }
 
     async fn spill(&self) -> Result<()> {
-        let data = std::mem::take(&mut *self.data.lock().await);
+        let data = self.data.lock().await.drain();
         let rss = self.rss.clone();
         let partitioning = self.partitioning.clone();
         let uncompressed_size =
@@ -26,12 +26,19 @@ use datafusion::{
 };
 use tokio::sync::Mutex;
 
-use crate::{common::ipc_compression::IpcCompressionWriter, shuffle::ShuffleRepartitioner};
+use crate::{
+    common::ipc_compression::IpcCompressionWriter,
+    shuffle::{
+        checksum::{write_checksum_file, PartitionChecksumWriter, ShuffleChecksumAlgorithm},
+        ShuffleRepartitioner,
+    },
+};
 
 pub struct SingleShuffleRepartitioner {
     output_data_file: String,
     output_index_file: String,
-    output_data: Arc<Mutex<Option<IpcCompressionWriter<File>>>>,
+    output_checksum_file: Option<String>,
+    output_data: Arc<Mutex<Option<IpcCompressionWriter<PartitionChecksumWriter<File>>>>>,
     metrics: BaselineMetrics,
     data_size_metric: Count,
 }
@@ -40,12 +47,14 @@ impl SingleShuffleRepartitioner {
     pub fn new(
         output_data_file: String,
         output_index_file: String,
+        output_checksum_file: Option<String>,
         metrics: BaselineMetrics,
         data_size_metric: Count,
     ) -> Self {
         Self {
             output_data_file,
             output_index_file,
+            output_checksum_file,
             output_data: Arc::new(Mutex::default()),
             metrics,
             data_size_metric,
@@ -54,17 +63,24 @@ impl SingleShuffleRepartitioner {
 
     fn get_output_writer<'a>(
         &self,
-        output_data: &'a mut Option<IpcCompressionWriter<File>>,
-    ) -> Result<&'a mut IpcCompressionWriter<File>> {
+        output_data: &'a mut Option<IpcCompressionWriter<PartitionChecksumWriter<File>>>,
+    ) -> Result<&'a mut IpcCompressionWriter<PartitionChecksumWriter<File>>> {
         if output_data.is_none() {
-            *output_data = Some(IpcCompressionWriter::new(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&self.output_data_file)?,
+            let checksum_algorithm = match &self.output_checksum_file {
+                Some(_) => Some(ShuffleChecksumAlgorithm::configured()?),
+                None => None,
+            };
+            *output_data = Some(IpcCompressionWriter::try_new(
+                PartitionChecksumWriter::new(
+                    OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&self.output_data_file)?,
+                    checksum_algorithm,
+                ),
                 true,
-            ));
+            )?);
         }
         Ok(output_data.as_mut().unwrap())
     }
@@ -86,13 +102,16 @@ impl ShuffleRepartitioner for SingleShuffleRepartitioner {
         let output_data = std::mem::take(&mut *self.output_data.lock().await);
 
         // write index file
-        if let Some(output_writer) = output_data {
-            let mut output_file = output_writer.finish_into_inner()?;
+        let checksum = if let Some(output_writer) = output_data {
+            let mut output_writer = output_writer.finish_into_inner()?;
+            output_writer.finish_partition();
+            let (mut output_file, checksums) = output_writer.into_inner();
             let offset = output_file.stream_position()?;
             let mut output_index = File::create(&self.output_index_file)?;
             output_index.write_all(&[0u8; 8])?;
             output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
             output_index.sync_data()?;
+            checksums.first().cloned()
         } else {
             // write empty data file and index file
             let output_data = File::create(&self.output_data_file)?;
@@ -100,6 +119,16 @@ impl ShuffleRepartitioner for SingleShuffleRepartitioner {
             let mut output_index = File::create(&self.output_index_file)?;
             output_index.write_all(&[0u8; 16])?;
             output_index.sync_data()?;
+            None
+        };
+
+        // write checksum file
+        if let Some(output_checksum_file) = &self.output_checksum_file {
+            let checksum = match checksum {
+                Some(checksum) => checksum,
+                None => ShuffleChecksumAlgorithm::configured()?.compute(std::io::empty())?,
+            };
+            write_checksum_file(output_checksum_file, &[checksum])?;
         }
         Ok(())
     }
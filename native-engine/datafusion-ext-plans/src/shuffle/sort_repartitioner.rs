@@ -22,10 +22,7 @@ use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::{
     common::{DataFusionError, Result},
-    physical_plan::{
-        metrics::{Count, ExecutionPlanMetricsSet},
-        Partitioning,
-    },
+    physical_plan::metrics::{Count, ExecutionPlanMetricsSet},
 };
 use datafusion_ext_commons::{
     df_execution_err,
@@ -39,7 +36,12 @@ use crate::{
         spill::{try_new_spill, Spill},
         MemConsumer, MemConsumerInfo, MemManager,
     },
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner, ShuffleSpill},
+    shuffle::{
+        buffered_data::BufferedData,
+        checksum::{write_checksum_file, PartitionChecksumWriter, ShuffleChecksumAlgorithm},
+        split_index::{write_split_index_file, ShuffleSplitIndex},
+        Partitioning, ShuffleRepartitioner, ShuffleSpill,
+    },
 };
 
 pub struct SortShuffleRepartitioner {
@@ -47,6 +49,8 @@ pub struct SortShuffleRepartitioner {
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
     output_data_file: String,
     output_index_file: String,
+    output_checksum_file: Option<String>,
+    output_split_index_file: Option<String>,
     data: Mutex<BufferedData>,
     spills: Mutex<Vec<ShuffleSpill>>,
     partitioning: Partitioning,
@@ -60,6 +64,8 @@ impl SortShuffleRepartitioner {
         partition_id: usize,
         output_data_file: String,
         output_index_file: String,
+        output_checksum_file: Option<String>,
+        output_split_index_file: Option<String>,
         partitioning: Partitioning,
         metrics: &ExecutionPlanMetricsSet,
         data_size_metric: Count,
@@ -70,7 +76,9 @@ impl SortShuffleRepartitioner {
             mem_consumer_info: None,
             output_data_file,
             output_index_file,
-            data: Mutex::default(),
+            output_checksum_file,
+            output_split_index_file,
+            data: Mutex::new(BufferedData::new(partition_id)),
             spills: Mutex::default(),
             partitioning,
             num_output_partitions,
@@ -97,17 +105,18 @@ impl MemConsumer for SortShuffleRepartitioner {
     }
 
     async fn spill(&self) -> Result<()> {
-        let data = std::mem::take(&mut *self.data.lock().await);
+        let data = self.data.lock().await.drain();
         let mut spill = try_new_spill(&self.spill_metrics)?;
 
-        let (uncompressed_size, offsets) =
-            data.write(spill.get_buf_writer(), &self.partitioning)?;
+        let (uncompressed_size, offsets, _, split_index) =
+            data.write(spill.get_buf_writer(), &self.partitioning, None)?;
         self.data_size_metric.add(uncompressed_size);
 
-        self.spills
-            .lock()
-            .await
-            .push(ShuffleSpill { spill, offsets });
+        self.spills.lock().await.push(ShuffleSpill {
+            spill,
+            offsets,
+            split_index,
+        });
         self.update_mem_used(0).await?;
         Ok(())
     }
@@ -140,7 +149,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
     async fn shuffle_write(&self) -> Result<()> {
         self.set_spillable(false);
         let mut spills = std::mem::take(&mut *self.spills.lock().await);
-        let data = std::mem::take(&mut *self.data.lock().await);
+        let data = self.data.lock().await.drain();
 
         log::info!(
             "sort repartitioner starts outputting with {} ({} spills)",
@@ -151,6 +160,12 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
         let data_size_metric = self.data_size_metric.clone();
         let data_file = self.output_data_file.clone();
         let index_file = self.output_index_file.clone();
+        let checksum_file = self.output_checksum_file.clone();
+        let split_index_file = self.output_split_index_file.clone();
+        let checksum_algorithm = match &checksum_file {
+            Some(_) => Some(ShuffleChecksumAlgorithm::configured()?),
+            None => None,
+        };
 
         // no spills - directly write current batches into final file
         if spills.is_empty() {
@@ -162,17 +177,25 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                     .truncate(true)
                     .open(&data_file)?;
 
-                let (uncompressed_size, offsets) = data.write(&mut output_data, &partitioning)?;
+                let (uncompressed_size, offsets, checksums, split_index) =
+                    data.write(&mut output_data, &partitioning, checksum_algorithm)?;
                 data_size_metric.add(uncompressed_size);
                 output_data.sync_data()?;
                 output_data.flush()?;
 
                 let mut output_index = File::create(&index_file)?;
-                for offset in offsets {
+                for &offset in &offsets {
                     output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
                 }
                 output_index.sync_data()?;
                 output_index.flush()?;
+
+                if let Some(checksum_file) = checksum_file {
+                    write_checksum_file(&checksum_file, &checksums)?;
+                }
+                if let Some(split_index_file) = split_index_file {
+                    write_split_index_file(&split_index_file, &split_index)?;
+                }
                 Ok::<(), DataFusionError>(())
             })
             .await
@@ -185,6 +208,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
             cur: usize,
             reader: BufReader<Box<dyn Read + Send + 'a>>,
             offsets: Vec<u64>,
+            split_index: ShuffleSplitIndex,
         }
 
         impl<'a> KeyForRadixTournamentTree for SpillCursor<'a> {
@@ -206,23 +230,30 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
         if data.mem_used() > 0 {
             let mut spill = Box::new(vec![]);
             let writer = spill.get_buf_writer();
-            let (uncompressed_size, offsets) = data.write(writer, &self.partitioning)?;
+            let (uncompressed_size, offsets, _, split_index) =
+                data.write(writer, &self.partitioning, None)?;
             self.data_size_metric.add(uncompressed_size);
 
             self.update_mem_used(spill.len()).await?;
-            spills.push(ShuffleSpill { spill, offsets });
+            spills.push(ShuffleSpill {
+                spill,
+                offsets,
+                split_index,
+            });
         }
 
         let num_output_partitions = self.num_output_partitions;
         let mut offsets = vec![0];
+        let mut split_index = ShuffleSplitIndex::new(num_output_partitions);
 
         // append partition in each spills
         tokio::task::spawn_blocking(move || {
-            let mut output_data = OpenOptions::new()
+            let output_data = OpenOptions::new()
                 .write(true)
                 .create(true)
                 .truncate(true)
-                .open(data_file)?;
+                .open(&data_file)?;
+            let mut output_data = PartitionChecksumWriter::new(output_data, checksum_algorithm);
             let mut cur_partition_id = 0;
 
             if !spills.is_empty() {
@@ -234,6 +265,7 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                             cur: 0,
                             reader: spill.spill.get_buf_reader(),
                             offsets: std::mem::take(&mut spill.offsets),
+                            split_index: std::mem::take(&mut spill.split_index),
                         })
                         .map(|mut spill| {
                             spill.skip_empty_partitions();
@@ -251,7 +283,8 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                     }
 
                     while cur_partition_id < min_spill.cur {
-                        offsets.push(output_data.stream_position()?);
+                        offsets.push(output_data.get_mut().stream_position()?);
+                        output_data.finish_partition();
                         cur_partition_id += 1;
                     }
                     let (spill_offset_start, spill_offset_end) = (
@@ -262,24 +295,39 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
                     let spill_range = spill_offset_start as usize..spill_offset_end as usize;
                     let reader = &mut min_spill.reader;
                     std::io::copy(&mut reader.take(spill_range.len() as u64), &mut output_data)?;
+                    split_index.extend_partition(
+                        cur_partition_id,
+                        min_spill.split_index.blocks(cur_partition_id),
+                    );
 
                     // forward partition id in min_spill
                     min_spill.cur += 1;
                     min_spill.skip_empty_partitions();
                 }
             }
+            // add one extra offset at last to ease partition length computation
+            let end_offset = output_data.get_mut().stream_position()?;
+            while offsets.len() <= num_output_partitions {
+                offsets.push(end_offset);
+                output_data.finish_partition();
+            }
+            let (mut output_data, checksums) = output_data.into_inner();
             output_data.sync_data()?;
             output_data.flush()?;
 
-            // add one extra offset at last to ease partition length computation
-            offsets.resize(num_output_partitions + 1, output_data.stream_position()?);
-
             let mut output_index = File::create(index_file)?;
-            for offset in offsets {
+            for &offset in &offsets {
                 output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
             }
             output_index.sync_data()?;
             output_index.flush()?;
+
+            if let Some(checksum_file) = checksum_file {
+                write_checksum_file(&checksum_file, &checksums)?;
+            }
+            if let Some(split_index_file) = split_index_file {
+                write_split_index_file(&split_index_file, &split_index)?;
+            }
             Ok::<(), DataFusionError>(())
         })
         .await
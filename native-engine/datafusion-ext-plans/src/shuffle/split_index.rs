@@ -0,0 +1,208 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    ops::Range,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+
+/// size and number of rows of an ipc block in a shuffle partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShuffleBlockInfo {
+    pub len: u64,
+    pub num_rows: u64,
+}
+
+/// ipc blocks of each shuffle partition. blocks can be decoded independently,
+/// so a skewed partition can be read in several sub-ranges split at block
+/// boundaries.
+///
+/// the split index file extends the offsets in shuffle index file:
+/// (num_partitions + 1) starting block indices of each partition, followed by
+/// length and number of rows of each block, all written as little-endian i64.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShuffleSplitIndex {
+    partition_blocks: Vec<Vec<ShuffleBlockInfo>>,
+}
+
+impl ShuffleSplitIndex {
+    pub fn new(num_partitions: usize) -> Self {
+        Self {
+            partition_blocks: vec![vec![]; num_partitions],
+        }
+    }
+
+    pub fn num_partitions(&self) -> usize {
+        self.partition_blocks.len()
+    }
+
+    pub fn add_block(&mut self, partition_id: usize, len: u64, num_rows: u64) {
+        if len > 0 {
+            self.partition_blocks[partition_id].push(ShuffleBlockInfo { len, num_rows });
+        }
+    }
+
+    pub fn extend_partition(&mut self, partition_id: usize, blocks: &[ShuffleBlockInfo]) {
+        self.partition_blocks[partition_id].extend_from_slice(blocks);
+    }
+
+    pub fn blocks(&self, partition_id: usize) -> &[ShuffleBlockInfo] {
+        &self.partition_blocks[partition_id]
+    }
+
+    pub fn num_rows(&self, partition_id: usize) -> u64 {
+        self.blocks(partition_id)
+            .iter()
+            .map(|block| block.num_rows)
+            .sum()
+    }
+
+    pub fn write_to<W: Write>(&self, mut w: W) -> Result<()> {
+        let mut num_blocks = 0;
+        w.write_i64::<LittleEndian>(0)?;
+        for blocks in &self.partition_blocks {
+            num_blocks += blocks.len();
+            w.write_i64::<LittleEndian>(num_blocks as i64)?;
+        }
+        for block in self.partition_blocks.iter().flatten() {
+            w.write_i64::<LittleEndian>(block.len as i64)?;
+            w.write_i64::<LittleEndian>(block.num_rows as i64)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(mut r: R, num_partitions: usize) -> Result<Self> {
+        let mut block_indices = vec![];
+        for _ in 0..=num_partitions {
+            block_indices.push(r.read_i64::<LittleEndian>()? as usize);
+        }
+        if block_indices[0] != 0 || block_indices.windows(2).any(|w| w[0] > w[1]) {
+            return df_execution_err!("invalid shuffle split index: {block_indices:?}");
+        }
+
+        let mut split_index = Self::new(num_partitions);
+        for (partition_id, w) in block_indices.windows(2).enumerate() {
+            for _ in w[0]..w[1] {
+                let len = r.read_i64::<LittleEndian>()? as u64;
+                let num_rows = r.read_i64::<LittleEndian>()? as u64;
+                split_index.add_block(partition_id, len, num_rows);
+            }
+        }
+        Ok(split_index)
+    }
+
+    /// splits a partition starting at partition_offset of the data file into
+    /// at most num_splits byte ranges with similar number of rows, returns
+    /// the ranges and their number of rows
+    pub fn split_partition(
+        &self,
+        partition_id: usize,
+        partition_offset: u64,
+        num_splits: usize,
+    ) -> Vec<(Range<u64>, u64)> {
+        let total_rows = self.num_rows(partition_id);
+        let num_splits = num_splits.max(1) as u64;
+        let target_rows = ((total_rows + num_splits - 1) / num_splits).max(1);
+        let mut splits = vec![];
+        let mut split_start = partition_offset;
+        let mut split_end = partition_offset;
+        let mut split_rows = 0;
+
+        for block in self.blocks(partition_id) {
+            split_end += block.len;
+            split_rows += block.num_rows;
+            if split_rows >= target_rows {
+                splits.push((split_start..split_end, split_rows));
+                split_start = split_end;
+                split_rows = 0;
+            }
+        }
+        if split_end > split_start {
+            splits.push((split_start..split_end, split_rows));
+        }
+        splits
+    }
+}
+
+pub fn write_split_index_file(
+    split_index_file: &str,
+    split_index: &ShuffleSplitIndex,
+) -> Result<()> {
+    let mut output = BufWriter::new(File::create(split_index_file)?);
+    split_index.write_to(&mut output)?;
+    output
+        .into_inner()
+        .or_else(|e| df_execution_err!("{e}"))?
+        .sync_data()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use datafusion::common::Result;
+
+    use crate::shuffle::split_index::ShuffleSplitIndex;
+
+    #[test]
+    fn test_split_index_read_write() -> Result<()> {
+        let mut split_index = ShuffleSplitIndex::new(4);
+        split_index.add_block(0, 100, 10);
+        split_index.add_block(0, 200, 20);
+        split_index.add_block(2, 50, 5);
+        split_index.add_block(3, 0, 0); // empty blocks are ignored
+
+        let mut buf = vec![];
+        split_index.write_to(&mut buf)?;
+        assert_eq!(buf.len(), (5 + 3 * 2) * 8);
+
+        let read = ShuffleSplitIndex::read_from(Cursor::new(&buf), 4)?;
+        assert_eq!(read, split_index);
+        assert_eq!(read.num_rows(0), 30);
+        assert_eq!(read.num_rows(1), 0);
+        assert_eq!(read.num_rows(2), 5);
+        assert!(ShuffleSplitIndex::read_from(Cursor::new(&buf[..80]), 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_partition() -> Result<()> {
+        let mut split_index = ShuffleSplitIndex::new(2);
+        for i in 0..6 {
+            split_index.add_block(1, 100 + i, 10);
+        }
+
+        // six blocks of 10 rows, split into three ranges of 20 rows
+        assert_eq!(
+            split_index.split_partition(1, 1000, 3),
+            vec![(1000..1201, 20), (1201..1406, 20), (1406..1615, 20)],
+        );
+
+        // not enough blocks for the requested splits
+        assert_eq!(split_index.split_partition(1, 1000, 10).len(), 6);
+        assert_eq!(
+            split_index.split_partition(1, 1000, 1),
+            vec![(1000..1615, 60)],
+        );
+        assert!(split_index.split_partition(0, 0, 3).is_empty());
+        Ok(())
+    }
+}
@@ -25,8 +25,7 @@ use datafusion::{
         expressions::PhysicalSortExpr,
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         stream::RecordBatchStreamAdapter,
-        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
-        Statistics,
+        DisplayAs, DisplayFormatType, ExecutionPlan, SendableRecordBatchStream, Statistics,
     },
 };
 use datafusion_ext_commons::df_execution_err;
@@ -37,7 +36,7 @@ use crate::{
     memmgr::MemManager,
     shuffle::{
         single_repartitioner::SingleShuffleRepartitioner,
-        sort_repartitioner::SortShuffleRepartitioner, ShuffleRepartitioner,
+        sort_repartitioner::SortShuffleRepartitioner, Partitioning, ShuffleRepartitioner,
     },
 };
 
@@ -54,13 +53,17 @@ pub struct ShuffleWriterExec {
     output_data_file: String,
     /// Output index file path
     output_index_file: String,
+    /// Output checksum file path, None if checksum is disabled
+    output_checksum_file: Option<String>,
+    /// Output split index file path, None if split index is disabled
+    output_split_index_file: Option<String>,
     /// Metrics
     metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for ShuffleWriterExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "ShuffleWriterExec: partitioning={:?}", self.partitioning)
+        write!(f, "ShuffleWriterExec: partitioning={}", self.partitioning)
     }
 }
 
@@ -76,8 +79,10 @@ impl ExecutionPlan for ShuffleWriterExec {
         self.input.schema()
     }
 
-    fn output_partitioning(&self) -> Partitioning {
-        self.partitioning.clone()
+    fn output_partitioning(&self) -> datafusion::physical_plan::Partitioning {
+        datafusion::physical_plan::Partitioning::UnknownPartitioning(
+            self.partitioning.partition_count(),
+        )
     }
 
     fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
@@ -98,6 +103,8 @@ impl ExecutionPlan for ShuffleWriterExec {
                 self.partitioning.clone(),
                 self.output_data_file.clone(),
                 self.output_index_file.clone(),
+                self.output_checksum_file.clone(),
+                self.output_split_index_file.clone(),
             )?)),
             _ => df_execution_err!("ShuffleWriterExec wrong number of children"),
         }
@@ -115,14 +122,17 @@ impl ExecutionPlan for ShuffleWriterExec {
             p if p.partition_count() == 1 => Arc::new(SingleShuffleRepartitioner::new(
                 self.output_data_file.clone(),
                 self.output_index_file.clone(),
+                self.output_checksum_file.clone(),
                 BaselineMetrics::new(&self.metrics, partition),
                 data_size_metric,
             )),
-            Partitioning::Hash(..) => {
+            _ => {
                 let partitioner = Arc::new(SortShuffleRepartitioner::new(
                     partition,
                     self.output_data_file.clone(),
                     self.output_index_file.clone(),
+                    self.output_checksum_file.clone(),
+                    self.output_split_index_file.clone(),
                     self.partitioning.clone(),
                     &self.metrics,
                     data_size_metric,
@@ -130,7 +140,6 @@ impl ExecutionPlan for ShuffleWriterExec {
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
-            p => unreachable!("unsupported partitioning: {:?}", p),
         };
 
         let input = stat_input(
@@ -165,6 +174,8 @@ impl ShuffleWriterExec {
         partitioning: Partitioning,
         output_data_file: String,
         output_index_file: String,
+        output_checksum_file: Option<String>,
+        output_split_index_file: Option<String>,
     ) -> Result<Self> {
         Ok(ShuffleWriterExec {
             input,
@@ -172,6 +183,8 @@ impl ShuffleWriterExec {
             metrics: ExecutionPlanMetricsSet::new(),
             output_data_file,
             output_index_file,
+            output_checksum_file,
+            output_split_index_file,
         })
     }
 }
@@ -0,0 +1,698 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::Formatter,
+    io::{Cursor, Write},
+    sync::{Arc, Weak},
+    task::Poll,
+    time::Duration,
+};
+
+use arrow::{compute::concat_batches, datatypes::SchemaRef, record_batch::RecordBatch};
+use async_trait::async_trait;
+use datafusion::{
+    common::{JoinSide, Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::{PhysicalExpr, PhysicalSortExpr},
+    physical_plan::{
+        expressions::Column,
+        joins::utils::{check_join_is_valid, JoinFilter, JoinOn},
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    df_unimplemented_err, downcast_any,
+    io::{read_one_batch, write_one_batch},
+    streams::coalesce_stream::CoalesceInput,
+};
+use futures::{lock::Mutex, stream::once, StreamExt, TryStreamExt};
+use parking_lot::Mutex as SyncMutex;
+
+use crate::{
+    broadcast_join_exec::RecordBatchStreamsWrapperExec,
+    common::output::TaskOutputter,
+    joins::{
+        build_join_schema,
+        hash_join::{execute_hash_join, HashJoinParams},
+        join_hash_map::JoinHashMap,
+        JoinType,
+    },
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{try_new_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+    sort_exec::SortExec,
+    sort_merge_join_exec::SortMergeJoinExec,
+};
+
+#[derive(Debug)]
+pub struct ShuffledHashJoinExec {
+    /// Left joining execution plan
+    left: Arc<dyn ExecutionPlan>,
+    /// Right joining execution plan
+    right: Arc<dyn ExecutionPlan>,
+    /// Set of common columns used to join on
+    on: JoinOn,
+    /// How the join is performed
+    join_type: JoinType,
+    /// Optional filter before outputting
+    join_filter: Option<JoinFilter>,
+    /// Side of the hash map to build
+    build_side: JoinSide,
+    /// The schema once the join is applied
+    schema: SchemaRef,
+    /// Execution metrics
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl ShuffledHashJoinExec {
+    pub fn try_new(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        on: JoinOn,
+        join_type: JoinType,
+        join_filter: Option<JoinFilter>,
+        build_side: JoinSide,
+    ) -> Result<Self> {
+        let left_schema = left.schema();
+        let right_schema = right.schema();
+
+        check_join_is_valid(&left_schema, &right_schema, &on)?;
+        if join_type == JoinType::NullAwareAnti {
+            // null-aware anti join requires the whole right side, which is not
+            // available in a shuffled partition
+            return df_unimplemented_err!("ShuffledHashJoin does not support null-aware anti join");
+        }
+        let schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type));
+
+        Ok(Self {
+            left,
+            right,
+            on,
+            join_type,
+            join_filter,
+            build_side,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+}
+
+impl DisplayAs for ShuffledHashJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "ShuffledHashJoin [build_side={:?}]", self.build_side)
+    }
+}
+
+impl ExecutionPlan for ShuffledHashJoinExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.right.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.left.clone(), self.right.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            children[1].clone(),
+            self.on.clone(),
+            self.join_type,
+            self.join_filter.clone(),
+            self.build_side,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let build_buffer = Arc::new(BuildSideBuffer {
+            name: format!("ShuffledHashJoin[partition={}]", partition),
+            mem_consumer_info: None,
+            data: Default::default(),
+            spill_metrics: SpillMetrics::new(&self.metrics, partition),
+        });
+        MemManager::register_consumer(build_buffer.clone(), true);
+
+        let stream = execute_shuffled_hash_join(
+            self.left.clone(),
+            self.right.clone(),
+            partition,
+            context,
+            self.on.clone(),
+            self.join_type,
+            self.join_filter.clone(),
+            self.build_side,
+            self.schema(),
+            build_buffer,
+            BaselineMetrics::new(&self.metrics, partition),
+        );
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            once(stream).try_flatten(),
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        unimplemented!()
+    }
+}
+
+async fn execute_shuffled_hash_join(
+    left: Arc<dyn ExecutionPlan>,
+    right: Arc<dyn ExecutionPlan>,
+    partition: usize,
+    context: Arc<TaskContext>,
+    on: JoinOn,
+    join_type: JoinType,
+    join_filter: Option<JoinFilter>,
+    build_side: JoinSide,
+    schema: SchemaRef,
+    build_buffer: Arc<BuildSideBuffer>,
+    metrics: BaselineMetrics,
+) -> Result<SendableRecordBatchStream> {
+    let left_keys: Vec<Arc<dyn PhysicalExpr>> = on.iter().map(|(l, _)| l.clone()).collect();
+    let right_keys: Vec<Arc<dyn PhysicalExpr>> = on.iter().map(|(_, r)| r.clone()).collect();
+    let (build, probe, build_keys, probe_keys) = match build_side {
+        JoinSide::Left => (left, right, left_keys, right_keys),
+        JoinSide::Right => (right, left, right_keys, left_keys),
+    };
+
+    // buffer all batches from the build side, batches are spilled if memory
+    // is not enough
+    let build_schema = build.schema();
+    let mut build_input = context
+        .coalesce_with_default_batch_size(build.execute(partition, context.clone())?, &metrics)?;
+    while let Some(batch) = build_input.next().await.transpose()? {
+        build_buffer.insert_batch(batch).await?;
+    }
+
+    let build_spills = match build_buffer.take_build_side().await? {
+        // build side fits in memory, use hash join
+        BuildSide::InMemory(build_batches) => {
+            let data_batch = concat_batches(&build_schema, &build_batches)?;
+            drop(build_batches);
+            drop(build_buffer);
+
+            let join_hash_map = JoinHashMap::try_new(
+                format!("ShuffledHashJoinHashMap[partition={}]", partition),
+                data_batch,
+                &build_keys,
+            )
+            .await?;
+            log::info!(
+                "ShuffledHashJoin is using hash join mode, num build side rows: {}",
+                join_hash_map.num_rows(),
+            );
+
+            let probe_input = context.coalesce_with_default_batch_size(
+                probe.execute(partition, context.clone())?,
+                &metrics,
+            )?;
+            let join_params = HashJoinParams {
+                join_type,
+                build_side,
+                probe_keys,
+                join_filter,
+                probe_schema: probe.schema(),
+                output_schema: schema.clone(),
+            };
+            let metrics_cloned = metrics.clone();
+            let output = context.output_with_sender("ShuffledHashJoin", schema, move |sender| {
+                execute_hash_join(
+                    probe_input,
+                    join_hash_map,
+                    join_params,
+                    metrics_cloned,
+                    sender,
+                )
+            })?;
+            return Ok(context.coalesce_with_default_batch_size(output, &metrics)?);
+        }
+        BuildSide::Spilled(build_spills) => build_spills,
+    };
+
+    // build side exceeds memory, fall back to sort-merge join. all buffered
+    // batches have been spilled and are replayed into the sorter
+    drop(build_buffer);
+    log::info!(
+        "ShuffledHashJoin falls back to sort-merge join mode, num build side spills: {}",
+        build_spills.len(),
+    );
+
+    let build_stream = replay_spills(build_spills, build_schema.clone(), context.clone())?;
+    let build: Arc<dyn ExecutionPlan> = Arc::new(RecordBatchStreamsWrapperExec {
+        schema: build_schema,
+        stream: SyncMutex::new(Some(build_stream)),
+        output_partitioning: probe.output_partitioning(),
+    });
+    let (left, right) = match build_side {
+        JoinSide::Left => (build, probe),
+        JoinSide::Right => (probe, build),
+    };
+
+    let sort_exprs = |keys: Vec<Arc<dyn PhysicalExpr>>| -> Vec<PhysicalSortExpr> {
+        keys.iter()
+            .map(|key| PhysicalSortExpr {
+                expr: Arc::new(Column::new(
+                    "",
+                    downcast_any!(key, Column).expect("requires column").index(),
+                )),
+                options: Default::default(),
+            })
+            .collect()
+    };
+    let left_sort_exprs = sort_exprs(on.iter().map(|(l, _)| l.clone()).collect());
+    let right_sort_exprs = sort_exprs(on.iter().map(|(_, r)| r.clone()).collect());
+    let sort_options = left_sort_exprs.iter().map(|se| se.options).collect();
+    let left_sorted = Arc::new(SortExec::new(left, left_sort_exprs, None));
+    let right_sorted = Arc::new(SortExec::new(right, right_sort_exprs, None));
+    let join = Arc::new(SortMergeJoinExec::try_new(
+        left_sorted.clone(),
+        right_sorted.clone(),
+        on,
+        join_type,
+        join_filter,
+        sort_options,
+    )?);
+
+    let join_schema = join.schema();
+    let completed = join
+        .execute(partition, context)?
+        .chain(futures::stream::poll_fn(move |_| {
+            // update metrics
+            let left_sorted_metrics = left_sorted.metrics().unwrap();
+            let right_sorted_metrics = right_sorted.metrics().unwrap();
+            let join_metrics = join.metrics().unwrap();
+            metrics.record_output(join_metrics.output_rows().unwrap_or(0));
+            metrics.elapsed_compute().add_duration(Duration::from_nanos(
+                [
+                    left_sorted_metrics.elapsed_compute(),
+                    right_sorted_metrics.elapsed_compute(),
+                    join_metrics.elapsed_compute(),
+                ]
+                .into_iter()
+                .flatten()
+                .sum::<usize>() as u64,
+            ));
+            Poll::Ready(None)
+        }));
+    Ok(Box::pin(RecordBatchStreamAdapter::new(
+        join_schema,
+        completed,
+    )))
+}
+
+fn replay_spills(
+    spills: Vec<Box<dyn Spill>>,
+    schema: SchemaRef,
+    context: Arc<TaskContext>,
+) -> Result<SendableRecordBatchStream> {
+    context.output_with_sender(
+        "ShuffledHashJoin.replay",
+        schema.clone(),
+        |sender| async move {
+            for spill in spills {
+                let mut reader = spill.get_compressed_reader();
+                while let Some(batch) = read_one_batch(&mut reader, &schema)? {
+                    sender.send(Ok(batch), None).await;
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+/// buffers batches of the build side, batches are spilled under memory
+/// pressure
+struct BuildSideBuffer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    data: Mutex<BuildSideData>,
+    spill_metrics: SpillMetrics,
+}
+
+#[derive(Default)]
+struct BuildSideData {
+    staging_batches: Vec<RecordBatch>,
+    staging_mem_used: usize,
+    spills: Vec<Box<dyn Spill>>,
+}
+
+impl BuildSideData {
+    fn spill_staging_batches(&mut self, spill_metrics: &SpillMetrics) -> Result<()> {
+        let batches = std::mem::take(&mut self.staging_batches);
+        if !batches.is_empty() {
+            let mut spill = try_new_spill(spill_metrics)?;
+            let mut writer = spill.get_compressed_writer();
+            for batch in batches {
+                let mut buf = vec![];
+                write_one_batch(&batch, &mut Cursor::new(&mut buf))?;
+                writer.write_all(&buf)?;
+            }
+            drop(writer);
+            self.spills.push(spill);
+        }
+        self.staging_mem_used = 0;
+        Ok(())
+    }
+}
+
+/// build side data taken from the buffer after all batches are inserted
+enum BuildSide {
+    InMemory(Vec<RecordBatch>),
+    Spilled(Vec<Box<dyn Spill>>),
+}
+
+impl BuildSideBuffer {
+    async fn insert_batch(&self, batch: RecordBatch) -> Result<()> {
+        let mut data = self.data.lock().await;
+        data.staging_mem_used += batch.get_array_mem_size();
+        data.staging_batches.push(batch);
+        let mem_used = data.staging_mem_used;
+        drop(data);
+
+        self.update_mem_used(mem_used).await?;
+        Ok(())
+    }
+
+    /// finishes buffering and takes all batches of the build side. the buffer
+    /// is made unspillable before checking whether it has been spilled, so the
+    /// check and the take see the same data. if any batch has been spilled,
+    /// the in-memory batches are spilled too.
+    async fn take_build_side(&self) -> Result<BuildSide> {
+        self.set_spillable(false);
+        let mut data = self.data.lock().await;
+        let build_side = if data.spills.is_empty() {
+            data.staging_mem_used = 0;
+            BuildSide::InMemory(std::mem::take(&mut data.staging_batches))
+        } else {
+            data.spill_staging_batches(&self.spill_metrics)?;
+            BuildSide::Spilled(std::mem::take(&mut data.spills))
+        };
+        drop(data);
+
+        self.update_mem_used(0).await?;
+        Ok(build_side)
+    }
+}
+
+#[async_trait]
+impl MemConsumer for BuildSideBuffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<()> {
+        self.data
+            .lock()
+            .await
+            .spill_staging_batches(&self.spill_metrics)?;
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+impl Drop for BuildSideBuffer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        assert_batches_sorted_eq,
+        common::{JoinSide, Result},
+        physical_expr::{expressions::Column, PhysicalExpr},
+        physical_plan::{common, memory::MemoryExec, metrics::BaselineMetrics, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        joins::JoinType,
+        memmgr::{metrics::SpillMetrics, MemConsumer, MemManager},
+        shuffled_hash_join_exec::{
+            execute_shuffled_hash_join, replay_spills, BuildSide, BuildSideBuffer,
+            ShuffledHashJoinExec,
+        },
+    };
+
+    fn build_table(
+        a: (&str, &Vec<i32>),
+        b: (&str, &Vec<i32>),
+        c: (&str, &Vec<i32>),
+    ) -> Arc<dyn ExecutionPlan> {
+        let schema = Schema::new(vec![
+            Field::new(a.0, DataType::Int32, false),
+            Field::new(b.0, DataType::Int32, false),
+            Field::new(c.0, DataType::Int32, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(a.1.clone())),
+                Arc::new(Int32Array::from(b.1.clone())),
+                Arc::new(Int32Array::from(c.1.clone())),
+            ],
+        )
+        .unwrap();
+        let schema = batch.schema();
+        Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap())
+    }
+
+    fn build_test_tables() -> (Arc<dyn ExecutionPlan>, Arc<dyn ExecutionPlan>) {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3, 3]),
+            ("b1", &vec![4, 5, 7, 7]),
+            ("c1", &vec![7, 8, 9, 10]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b1", &vec![4, 5, 6, 7]),
+            ("c2", &vec![70, 80, 90, 100]),
+        );
+        (left, right)
+    }
+
+    fn join_on(
+        left: &Arc<dyn ExecutionPlan>,
+        right: &Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<(Arc<dyn PhysicalExpr>, Arc<dyn PhysicalExpr>)>> {
+        Ok(vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b1", &right.schema())?),
+        )])
+    }
+
+    fn expected_output(join_type: JoinType) -> Vec<&'static str> {
+        match join_type {
+            JoinType::Inner => vec![
+                "+----+----+----+----+----+-----+",
+                "| a1 | b1 | c1 | a2 | b1 | c2  |",
+                "+----+----+----+----+----+-----+",
+                "| 1  | 4  | 7  | 10 | 4  | 70  |",
+                "| 2  | 5  | 8  | 20 | 5  | 80  |",
+                "| 3  | 7  | 9  | 40 | 7  | 100 |",
+                "| 3  | 7  | 10 | 40 | 7  | 100 |",
+                "+----+----+----+----+----+-----+",
+            ],
+            JoinType::Full => vec![
+                "+----+----+----+----+----+-----+",
+                "| a1 | b1 | c1 | a2 | b1 | c2  |",
+                "+----+----+----+----+----+-----+",
+                "|    |    |    | 30 | 6  | 90  |",
+                "| 1  | 4  | 7  | 10 | 4  | 70  |",
+                "| 2  | 5  | 8  | 20 | 5  | 80  |",
+                "| 3  | 7  | 9  | 40 | 7  | 100 |",
+                "| 3  | 7  | 10 | 40 | 7  | 100 |",
+                "+----+----+----+----+----+-----+",
+            ],
+            JoinType::RightAnti => vec![
+                "+----+----+----+",
+                "| a2 | b1 | c2 |",
+                "+----+----+----+",
+                "| 30 | 6  | 90 |",
+                "+----+----+----+",
+            ],
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shuffled_hash_join() -> Result<()> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        for build_side in [JoinSide::Left, JoinSide::Right] {
+            for join_type in [JoinType::Inner, JoinType::Full, JoinType::RightAnti] {
+                let (left, right) = build_test_tables();
+                let on = join_on(&left, &right)?;
+                let join =
+                    ShuffledHashJoinExec::try_new(left, right, on, join_type, None, build_side)?;
+                let stream = join.execute(0, task_ctx.clone())?;
+                let batches = common::collect(stream).await?;
+                assert_batches_sorted_eq!(expected_output(join_type), &batches);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shuffled_hash_join_spilled() -> Result<()> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        for join_type in [JoinType::Inner, JoinType::Full, JoinType::RightAnti] {
+            let (left, right) = build_test_tables();
+            let on = join_on(&left, &right)?;
+            let join = ShuffledHashJoinExec::try_new(
+                left.clone(),
+                right.clone(),
+                on.clone(),
+                join_type,
+                None,
+                JoinSide::Left,
+            )?;
+
+            // put build side data into a spilled buffer to force sort-merge join
+            let build_buffer = Arc::new(BuildSideBuffer {
+                name: "test".to_string(),
+                mem_consumer_info: None,
+                data: Default::default(),
+                spill_metrics: SpillMetrics::new(&join.metrics, 0),
+            });
+            MemManager::register_consumer(build_buffer.clone(), true);
+            let build_batches = common::collect(left.execute(0, task_ctx.clone())?).await?;
+            for batch in build_batches {
+                build_buffer.insert_batch(batch).await?;
+            }
+            build_buffer.spill().await?;
+
+            let empty_left = Arc::new(MemoryExec::try_new(&[vec![]], left.schema(), None)?);
+            let stream = execute_shuffled_hash_join(
+                empty_left,
+                right,
+                0,
+                task_ctx.clone(),
+                on,
+                join_type,
+                None,
+                JoinSide::Left,
+                join.schema(),
+                build_buffer,
+                BaselineMetrics::new(&join.metrics, 0),
+            )
+            .await?;
+            let batches = common::collect(stream).await?;
+            assert_batches_sorted_eq!(expected_output(join_type), &batches);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_side_spilled_before_take() -> Result<()> {
+        MemManager::init(1000000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let (left, _right) = build_test_tables();
+        let build_batches = common::collect(left.execute(0, task_ctx.clone())?).await?;
+        let new_build_buffer = || {
+            let build_buffer = Arc::new(BuildSideBuffer {
+                name: "test".to_string(),
+                mem_consumer_info: None,
+                data: Default::default(),
+                spill_metrics: SpillMetrics::new(&Default::default(), 0),
+            });
+            MemManager::register_consumer(build_buffer.clone(), true);
+            build_buffer
+        };
+
+        // not spilled, all batches are taken in memory
+        let build_buffer = new_build_buffer();
+        for batch in build_batches.clone() {
+            build_buffer.insert_batch(batch).await?;
+        }
+        match build_buffer.take_build_side().await? {
+            BuildSide::InMemory(batches) => assert_eq!(batches, build_batches),
+            BuildSide::Spilled(_) => panic!("build side should not be spilled"),
+        }
+
+        // spilled between building and probing, batches inserted after the
+        // spill are spilled too and no rows are lost
+        let build_buffer = new_build_buffer();
+        for batch in build_batches.clone() {
+            build_buffer.insert_batch(batch).await?;
+        }
+        build_buffer.spill().await?;
+        for batch in build_batches.clone() {
+            build_buffer.insert_batch(batch).await?;
+        }
+        let spills = match build_buffer.take_build_side().await? {
+            BuildSide::InMemory(_) => panic!("build side should be spilled"),
+            BuildSide::Spilled(spills) => spills,
+        };
+        assert_eq!(spills.len(), 2);
+        assert!(build_buffer.data.lock().await.staging_batches.is_empty());
+
+        let replayed = common::collect(replay_spills(spills, left.schema(), task_ctx)?).await?;
+        let num_rows =
+            |batches: &[RecordBatch]| -> usize { batches.iter().map(|b| b.num_rows()).sum() };
+        assert_eq!(num_rows(&replayed), num_rows(&build_batches) * 2);
+        Ok(())
+    }
+}
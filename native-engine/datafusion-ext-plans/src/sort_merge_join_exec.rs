@@ -26,10 +26,9 @@ use datafusion::{
     common::JoinSide,
     error::Result,
     execution::context::TaskContext,
-    logical_expr::{JoinType, JoinType::*},
     physical_expr::{expressions::Column, PhysicalSortExpr},
     physical_plan::{
-        joins::utils::{build_join_schema, check_join_is_valid, ColumnIndex, JoinFilter, JoinOn},
+        joins::utils::{check_join_is_valid, ColumnIndex, JoinFilter, JoinOn},
         metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet, ScopedTimerGuard},
         stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
@@ -37,15 +36,19 @@ use datafusion::{
     },
 };
 use datafusion_ext_commons::{
-    batch_size, df_execution_err, downcast_any, streams::coalesce_stream::CoalesceInput,
+    batch_size, df_execution_err, df_unimplemented_err, downcast_any,
+    streams::coalesce_stream::CoalesceInput,
 };
 use futures::{StreamExt, TryStreamExt};
 use parking_lot::Mutex as SyncMutex;
 
-use crate::common::{
-    batch_selection::{interleave_batches, take_batch_opt},
-    column_pruning::ExecuteWithColumnPruning,
-    output::{TaskOutputter, WrappedRecordBatchSender},
+use crate::{
+    common::{
+        batch_selection::{interleave_batches, take_batch_opt},
+        column_pruning::ExecuteWithColumnPruning,
+        output::{TaskOutputter, WrappedRecordBatchSender},
+    },
+    joins::{build_join_schema, check_null_aware_anti_join, JoinType, JoinType::*},
 };
 
 #[derive(Debug)]
@@ -81,12 +84,6 @@ impl SortMergeJoinExec {
         let left_schema = left.schema();
         let right_schema = right.schema();
 
-        if matches!(join_type, LeftSemi | LeftAnti | RightSemi | RightAnti,) {
-            if join_filter.is_some() {
-                df_execution_err!("Semi/Anti join with filter is not supported yet")?;
-            }
-        }
-
         check_join_is_valid(&left_schema, &right_schema, &on)?;
         if sort_options.len() != on.len() {
             df_execution_err!(
@@ -96,7 +93,20 @@ impl SortMergeJoinExec {
             )?;
         }
 
-        let schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type).0);
+        if join_type == NullAwareAnti {
+            // null keys of the right side must be sorted first, so that we can
+            // know whether the right side contains null keys before outputting
+            // any left rows. note that the right side must be complete in each
+            // partition (like the broadcasted side of BroadcastJoinExec)
+            check_null_aware_anti_join(&on, &join_filter)?;
+            if !sort_options[0].nulls_first {
+                return df_unimplemented_err!(
+                    "SortMergeJoin with null-aware anti join requires nulls first"
+                );
+            }
+        }
+
+        let schema = Arc::new(build_join_schema(&left_schema, &right_schema, &join_type));
         Ok(Self {
             left,
             right,
@@ -168,7 +178,7 @@ impl ExecutionPlan for SortMergeJoinExec {
 
     fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
         match self.join_type {
-            Left | LeftSemi | LeftAnti => self.left.output_ordering(),
+            Left | LeftSemi | LeftAnti | Existence | NullAwareAnti => self.left.output_ordering(),
             Right | RightSemi | RightAnti => self.right.output_ordering(),
             Inner => self.left.output_ordering(),
             Full => None,
@@ -266,9 +276,15 @@ impl JoinParams {
                         right_projection.push(i - num_left_fields);
                     }
                 }
-                LeftSemi | LeftAnti => {
+                LeftSemi | LeftAnti | NullAwareAnti => {
                     left_projection.push(i);
                 }
+                Existence => {
+                    // the exists column is not from left side
+                    if i < num_left_fields {
+                        left_projection.push(i);
+                    }
+                }
                 RightSemi | RightAnti => {
                     right_projection.push(i);
                 }
@@ -418,7 +434,7 @@ async fn execute_join(
     forward!(rcur);
 
     let join_type = join_params.join_type;
-    let mut joiner = Joiner::new();
+    let mut joiner = Joiner::new(join_type);
     let mut leqs = vec![];
     let mut reqs = vec![];
 
@@ -434,13 +450,27 @@ async fn execute_join(
         }};
     }
 
+    // null-aware anti join: all left rows are output if the right side is
+    // empty, and nothing is output if the right side contains null keys (which
+    // are sorted first)
+    let right_empty = rcur.finished;
+    if join_type == NullAwareAnti && !right_empty && rcur.is_null_key(rcur.cur_idx) {
+        return Ok(());
+    }
+
     // process records until one side is exhausted
     while !lcur.finished && !rcur.finished {
         let r = compare_cursor(&lcur, lcur.cur_idx, &rcur, rcur.cur_idx);
         match r {
             Ordering::Less => {
-                if matches!(join_type, Left | LeftAnti | Full) {
-                    joiner_accept_pair!(Some(lcur.cur_idx), None);
+                match join_type {
+                    Left | LeftAnti | Full | Existence => {
+                        joiner_accept_pair!(Some(lcur.cur_idx), None);
+                    }
+                    NullAwareAnti if !lcur.is_null_key(lcur.cur_idx) => {
+                        joiner_accept_pair!(Some(lcur.cur_idx), None);
+                    }
+                    _ => {}
                 }
                 forward!(lcur);
                 lcur.clear_outdated(joiner.l_min_reserved_bidx);
@@ -452,6 +482,64 @@ async fn execute_join(
                 forward!(rcur);
                 rcur.clear_outdated(joiner.r_min_reserved_bidx);
             }
+            Ordering::Equal if join_params.join_filter.is_some() && is_semi_or_anti(join_type) => {
+                // semi/anti join with filter: collect all rows with the same key from both
+                // sides, then find out probed rows which have at least one pair passing
+                // the filter
+                let lidx0 = lcur.cur_idx;
+                let ridx0 = rcur.cur_idx;
+                while !lcur.finished && lcur.row(lcur.cur_idx) == lcur.row(lidx0) {
+                    leqs.push(lcur.cur_idx);
+                    forward!(lcur);
+                }
+                while !rcur.finished && rcur.row(rcur.cur_idx) == rcur.row(ridx0) {
+                    reqs.push(rcur.cur_idx);
+                    forward!(rcur);
+                }
+
+                let join_filter = join_params.join_filter.as_ref().unwrap();
+                let emit_matched = matches!(join_type, LeftSemi | RightSemi);
+                match join_type {
+                    LeftSemi | LeftAnti | Existence => {
+                        let matched = eval_filter_matches(
+                            join_filter,
+                            join_params.batch_size,
+                            &lcur,
+                            &rcur,
+                            &leqs,
+                            &reqs,
+                            JoinSide::Left,
+                        )?;
+                        for (&l, matched) in leqs.iter().zip(matched) {
+                            if join_type == Existence {
+                                joiner_accept_pair!(Some(l), matched.then_some(ridx0));
+                            } else if matched == emit_matched {
+                                joiner_accept_pair!(Some(l), None);
+                            }
+                        }
+                    }
+                    _ => {
+                        let matched = eval_filter_matches(
+                            join_filter,
+                            join_params.batch_size,
+                            &lcur,
+                            &rcur,
+                            &leqs,
+                            &reqs,
+                            JoinSide::Right,
+                        )?;
+                        for (&r, matched) in reqs.iter().zip(matched) {
+                            if matched == emit_matched {
+                                joiner_accept_pair!(None, Some(r));
+                            }
+                        }
+                    }
+                }
+                leqs.clear();
+                reqs.clear();
+                lcur.clear_outdated(joiner.l_min_reserved_bidx);
+                rcur.clear_outdated(joiner.r_min_reserved_bidx);
+            }
             Ordering::Equal => {
                 let lidx0 = lcur.cur_idx;
                 let ridx0 = rcur.cur_idx;
@@ -490,12 +578,17 @@ async fn execute_join(
                             joiner_accept_pair!(Some(l), None);
                         }
                     }
+                    Existence => {
+                        for &l in &leqs {
+                            joiner_accept_pair!(Some(l), Some(ridx0));
+                        }
+                    }
                     RightSemi => {
                         for &r in &reqs {
                             joiner_accept_pair!(None, Some(r));
                         }
                     }
-                    LeftAnti | RightAnti => {}
+                    LeftAnti | RightAnti | NullAwareAnti => {}
                 }
 
                 if leq {
@@ -509,7 +602,10 @@ async fn execute_join(
                             LeftSemi => {
                                 joiner_accept_pair!(Some(lcur.cur_idx), None);
                             }
-                            RightSemi | LeftAnti | RightAnti => {}
+                            Existence => {
+                                joiner_accept_pair!(Some(lcur.cur_idx), Some(ridx0));
+                            }
+                            RightSemi | LeftAnti | RightAnti | NullAwareAnti => {}
                         }
                         forward!(lcur);
                         lcur.clear_outdated(joiner.l_min_reserved_bidx);
@@ -526,7 +622,7 @@ async fn execute_join(
                             RightSemi => {
                                 joiner_accept_pair!(None, Some(rcur.cur_idx));
                             }
-                            LeftSemi | LeftAnti | RightAnti => {}
+                            LeftSemi | LeftAnti | RightAnti | Existence | NullAwareAnti => {}
                         }
                         forward!(rcur);
                         rcur.clear_outdated(joiner.r_min_reserved_bidx);
@@ -549,9 +645,14 @@ async fn execute_join(
     }
 
     // process rest records in inexhausted side
-    if matches!(join_type, Left | LeftAnti | Full) {
+    if matches!(
+        join_type,
+        Left | LeftAnti | Full | Existence | NullAwareAnti
+    ) {
         while !lcur.finished {
-            joiner_accept_pair!(Some(lcur.cur_idx), None);
+            if join_type != NullAwareAnti || right_empty || !lcur.is_null_key(lcur.cur_idx) {
+                joiner_accept_pair!(Some(lcur.cur_idx), None);
+            }
             forward!(lcur);
             lcur.clear_outdated(joiner.l_min_reserved_bidx);
         }
@@ -682,6 +783,14 @@ impl StreamCursor {
         self.on_rows[bidx].row(ridx)
     }
 
+    #[inline]
+    fn is_null_key(&self, idx: (usize, usize)) -> bool {
+        self.on_row_null_buffers[idx.0]
+            .as_ref()
+            .map(|nb| nb.is_null(idx.1))
+            .unwrap_or(false)
+    }
+
     #[inline]
     fn num_buffered_batches(&self) -> usize {
         self.batches.len() - self.num_null_batches
@@ -700,19 +809,22 @@ impl StreamCursor {
     }
 }
 
-#[derive(Default)]
 struct Joiner {
+    join_type: JoinType,
     ljoins: Vec<(usize, usize)>,
     rjoins: Vec<(usize, usize)>,
+    exists: Vec<bool>,
     l_min_reserved_bidx: usize,
     r_min_reserved_bidx: usize,
 }
 
 impl Joiner {
-    fn new() -> Self {
+    fn new(join_type: JoinType) -> Self {
         Self {
+            join_type,
             ljoins: vec![],
             rjoins: vec![],
+            exists: vec![],
             l_min_reserved_bidx: usize::MAX,
             r_min_reserved_bidx: usize::MAX,
         }
@@ -733,7 +845,12 @@ impl Joiner {
             self.ljoins.push((0, 0));
         }
 
-        if let Some((bidx, ridx)) = r {
+        if self.join_type == Existence {
+            // existence join only outputs whether the left row is matched, so
+            // right rows are not reserved
+            self.exists.push(r.is_some());
+            self.rjoins.push((0, 0));
+        } else if let Some((bidx, ridx)) = r {
             self.rjoins.push((bidx, ridx));
             self.r_min_reserved_bidx = self.r_min_reserved_bidx.min(bidx);
         } else {
@@ -760,44 +877,14 @@ impl Joiner {
         self.l_min_reserved_bidx = usize::MAX;
         self.r_min_reserved_bidx = usize::MAX;
 
-        if let Some(join_filter) = &join_params.join_filter {
-            let num_intermediate_rows = std::cmp::max(self.ljoins.len(), self.rjoins.len());
+        // filter of semi/anti joins is already evaluated before accepting pairs
+        let join_filter = join_params
+            .join_filter
+            .as_ref()
+            .filter(|_| !is_semi_or_anti(join_params.join_type));
 
-            // get intermediate batch
-            let intermediate_columns = join_filter
-                .column_indices()
-                .iter()
-                .map(|ci| {
-                    let (cur, joins) = match ci.side {
-                        JoinSide::Left => (&lcur, &self.ljoins),
-                        JoinSide::Right => (&rcur, &self.rjoins),
-                    };
-                    let arrays = cur
-                        .batches
-                        .iter()
-                        .map(|b| b.column(ci.index).as_ref())
-                        .collect::<Vec<_>>();
-                    Ok(arrow::compute::interleave(&arrays, joins)?)
-                })
-                .collect::<Result<Vec<_>>>()?;
-
-            let intermediate_batch = RecordBatch::try_new_with_options(
-                Arc::new(join_filter.schema().clone()),
-                intermediate_columns,
-                &RecordBatchOptions::new().with_row_count(Some(num_intermediate_rows)),
-            )?;
-
-            // evalute filter
-            let filtered_array = join_filter
-                .expression()
-                .evaluate(&intermediate_batch)?
-                .into_array(intermediate_batch.num_rows())?;
-            let filtered = as_boolean_array(&filtered_array);
-            let filtered = if filtered.null_count() > 0 {
-                prep_null_mask_filter(filtered)
-            } else {
-                filtered.clone()
-            };
+        if let Some(join_filter) = join_filter {
+            let filtered = eval_join_filter(join_filter, lcur, rcur, &self.ljoins, &self.rjoins)?;
 
             // apply filter
             let mut retained = 0;
@@ -843,13 +930,23 @@ impl Joiner {
         };
 
         let output_columns = match join_params.join_type {
-            LeftSemi | LeftAnti => lcols()?,
+            LeftSemi | LeftAnti | NullAwareAnti => lcols()?,
             RightSemi | RightAnti => rcols()?,
+            Existence => {
+                let mut output_columns = lcols()?;
+                // exists column may be pruned
+                if output_columns.len() < join_params.output_schema.fields().len() {
+                    let exists = BooleanArray::from(std::mem::take(&mut self.exists));
+                    output_columns.push(Arc::new(exists));
+                }
+                output_columns
+            }
             _ => [lcols()?, rcols()?].concat(),
         };
         let num_output_records = std::cmp::max(self.ljoins.len(), self.rjoins.len());
         self.ljoins.clear();
         self.rjoins.clear();
+        self.exists.clear();
         let batch = RecordBatch::try_new_with_options(
             join_params.output_schema.clone(),
             output_columns,
@@ -859,6 +956,119 @@ impl Joiner {
     }
 }
 
+fn is_semi_or_anti(join_type: JoinType) -> bool {
+    matches!(
+        join_type,
+        LeftSemi | LeftAnti | RightSemi | RightAnti | Existence | NullAwareAnti
+    )
+}
+
+/// evaluates join filter on the given pairs, null results are treated as false
+fn eval_join_filter(
+    join_filter: &JoinFilter,
+    lcur: &StreamCursor,
+    rcur: &StreamCursor,
+    ljoins: &[(usize, usize)],
+    rjoins: &[(usize, usize)],
+) -> Result<BooleanArray> {
+    let num_intermediate_rows = std::cmp::max(ljoins.len(), rjoins.len());
+
+    // get intermediate batch
+    let intermediate_columns = join_filter
+        .column_indices()
+        .iter()
+        .map(|ci| {
+            let (cur, joins) = match ci.side {
+                JoinSide::Left => (lcur, ljoins),
+                JoinSide::Right => (rcur, rjoins),
+            };
+            let arrays = cur
+                .batches
+                .iter()
+                .map(|b| b.column(ci.index).as_ref())
+                .collect::<Vec<_>>();
+            Ok(arrow::compute::interleave(&arrays, joins)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let intermediate_batch = RecordBatch::try_new_with_options(
+        Arc::new(join_filter.schema().clone()),
+        intermediate_columns,
+        &RecordBatchOptions::new().with_row_count(Some(num_intermediate_rows)),
+    )?;
+
+    // evalute filter
+    let filtered_array = join_filter
+        .expression()
+        .evaluate(&intermediate_batch)?
+        .into_array(intermediate_batch.num_rows())?;
+    let filtered = as_boolean_array(&filtered_array);
+    Ok(if filtered.null_count() > 0 {
+        prep_null_mask_filter(filtered)
+    } else {
+        filtered.clone()
+    })
+}
+
+/// evaluates join filter on all pairs of rows sharing the same join key,
+/// returns whether each row of the probed side has at least one pair passing
+/// the filter. pairs of already matched rows are skipped.
+fn eval_filter_matches(
+    join_filter: &JoinFilter,
+    batch_size: usize,
+    lcur: &StreamCursor,
+    rcur: &StreamCursor,
+    leqs: &[(usize, usize)],
+    reqs: &[(usize, usize)],
+    probed_side: JoinSide,
+) -> Result<Vec<bool>> {
+    let (num_probed, num_build) = match probed_side {
+        JoinSide::Left => (leqs.len(), reqs.len()),
+        JoinSide::Right => (reqs.len(), leqs.len()),
+    };
+    let mut matched = vec![false; num_probed];
+    let mut ljoins = Vec::with_capacity(batch_size);
+    let mut rjoins = Vec::with_capacity(batch_size);
+    let mut probed_indices = Vec::with_capacity(batch_size);
+
+    let eval_pairs = |ljoins: &mut Vec<(usize, usize)>,
+                      rjoins: &mut Vec<(usize, usize)>,
+                      probed_indices: &mut Vec<usize>,
+                      matched: &mut [bool]|
+     -> Result<()> {
+        let filtered = eval_join_filter(join_filter, lcur, rcur, ljoins, rjoins)?;
+        for (&pi, selected) in probed_indices.iter().zip(filtered.values().iter()) {
+            matched[pi] |= selected;
+        }
+        ljoins.clear();
+        rjoins.clear();
+        probed_indices.clear();
+        Ok(())
+    };
+
+    for pi in 0..num_probed {
+        for bi in 0..num_build {
+            if matched[pi] {
+                break;
+            }
+            let (l, r) = match probed_side {
+                JoinSide::Left => (leqs[pi], reqs[bi]),
+                JoinSide::Right => (leqs[bi], reqs[pi]),
+            };
+            ljoins.push(l);
+            rjoins.push(r);
+            probed_indices.push(pi);
+            if probed_indices.len() >= batch_size {
+                eval_pairs(&mut ljoins, &mut rjoins, &mut probed_indices, &mut matched)?;
+            }
+        }
+    }
+    if !probed_indices.is_empty() {
+        eval_pairs(&mut ljoins, &mut rjoins, &mut probed_indices, &mut matched)?;
+    }
+    Ok(matched)
+}
+
 fn compare_cursor(
     lcur: &StreamCursor,
     lidx: (usize, usize),
@@ -900,14 +1110,18 @@ mod tests {
     };
     use datafusion::{
         assert_batches_sorted_eq,
+        common::JoinSide,
         error::Result,
-        logical_expr::{JoinType, JoinType::*},
-        physical_expr::expressions::Column,
+        logical_expr::Operator,
+        physical_expr::expressions::{BinaryExpr, Column},
         physical_plan::{common, joins::utils::*, memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
 
-    use crate::sort_merge_join_exec::SortMergeJoinExec;
+    use crate::{
+        joins::{JoinType, JoinType::*},
+        sort_merge_join_exec::SortMergeJoinExec,
+    };
 
     fn columns(schema: &Schema) -> Vec<String> {
         schema.fields().iter().map(|f| f.name().clone()).collect()
@@ -1419,6 +1633,247 @@ mod tests {
         Ok(())
     }
 
+    /// join on b1 with filter `c1 < c2`
+    async fn join_collect_with_lt_filter(
+        left: Arc<dyn ExecutionPlan>,
+        right: Arc<dyn ExecutionPlan>,
+        join_type: JoinType,
+    ) -> Result<Vec<RecordBatch>> {
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b1", &right.schema())?),
+        )];
+        let filter_schema = Schema::new(vec![
+            Field::new("c1", DataType::Int32, true),
+            Field::new("c2", DataType::Int32, true),
+        ]);
+        let join_filter = JoinFilter::new(
+            Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("c1", 0)),
+                Operator::Lt,
+                Arc::new(Column::new("c2", 1)),
+            )),
+            vec![
+                ColumnIndex {
+                    index: 2,
+                    side: JoinSide::Left,
+                },
+                ColumnIndex {
+                    index: 2,
+                    side: JoinSide::Right,
+                },
+            ],
+            filter_schema,
+        );
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let join = SortMergeJoinExec::try_new(
+            left,
+            right,
+            on,
+            join_type,
+            Some(join_filter),
+            vec![SortOptions::default()],
+        )?;
+        let stream = join.execute(0, task_ctx)?;
+        common::collect(stream).await
+    }
+
+    #[tokio::test]
+    async fn join_semi_with_filter() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3, 4, 5]),
+            ("b1", &vec![4, 5, 5, 6, 7]), // 7 does not exist on the right
+            ("c1", &vec![7, 8, 85, 95, 9]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b1", &vec![4, 5, 5, 6]), // 5 is double on the right
+            ("c2", &vec![70, 10, 80, 90]),
+        );
+
+        let batches = join_collect_with_lt_filter(left, right, LeftSemi).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 1  | 4  | 7  |",
+            "| 2  | 5  | 8  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_anti_with_filter() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3, 4, 5]),
+            ("b1", &vec![4, 5, 5, 6, 7]), // 7 does not exist on the right
+            ("c1", &vec![7, 8, 85, 95, 9]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b1", &vec![4, 5, 5, 6]), // 5 is double on the right
+            ("c2", &vec![70, 10, 80, 90]),
+        );
+
+        let batches = join_collect_with_lt_filter(left, right, LeftAnti).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 3  | 5  | 85 |",
+            "| 4  | 6  | 95 |",
+            "| 5  | 7  | 9  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_right_semi_with_filter() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3]),
+            ("b1", &vec![4, 5, 5]),
+            ("c1", &vec![7, 8, 85]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b1", &vec![4, 5, 5, 6]), // 6 does not exist on the left
+            ("c2", &vec![5, 10, 80, 90]),
+        );
+
+        let batches = join_collect_with_lt_filter(left, right, RightSemi).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a2 | b1 | c2 |",
+            "+----+----+----+",
+            "| 20 | 5  | 10 |",
+            "| 30 | 5  | 80 |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_existence() -> Result<()> {
+        let left = build_table_i32_nullable(
+            ("a1", &vec![Some(1), Some(2), Some(3), Some(4)]),
+            ("b1", &vec![None, Some(4), Some(5), Some(7)]), // 7 does not exist on the right
+            ("c1", &vec![Some(7), Some(8), Some(9), Some(10)]),
+        );
+        let right = build_table_i32_nullable(
+            ("a2", &vec![Some(10), Some(20), Some(30), Some(40)]),
+            ("b1", &vec![None, Some(4), Some(5), Some(5)]), // 5 is double on the right
+            ("c2", &vec![Some(70), Some(80), Some(90), Some(100)]),
+        );
+        let on: JoinOn = vec![(
+            Arc::new(Column::new_with_schema("b1", &left.schema())?),
+            Arc::new(Column::new_with_schema("b1", &right.schema())?),
+        )];
+
+        let (_, batches) = join_collect(left, right, on, Existence).await?;
+        let expected = vec![
+            "+----+----+----+--------+",
+            "| a1 | b1 | c1 | exists |",
+            "+----+----+----+--------+",
+            "| 1  |    | 7  | false  |",
+            "| 2  | 4  | 8  | true   |",
+            "| 3  | 5  | 9  | true   |",
+            "| 4  | 7  | 10 | false  |",
+            "+----+----+----+--------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_existence_with_filter() -> Result<()> {
+        let left = build_table(
+            ("a1", &vec![1, 2, 3, 4, 5]),
+            ("b1", &vec![4, 5, 5, 6, 7]), // 7 does not exist on the right
+            ("c1", &vec![7, 8, 85, 95, 9]),
+        );
+        let right = build_table(
+            ("a2", &vec![10, 20, 30, 40]),
+            ("b1", &vec![4, 5, 5, 6]), // 5 is double on the right
+            ("c2", &vec![70, 10, 80, 90]),
+        );
+
+        let batches = join_collect_with_lt_filter(left, right, Existence).await?;
+        let expected = vec![
+            "+----+----+----+--------+",
+            "| a1 | b1 | c1 | exists |",
+            "+----+----+----+--------+",
+            "| 1  | 4  | 7  | true   |",
+            "| 2  | 5  | 8  | true   |",
+            "| 3  | 5  | 85 | false  |",
+            "| 4  | 6  | 95 | false  |",
+            "| 5  | 7  | 9  | false  |",
+            "+----+----+----+--------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn join_null_aware_anti() -> Result<()> {
+        let left = build_table_i32_nullable(
+            ("a1", &vec![Some(1), Some(2), Some(3)]),
+            ("b1", &vec![None, Some(4), Some(7)]),
+            ("c1", &vec![Some(7), Some(8), Some(9)]),
+        );
+        let null_aware_anti_join = |right_keys: Vec<Option<i32>>| {
+            let left = left.clone();
+            async move {
+                let num_rows = right_keys.len();
+                let right = build_table_i32_nullable(
+                    ("a2", &vec![Some(0); num_rows]),
+                    ("b1", &right_keys),
+                    ("c2", &vec![Some(0); num_rows]),
+                );
+                let on: JoinOn = vec![(
+                    Arc::new(Column::new_with_schema("b1", &left.schema())?),
+                    Arc::new(Column::new_with_schema("b1", &right.schema())?),
+                )];
+                join_collect(left, right, on, NullAwareAnti).await
+            }
+        };
+
+        // left rows with null keys are excluded
+        let (_, batches) = null_aware_anti_join(vec![Some(4), Some(5)]).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 3  | 7  | 9  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+
+        // nothing is output if right side contains null keys
+        let (_, batches) = null_aware_anti_join(vec![None, Some(5)]).await?;
+        assert!(batches.iter().all(|batch| batch.num_rows() == 0));
+
+        // all rows are output if right side is empty
+        let (_, batches) = null_aware_anti_join(vec![]).await?;
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 1  |    | 7  |",
+            "| 2  | 4  | 8  |",
+            "| 3  | 7  | 9  |",
+            "+----+----+----+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn join_with_duplicated_column_names() -> Result<()> {
         let left = build_table(
@@ -16,24 +16,32 @@ use std::sync::Arc;
 
 use arrow::{array::ArrayRef, datatypes::FieldRef, record_batch::RecordBatch};
 use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion_ext_commons::{df_execution_err, df_unimplemented_err};
 
 use crate::{
     agg::{create_agg, AggFunction},
     window::{
         processors::{
-            agg_processor::AggProcessor, rank_processor::RankProcessor,
-            row_number_processor::RowNumberProcessor,
+            agg_processor::AggProcessor, lag_processor::LagProcessor,
+            lead_processor::LeadProcessor, nth_value_processor::NthValueProcessor,
+            ntile_processor::NTileProcessor, percent_rank_processor::PercentRankProcessor,
+            rank_processor::RankProcessor, row_number_processor::RowNumberProcessor,
         },
         window_context::WindowContext,
+        window_frame::WindowFrame,
     },
 };
 
 pub mod processors;
 pub mod window_context;
+pub mod window_frame;
+pub mod window_partition_buffer;
+pub mod window_sliding_buffer;
 
 #[derive(Debug, Clone, Copy)]
 pub enum WindowFunction {
     RankLike(WindowRankType),
+    Offset(WindowOffsetType),
     Agg(AggFunction),
 }
 
@@ -42,6 +50,18 @@ pub enum WindowRankType {
     RowNumber,
     Rank,
     DenseRank,
+    PercentRank,
+    CumeDist,
+    NTile(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WindowOffsetType {
+    Lag { offset: i64, ignore_nulls: bool },
+    Lead { offset: i64, ignore_nulls: bool },
+    NthValue { nth: i64, ignore_nulls: bool },
+    FirstValue { ignore_nulls: bool },
+    LastValue { ignore_nulls: bool },
 }
 
 pub trait WindowFunctionProcessor: Send + Sync {
@@ -51,6 +71,20 @@ pub trait WindowFunctionProcessor: Send + Sync {
         context: &WindowContext,
         batch: &RecordBatch,
     ) -> Result<ArrayRef>;
+
+    /// evaluates a chunk of a single partition which is streamed back from
+    /// spills, given the number of rows in the partition. the chunk starts
+    /// from the partition_row_offset-th row, previous rows have been
+    /// processed in previous calls.
+    fn process_partition_chunk(
+        &mut self,
+        _context: &WindowContext,
+        _chunk: &RecordBatch,
+        _partition_num_rows: usize,
+        _partition_row_offset: usize,
+    ) -> Result<ArrayRef> {
+        df_unimplemented_err!("window function cannot process partition chunks")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +92,7 @@ pub struct WindowExpr {
     field: FieldRef,
     func: WindowFunction,
     children: Vec<Arc<dyn PhysicalExpr>>,
+    frame: WindowFrame,
 }
 
 impl WindowExpr {
@@ -65,11 +100,76 @@ impl WindowExpr {
         func: WindowFunction,
         children: Vec<Arc<dyn PhysicalExpr>>,
         field: FieldRef,
+        frame: WindowFrame,
     ) -> Self {
         Self {
             field,
             func,
             children,
+            frame,
+        }
+    }
+
+    /// returns true if the function cannot be evaluated by streaming rows
+    /// and requires complete partitions in each input batch
+    pub fn requires_full_partition(&self) -> bool {
+        match self.func {
+            WindowFunction::RankLike(
+                WindowRankType::PercentRank | WindowRankType::CumeDist | WindowRankType::NTile(_),
+            ) => true,
+            WindowFunction::RankLike(_) => false,
+            WindowFunction::Offset(WindowOffsetType::Lag { offset, .. }) => offset < 0,
+            WindowFunction::Offset(WindowOffsetType::Lead { offset, .. }) => offset > 0,
+            WindowFunction::Offset(_) | WindowFunction::Agg(_) => !self.frame.is_running(),
+        }
+    }
+
+    /// returns true if a partition can be evaluated chunk by chunk with the
+    /// number of rows in the partition, so spilled partitions need not be
+    /// materialized
+    pub fn processes_partition_chunks(&self) -> bool {
+        !self.requires_full_partition()
+            || matches!(
+                self.func,
+                WindowFunction::RankLike(
+                    WindowRankType::PercentRank
+                        | WindowRankType::CumeDist
+                        | WindowRankType::NTile(_)
+                )
+            )
+    }
+
+    /// returns true if the function reads all following peers of each row,
+    /// so a chunk cannot end in the middle of a peer group
+    pub fn reads_following_peers(&self) -> bool {
+        matches!(
+            self.func,
+            WindowFunction::RankLike(WindowRankType::CumeDist)
+        )
+    }
+
+    /// returns the number of preceding and following rows that each output
+    /// row reads, or None if complete partitions are required. functions not
+    /// requiring full partitions are evaluated by streaming and read no
+    /// other rows.
+    pub fn bounded_rows(&self) -> Option<(usize, usize)> {
+        if !self.requires_full_partition() {
+            return Some((0, 0));
+        }
+        match self.func {
+            WindowFunction::RankLike(_) => None,
+            WindowFunction::Offset(WindowOffsetType::Lag {
+                offset,
+                ignore_nulls: false,
+            }) => Some((0, offset.unsigned_abs() as usize)),
+            WindowFunction::Offset(WindowOffsetType::Lead {
+                offset,
+                ignore_nulls: false,
+            }) => Some((0, offset as usize)),
+            WindowFunction::Offset(
+                WindowOffsetType::Lag { .. } | WindowOffsetType::Lead { .. },
+            ) => None,
+            WindowFunction::Offset(_) | WindowFunction::Agg(_) => self.frame.bounded_rows(),
         }
     }
 
@@ -87,10 +187,88 @@ impl WindowExpr {
             WindowFunction::RankLike(WindowRankType::DenseRank) => {
                 Ok(Box::new(RankProcessor::new(true)))
             }
+            WindowFunction::RankLike(WindowRankType::PercentRank) => {
+                Ok(Box::new(PercentRankProcessor::new(false)))
+            }
+            WindowFunction::RankLike(WindowRankType::CumeDist) => {
+                Ok(Box::new(PercentRankProcessor::new(true)))
+            }
+            WindowFunction::RankLike(WindowRankType::NTile(num_buckets)) => {
+                if num_buckets <= 0 {
+                    return df_execution_err!(
+                        "ntile() requires a positive number of buckets, got {num_buckets}"
+                    );
+                }
+                Ok(Box::new(NTileProcessor::new(num_buckets as usize)))
+            }
+            WindowFunction::Offset(WindowOffsetType::Lag {
+                offset,
+                ignore_nulls,
+            }) => Ok(create_lag_or_lead(
+                self.children.clone(),
+                -offset,
+                ignore_nulls,
+            )),
+            WindowFunction::Offset(WindowOffsetType::Lead {
+                offset,
+                ignore_nulls,
+            }) => Ok(create_lag_or_lead(
+                self.children.clone(),
+                offset,
+                ignore_nulls,
+            )),
+            WindowFunction::Offset(WindowOffsetType::NthValue { nth, ignore_nulls }) => {
+                if nth <= 0 {
+                    return df_execution_err!("nth_value() requires a positive offset, got {nth}");
+                }
+                Ok(Box::new(NthValueProcessor::new_nth(
+                    self.children[0].clone(),
+                    nth as usize,
+                    ignore_nulls,
+                    self.frame.clone(),
+                )))
+            }
+            WindowFunction::Offset(WindowOffsetType::FirstValue { ignore_nulls }) => {
+                Ok(Box::new(NthValueProcessor::new_nth(
+                    self.children[0].clone(),
+                    1,
+                    ignore_nulls,
+                    self.frame.clone(),
+                )))
+            }
+            WindowFunction::Offset(WindowOffsetType::LastValue { ignore_nulls }) => {
+                Ok(Box::new(NthValueProcessor::new_last(
+                    self.children[0].clone(),
+                    ignore_nulls,
+                    self.frame.clone(),
+                )))
+            }
             WindowFunction::Agg(agg_func) => {
                 let agg = create_agg(agg_func, &self.children, &context.input_schema)?;
-                Ok(Box::new(AggProcessor::try_new(agg)?))
+                Ok(Box::new(AggProcessor::try_new(agg, self.frame.clone())?))
             }
         }
     }
 }
+
+/// creates processor of lag/lead with a relative offset, negative offsets are
+/// preceding rows (lag) and positive offsets are following rows (lead)
+fn create_lag_or_lead(
+    children: Vec<Arc<dyn PhysicalExpr>>,
+    relative_offset: i64,
+    ignore_nulls: bool,
+) -> Box<dyn WindowFunctionProcessor> {
+    if relative_offset > 0 {
+        Box::new(LeadProcessor::new(
+            children,
+            relative_offset as usize,
+            ignore_nulls,
+        ))
+    } else {
+        Box::new(LagProcessor::new(
+            children,
+            (-relative_offset) as usize,
+            ignore_nulls,
+        ))
+    }
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
 use arrow::{array::ArrayRef, record_batch::RecordBatch};
 use datafusion::common::{Result, ScalarValue};
@@ -23,18 +23,23 @@ use crate::{
         acc::{create_acc_from_initial_value, OwnedAccumStateRow},
         Agg,
     },
-    window::{window_context::WindowContext, WindowFunctionProcessor},
+    window::{
+        window_context::WindowContext,
+        window_frame::{WindowFrame, WindowFrameBound},
+        WindowFunctionProcessor,
+    },
 };
 
 pub struct AggProcessor {
     cur_partition: SlimBytes,
     agg: Arc<dyn Agg>,
+    frame: WindowFrame,
     acc_init: OwnedAccumStateRow,
     acc: OwnedAccumStateRow,
 }
 
 impl AggProcessor {
-    pub fn try_new(agg: Arc<dyn Agg>) -> Result<Self> {
+    pub fn try_new(agg: Arc<dyn Agg>, frame: WindowFrame) -> Result<Self> {
         let (acc, accum_state_val_addrs) = create_acc_from_initial_value(agg.accums_initial())?;
 
         let mut agg = agg;
@@ -46,26 +51,197 @@ impl AggProcessor {
         Ok(Self {
             cur_partition: Default::default(),
             agg,
+            frame,
             acc_init: acc.clone(),
             acc,
         })
     }
-}
-
-impl WindowFunctionProcessor for AggProcessor {
-    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
-        let partition_rows = context.get_partition_rows(batch)?;
-        let mut output = vec![];
 
-        let children_cols: Vec<ArrayRef> = self
-            .agg
+    fn evaluate_children(&self, batch: &RecordBatch) -> Result<Vec<ArrayRef>> {
+        self.agg
             .exprs()
             .iter()
             .map(|expr| {
                 expr.evaluate(batch)
                     .and_then(|v| v.into_array(batch.num_rows()))
             })
-            .collect::<Result<_>>()?;
+            .collect()
+    }
+
+    /// evaluates non-running frames, the batch must contain only complete
+    /// partitions
+    fn process_batch_with_frames(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: &[Range<usize>],
+    ) -> Result<ArrayRef> {
+        let children_cols = self.evaluate_children(batch)?;
+        let frames = self.frame.compute_frames(context, batch, partitions)?;
+        let mut output = Vec::with_capacity(batch.num_rows());
+
+        for partition in partitions {
+            if self.frame.start == WindowFrameBound::UnboundedPreceding {
+                // frame ends are non-decreasing, accumulate rows incrementally
+                let mut acc = self.acc_init.clone();
+                let mut cur_end = partition.start;
+                let mut last_value: Option<ScalarValue> = None;
+                for row_idx in partition.clone() {
+                    let frame_end = frames[row_idx].end;
+                    if frame_end > cur_end || last_value.is_none() {
+                        for i in cur_end..frame_end {
+                            self.partial_update(&mut acc, &children_cols, i)?;
+                        }
+                        cur_end = frame_end;
+                        last_value = Some(self.agg.final_merge(&mut acc.clone().as_mut())?);
+                    }
+                    output.push(last_value.clone().unwrap());
+                }
+            } else if self.frame.end == WindowFrameBound::UnboundedFollowing {
+                // frame starts are non-increasing in reversed order, prepend rows to
+                // the accumulator by merging it into a new one
+                let mut acc = self.acc_init.clone();
+                let mut cur_start = partition.end;
+                let mut last_value: Option<ScalarValue> = None;
+                let mut partition_output = Vec::with_capacity(partition.len());
+                for row_idx in partition.clone().rev() {
+                    let frame_start = frames[row_idx].start;
+                    if frame_start < cur_start || last_value.is_none() {
+                        let mut new_acc = self.acc_init.clone();
+                        for i in frame_start..cur_start {
+                            self.partial_update(&mut new_acc, &children_cols, i)?;
+                        }
+                        self.partial_merge(&mut new_acc, &mut acc)?;
+                        acc = new_acc;
+                        cur_start = frame_start;
+                        last_value = Some(self.agg.final_merge(&mut acc.clone().as_mut())?);
+                    }
+                    partition_output.push(last_value.clone().unwrap());
+                }
+                output.extend(partition_output.into_iter().rev());
+            } else {
+                // sliding frame, frame starts and ends are non-decreasing so rows
+                // are added and removed incrementally
+                let mut sliding = SlidingAcc::new(partition.start, self.acc_init.clone());
+                let mut last: Option<(Range<usize>, ScalarValue)> = None;
+                for row_idx in partition.clone() {
+                    let frame = &frames[row_idx];
+                    match &last {
+                        Some((last_frame, value)) if last_frame == frame => {
+                            output.push(value.clone());
+                        }
+                        _ => {
+                            let value = self.slide_frame(&mut sliding, &children_cols, frame)?;
+                            output.push(value.clone());
+                            last = Some((frame.clone(), value));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Arc::new(ScalarValue::iter_to_array(output.into_iter())?))
+    }
+
+    /// moves the sliding accumulator to the frame and evaluates it
+    fn slide_frame(
+        &self,
+        sliding: &mut SlidingAcc,
+        children_cols: &[ArrayRef],
+        frame: &Range<usize>,
+    ) -> Result<ScalarValue> {
+        if frame.start < sliding.start || frame.end < sliding.end {
+            *sliding = SlidingAcc::new(frame.start, self.acc_init.clone());
+        }
+        for i in sliding.end..frame.end {
+            self.partial_update(&mut sliding.back, children_cols, i)?;
+        }
+        sliding.end = frame.end;
+
+        while sliding.start < frame.start {
+            if sliding.front.is_empty() {
+                // move rows of the back accumulator to the front stack, rows are
+                // prepended by merging so that order-sensitive aggs are kept
+                let mut suffix_acc = self.acc_init.clone();
+                for i in (sliding.mid..sliding.end).rev() {
+                    let mut acc = self.acc_init.clone();
+                    self.partial_update(&mut acc, children_cols, i)?;
+                    self.partial_merge(&mut acc, &mut suffix_acc)?;
+                    sliding.front.push(acc.clone());
+                    suffix_acc = acc;
+                }
+                sliding.mid = sliding.end;
+                sliding.back = self.acc_init.clone();
+            }
+            sliding.front.pop();
+            sliding.start += 1;
+        }
+
+        let mut acc = match sliding.front.last() {
+            Some(front_acc) => front_acc.clone(),
+            None => self.acc_init.clone(),
+        };
+        self.partial_merge(&mut acc, &mut sliding.back.clone())?;
+        self.agg.final_merge(&mut acc.as_mut())
+    }
+
+    fn partial_merge(
+        &self,
+        acc: &mut OwnedAccumStateRow,
+        merging_acc: &mut OwnedAccumStateRow,
+    ) -> Result<()> {
+        self.agg
+            .partial_merge(&mut acc.as_mut(), &mut merging_acc.as_mut())
+            .map_err(|err| err.context("window: agg_processor partial_merge() error"))
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut OwnedAccumStateRow,
+        children_cols: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        self.agg
+            .partial_update(&mut acc.as_mut(), children_cols, row_idx)
+            .map_err(|err| err.context("window: agg_processor partial_update() error"))
+    }
+}
+
+/// accumulator of a sliding frame [start, end) with two stacks. the front
+/// stack holds aggregations of [i, mid) for each i in [start, mid), with the
+/// top being [start, mid), and the back accumulator aggregates [mid, end).
+/// rows leaving the frame are popped from the front stack, which is rebuilt
+/// from the back rows when empty, so each row is updated and merged a
+/// constant number of times.
+struct SlidingAcc {
+    start: usize,
+    mid: usize,
+    end: usize,
+    front: Vec<OwnedAccumStateRow>,
+    back: OwnedAccumStateRow,
+}
+
+impl SlidingAcc {
+    fn new(start: usize, acc_init: OwnedAccumStateRow) -> Self {
+        Self {
+            start,
+            mid: start,
+            end: start,
+            front: vec![],
+            back: acc_init,
+        }
+    }
+}
+
+impl WindowFunctionProcessor for AggProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        if !self.frame.is_running() {
+            let partitions = context.get_partition_ranges(batch)?;
+            return self.process_batch_with_frames(context, batch, &partitions);
+        }
+        let partition_rows = context.get_partition_rows(batch)?;
+        let mut output = vec![];
+
+        let children_cols = self.evaluate_children(batch)?;
 
         for row_idx in 0..batch.num_rows() {
             let same_partition = !context.has_partition() || {
@@ -91,20 +267,15 @@ impl WindowFunctionProcessor for AggProcessor {
 
     fn process_batch_without_partitions(
         &mut self,
-        _: &WindowContext,
+        context: &WindowContext,
         batch: &RecordBatch,
     ) -> Result<ArrayRef> {
+        if !self.frame.is_running() {
+            return self.process_batch_with_frames(context, batch, &[0..batch.num_rows()]);
+        }
         let mut output = vec![];
 
-        let children_cols: Vec<ArrayRef> = self
-            .agg
-            .exprs()
-            .iter()
-            .map(|expr| {
-                expr.evaluate(batch)
-                    .and_then(|v| v.into_array(batch.num_rows()))
-            })
-            .collect::<Result<_>>()?;
+        let children_cols = self.evaluate_children(batch)?;
 
         for row_idx in 0..batch.num_rows() {
             self.agg
@@ -0,0 +1,127 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use arrow::{
+    array::{new_null_array, ArrayRef},
+    record_batch::RecordBatch,
+    row::Rows,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::{cast::cast, slim_bytes::SlimBytes};
+
+use crate::window::{window_context::WindowContext, WindowFunctionProcessor};
+
+/// lag(input, offset, default), keeps the last `offset` values of current
+/// partition so that it works across batches. the history grows as rows
+/// arrive, so a huge offset does not allocate anything upfront.
+pub struct LagProcessor {
+    cur_partition: SlimBytes,
+    children: Vec<Arc<dyn PhysicalExpr>>,
+    offset: usize,
+    ignore_nulls: bool,
+    history: VecDeque<ScalarValue>,
+}
+
+impl LagProcessor {
+    pub fn new(children: Vec<Arc<dyn PhysicalExpr>>, offset: usize, ignore_nulls: bool) -> Self {
+        Self {
+            cur_partition: Default::default(),
+            children,
+            offset,
+            ignore_nulls,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn process_rows(
+        &mut self,
+        batch: &RecordBatch,
+        partition_rows: Option<&Rows>,
+    ) -> Result<ArrayRef> {
+        let (values, defaults) = evaluate_value_and_default(&self.children, batch)?;
+        let mut output = Vec::with_capacity(batch.num_rows());
+
+        for row_idx in 0..batch.num_rows() {
+            if let Some(partition_rows) = partition_rows {
+                let partition_row = partition_rows.row(row_idx);
+                if partition_row.as_ref() != self.cur_partition.as_ref() {
+                    self.cur_partition = partition_row.as_ref().into();
+                    self.history.clear();
+                }
+            }
+
+            let value = ScalarValue::try_from_array(&values, row_idx)?;
+            if self.offset == 0 {
+                output.push(value);
+                continue;
+            }
+            if self.history.len() >= self.offset {
+                output.push(self.history[self.history.len() - self.offset].clone());
+            } else {
+                output.push(ScalarValue::try_from_array(&defaults, row_idx)?);
+            }
+            if !(self.ignore_nulls && value.is_null()) {
+                self.history.push_back(value);
+                if self.history.len() > self.offset {
+                    self.history.pop_front();
+                }
+            }
+        }
+        if output.is_empty() {
+            return Ok(new_null_array(values.data_type(), 0));
+        }
+        Ok(ScalarValue::iter_to_array(output)?)
+    }
+}
+
+impl WindowFunctionProcessor for LagProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partition_rows = context.get_partition_rows(batch)?;
+        self.process_rows(batch, Some(&partition_rows))
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        _: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_rows(batch, None)
+    }
+}
+
+/// evaluates the input value and the default value (null if not specified,
+/// casted to the input type) of lag/lead
+pub fn evaluate_value_and_default(
+    children: &[Arc<dyn PhysicalExpr>],
+    batch: &RecordBatch,
+) -> Result<(ArrayRef, ArrayRef)> {
+    let values = children[0]
+        .evaluate(batch)
+        .and_then(|v| v.into_array(batch.num_rows()))?;
+    let defaults = match children.get(1) {
+        Some(default) => {
+            let defaults = default
+                .evaluate(batch)
+                .and_then(|v| v.into_array(batch.num_rows()))?;
+            cast(&defaults, values.data_type())?
+        }
+        None => new_null_array(values.data_type(), batch.num_rows()),
+    };
+    Ok((values, defaults))
+}
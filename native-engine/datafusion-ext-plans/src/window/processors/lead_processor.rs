@@ -0,0 +1,97 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{array::ArrayRef, compute::interleave, record_batch::RecordBatch};
+use datafusion::{common::Result, physical_expr::PhysicalExpr};
+
+use crate::window::{
+    processors::lag_processor::evaluate_value_and_default, window_context::WindowContext,
+    WindowFunctionProcessor,
+};
+
+/// lead(input, offset, default), requires complete partitions in each batch.
+pub struct LeadProcessor {
+    children: Vec<Arc<dyn PhysicalExpr>>,
+    offset: usize,
+    ignore_nulls: bool,
+}
+
+impl LeadProcessor {
+    pub fn new(children: Vec<Arc<dyn PhysicalExpr>>, offset: usize, ignore_nulls: bool) -> Self {
+        Self {
+            children,
+            offset,
+            ignore_nulls,
+        }
+    }
+
+    fn process_partitions(
+        &self,
+        batch: &RecordBatch,
+        partitions: &[Range<usize>],
+    ) -> Result<ArrayRef> {
+        let (values, defaults) = evaluate_value_and_default(&self.children, batch)?;
+
+        // (0, idx) takes value at idx, (1, idx) takes default value at idx
+        let mut indices = Vec::with_capacity(batch.num_rows());
+        for partition in partitions {
+            if self.offset == 0 {
+                indices.extend(partition.clone().map(|row_idx| (0, row_idx)));
+            } else if !self.ignore_nulls {
+                for row_idx in partition.clone() {
+                    if row_idx + self.offset < partition.end {
+                        indices.push((0, row_idx + self.offset));
+                    } else {
+                        indices.push((1, row_idx));
+                    }
+                }
+            } else {
+                let non_nulls = partition
+                    .clone()
+                    .filter(|&row_idx| values.is_valid(row_idx))
+                    .collect::<Vec<_>>();
+                let mut num_non_nulls_before = 0;
+                for row_idx in partition.clone() {
+                    while num_non_nulls_before < non_nulls.len()
+                        && non_nulls[num_non_nulls_before] <= row_idx
+                    {
+                        num_non_nulls_before += 1;
+                    }
+                    match non_nulls.get(num_non_nulls_before + self.offset - 1) {
+                        Some(&lead_idx) => indices.push((0, lead_idx)),
+                        None => indices.push((1, row_idx)),
+                    }
+                }
+            }
+        }
+        Ok(interleave(&[values.as_ref(), defaults.as_ref()], &indices)?)
+    }
+}
+
+impl WindowFunctionProcessor for LeadProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = context.get_partition_ranges(batch)?;
+        self.process_partitions(batch, &partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        _: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(batch, &[0..batch.num_rows()])
+    }
+}
@@ -13,5 +13,10 @@
 // limitations under the License.
 
 pub mod agg_processor;
+pub mod lag_processor;
+pub mod lead_processor;
+pub mod nth_value_processor;
+pub mod ntile_processor;
+pub mod percent_rank_processor;
 pub mod rank_processor;
 pub mod row_number_processor;
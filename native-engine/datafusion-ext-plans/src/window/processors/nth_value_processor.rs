@@ -0,0 +1,186 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{new_null_array, ArrayRef, UInt32Array},
+    compute::take,
+    record_batch::RecordBatch,
+    row::Rows,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::slim_bytes::SlimBytes;
+
+use crate::window::{
+    window_context::WindowContext, window_frame::WindowFrame, WindowFunctionProcessor,
+};
+
+/// nth_value(input, n), first_value(input) and last_value(input) over a
+/// window frame. running frames are evaluated in streaming, while other
+/// frames require complete partitions in each batch.
+pub struct NthValueProcessor {
+    cur_partition: SlimBytes,
+    child: Arc<dyn PhysicalExpr>,
+    nth: Option<usize>, // None for last_value
+    ignore_nulls: bool,
+    frame: WindowFrame,
+    num_seen: usize,
+    nth_value: Option<ScalarValue>,
+    last_value: Option<ScalarValue>,
+}
+
+impl NthValueProcessor {
+    pub fn new_nth(
+        child: Arc<dyn PhysicalExpr>,
+        nth: usize,
+        ignore_nulls: bool,
+        frame: WindowFrame,
+    ) -> Self {
+        Self::new(child, Some(nth), ignore_nulls, frame)
+    }
+
+    pub fn new_last(child: Arc<dyn PhysicalExpr>, ignore_nulls: bool, frame: WindowFrame) -> Self {
+        Self::new(child, None, ignore_nulls, frame)
+    }
+
+    fn new(
+        child: Arc<dyn PhysicalExpr>,
+        nth: Option<usize>,
+        ignore_nulls: bool,
+        frame: WindowFrame,
+    ) -> Self {
+        Self {
+            cur_partition: Default::default(),
+            child,
+            nth,
+            ignore_nulls,
+            frame,
+            num_seen: 0,
+            nth_value: None,
+            last_value: None,
+        }
+    }
+
+    fn process_running(
+        &mut self,
+        values: &ArrayRef,
+        partition_rows: Option<&Rows>,
+    ) -> Result<ArrayRef> {
+        let null_value = ScalarValue::try_from(values.data_type())?;
+        let mut output = Vec::with_capacity(values.len());
+
+        for row_idx in 0..values.len() {
+            if let Some(partition_rows) = partition_rows {
+                let partition_row = partition_rows.row(row_idx);
+                if partition_row.as_ref() != self.cur_partition.as_ref() {
+                    self.cur_partition = partition_row.as_ref().into();
+                    self.num_seen = 0;
+                    self.nth_value = None;
+                    self.last_value = None;
+                }
+            }
+
+            if !(self.ignore_nulls && values.is_null(row_idx)) {
+                let value = ScalarValue::try_from_array(values, row_idx)?;
+                self.num_seen += 1;
+                if Some(self.num_seen) == self.nth {
+                    self.nth_value = Some(value.clone());
+                }
+                self.last_value = Some(value);
+            }
+            let selected = match self.nth {
+                Some(_) => &self.nth_value,
+                None => &self.last_value,
+            };
+            output.push(selected.clone().unwrap_or_else(|| null_value.clone()));
+        }
+        if output.is_empty() {
+            return Ok(new_null_array(values.data_type(), 0));
+        }
+        Ok(ScalarValue::iter_to_array(output)?)
+    }
+
+    fn process_frames(
+        &self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        values: &ArrayRef,
+        partitions: &[Range<usize>],
+    ) -> Result<ArrayRef> {
+        let frames = self.frame.compute_frames(context, batch, partitions)?;
+        let non_nulls = if self.ignore_nulls {
+            (0..values.len())
+                .filter(|&row_idx| values.is_valid(row_idx))
+                .collect::<Vec<_>>()
+        } else {
+            vec![]
+        };
+
+        let indices = frames
+            .iter()
+            .map(|frame| {
+                let selected_idx = match (self.nth, self.ignore_nulls) {
+                    (Some(nth), false) => Some(frame.start + nth - 1).filter(|&i| i < frame.end),
+                    (Some(nth), true) => {
+                        let i = non_nulls.partition_point(|&p| p < frame.start) + nth - 1;
+                        non_nulls.get(i).copied().filter(|&i| i < frame.end)
+                    }
+                    (None, false) => frame.end.checked_sub(1).filter(|&i| i >= frame.start),
+                    (None, true) => {
+                        let i = non_nulls.partition_point(|&p| p < frame.end);
+                        i.checked_sub(1)
+                            .map(|i| non_nulls[i])
+                            .filter(|&i| i >= frame.start)
+                    }
+                };
+                selected_idx.map(|i| i as u32)
+            })
+            .collect::<UInt32Array>();
+        Ok(take(values, &indices, None)?)
+    }
+}
+
+impl WindowFunctionProcessor for NthValueProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let values = self
+            .child
+            .evaluate(batch)
+            .and_then(|v| v.into_array(batch.num_rows()))?;
+        if !self.frame.is_running() {
+            let partitions = context.get_partition_ranges(batch)?;
+            return self.process_frames(context, batch, &values, &partitions);
+        }
+        let partition_rows = context.get_partition_rows(batch)?;
+        self.process_running(&values, Some(&partition_rows))
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        let values = self
+            .child
+            .evaluate(batch)
+            .and_then(|v| v.into_array(batch.num_rows()))?;
+        if !self.frame.is_running() {
+            return self.process_frames(context, batch, &values, &[0..batch.num_rows()]);
+        }
+        self.process_running(&values, None)
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Int32Builder},
+    record_batch::RecordBatch,
+};
+use datafusion::common::Result;
+
+use crate::window::{window_context::WindowContext, WindowFunctionProcessor};
+
+/// ntile(n), requires complete partitions in each batch, or chunks of a
+/// partition with the number of rows in the partition.
+pub struct NTileProcessor {
+    num_buckets: usize,
+}
+
+impl NTileProcessor {
+    pub fn new(num_buckets: usize) -> Self {
+        Self { num_buckets }
+    }
+
+    fn process_partitions(&self, num_rows: usize, partitions: &[Range<usize>]) -> ArrayRef {
+        let mut builder = Int32Builder::with_capacity(num_rows);
+
+        for partition in partitions {
+            for i in 0..partition.len() {
+                builder.append_value(self.bucket(partition.len(), i));
+            }
+        }
+        Arc::new(builder.finish())
+    }
+
+    /// returns the bucket of the i-th row in a partition of num_rows rows
+    fn bucket(&self, num_rows: usize, i: usize) -> i32 {
+        // the first (num_rows % num_buckets) buckets have one more row, same as spark
+        let bucket_size = num_rows / self.num_buckets;
+        let num_padded_buckets = num_rows % self.num_buckets;
+        let num_padded_rows = num_padded_buckets * (bucket_size + 1);
+
+        let bucket = if i < num_padded_rows {
+            i / (bucket_size + 1)
+        } else {
+            (i - num_padded_rows) / bucket_size + num_padded_buckets
+        };
+        bucket as i32 + 1
+    }
+}
+
+impl WindowFunctionProcessor for NTileProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = context.get_partition_ranges(batch)?;
+        Ok(self.process_partitions(batch.num_rows(), &partitions))
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        _: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        Ok(self.process_partitions(batch.num_rows(), &[0..batch.num_rows()]))
+    }
+
+    fn process_partition_chunk(
+        &mut self,
+        _: &WindowContext,
+        chunk: &RecordBatch,
+        partition_num_rows: usize,
+        partition_row_offset: usize,
+    ) -> Result<ArrayRef> {
+        let mut builder = Int32Builder::with_capacity(chunk.num_rows());
+        for i in partition_row_offset..partition_row_offset + chunk.num_rows() {
+            builder.append_value(self.bucket(partition_num_rows, i));
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
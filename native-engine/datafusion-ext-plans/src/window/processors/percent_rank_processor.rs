@@ -0,0 +1,141 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Builder},
+    record_batch::RecordBatch,
+    row::OwnedRow,
+};
+use datafusion::common::Result;
+
+use crate::window::{window_context::WindowContext, WindowFunctionProcessor};
+
+/// percent_rank() and cume_dist(), requires complete partitions in each batch,
+/// or chunks of a partition with the number of rows in the partition.
+pub struct PercentRankProcessor {
+    is_cume_dist: bool,
+    last_peer: Option<(OwnedRow, usize)>, // last order row and its peer start
+}
+
+impl PercentRankProcessor {
+    pub fn new(is_cume_dist: bool) -> Self {
+        Self {
+            is_cume_dist,
+            last_peer: None,
+        }
+    }
+
+    fn process_partitions(
+        &self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: &[Range<usize>],
+    ) -> Result<ArrayRef> {
+        let order_rows = context.get_order_rows(batch)?;
+        let mut builder = Float64Builder::with_capacity(batch.num_rows());
+
+        for partition in partitions {
+            let num_rows = partition.len() as f64;
+            let mut peer_start = partition.start;
+            while peer_start < partition.end {
+                let mut peer_end = peer_start + 1;
+                while peer_end < partition.end
+                    && order_rows.row(peer_end) == order_rows.row(peer_start)
+                {
+                    peer_end += 1;
+                }
+                let value = if self.is_cume_dist {
+                    (peer_end - partition.start) as f64 / num_rows
+                } else if num_rows > 1.0 {
+                    (peer_start - partition.start) as f64 / (num_rows - 1.0)
+                } else {
+                    0.0
+                };
+                for _ in peer_start..peer_end {
+                    builder.append_value(value);
+                }
+                peer_start = peer_end;
+            }
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+impl WindowFunctionProcessor for PercentRankProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let partitions = context.get_partition_ranges(batch)?;
+        self.process_partitions(context, batch, &partitions)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_partitions(context, batch, &[0..batch.num_rows()])
+    }
+
+    fn process_partition_chunk(
+        &mut self,
+        context: &WindowContext,
+        chunk: &RecordBatch,
+        partition_num_rows: usize,
+        partition_row_offset: usize,
+    ) -> Result<ArrayRef> {
+        let order_rows = context.get_order_rows(chunk)?;
+        let mut builder = Float64Builder::with_capacity(chunk.num_rows());
+        let num_rows = partition_num_rows as f64;
+
+        // the first peer group may start in previous chunks. for cume_dist,
+        // chunks always end at the end of a peer group.
+        let mut peer_start = match self.last_peer.take() {
+            Some((last_row, last_peer_start))
+                if partition_row_offset > 0
+                    && chunk.num_rows() > 0
+                    && last_row.row() == order_rows.row(0) =>
+            {
+                last_peer_start
+            }
+            _ => partition_row_offset,
+        };
+        let mut i = 0;
+        while i < chunk.num_rows() {
+            let mut peer_end = i + 1;
+            while peer_end < chunk.num_rows() && order_rows.row(peer_end) == order_rows.row(i) {
+                peer_end += 1;
+            }
+            if i > 0 {
+                peer_start = partition_row_offset + i;
+            }
+            let value = if self.is_cume_dist {
+                (partition_row_offset + peer_end) as f64 / num_rows
+            } else if num_rows > 1.0 {
+                peer_start as f64 / (num_rows - 1.0)
+            } else {
+                0.0
+            };
+            for _ in i..peer_end {
+                builder.append_value(value);
+            }
+            i = peer_end;
+        }
+        if chunk.num_rows() > 0 {
+            let last_row = order_rows.row(chunk.num_rows() - 1).owned();
+            self.last_peer = Some((last_row, peer_start));
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
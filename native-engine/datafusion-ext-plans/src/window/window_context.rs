@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::{Arc, Mutex as SyncMutex};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex as SyncMutex},
+};
 
 use arrow::{
     datatypes::{Field, FieldRef, Fields, Schema, SchemaRef},
@@ -122,6 +125,43 @@ impl WindowContext {
         !self.partition_schema.fields().is_empty()
     }
 
+    pub fn requires_full_partition(&self) -> bool {
+        self.window_exprs
+            .iter()
+            .any(|expr| expr.requires_full_partition())
+    }
+
+    /// returns the max number of preceding and following rows read by all
+    /// window functions, or None if complete partitions are required
+    pub fn bounded_rows(&self) -> Option<(usize, usize)> {
+        self.window_exprs
+            .iter()
+            .try_fold((0, 0), |(preceding, following), expr| {
+                let (expr_preceding, expr_following) = expr.bounded_rows()?;
+                Some((preceding.max(expr_preceding), following.max(expr_following)))
+            })
+    }
+
+    /// splits a batch containing only complete partitions into row ranges of
+    /// each partition
+    pub fn get_partition_ranges(&self, batch: &RecordBatch) -> Result<Vec<Range<usize>>> {
+        if !self.has_partition() {
+            return Ok(vec![0..batch.num_rows()]);
+        }
+        let partition_rows = self.get_partition_rows(batch)?;
+        let mut ranges = vec![];
+        let mut start = 0;
+        for row_idx in 1..=batch.num_rows() {
+            if row_idx == batch.num_rows()
+                || partition_rows.row(row_idx) != partition_rows.row(start)
+            {
+                ranges.push(start..row_idx);
+                start = row_idx;
+            }
+        }
+        Ok(ranges)
+    }
+
     pub fn get_partition_rows(&self, batch: &RecordBatch) -> Result<Rows> {
         Ok(self
             .partition_row_converter
@@ -0,0 +1,386 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp::Ordering, ops::Range};
+
+use arrow::{
+    array::{as_primitive_array, ArrayRef},
+    datatypes::*,
+    record_batch::RecordBatch,
+};
+use datafusion::common::{Result, ScalarValue};
+use datafusion_ext_commons::{df_execution_err, df_unimplemented_err};
+
+use crate::window::window_context::WindowContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrameUnits {
+    Rows,
+    Range,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowFrameBound {
+    UnboundedPreceding,
+    Preceding(ScalarValue),
+    CurrentRow,
+    Following(ScalarValue),
+    UnboundedFollowing,
+}
+
+impl WindowFrameBound {
+    fn is_offset(&self) -> bool {
+        matches!(self, Self::Preceding(_) | Self::Following(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFrame {
+    pub units: WindowFrameUnits,
+    pub start: WindowFrameBound,
+    pub end: WindowFrameBound,
+}
+
+impl Default for WindowFrame {
+    /// ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+    fn default() -> Self {
+        Self {
+            units: WindowFrameUnits::Rows,
+            start: WindowFrameBound::UnboundedPreceding,
+            end: WindowFrameBound::CurrentRow,
+        }
+    }
+}
+
+impl WindowFrame {
+    pub fn try_new(
+        units: WindowFrameUnits,
+        start: WindowFrameBound,
+        end: WindowFrameBound,
+    ) -> Result<Self> {
+        if start == WindowFrameBound::UnboundedFollowing {
+            return df_execution_err!("window frame start cannot be UNBOUNDED FOLLOWING");
+        }
+        if end == WindowFrameBound::UnboundedPreceding {
+            return df_execution_err!("window frame end cannot be UNBOUNDED PRECEDING");
+        }
+        if units == WindowFrameUnits::Rows {
+            for bound in [&start, &end] {
+                if let WindowFrameBound::Preceding(v) | WindowFrameBound::Following(v) = bound {
+                    rows_offset(v)?;
+                }
+            }
+        }
+        Ok(Self { units, start, end })
+    }
+
+    /// returns true if the frame is a running frame from partition start to
+    /// the current row, which can be evaluated without looking ahead
+    pub fn is_running(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// returns the number of preceding and following rows of a ROWS frame
+    /// bounded at both sides, or None if the frame is unbounded or in RANGE
+    /// units
+    pub fn bounded_rows(&self) -> Option<(usize, usize)> {
+        if self.units != WindowFrameUnits::Rows {
+            return None;
+        }
+        let delta = |bound: &WindowFrameBound| match bound {
+            WindowFrameBound::Preceding(v) => rows_offset(v).ok().map(|v| -v),
+            WindowFrameBound::Following(v) => rows_offset(v).ok(),
+            WindowFrameBound::CurrentRow => Some(0),
+            _ => None,
+        };
+        let (start_delta, end_delta) = (delta(&self.start)?, delta(&self.end)?);
+        let preceding = 0i64
+            .max(start_delta.saturating_neg())
+            .max(end_delta.saturating_neg());
+        let following = 0i64.max(start_delta).max(end_delta);
+        Some((preceding as usize, following as usize))
+    }
+
+    /// computes frame of each row in the batch. the batch must contain only
+    /// complete partitions, and returned frames never cross partitions.
+    pub fn compute_frames(
+        &self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        partitions: &[Range<usize>],
+    ) -> Result<Vec<Range<usize>>> {
+        let mut frames = vec![0..0; batch.num_rows()];
+
+        match self.units {
+            WindowFrameUnits::Rows => {
+                let start_delta = match &self.start {
+                    WindowFrameBound::Preceding(v) => Some(-rows_offset(v)?),
+                    WindowFrameBound::Following(v) => Some(rows_offset(v)?),
+                    WindowFrameBound::CurrentRow => Some(0),
+                    _ => None,
+                };
+                let end_delta = match &self.end {
+                    WindowFrameBound::Preceding(v) => Some(-rows_offset(v)?),
+                    WindowFrameBound::Following(v) => Some(rows_offset(v)?),
+                    WindowFrameBound::CurrentRow => Some(0),
+                    _ => None,
+                };
+                for partition in partitions {
+                    let clamp = |idx: i64| idx.clamp(partition.start as i64, partition.end as i64);
+                    for row_idx in partition.clone() {
+                        let start = start_delta
+                            .map(|delta| clamp(row_idx as i64 + delta) as usize)
+                            .unwrap_or(partition.start);
+                        let end = end_delta
+                            .map(|delta| clamp(row_idx as i64 + delta + 1) as usize)
+                            .unwrap_or(partition.end);
+                        frames[row_idx] = start..end.max(start);
+                    }
+                }
+            }
+            WindowFrameUnits::Range if self.start.is_offset() || self.end.is_offset() => {
+                if context.order_spec.len() != 1 {
+                    return df_execution_err!(
+                        "RANGE window frame with offsets requires exactly one order by expression"
+                    );
+                }
+                let order = &context.order_spec[0];
+                let keys = order
+                    .expr
+                    .evaluate(batch)
+                    .and_then(|v| v.into_array(batch.num_rows()))?;
+                let descending = order.options.descending;
+
+                match keys.data_type() {
+                    DataType::Float32 | DataType::Float64 => {
+                        let keys = arrow::compute::cast(&keys, &DataType::Float64)?;
+                        // adding 0.0 normalizes -0.0 to 0.0 so that they are peers
+                        let keys = as_primitive_array::<Float64Type>(&keys)
+                            .iter()
+                            .map(|v| v.map(|v| (if descending { -v } else { v }) + 0.0))
+                            .collect::<Vec<_>>();
+                        let start = range_delta(&self.start, float_offset)?;
+                        let end = range_delta(&self.end, float_offset)?;
+                        compute_range_frames(&keys, start, end, partitions, &mut frames);
+                    }
+                    dt => {
+                        let keys = int_range_keys(&keys)?;
+                        let keys = keys
+                            .into_iter()
+                            .map(|v| v.map(|v| if descending { -v } else { v }))
+                            .collect::<Vec<_>>();
+                        let start = range_delta(&self.start, |v| int_offset(v, dt))?;
+                        let end = range_delta(&self.end, |v| int_offset(v, dt))?;
+                        compute_range_frames(&keys, start, end, partitions, &mut frames);
+                    }
+                }
+            }
+            WindowFrameUnits::Range => {
+                // frame bounds are either unbounded or the peers of current row
+                let order_rows = context.get_order_rows(batch)?;
+                for partition in partitions {
+                    let mut peer_start = partition.start;
+                    while peer_start < partition.end {
+                        let mut peer_end = peer_start + 1;
+                        while peer_end < partition.end
+                            && order_rows.row(peer_end) == order_rows.row(peer_start)
+                        {
+                            peer_end += 1;
+                        }
+                        for row_idx in peer_start..peer_end {
+                            let start = match self.start {
+                                WindowFrameBound::CurrentRow => peer_start,
+                                _ => partition.start,
+                            };
+                            let end = match self.end {
+                                WindowFrameBound::CurrentRow => peer_end,
+                                _ => partition.end,
+                            };
+                            frames[row_idx] = start..end;
+                        }
+                        peer_start = peer_end;
+                    }
+                }
+            }
+        }
+        Ok(frames)
+    }
+}
+
+fn rows_offset(value: &ScalarValue) -> Result<i64> {
+    Ok(match value {
+        ScalarValue::Int8(Some(v)) => *v as i64,
+        ScalarValue::Int16(Some(v)) => *v as i64,
+        ScalarValue::Int32(Some(v)) => *v as i64,
+        ScalarValue::Int64(Some(v)) => *v,
+        ScalarValue::UInt8(Some(v)) => *v as i64,
+        ScalarValue::UInt16(Some(v)) => *v as i64,
+        ScalarValue::UInt32(Some(v)) => *v as i64,
+        ScalarValue::UInt64(Some(v)) => *v as i64,
+        other => return df_execution_err!("invalid ROWS window frame offset: {other:?}"),
+    })
+}
+
+fn float_offset(value: &ScalarValue) -> Result<f64> {
+    Ok(match value {
+        ScalarValue::Float32(Some(v)) => *v as f64,
+        ScalarValue::Float64(Some(v)) => *v,
+        ScalarValue::Decimal128(Some(v), _, scale) => *v as f64 / 10f64.powi(*scale as i32),
+        other => rows_offset(other)? as f64,
+    })
+}
+
+fn int_offset(value: &ScalarValue, key_type: &DataType) -> Result<i128> {
+    let key_scale = match key_type {
+        DataType::Decimal128(_, scale) => *scale as i32,
+        _ => 0,
+    };
+    let (v, scale) = match value {
+        ScalarValue::Decimal128(Some(v), _, scale) => (*v, *scale as i32),
+        other => (rows_offset(other)? as i128, 0),
+    };
+    Ok(match key_scale.cmp(&scale) {
+        Ordering::Equal => v,
+        Ordering::Greater => v * 10i128.pow((key_scale - scale) as u32),
+        Ordering::Less => v / 10i128.pow((scale - key_scale) as u32),
+    })
+}
+
+fn int_range_keys(keys: &ArrayRef) -> Result<Vec<Option<i128>>> {
+    macro_rules! collect_keys {
+        ($arrowty:ident) => {{
+            as_primitive_array::<$arrowty>(keys)
+                .iter()
+                .map(|v| v.map(|v| v as i128))
+                .collect()
+        }};
+    }
+    Ok(match keys.data_type() {
+        DataType::Int8 => collect_keys!(Int8Type),
+        DataType::Int16 => collect_keys!(Int16Type),
+        DataType::Int32 => collect_keys!(Int32Type),
+        DataType::Int64 => collect_keys!(Int64Type),
+        DataType::UInt8 => collect_keys!(UInt8Type),
+        DataType::UInt16 => collect_keys!(UInt16Type),
+        DataType::UInt32 => collect_keys!(UInt32Type),
+        DataType::UInt64 => collect_keys!(UInt64Type),
+        DataType::Date32 => collect_keys!(Date32Type),
+        DataType::Date64 => collect_keys!(Date64Type),
+        // interval offsets are converted to microseconds
+        DataType::Timestamp(TimeUnit::Microsecond, _) => collect_keys!(TimestampMicrosecondType),
+        DataType::Duration(TimeUnit::Microsecond) => collect_keys!(DurationMicrosecondType),
+        DataType::Decimal128(..) => as_primitive_array::<Decimal128Type>(keys).iter().collect(),
+        other => {
+            return df_unimplemented_err!("unsupported RANGE window frame order type: {other}");
+        }
+    })
+}
+
+fn range_delta<T: RangeKey>(
+    bound: &WindowFrameBound,
+    offset: impl Fn(&ScalarValue) -> Result<T>,
+) -> Result<Option<T>> {
+    Ok(match bound {
+        WindowFrameBound::Preceding(v) => Some(offset(v)?.neg()),
+        WindowFrameBound::Following(v) => Some(offset(v)?),
+        WindowFrameBound::CurrentRow => Some(T::default()),
+        _ => None,
+    })
+}
+
+/// computes RANGE frames with value offsets. keys must be in ascending order
+/// within each partition (descending keys are negated by the caller), and
+/// rows with null keys are only peers of each other.
+fn compute_range_frames<T: RangeKey>(
+    keys: &[Option<T>],
+    start_delta: Option<T>,
+    end_delta: Option<T>,
+    partitions: &[Range<usize>],
+    frames: &mut [Range<usize>],
+) {
+    for partition in partitions {
+        let partition_keys = &keys[partition.clone()];
+        let num_nulls = partition_keys.iter().filter(|key| key.is_none()).count();
+        let (nulls, non_nulls) = if partition_keys.first().map(|k| k.is_none()) == Some(true) {
+            let nulls_end = partition.start + num_nulls;
+            (partition.start..nulls_end, nulls_end..partition.end)
+        } else {
+            let nulls_start = partition.end - num_nulls;
+            (nulls_start..partition.end, partition.start..nulls_start)
+        };
+        let non_null_keys = &keys[non_nulls.clone()];
+
+        for row_idx in partition.clone() {
+            let (start, end) = match keys[row_idx] {
+                None => (
+                    start_delta.map(|_| nulls.start).unwrap_or(partition.start),
+                    end_delta.map(|_| nulls.end).unwrap_or(partition.end),
+                ),
+                Some(key) => (
+                    start_delta
+                        .map(|delta| {
+                            let bound = key.add(delta);
+                            non_nulls.start
+                                + non_null_keys
+                                    .partition_point(|k| k.unwrap().cmp_key(&bound).is_lt())
+                        })
+                        .unwrap_or(partition.start),
+                    end_delta
+                        .map(|delta| {
+                            let bound = key.add(delta);
+                            non_nulls.start
+                                + non_null_keys
+                                    .partition_point(|k| k.unwrap().cmp_key(&bound).is_le())
+                        })
+                        .unwrap_or(partition.end),
+                ),
+            };
+            frames[row_idx] = start..end.max(start);
+        }
+    }
+}
+
+trait RangeKey: Copy + Default {
+    fn add(self, delta: Self) -> Self;
+    fn neg(self) -> Self;
+    fn cmp_key(&self, other: &Self) -> Ordering;
+}
+
+impl RangeKey for i128 {
+    fn add(self, delta: Self) -> Self {
+        self.saturating_add(delta)
+    }
+
+    fn neg(self) -> Self {
+        self.saturating_neg()
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl RangeKey for f64 {
+    fn add(self, delta: Self) -> Self {
+        self + delta + 0.0
+    }
+
+    fn neg(self) -> Self {
+        -self
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
+}
@@ -0,0 +1,501 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    io::{Cursor, Write},
+    sync::{Arc, Weak},
+};
+
+use arrow::{compute::concat_batches, datatypes::SchemaRef, record_batch::RecordBatch};
+use async_trait::async_trait;
+use datafusion::{common::Result, physical_plan::metrics::ExecutionPlanMetricsSet};
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    io::{read_one_batch, write_one_batch},
+    slim_bytes::SlimBytes,
+};
+use futures::lock::Mutex;
+
+use crate::{
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{try_new_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
+    window::window_context::WindowContext,
+};
+
+/// buffers input batches until their partitions are complete, so that window
+/// functions requiring full partitions can be evaluated. buffered batches are
+/// spilled under memory pressure and replayed in the original order.
+///
+/// a spilled partition is returned as spills and streamed back in chunks by
+/// the caller. it is only materialized if some window function cannot be
+/// evaluated by chunks, in which case spilled batches are replayed one by one
+/// and counted in used memory, until the next insertion (when the replayed
+/// partitions have been processed) or until the buffer is dropped.
+pub struct WindowPartitionBuffer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    context: Arc<WindowContext>,
+    input_schema: SchemaRef,
+    data: Mutex<BufferedData>,
+    spill_metrics: SpillMetrics,
+}
+
+#[derive(Default)]
+struct BufferedData {
+    last_partition: SlimBytes,
+    spills: Vec<Box<dyn Spill>>,
+    staging_batches: Vec<RecordBatch>,
+    staging_mem_used: usize,
+    replayed_mem_used: usize,
+    num_buffered_rows: usize,
+}
+
+/// complete partitions returned by the buffer
+pub enum ReplayedPartitions {
+    /// partitions buffered in memory, concatenated into one batch
+    InMemory(RecordBatch),
+
+    /// a spilled partition, followed by the other partitions completed by the
+    /// same input batch
+    Spilled(SpilledPartition, Option<RecordBatch>),
+}
+
+/// a complete partition of which the leading rows are spilled. rows are read
+/// from spills and then from in-memory batches, the number of rows is counted
+/// while buffering so the partition need not be read twice.
+pub struct SpilledPartition {
+    spills: Vec<Box<dyn Spill>>,
+    batches: Vec<RecordBatch>,
+    num_rows: usize,
+}
+
+impl SpilledPartition {
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn spills(&self) -> &[Box<dyn Spill>] {
+        &self.spills
+    }
+
+    pub fn batches(&self) -> &[RecordBatch] {
+        &self.batches
+    }
+}
+
+impl WindowPartitionBuffer {
+    pub fn try_new(
+        context: Arc<WindowContext>,
+        input_schema: SchemaRef,
+        partition: usize,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Arc<Self>> {
+        let buffer = Arc::new(Self {
+            name: format!("WindowPartitionBuffer[partition={}]", partition),
+            mem_consumer_info: None,
+            context,
+            input_schema,
+            data: Default::default(),
+            spill_metrics: SpillMetrics::new(metrics, partition),
+        });
+        MemManager::register_consumer(buffer.clone(), true);
+        Ok(buffer)
+    }
+
+    /// inserts an input batch, returns all partitions completed by this batch
+    /// (None if no partition is completed)
+    pub async fn insert_batch(&self, batch: RecordBatch) -> Result<Option<ReplayedPartitions>> {
+        if batch.num_rows() == 0 {
+            return Ok(None);
+        }
+        let mut data = self.data.lock().await;
+        data.replayed_mem_used = 0;
+
+        // find the start of the last partition in this batch, and the end of
+        // the buffered partition
+        let split = if self.context.has_partition() {
+            let partition_rows = self.context.get_partition_rows(&batch)?;
+            let last_row = partition_rows.row(batch.num_rows() - 1);
+            let mut split_idx = batch.num_rows() - 1;
+            while split_idx > 0 && partition_rows.row(split_idx - 1) == last_row {
+                split_idx -= 1;
+            }
+            let mut buffered_end = 0;
+            while buffered_end < split_idx
+                && partition_rows.row(buffered_end).as_ref() == data.last_partition.as_ref()
+            {
+                buffered_end += 1;
+            }
+            if split_idx > 0 || last_row.as_ref() != data.last_partition.as_ref() {
+                data.last_partition = last_row.as_ref().into();
+                Some((split_idx, buffered_end))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let (spills, mut batches, num_spilled_rows, remaining) = match split {
+            Some((split_idx, buffered_end)) => {
+                let (spills, mut batches, num_buffered_rows) = data.take_buffered();
+                data.add_batch(batch.slice(split_idx, batch.num_rows() - split_idx));
+
+                // a spilled partition is followed by its tail rows in this
+                // batch, other complete partitions are kept in memory
+                let tail_end = if spills.is_empty() {
+                    split_idx
+                } else {
+                    buffered_end
+                };
+                let remaining =
+                    (split_idx > tail_end).then(|| batch.slice(tail_end, split_idx - tail_end));
+                let tail = (tail_end > 0).then(|| batch.slice(0, tail_end));
+                for complete_batch in tail.iter().chain(&remaining) {
+                    data.replayed_mem_used += complete_batch.get_array_mem_size();
+                }
+                batches.extend(tail);
+                (spills, batches, num_buffered_rows + tail_end, remaining)
+            }
+            None => {
+                data.add_batch(batch);
+                (vec![], vec![], 0, None)
+            }
+        };
+        let mem_used = data.mem_used();
+        drop(data);
+        self.update_mem_used(mem_used).await?;
+
+        if spills.is_empty() {
+            batches.extend(remaining);
+            if batches.is_empty() {
+                return Ok(None);
+            }
+            let batch = self.concat_replayed(batches).await?;
+            return Ok(Some(ReplayedPartitions::InMemory(batch)));
+        }
+        let spilled = SpilledPartition {
+            spills,
+            batches,
+            num_rows: num_spilled_rows,
+        };
+        Ok(Some(ReplayedPartitions::Spilled(spilled, remaining)))
+    }
+
+    /// takes all remaining buffered batches after input is exhausted
+    pub async fn finish(&self) -> Result<Option<ReplayedPartitions>> {
+        let mut data = self.data.lock().await;
+        let (spills, staging_batches, num_buffered_rows) = data.take_buffered();
+        drop(data);
+
+        // remaining partition is no longer spillable and is counted as
+        // unspillable memory until the buffer is dropped
+        self.set_spillable(false);
+        if staging_batches.is_empty() && spills.is_empty() {
+            self.update_mem_used(0).await?;
+            return Ok(None);
+        }
+        if spills.is_empty() {
+            return Ok(Some(ReplayedPartitions::InMemory(
+                self.concat_replayed(staging_batches).await?,
+            )));
+        }
+        let spilled = SpilledPartition {
+            spills,
+            batches: staging_batches,
+            num_rows: num_buffered_rows,
+        };
+        Ok(Some(ReplayedPartitions::Spilled(spilled, None)))
+    }
+
+    /// materializes replayed partitions into one batch, for window functions
+    /// that cannot be evaluated by chunks
+    pub async fn materialize(&self, replayed: ReplayedPartitions) -> Result<RecordBatch> {
+        match replayed {
+            ReplayedPartitions::InMemory(batch) => Ok(batch),
+            ReplayedPartitions::Spilled(spilled, remaining) => {
+                let mut batches = self.replay_spills(spilled.spills).await?;
+                batches.extend(spilled.batches);
+                batches.extend(remaining);
+                self.concat_replayed(batches).await
+            }
+        }
+    }
+
+    /// reads spilled batches one by one, updating used memory after each
+    /// batch so that memory pressure is handled while replaying
+    async fn replay_spills(&self, spills: Vec<Box<dyn Spill>>) -> Result<Vec<RecordBatch>> {
+        let mut batches = vec![];
+        for spill in spills {
+            let mut reader = spill.get_compressed_reader();
+            while let Some(batch) = read_one_batch(&mut reader, &self.input_schema)? {
+                self.add_replayed_mem_used(batch.get_array_mem_size() as isize)
+                    .await?;
+                batches.push(batch);
+            }
+        }
+        Ok(batches)
+    }
+
+    async fn concat_replayed(&self, batches: Vec<RecordBatch>) -> Result<RecordBatch> {
+        if batches.len() == 1 {
+            return Ok(batches.into_iter().next().unwrap());
+        }
+        let batches_mem_size = batches
+            .iter()
+            .map(|batch| batch.get_array_mem_size())
+            .sum::<usize>();
+        let concatenated = concat_batches(&self.input_schema, &batches)?;
+        drop(batches);
+        self.add_replayed_mem_used(
+            concatenated.get_array_mem_size() as isize - batches_mem_size as isize,
+        )
+        .await?;
+        Ok(concatenated)
+    }
+
+    async fn add_replayed_mem_used(&self, diff: isize) -> Result<()> {
+        let mut data = self.data.lock().await;
+        data.replayed_mem_used = data.replayed_mem_used.saturating_add_signed(diff);
+        let mem_used = data.mem_used();
+        drop(data);
+        self.update_mem_used(mem_used).await
+    }
+}
+
+impl BufferedData {
+    fn mem_used(&self) -> usize {
+        self.staging_mem_used + self.replayed_mem_used
+    }
+
+    fn add_batch(&mut self, batch: RecordBatch) {
+        self.staging_mem_used += batch.get_array_mem_size();
+        self.num_buffered_rows += batch.num_rows();
+        self.staging_batches.push(batch);
+    }
+
+    /// takes spills, in-memory batches and number of rows of the buffered
+    /// partition. the in-memory batches are counted as replayed from now on
+    fn take_buffered(&mut self) -> (Vec<Box<dyn Spill>>, Vec<RecordBatch>, usize) {
+        self.replayed_mem_used += std::mem::take(&mut self.staging_mem_used);
+        (
+            std::mem::take(&mut self.spills),
+            std::mem::take(&mut self.staging_batches),
+            std::mem::take(&mut self.num_buffered_rows),
+        )
+    }
+
+    fn try_into_spill(&mut self, spill: &mut Box<dyn Spill>) -> Result<()> {
+        let mut writer = spill.get_compressed_writer();
+        for batch in std::mem::take(&mut self.staging_batches) {
+            let mut buf = vec![];
+            write_one_batch(&batch, &mut Cursor::new(&mut buf))?;
+            writer.write_all(&buf)?;
+        }
+        self.staging_mem_used = 0;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MemConsumer for WindowPartitionBuffer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<()> {
+        let mut data = self.data.lock().await;
+        if !data.staging_batches.is_empty() {
+            let mut spill = try_new_spill(&self.spill_metrics)?;
+            data.try_into_spill(&mut spill)?;
+            data.spills.push(spill);
+        }
+        let mem_used = data.mem_used();
+        drop(data);
+
+        self.update_mem_used(mem_used).await?;
+        Ok(())
+    }
+}
+
+impl Drop for WindowPartitionBuffer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+    use datafusion::{
+        physical_expr::{expressions::Column, PhysicalExpr},
+        physical_plan::metrics::ExecutionPlanMetricsSet,
+    };
+
+    use crate::{
+        agg::AggFunction,
+        memmgr::{MemConsumer, MemManager},
+        window::{
+            window_context::WindowContext,
+            window_frame::WindowFrame,
+            window_partition_buffer::{ReplayedPartitions, WindowPartitionBuffer},
+            WindowExpr, WindowFunction,
+        },
+    };
+
+    fn build_batch(a: Vec<i32>, b: Vec<i32>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(Int32Array::from(a)), Arc::new(Int32Array::from(b))],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_window_partition_buffer_spill() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let schema = build_batch(vec![], vec![]).schema();
+        let window_expr = WindowExpr::new(
+            WindowFunction::Agg(AggFunction::Sum),
+            vec![Arc::new(Column::new("b", 1))],
+            Arc::new(Field::new("b_sum", DataType::Int64, true)),
+            WindowFrame::default(),
+        );
+        let context = Arc::new(WindowContext::try_new(
+            schema.clone(),
+            vec![window_expr],
+            vec![Arc::new(Column::new("a", 0)) as Arc<dyn PhysicalExpr>],
+            vec![],
+        )?);
+        let metrics = ExecutionPlanMetricsSet::new();
+        let buffer = WindowPartitionBuffer::try_new(context, schema, 0, &metrics)?;
+
+        // partition 1 spans all batches and is spilled twice
+        let complete = buffer
+            .insert_batch(build_batch(vec![1, 1], vec![1, 2]))
+            .await?;
+        assert!(complete.is_none());
+        buffer.spill().await?;
+        let complete = buffer
+            .insert_batch(build_batch(vec![1, 1], vec![3, 4]))
+            .await?;
+        assert!(complete.is_none());
+        buffer.spill().await?;
+
+        // partition 2 completes partition 1, which is returned as spills with
+        // its tail row in this batch
+        let complete = buffer
+            .insert_batch(build_batch(vec![1, 2], vec![5, 6]))
+            .await?
+            .expect("partition 1 should be complete");
+        let ReplayedPartitions::Spilled(spilled, remaining) = complete else {
+            panic!("partition 1 should be spilled");
+        };
+        assert_eq!(spilled.num_rows(), 5);
+        assert_eq!(spilled.spills().len(), 2);
+        assert_eq!(spilled.batches().len(), 1);
+        assert!(remaining.is_none());
+
+        // materialized partitions are counted in used memory
+        let complete = buffer
+            .materialize(ReplayedPartitions::Spilled(spilled, remaining))
+            .await?;
+        assert_eq!(
+            complete
+                .column(1)
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec(),
+            vec![1, 2, 3, 4, 5],
+        );
+        assert!(buffer.mem_used_percent() > 0.0);
+
+        let Some(ReplayedPartitions::InMemory(remaining)) = buffer.finish().await? else {
+            panic!("partition 2 remains in memory");
+        };
+        assert_eq!(remaining.num_rows(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_partition_buffer_spilled_partition_followed_by_others(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let schema = build_batch(vec![], vec![]).schema();
+        let window_expr = WindowExpr::new(
+            WindowFunction::Agg(AggFunction::Sum),
+            vec![Arc::new(Column::new("b", 1))],
+            Arc::new(Field::new("b_sum", DataType::Int64, true)),
+            WindowFrame::default(),
+        );
+        let context = Arc::new(WindowContext::try_new(
+            schema.clone(),
+            vec![window_expr],
+            vec![Arc::new(Column::new("a", 0)) as Arc<dyn PhysicalExpr>],
+            vec![],
+        )?);
+        let metrics = ExecutionPlanMetricsSet::new();
+        let buffer = WindowPartitionBuffer::try_new(context, schema, 0, &metrics)?;
+
+        assert!(buffer
+            .insert_batch(build_batch(vec![1, 1], vec![1, 2]))
+            .await?
+            .is_none());
+        buffer.spill().await?;
+
+        // partitions 2 and 3 are complete in memory, partition 4 is buffered
+        let complete = buffer
+            .insert_batch(build_batch(vec![1, 2, 2, 3, 4], vec![3, 4, 5, 6, 7]))
+            .await?
+            .expect("partitions 1-3 should be complete");
+        let ReplayedPartitions::Spilled(spilled, remaining) = complete else {
+            panic!("partition 1 should be spilled");
+        };
+        assert_eq!(spilled.num_rows(), 3);
+        let tail = &spilled.batches()[0];
+        assert_eq!(
+            tail.column(1).as_primitive::<Int32Type>().values().to_vec(),
+            vec![3],
+        );
+        let remaining = remaining.expect("partitions 2 and 3 should be complete");
+        assert_eq!(
+            remaining
+                .column(1)
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec(),
+            vec![4, 5, 6],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use arrow::{compute::concat_batches, datatypes::SchemaRef, record_batch::RecordBatch};
+use datafusion::common::Result;
+
+/// buffers input rows for window functions reading a bounded number of
+/// preceding and following rows. rows are emitted as soon as their following
+/// rows arrive, so complete partitions are never buffered and memory usage is
+/// bounded by the frame size.
+pub struct WindowSlidingBuffer {
+    input_schema: SchemaRef,
+    preceding: usize,
+    following: usize,
+    batches: Vec<RecordBatch>,
+    num_rows: usize,
+    num_emitted: usize,
+}
+
+impl WindowSlidingBuffer {
+    pub fn new(input_schema: SchemaRef, preceding: usize, following: usize) -> Self {
+        Self {
+            input_schema,
+            preceding,
+            following,
+            batches: vec![],
+            num_rows: 0,
+            num_emitted: 0,
+        }
+    }
+
+    /// inserts an input batch, returns all buffered rows and the range of rows
+    /// to be emitted. rows out of the range are only read as preceding or
+    /// following rows.
+    pub fn insert_batch(
+        &mut self,
+        batch: RecordBatch,
+    ) -> Result<Option<(RecordBatch, Range<usize>)>> {
+        self.num_rows += batch.num_rows();
+        self.batches.push(batch);
+        self.emit(self.num_rows.saturating_sub(self.following))
+    }
+
+    /// emits all remaining rows after input is exhausted
+    pub fn finish(&mut self) -> Result<Option<(RecordBatch, Range<usize>)>> {
+        self.emit(self.num_rows)
+    }
+
+    fn emit(&mut self, emit_end: usize) -> Result<Option<(RecordBatch, Range<usize>)>> {
+        if emit_end <= self.num_emitted {
+            return Ok(None);
+        }
+        let batch = concat_batches(&self.input_schema, &std::mem::take(&mut self.batches))?;
+        let emit_range = self.num_emitted..emit_end;
+
+        // keep preceding rows of the next emitted row
+        let num_dropped = emit_end.saturating_sub(self.preceding);
+        self.batches
+            .push(batch.slice(num_dropped, self.num_rows - num_dropped));
+        self.num_rows -= num_dropped;
+        self.num_emitted = emit_end - num_dropped;
+        Ok(Some((batch, emit_range)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
+
+    use crate::window::window_sliding_buffer::WindowSlidingBuffer;
+
+    fn build_batch(a: Vec<i32>) -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("a", DataType::Int32, false)]);
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int32Array::from(a))]).unwrap()
+    }
+
+    fn values(batch: &RecordBatch) -> Vec<i32> {
+        batch
+            .column(0)
+            .as_primitive::<Int32Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_window_sliding_buffer() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = build_batch(vec![]).schema();
+        let mut buffer = WindowSlidingBuffer::new(schema, 1, 2);
+
+        // not enough following rows
+        assert!(buffer.insert_batch(build_batch(vec![1, 2]))?.is_none());
+
+        let (batch, emit_range) = buffer.insert_batch(build_batch(vec![3]))?.unwrap();
+        assert_eq!(values(&batch), vec![1, 2, 3]);
+        assert_eq!(emit_range, 0..1);
+
+        // rows before the preceding row of next emitted row are dropped
+        let (batch, emit_range) = buffer.insert_batch(build_batch(vec![4, 5]))?.unwrap();
+        assert_eq!(values(&batch), vec![1, 2, 3, 4, 5]);
+        assert_eq!(emit_range, 1..3);
+
+        let (batch, emit_range) = buffer.finish()?.unwrap();
+        assert_eq!(values(&batch), vec![3, 4, 5]);
+        assert_eq!(emit_range, 1..3);
+        assert!(buffer.finish()?.is_none());
+        Ok(())
+    }
+}
@@ -12,31 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{any::Any, fmt::Formatter, sync::Arc};
+use std::{any::Any, fmt::Formatter, ops::Range, sync::Arc};
 
 use arrow::{
     array::{Array, ArrayRef},
+    compute::concat_batches,
     datatypes::SchemaRef,
     error::ArrowError,
     record_batch::{RecordBatch, RecordBatchOptions},
+    row::OwnedRow,
 };
 use datafusion::{
     common::{Result, Statistics},
     execution::context::TaskContext,
     physical_expr::PhysicalSortExpr,
     physical_plan::{
-        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet, ScopedTimerGuard},
         stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PhysicalExpr,
         SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::{cast::cast, streams::coalesce_stream::CoalesceInput};
+use datafusion_ext_commons::{
+    batch_size, cast::cast, io::read_one_batch, streams::coalesce_stream::CoalesceInput,
+};
 use futures::{stream::once, StreamExt, TryFutureExt, TryStreamExt};
 
 use crate::{
-    common::output::TaskOutputter,
-    window::{window_context::WindowContext, WindowExpr, WindowFunctionProcessor},
+    common::output::{TaskOutputter, WrappedRecordBatchSender},
+    window::{
+        window_context::WindowContext,
+        window_partition_buffer::{ReplayedPartitions, WindowPartitionBuffer},
+        window_sliding_buffer::WindowSlidingBuffer,
+        WindowExpr, WindowFunctionProcessor,
+    },
 };
 
 #[derive(Debug)]
@@ -111,17 +120,38 @@ impl ExecutionPlan for WindowExec {
         partition: usize,
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        // at this moment only supports ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
         let input = self.input.execute(partition, context.clone())?;
         let coalesced = context.coalesce_with_default_batch_size(
             input,
             &BaselineMetrics::new(&self.metrics, partition),
         )?;
 
+        let buffer = if !self.context.requires_full_partition() {
+            None
+        } else {
+            match self.context.bounded_rows() {
+                Some((preceding, following))
+                    if preceding.saturating_add(following) <= MAX_SLIDING_ROWS =>
+                {
+                    Some(WindowBuffer::Sliding(WindowSlidingBuffer::new(
+                        self.input.schema(),
+                        preceding,
+                        following,
+                    )))
+                }
+                _ => Some(WindowBuffer::Partition(WindowPartitionBuffer::try_new(
+                    self.context.clone(),
+                    self.input.schema(),
+                    partition,
+                    &self.metrics,
+                )?)),
+            }
+        };
         let stream = execute_window(
             coalesced,
             context.clone(),
             self.context.clone(),
+            buffer,
             BaselineMetrics::new(&self.metrics, partition),
         )
         .map_err(|e| ArrowError::ExternalError(Box::new(e)));
@@ -141,10 +171,22 @@ impl ExecutionPlan for WindowExec {
     }
 }
 
+/// max number of preceding and following rows buffered by the sliding buffer,
+/// functions reading more rows are evaluated with complete partitions, which
+/// are buffered with memory management and spilling
+const MAX_SLIDING_ROWS: usize = 1 << 16;
+
+/// buffers input rows for functions that cannot be evaluated by streaming
+enum WindowBuffer {
+    Partition(Arc<WindowPartitionBuffer>),
+    Sliding(WindowSlidingBuffer),
+}
+
 async fn execute_window(
     mut input: SendableRecordBatchStream,
     task_context: Arc<TaskContext>,
     context: Arc<WindowContext>,
+    mut buffer: Option<WindowBuffer>,
     metrics: BaselineMetrics,
 ) -> Result<SendableRecordBatchStream> {
     let mut processors: Vec<Box<dyn WindowFunctionProcessor>> = context
@@ -160,42 +202,314 @@ async fn execute_window(
             let elapsed_time = metrics.elapsed_compute().clone();
             let mut timer = elapsed_time.timer();
 
-            let window_cols: Vec<ArrayRef> = processors
-                .iter_mut()
-                .map(|processor| {
-                    if context.partition_spec.is_empty() {
-                        processor.process_batch_without_partitions(&context, &batch)
-                    } else {
-                        processor.process_batch(&context, &batch)
+            // frames looking ahead need complete partitions or enough following
+            // rows in each processed batch, so input batches are buffered
+            let buffered = match &mut buffer {
+                Some(WindowBuffer::Partition(partition_buffer)) => {
+                    if let Some(replayed) = partition_buffer.insert_batch(batch).await? {
+                        process_replayed_partitions(
+                            &context,
+                            &mut processors,
+                            partition_buffer,
+                            replayed,
+                            &sender,
+                            &metrics,
+                            &mut timer,
+                        )
+                        .await?;
                     }
-                })
-                .collect::<Result<_>>()?;
+                    continue;
+                }
+                Some(WindowBuffer::Sliding(sliding_buffer)) => sliding_buffer
+                    .insert_batch(batch)?
+                    .map(|(batch, emit_range)| (emit_range, batch)),
+                None => Some((0..batch.num_rows(), batch)),
+            };
+            let Some((emit_range, batch)) = buffered else {
+                continue;
+            };
+            if emit_range.is_empty() {
+                continue;
+            }
 
-            let outputs: Vec<ArrayRef> = batch
-                .columns()
-                .iter()
-                .chain(&window_cols)
-                .zip(context.output_schema.fields())
-                .map(|(array, field)| {
-                    if array.data_type() != field.data_type() {
-                        return cast(&array, field.data_type());
-                    }
-                    Ok(array.clone())
-                })
-                .collect::<Result<_>>()?;
-            let output_batch = RecordBatch::try_new_with_options(
-                context.output_schema.clone(),
-                outputs,
-                &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
-            )?;
-
-            metrics.record_output(output_batch.num_rows());
-            sender.send(Ok(output_batch), Some(&mut timer)).await;
+            let output_batch = process_window_batch(&context, &mut processors, &batch, emit_range)?;
+            send_output_batch(&sender, &metrics, &mut timer, output_batch).await;
+        }
+
+        // process remaining buffered rows
+        let elapsed_time = metrics.elapsed_compute().clone();
+        let mut timer = elapsed_time.timer();
+        let buffered = match &mut buffer {
+            Some(WindowBuffer::Partition(partition_buffer)) => {
+                if let Some(replayed) = partition_buffer.finish().await? {
+                    process_replayed_partitions(
+                        &context,
+                        &mut processors,
+                        partition_buffer,
+                        replayed,
+                        &sender,
+                        &metrics,
+                        &mut timer,
+                    )
+                    .await?;
+                }
+                None
+            }
+            Some(WindowBuffer::Sliding(sliding_buffer)) => sliding_buffer
+                .finish()?
+                .map(|(batch, emit_range)| (emit_range, batch)),
+            None => None,
+        };
+        if let Some((emit_range, batch)) = buffered {
+            if !emit_range.is_empty() {
+                let output_batch =
+                    process_window_batch(&context, &mut processors, &batch, emit_range)?;
+                send_output_batch(&sender, &metrics, &mut timer, output_batch).await;
+            }
         }
         Ok(())
     })
 }
 
+/// processes partitions replayed from the partition buffer. a spilled
+/// partition is streamed back from spills chunk by chunk if all window
+/// functions can be evaluated by chunks, otherwise it is materialized.
+async fn process_replayed_partitions(
+    context: &WindowContext,
+    processors: &mut [Box<dyn WindowFunctionProcessor>],
+    partition_buffer: &WindowPartitionBuffer,
+    replayed: ReplayedPartitions,
+    sender: &WrappedRecordBatchSender,
+    metrics: &BaselineMetrics,
+    timer: &mut ScopedTimerGuard<'_>,
+) -> Result<()> {
+    let processes_chunks = context
+        .window_exprs
+        .iter()
+        .all(|expr| expr.processes_partition_chunks());
+    let (spilled, remaining) = match replayed {
+        ReplayedPartitions::Spilled(spilled, remaining) if processes_chunks => (spilled, remaining),
+        replayed => {
+            let batch = partition_buffer.materialize(replayed).await?;
+            let output_batch =
+                process_window_batch(context, processors, &batch, 0..batch.num_rows())?;
+            send_output_batch(sender, metrics, timer, output_batch).await;
+            return Ok(());
+        }
+    };
+
+    let mut chunk_processor = PartitionChunkProcessor::new(context, spilled.num_rows());
+    for spill in spilled.spills() {
+        let mut reader = spill.get_compressed_reader();
+        while let Some(chunk) = read_one_batch(&mut reader, &context.input_schema)? {
+            if let Some(output_batch) = chunk_processor.process_chunk(processors, chunk)? {
+                send_output_batch(sender, metrics, timer, output_batch).await;
+            }
+        }
+    }
+    for chunk in spilled.batches() {
+        if let Some(output_batch) = chunk_processor.process_chunk(processors, chunk.clone())? {
+            send_output_batch(sender, metrics, timer, output_batch).await;
+        }
+    }
+    if let Some(output_batch) = chunk_processor.finish(processors)? {
+        send_output_batch(sender, metrics, timer, output_batch).await;
+    }
+    drop(spilled);
+
+    if let Some(batch) = remaining {
+        let output_batch = process_window_batch(context, processors, &batch, 0..batch.num_rows())?;
+        send_output_batch(sender, metrics, timer, output_batch).await;
+    }
+    Ok(())
+}
+
+/// evaluates window functions on chunks of a single spilled partition. if a
+/// function reads all following peers, the last peer group of each chunk is
+/// carried to the next chunk, so only that group is buffered in memory.
+struct PartitionChunkProcessor<'a> {
+    context: &'a WindowContext,
+    partition_num_rows: usize,
+    num_processed_rows: usize,
+    carried_batches: Vec<RecordBatch>,
+    carried_row: Option<OwnedRow>,
+}
+
+impl<'a> PartitionChunkProcessor<'a> {
+    fn new(context: &'a WindowContext, partition_num_rows: usize) -> Self {
+        Self {
+            context,
+            partition_num_rows,
+            num_processed_rows: 0,
+            carried_batches: vec![],
+            carried_row: None,
+        }
+    }
+
+    fn process_chunk(
+        &mut self,
+        processors: &mut [Box<dyn WindowFunctionProcessor>],
+        chunk: RecordBatch,
+    ) -> Result<Option<RecordBatch>> {
+        let reads_following_peers = self
+            .context
+            .window_exprs
+            .iter()
+            .any(|expr| expr.reads_following_peers());
+        if !reads_following_peers || chunk.num_rows() == 0 {
+            return self.process_rows(processors, chunk);
+        }
+
+        // find the start of the last peer group in this chunk
+        let order_rows = self.context.get_order_rows(&chunk)?;
+        let last_row = order_rows.row(chunk.num_rows() - 1);
+        let mut peer_start = chunk.num_rows() - 1;
+        while peer_start > 0 && order_rows.row(peer_start - 1) == last_row {
+            peer_start -= 1;
+        }
+
+        // the carried peer group continues through the whole chunk
+        if peer_start == 0
+            && self
+                .carried_row
+                .as_ref()
+                .is_some_and(|carried_row| carried_row.row() == last_row)
+        {
+            self.carried_batches.push(chunk);
+            return Ok(None);
+        }
+        let mut batches = std::mem::take(&mut self.carried_batches);
+        batches.push(chunk.slice(0, peer_start));
+        self.carried_batches
+            .push(chunk.slice(peer_start, chunk.num_rows() - peer_start));
+        self.carried_row = Some(last_row.owned());
+
+        let batch = concat_batches(&self.context.input_schema, &batches)?;
+        self.process_rows(processors, batch)
+    }
+
+    fn finish(
+        &mut self,
+        processors: &mut [Box<dyn WindowFunctionProcessor>],
+    ) -> Result<Option<RecordBatch>> {
+        let batches = std::mem::take(&mut self.carried_batches);
+        self.carried_row = None;
+        let batch = concat_batches(&self.context.input_schema, &batches)?;
+        self.process_rows(processors, batch)
+    }
+
+    fn process_rows(
+        &mut self,
+        processors: &mut [Box<dyn WindowFunctionProcessor>],
+        batch: RecordBatch,
+    ) -> Result<Option<RecordBatch>> {
+        if batch.num_rows() == 0 {
+            return Ok(None);
+        }
+        let context = self.context;
+        let window_cols: Vec<ArrayRef> = processors
+            .iter_mut()
+            .zip(&context.window_exprs)
+            .map(|(processor, expr)| {
+                if expr.requires_full_partition() {
+                    processor.process_partition_chunk(
+                        context,
+                        &batch,
+                        self.partition_num_rows,
+                        self.num_processed_rows,
+                    )
+                } else if context.partition_spec.is_empty() {
+                    processor.process_batch_without_partitions(context, &batch)
+                } else {
+                    processor.process_batch(context, &batch)
+                }
+            })
+            .collect::<Result<_>>()?;
+        self.num_processed_rows += batch.num_rows();
+        build_output_batch(context, &batch, window_cols).map(Some)
+    }
+}
+
+/// evaluates window functions and outputs rows in emit_range. functions
+/// requiring full partitions read all rows in the batch, while streaming
+/// functions only read the emitted rows.
+fn process_window_batch(
+    context: &WindowContext,
+    processors: &mut [Box<dyn WindowFunctionProcessor>],
+    batch: &RecordBatch,
+    emit_range: Range<usize>,
+) -> Result<RecordBatch> {
+    let emit_batch = batch.slice(emit_range.start, emit_range.len());
+    let window_cols: Vec<ArrayRef> = processors
+        .iter_mut()
+        .zip(&context.window_exprs)
+        .map(|(processor, expr)| {
+            let mut process = |batch: &RecordBatch| {
+                if context.partition_spec.is_empty() {
+                    processor.process_batch_without_partitions(context, batch)
+                } else {
+                    processor.process_batch(context, batch)
+                }
+            };
+            if expr.requires_full_partition() {
+                let window_col = process(batch)?;
+                return Ok(window_col.slice(emit_range.start, emit_range.len()));
+            }
+            process(&emit_batch)
+        })
+        .collect::<Result<_>>()?;
+    build_output_batch(context, &emit_batch, window_cols)
+}
+
+/// builds output batch from input rows and the evaluated window columns
+fn build_output_batch(
+    context: &WindowContext,
+    emit_batch: &RecordBatch,
+    window_cols: Vec<ArrayRef>,
+) -> Result<RecordBatch> {
+    let outputs: Vec<ArrayRef> = emit_batch
+        .columns()
+        .iter()
+        .chain(&window_cols)
+        .zip(context.output_schema.fields())
+        .map(|(array, field)| {
+            if array.data_type() != field.data_type() {
+                return cast(&array, field.data_type());
+            }
+            Ok(array.clone())
+        })
+        .collect::<Result<_>>()?;
+    Ok(RecordBatch::try_new_with_options(
+        context.output_schema.clone(),
+        outputs,
+        &RecordBatchOptions::new().with_row_count(Some(emit_batch.num_rows())),
+    )?)
+}
+
+async fn send_output_batch(
+    sender: &WrappedRecordBatchSender,
+    metrics: &BaselineMetrics,
+    timer: &mut ScopedTimerGuard<'_>,
+    output_batch: RecordBatch,
+) {
+    for output_batch in split_output_batch(output_batch) {
+        metrics.record_output(output_batch.num_rows());
+        sender.send(Ok(output_batch), Some(&mut *timer)).await;
+    }
+}
+
+/// splits output of concatenated partitions into batches of default batch size
+fn split_output_batch(batch: RecordBatch) -> Vec<RecordBatch> {
+    let batch_size = batch_size();
+    if batch.num_rows() <= batch_size {
+        return vec![batch];
+    }
+    (0..batch.num_rows())
+        .step_by(batch_size)
+        .map(|offset| batch.slice(offset, batch_size.min(batch.num_rows() - offset)))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -203,15 +517,24 @@ mod test {
     use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
     use datafusion::{
         assert_batches_eq,
-        physical_expr::{expressions::Column, PhysicalSortExpr},
+        common::ScalarValue,
+        physical_expr::{
+            expressions::{Column, Literal},
+            PhysicalExpr, PhysicalSortExpr,
+        },
         physical_plan::{memory::MemoryExec, ExecutionPlan},
         prelude::SessionContext,
     };
 
     use crate::{
         agg::AggFunction,
-        window::{WindowExpr, WindowFunction, WindowRankType},
-        window_exec::WindowExec,
+        memmgr::MemManager,
+        window::{
+            window_context::WindowContext,
+            window_frame::{WindowFrame, WindowFrameBound, WindowFrameUnits},
+            WindowExpr, WindowFunction, WindowOffsetType, WindowRankType,
+        },
+        window_exec::{process_window_batch, PartitionChunkProcessor, WindowExec},
     };
 
     fn build_table_i32(
@@ -264,21 +587,25 @@ mod test {
                     WindowFunction::RankLike(WindowRankType::RowNumber),
                     vec![],
                     Arc::new(Field::new("b1_row_number", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::RankLike(WindowRankType::Rank),
                     vec![],
                     Arc::new(Field::new("b1_rank", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::RankLike(WindowRankType::DenseRank),
                     vec![],
                     Arc::new(Field::new("b1_dense_rank", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::Agg(AggFunction::Sum),
                     vec![Arc::new(Column::new("b1", 1))],
                     Arc::new(Field::new("b1_sum", DataType::Int64, false)),
+                    WindowFrame::default(),
                 ),
             ],
             vec![Arc::new(Column::new("a1", 0))],
@@ -317,21 +644,25 @@ mod test {
                     WindowFunction::RankLike(WindowRankType::RowNumber),
                     vec![],
                     Arc::new(Field::new("b1_row_number", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::RankLike(WindowRankType::Rank),
                     vec![],
                     Arc::new(Field::new("b1_rank", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::RankLike(WindowRankType::DenseRank),
                     vec![],
                     Arc::new(Field::new("b1_dense_rank", DataType::Int32, false)),
+                    WindowFrame::default(),
                 ),
                 WindowExpr::new(
                     WindowFunction::Agg(AggFunction::Sum),
                     vec![Arc::new(Column::new("b1", 1))],
                     Arc::new(Field::new("b1_sum", DataType::Int64, false)),
+                    WindowFrame::default(),
                 ),
             ],
             vec![],
@@ -358,4 +689,657 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_window_frames() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input = build_table(
+            ("a1", &vec![1, 1, 1, 1, 2, 3, 3]),
+            ("b1", &vec![1, 2, 2, 5, 4, 1, 1]),
+            ("c1", &vec![1, 2, 3, 4, 5, 6, 7]),
+        );
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_sum_rows", DataType::Int64, false)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_sum_range", DataType::Int64, false)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Range,
+                        WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                        WindowFrameBound::CurrentRow,
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Count),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_count_peers", DataType::Int64, false)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Range,
+                        WindowFrameBound::UnboundedPreceding,
+                        WindowFrameBound::CurrentRow,
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Max),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_max_following", DataType::Int32, true)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                        WindowFrameBound::UnboundedFollowing,
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_total", DataType::Int64, false)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::UnboundedPreceding,
+                        WindowFrameBound::UnboundedFollowing,
+                    )?,
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+-------------+--------------+----------------+------------------+----------+",
+            "| a1 | b1 | c1 | c1_sum_rows | c1_sum_range | c1_count_peers | c1_max_following | c1_total |",
+            "+----+----+----+-------------+--------------+----------------+------------------+----------+",
+            "| 1  | 1  | 1  | 3           | 1            | 1              | 4                | 10       |",
+            "| 1  | 2  | 2  | 6           | 6            | 3              | 4                | 10       |",
+            "| 1  | 2  | 3  | 9           | 6            | 3              | 4                | 10       |",
+            "| 1  | 5  | 4  | 7           | 4            | 4              |                  | 10       |",
+            "| 2  | 4  | 5  | 5           | 5            | 1              |                  | 5        |",
+            "| 3  | 1  | 6  | 13          | 13           | 2              | 7                | 13       |",
+            "| 3  | 1  | 7  | 13          | 13           | 2              |                  | 13       |",
+            "+----+----+----+-------------+--------------+----------------+------------------+----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_sliding_agg_frames() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let a1 = [vec![1; 12], vec![2; 8]].concat();
+        let b1 = vec![1, 1, 2, 3, 5, 5, 5, 6, 8, 9, 9, 10, 1, 2, 2, 4, 7, 7, 8, 9];
+        let c1 = (0..20).map(|i| i * 7 % 11).collect::<Vec<i32>>();
+        let input = build_table(("a1", &a1), ("b1", &b1), ("c1", &c1));
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_sum", DataType::Int64, true)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::Preceding(ScalarValue::Int32(Some(2))),
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Max),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_max", DataType::Int32, true)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Range,
+                        WindowFrameBound::Preceding(ScalarValue::Int32(Some(2))),
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(2))),
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::First),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_first", DataType::Int32, true)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(3))),
+                    )?,
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let output = arrow::compute::concat_batches(&window.schema(), &batches)?;
+
+        // compare with frames aggregated row by row
+        let partitions = [0..12, 12..20];
+        let mut expected_sum = vec![];
+        let mut expected_max = vec![];
+        let mut expected_first = vec![];
+        for partition in partitions {
+            for i in partition.clone() {
+                let rows = i.saturating_sub(2).max(partition.start)..(i + 2).min(partition.end);
+                expected_sum.push(rows.map(|j| c1[j] as i64).sum::<i64>());
+                let peers = partition.clone().filter(|&j| (b1[j] - b1[i]).abs() <= 2);
+                expected_max.push(peers.map(|j| c1[j]).max());
+                expected_first.push(Some(i + 1).filter(|&j| j < partition.end).map(|j| c1[j]));
+            }
+        }
+        assert_eq!(
+            output
+                .column(3)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            expected_sum,
+        );
+        assert_eq!(
+            output
+                .column(4)
+                .as_primitive::<Int32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            expected_max,
+        );
+        assert_eq!(
+            output
+                .column(5)
+                .as_primitive::<Int32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            expected_first,
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_bool_and_or_frames() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // spark rewrites bool_and/bool_or (and every/any/some) to min/max, which
+        // are evaluated over boolean frames by the agg processor
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a1", DataType::Int32, false),
+            Field::new("b1", DataType::Int32, false),
+            Field::new("c1", DataType::Boolean, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 2, 2, 3])),
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 1, 2, 1])),
+                Arc::new(BooleanArray::from(vec![
+                    Some(true),
+                    Some(true),
+                    Some(false),
+                    Some(true),
+                    None,
+                    Some(true),
+                    None,
+                ])),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+        let frame = || {
+            WindowFrame::try_new(
+                WindowFrameUnits::Rows,
+                WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+            )
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Min),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_bool_and", DataType::Boolean, true)),
+                    frame()?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Max),
+                    vec![Arc::new(Column::new("c1", 2))],
+                    Arc::new(Field::new("c1_bool_or", DataType::Boolean, true)),
+                    frame()?,
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+-------+-------------+------------+",
+            "| a1 | b1 | c1    | c1_bool_and | c1_bool_or |",
+            "+----+----+-------+-------------+------------+",
+            "| 1  | 1  | true  | true        | true       |",
+            "| 1  | 2  | true  | false       | true       |",
+            "| 1  | 3  | false | false       | true       |",
+            "| 1  | 4  | true  | false       | true       |",
+            "| 2  | 1  |       | true        | true       |",
+            "| 2  | 2  | true  | true        | true       |",
+            "| 3  | 1  |       |             |            |",
+            "+----+----+-------+-------------+------------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_offset_functions() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a1", DataType::Int32, false),
+            Field::new("b1", DataType::Int32, false),
+            Field::new("c1", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 2, 2])),
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 1, 2])),
+                Arc::new(Int32Array::from(vec![
+                    Some(10),
+                    None,
+                    Some(30),
+                    None,
+                    Some(50),
+                    Some(60),
+                ])),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+
+        let c1: Arc<dyn PhysicalExpr> = Arc::new(Column::new("c1", 2));
+        let offset_expr = |func: WindowOffsetType, name: &str, frame: WindowFrame| {
+            WindowExpr::new(
+                WindowFunction::Offset(func),
+                vec![c1.clone()],
+                Arc::new(Field::new(name, DataType::Int32, true)),
+                frame,
+            )
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::Offset(WindowOffsetType::Lag {
+                        offset: 1,
+                        ignore_nulls: false,
+                    }),
+                    vec![
+                        c1.clone(),
+                        Arc::new(Literal::new(ScalarValue::Int32(Some(-1)))),
+                    ],
+                    Arc::new(Field::new("c1_lag", DataType::Int32, true)),
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::Lead {
+                        offset: 1,
+                        ignore_nulls: false,
+                    },
+                    "c1_lead",
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::Lag {
+                        offset: 1,
+                        ignore_nulls: true,
+                    },
+                    "c1_lag_in",
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::Lead {
+                        offset: 1,
+                        ignore_nulls: true,
+                    },
+                    "c1_lead_in",
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::FirstValue {
+                        ignore_nulls: false,
+                    },
+                    "c1_first",
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::LastValue { ignore_nulls: true },
+                    "c1_last_in",
+                    WindowFrame::default(),
+                ),
+                offset_expr(
+                    WindowOffsetType::NthValue {
+                        nth: 2,
+                        ignore_nulls: true,
+                    },
+                    "c1_nth_in",
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::UnboundedPreceding,
+                        WindowFrameBound::UnboundedFollowing,
+                    )?,
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+--------+---------+-----------+------------+----------+------------+-----------+",
+            "| a1 | b1 | c1 | c1_lag | c1_lead | c1_lag_in | c1_lead_in | c1_first | c1_last_in | c1_nth_in |",
+            "+----+----+----+--------+---------+-----------+------------+----------+------------+-----------+",
+            "| 1  | 1  | 10 | -1     |         |           | 30         | 10       | 10         | 30        |",
+            "| 1  | 2  |    | 10     | 30      | 10        | 30         | 10       | 10         | 30        |",
+            "| 1  | 3  | 30 |        |         | 10        |            | 10       | 30         | 30        |",
+            "| 1  | 4  |    | 30     |         | 30        |            | 10       | 30         | 30        |",
+            "| 2  | 1  | 50 | -1     | 60      |           | 60         | 50       | 50         | 60        |",
+            "| 2  | 2  | 60 | 50     |         | 50        |            | 50       | 60         | 60        |",
+            "+----+----+----+--------+---------+-----------+------------+----------+------------+-----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_lag_huge_offset() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a1", DataType::Int32, false),
+            Field::new("b1", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 2])),
+                Arc::new(Int32Array::from(vec![1, 2, 1])),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+
+        // history of lag grows with the input rows, not with the offset
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![WindowExpr::new(
+                WindowFunction::Offset(WindowOffsetType::Lag {
+                    offset: i32::MAX as i64,
+                    ignore_nulls: false,
+                }),
+                vec![
+                    Arc::new(Column::new("b1", 1)),
+                    Arc::new(Literal::new(ScalarValue::Int32(Some(-1)))),
+                ],
+                Arc::new(Field::new("b1_lag", DataType::Int32, true)),
+                WindowFrame::default(),
+            )],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+--------+",
+            "| a1 | b1 | b1_lag |",
+            "+----+----+--------+",
+            "| 1  | 1  | -1     |",
+            "| 1  | 2  | -1     |",
+            "| 2  | 1  | -1     |",
+            "+----+----+--------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_sliding_frames_without_partition() -> Result<(), Box<dyn std::error::Error>>
+    {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // frames bounded by rows are evaluated without buffering the whole input
+        let input = build_table(
+            ("a1", &vec![1, 1, 1, 1, 1]),
+            ("b1", &vec![1, 2, 3, 4, 5]),
+            ("c1", &vec![0, 0, 0, 0, 0]),
+        );
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::RowNumber),
+                    vec![],
+                    Arc::new(Field::new("b1_row_number", DataType::Int32, false)),
+                    WindowFrame::default(),
+                ),
+                WindowExpr::new(
+                    WindowFunction::Agg(AggFunction::Sum),
+                    vec![Arc::new(Column::new("b1", 1))],
+                    Arc::new(Field::new("b1_sum", DataType::Int64, false)),
+                    WindowFrame::try_new(
+                        WindowFrameUnits::Rows,
+                        WindowFrameBound::Preceding(ScalarValue::Int32(Some(1))),
+                        WindowFrameBound::Following(ScalarValue::Int32(Some(1))),
+                    )?,
+                ),
+                WindowExpr::new(
+                    WindowFunction::Offset(WindowOffsetType::Lead {
+                        offset: 1,
+                        ignore_nulls: false,
+                    }),
+                    vec![
+                        Arc::new(Column::new("b1", 1)),
+                        Arc::new(Literal::new(ScalarValue::Int32(Some(-1)))),
+                    ],
+                    Arc::new(Field::new("b1_lead", DataType::Int32, true)),
+                    WindowFrame::default(),
+                ),
+            ],
+            vec![],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        assert_eq!(window.context.bounded_rows(), Some((1, 1)));
+
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+---------------+--------+---------+",
+            "| a1 | b1 | c1 | b1_row_number | b1_sum | b1_lead |",
+            "+----+----+----+---------------+--------+---------+",
+            "| 1  | 1  | 0  | 1             | 3      | 2       |",
+            "| 1  | 2  | 0  | 2             | 6      | 3       |",
+            "| 1  | 3  | 0  | 3             | 9      | 4       |",
+            "| 1  | 4  | 0  | 4             | 12     | 5       |",
+            "| 1  | 5  | 0  | 5             | 9      | -1      |",
+            "+----+----+----+---------------+--------+---------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_distribution_functions() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input = build_table(
+            ("a1", &vec![1, 1, 1, 1, 2, 3, 3]),
+            ("b1", &vec![1, 2, 2, 3, 4, 1, 1]),
+            ("c1", &vec![0, 0, 0, 0, 0, 0, 0]),
+        );
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::PercentRank),
+                    vec![],
+                    Arc::new(Field::new("b1_percent_rank", DataType::Float64, false)),
+                    WindowFrame::default(),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::CumeDist),
+                    vec![],
+                    Arc::new(Field::new("b1_cume_dist", DataType::Float64, false)),
+                    WindowFrame::default(),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::NTile(3)),
+                    vec![],
+                    Arc::new(Field::new("b1_ntile", DataType::Int32, false)),
+                    WindowFrame::default(),
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+--------------------+--------------+----------+",
+            "| a1 | b1 | c1 | b1_percent_rank    | b1_cume_dist | b1_ntile |",
+            "+----+----+----+--------------------+--------------+----------+",
+            "| 1  | 1  | 0  | 0.0                | 0.25         | 1        |",
+            "| 1  | 2  | 0  | 0.3333333333333333 | 0.75         | 1        |",
+            "| 1  | 2  | 0  | 0.3333333333333333 | 0.75         | 2        |",
+            "| 1  | 3  | 0  | 1.0                | 1.0          | 3        |",
+            "| 2  | 4  | 0  | 0.0                | 1.0          | 1        |",
+            "| 3  | 1  | 0  | 0.0                | 1.0          | 1        |",
+            "| 3  | 1  | 0  | 0.0                | 1.0          | 2        |",
+            "+----+----+----+--------------------+--------------+----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_partition_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let batch = build_table_i32(
+            ("a1", &vec![1, 1, 1, 1, 1, 1, 1, 1]),
+            ("b1", &vec![1, 2, 2, 2, 3, 3, 4, 5]),
+            ("c1", &vec![0, 1, 2, 3, 4, 5, 6, 7]),
+        );
+        let window_exprs = vec![
+            WindowExpr::new(
+                WindowFunction::RankLike(WindowRankType::PercentRank),
+                vec![],
+                Arc::new(Field::new("b1_percent_rank", DataType::Float64, false)),
+                WindowFrame::default(),
+            ),
+            WindowExpr::new(
+                WindowFunction::RankLike(WindowRankType::CumeDist),
+                vec![],
+                Arc::new(Field::new("b1_cume_dist", DataType::Float64, false)),
+                WindowFrame::default(),
+            ),
+            WindowExpr::new(
+                WindowFunction::RankLike(WindowRankType::NTile(3)),
+                vec![],
+                Arc::new(Field::new("b1_ntile", DataType::Int32, false)),
+                WindowFrame::default(),
+            ),
+            WindowExpr::new(
+                WindowFunction::RankLike(WindowRankType::Rank),
+                vec![],
+                Arc::new(Field::new("b1_rank", DataType::Int32, false)),
+                WindowFrame::default(),
+            ),
+        ];
+        let context = Arc::new(WindowContext::try_new(
+            batch.schema(),
+            window_exprs,
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let create_processors = || {
+            context
+                .window_exprs
+                .iter()
+                .map(|expr| expr.create_processor(&context))
+                .collect::<datafusion::common::Result<Vec<_>>>()
+        };
+
+        // peer groups span chunks and a chunk is entirely in a peer group
+        let mut processors = create_processors()?;
+        let expected = process_window_batch(&context, &mut processors, &batch, 0..8)?;
+
+        let mut processors = create_processors()?;
+        let mut chunk_processor = PartitionChunkProcessor::new(&context, batch.num_rows());
+        let mut output_batches = vec![];
+        for (offset, len) in [(0, 2), (2, 1), (3, 2), (5, 1), (6, 2)] {
+            let chunk = batch.slice(offset, len);
+            output_batches.extend(chunk_processor.process_chunk(&mut processors, chunk)?);
+        }
+        output_batches.extend(chunk_processor.finish(&mut processors)?);
+
+        let expected_lines = vec![
+            "+----+----+----+---------------------+--------------+----------+---------+",
+            "| a1 | b1 | c1 | b1_percent_rank     | b1_cume_dist | b1_ntile | b1_rank |",
+            "+----+----+----+---------------------+--------------+----------+---------+",
+            "| 1  | 1  | 0  | 0.0                 | 0.125        | 1        | 1       |",
+            "| 1  | 2  | 1  | 0.14285714285714285 | 0.5          | 1        | 2       |",
+            "| 1  | 2  | 2  | 0.14285714285714285 | 0.5          | 1        | 2       |",
+            "| 1  | 2  | 3  | 0.14285714285714285 | 0.5          | 2        | 2       |",
+            "| 1  | 3  | 4  | 0.5714285714285714  | 0.75         | 2        | 5       |",
+            "| 1  | 3  | 5  | 0.5714285714285714  | 0.75         | 2        | 5       |",
+            "| 1  | 4  | 6  | 0.8571428571428571  | 0.875        | 3        | 7       |",
+            "| 1  | 5  | 7  | 1.0                 | 1.0          | 3        | 8       |",
+            "+----+----+----+---------------------+--------------+----------+---------+",
+        ];
+        assert_batches_eq!(expected_lines, &[expected]);
+        assert_batches_eq!(expected_lines, &output_batches);
+        Ok(())
+    }
 }
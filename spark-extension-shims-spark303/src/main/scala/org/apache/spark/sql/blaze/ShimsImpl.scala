@@ -30,6 +30,7 @@ import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleHandle
 import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
 import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
 import org.apache.spark.sql.catalyst.expressions.Like
@@ -59,6 +60,7 @@ import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeExec
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeLike
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.adaptive.BroadcastQueryStageExec
+import org.apache.spark.sql.internal.SQLConf
 import org.apache.spark.sql.types.IntegerType
 import org.apache.spark.sql.types.StringType
 import org.apache.spark.storage.BlockManagerId
@@ -101,6 +103,8 @@ import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
 import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsExec
 import org.apache.spark.sql.execution.blaze.plan.NativeSortBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortExec
+import org.apache.spark.sql.execution.blaze.plan.NativeShuffledHashJoinBase
+import org.apache.spark.sql.execution.blaze.plan.NativeShuffledHashJoinExec
 import org.apache.spark.sql.execution.blaze.plan.NativeSortMergeJoinBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortMergeJoinExec
 import org.apache.spark.sql.execution.blaze.plan.NativeTakeOrderedBase
@@ -110,6 +114,7 @@ import org.apache.spark.sql.execution.blaze.plan.NativeUnionExec
 import org.apache.spark.sql.execution.blaze.plan.NativeWindowBase
 import org.apache.spark.sql.execution.blaze.plan.NativeWindowExec
 import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.execution.joins.BroadcastHashJoinExec
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
 import org.apache.spark.sql.types.DataType
 import org.apache.spark.sql.SparkSession
@@ -153,7 +158,9 @@ class ShimsImpl extends Shims with Logging {
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
       joinType: JoinType,
-      condition: Option[Expression]): NativeBroadcastJoinBase =
+      broadcastSide: pb.JoinSide,
+      condition: Option[Expression],
+      isNullAwareAntiJoin: Boolean): NativeBroadcastJoinBase =
     NativeBroadcastJoinExec(
       left,
       right,
@@ -161,7 +168,9 @@ class ShimsImpl extends Shims with Logging {
       leftKeys,
       rightKeys,
       joinType,
-      condition)
+      broadcastSide,
+      condition,
+      isNullAwareAntiJoin)
 
   override def createNativeBroadcastNestedLoopJoinExec(
       left: SparkPlan,
@@ -179,6 +188,16 @@ class ShimsImpl extends Shims with Logging {
       condition: Option[Expression]): NativeSortMergeJoinBase =
     NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType, condition)
 
+  override def createNativeShuffledHashJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      leftKeys: Seq[Expression],
+      rightKeys: Seq[Expression],
+      joinType: JoinType,
+      buildSide: pb.JoinSide,
+      condition: Option[Expression]): NativeShuffledHashJoinBase =
+    NativeShuffledHashJoinExec(left, right, leftKeys, rightKeys, joinType, buildSide, condition)
+
   override def createNativeExpandExec(
       projections: Seq[Seq[Expression]],
       output: Seq[Attribute],
@@ -330,12 +349,15 @@ class ShimsImpl extends Shims with Logging {
       length: Long,
       numRecords: Long): FileSegment = new FileSegment(file, offset, length)
 
+  override def isShuffleChecksumSupported: Boolean = false
+
   override def commit(
       dep: ShuffleDependency[_, _, _],
       shuffleBlockResolver: IndexShuffleBlockResolver,
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus = {
 
@@ -361,7 +383,7 @@ class ShimsImpl extends Shims with Logging {
 
   override def getShuffleWriteExec(
       input: pb.PhysicalPlanNode,
-      nativeOutputPartitioning: pb.PhysicalHashRepartition.Builder): pb.PhysicalPlanNode = {
+      nativeOutputPartitioning: pb.PhysicalRepartition.Builder): pb.PhysicalPlanNode = {
     pb.PhysicalPlanNode
       .newBuilder()
       .setShuffleWriter(
@@ -428,6 +450,38 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[AggregateExpression].filter
   }
 
+  override def getWindowFunctionIgnoreNulls(expr: Expression): Boolean = {
+    false // IGNORE NULLS of window functions is not supported in spark303
+  }
+
+  override def getNthValueWindowFunction(expr: Expression): Option[(Expression, Int, Boolean)] = {
+    None // nth_value is not supported in spark303
+  }
+
+  override def getDayTimeIntervalMicros(literal: Literal): Option[Long] = {
+    None // day-time intervals are not supported in spark303
+  }
+
+  override def isNullOnDivideByZero(aggr: AggregateFunction): Boolean = {
+    false // statistical aggregates always return NaN when dividing by zero in spark303
+  }
+
+  override def isFailOnError(aggr: AggregateFunction): Boolean = {
+    SQLConf.get.ansiEnabled // sum/avg follow the session ansi mode in spark303
+  }
+
+  override def getAggregateOrdering(aggr: AggregateFunction): Seq[SortOrder] = {
+    Nil // within-group ordering of aggregates is not supported in spark303
+  }
+
+  override def convertDataType(sparkDataType: DataType): Option[pb.ArrowType] = {
+    None // ansi interval types are not supported in spark303
+  }
+
+  override def isNullAwareAntiJoin(exec: BroadcastHashJoinExec): Boolean = {
+    false // null-aware anti join is not supported in spark303
+  }
+
   private def executeNativeCustomShuffleReader(exec: CustomShuffleReaderExec): NativeRDD = {
     exec match {
       case CustomShuffleReaderExec(child, _, _) if isNative(child) =>
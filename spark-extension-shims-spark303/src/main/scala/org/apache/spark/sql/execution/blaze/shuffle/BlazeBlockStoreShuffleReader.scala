@@ -40,7 +40,8 @@ class BlazeBlockStoreShuffleReader[K, C](
     mapOutputTracker: MapOutputTracker = SparkEnv.get.mapOutputTracker,
     startMapId: Option[Int] = None,
     endMapId: Option[Int] = None,
-    shouldBatchFetch: Boolean = false)
+    shouldBatchFetch: Boolean = false,
+    override protected val partitionSplit: Option[(Int, Int)] = None)
     extends BlazeBlockStoreShuffleReaderBase[K, C](handle, context)
     with Logging {
 
@@ -74,7 +75,7 @@ class BlazeBlockStoreShuffleReader[K, C](
       SparkEnv.get.conf.get(config.SHUFFLE_DETECT_CORRUPT),
       SparkEnv.get.conf.get(config.SHUFFLE_DETECT_CORRUPT_MEMORY),
       readMetrics,
-      fetchContinuousBlocksInBatch).toCompletionIterator
+      fetchContinuousBlocksInBatch && partitionSplit.isEmpty).toCompletionIterator
   }
 
   private def fetchContinuousBlocksInBatch: Boolean = {
@@ -30,8 +30,18 @@ import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleHandle
 import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
+import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+import org.apache.spark.sql.catalyst.expressions.aggregate.BloomFilterAggregate
+import org.apache.spark.sql.catalyst.expressions.aggregate.Corr
+import org.apache.spark.sql.catalyst.expressions.aggregate.CovSample
+import org.apache.spark.sql.catalyst.expressions.BloomFilterMightContain
 import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.FrameLessOffsetWindowFunction
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
+import org.apache.spark.sql.catalyst.expressions.aggregate.StddevSamp
+import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.expressions.aggregate.VarianceSamp
 import org.apache.spark.sql.catalyst.expressions.Like
 import org.apache.spark.sql.catalyst.expressions.Literal
 import org.apache.spark.sql.catalyst.expressions.StringSplit
@@ -58,8 +68,12 @@ import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastExchangeExec
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeLike
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.adaptive.BroadcastQueryStageExec
+import org.apache.spark.sql.internal.SQLConf
+import org.apache.spark.sql.types.DayTimeIntervalType
 import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.LongType
 import org.apache.spark.sql.types.StringType
+import org.apache.spark.sql.types.YearMonthIntervalType
 import org.apache.spark.storage.BlockManagerId
 import org.apache.spark.storage.FileSegment
 import org.apache.spark.OneToOneDependency
@@ -70,6 +84,7 @@ import org.apache.spark.sql.catalyst.catalog.CatalogTable
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Generator
 import org.apache.spark.sql.catalyst.expressions.NamedExpression
+import org.apache.spark.sql.catalyst.expressions.NthValue
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.execution.adaptive.AQEShuffleReadExec
@@ -104,8 +119,10 @@ import org.apache.spark.sql.execution.blaze.plan.NativeWindowBase
 import org.apache.spark.sql.execution.blaze.plan.NativeWindowExec
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.execution.CoalescedMapperPartitionSpec
+import org.apache.spark.sql.execution.joins.BroadcastHashJoinExec
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastJoinExec
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeBroadcastNestedLoopJoinExec
+import org.apache.spark.sql.execution.joins.blaze.plan.NativeShuffledHashJoinExec
 import org.apache.spark.sql.execution.joins.blaze.plan.NativeSortMergeJoinExec
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
 import org.apache.spark.sql.types.DataType
@@ -150,7 +167,9 @@ class ShimsImpl extends Shims with Logging {
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
       joinType: JoinType,
-      condition: Option[Expression]): NativeBroadcastJoinBase =
+      broadcastSide: pb.JoinSide,
+      condition: Option[Expression],
+      isNullAwareAntiJoin: Boolean): NativeBroadcastJoinBase =
     NativeBroadcastJoinExec(
       left,
       right,
@@ -158,7 +177,9 @@ class ShimsImpl extends Shims with Logging {
       leftKeys,
       rightKeys,
       joinType,
-      condition)
+      broadcastSide,
+      condition,
+      isNullAwareAntiJoin)
 
   override def createNativeBroadcastNestedLoopJoinExec(
       left: SparkPlan,
@@ -176,6 +197,16 @@ class ShimsImpl extends Shims with Logging {
       condition: Option[Expression]): NativeSortMergeJoinBase =
     NativeSortMergeJoinExec(left, right, leftKeys, rightKeys, joinType, condition)
 
+  override def createNativeShuffledHashJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      leftKeys: Seq[Expression],
+      rightKeys: Seq[Expression],
+      joinType: JoinType,
+      buildSide: pb.JoinSide,
+      condition: Option[Expression]): NativeShuffledHashJoinBase =
+    NativeShuffledHashJoinExec(left, right, leftKeys, rightKeys, joinType, buildSide, condition)
+
   override def createNativeExpandExec(
       projections: Seq[Seq[Expression]],
       output: Seq[Attribute],
@@ -328,16 +359,18 @@ class ShimsImpl extends Shims with Logging {
       length: Long,
       numRecords: Long): FileSegment = new FileSegment(file, offset, length)
 
+  override def isShuffleChecksumSupported: Boolean = true
+
   override def commit(
       dep: ShuffleDependency[_, _, _],
       shuffleBlockResolver: IndexShuffleBlockResolver,
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus = {
 
-    val checksums = Array[Long]()
     shuffleBlockResolver.writeMetadataFileAndCommit(
       dep.shuffleId,
       mapId,
@@ -361,7 +394,7 @@ class ShimsImpl extends Shims with Logging {
 
   override def getShuffleWriteExec(
       input: pb.PhysicalPlanNode,
-      nativeOutputPartitioning: pb.PhysicalHashRepartition.Builder): pb.PhysicalPlanNode = {
+      nativeOutputPartitioning: pb.PhysicalRepartition.Builder): pb.PhysicalPlanNode = {
     pb.PhysicalPlanNode
       .newBuilder()
       .setShuffleWriter(
@@ -398,6 +431,17 @@ class ShimsImpl extends Shims with Logging {
                 .setReturnType(NativeConverters.convertDataType(StringType)))
             .build())
 
+      case e: BloomFilterMightContain =>
+        Some(
+          pb.PhysicalExprNode
+            .newBuilder()
+            .setBloomFilterMightContainExpr(
+              pb.BloomFilterMightContainExprNode
+                .newBuilder()
+                .setBloomFilterExpr(NativeConverters.convertExpr(e.bloomFilterExpression))
+                .setValueExpr(NativeConverters.convertExpr(e.valueExpression)))
+            .build())
+
       case _ => None
     }
   }
@@ -420,6 +464,21 @@ class ShimsImpl extends Shims with Logging {
         aggBuilder.addChildren(NativeConverters.convertExpr(child))
         Some(pb.PhysicalExprNode.newBuilder().setAggExpr(aggBuilder).build())
 
+      case e: BloomFilterAggregate
+          if e.estimatedNumItemsExpression.foldable && e.numBitsExpression.foldable =>
+        // same as BloomFilterAggregate.estimatedNumItems/numBits
+        val estimatedNumItems = Math.min(
+          e.estimatedNumItemsExpression.eval().asInstanceOf[Number].longValue,
+          SQLConf.get.getConf(SQLConf.RUNTIME_BLOOM_FILTER_MAX_NUM_ITEMS))
+        val numBits = Math.min(
+          e.numBitsExpression.eval().asInstanceOf[Number].longValue,
+          SQLConf.get.getConf(SQLConf.RUNTIME_BLOOM_FILTER_MAX_NUM_BITS))
+        aggBuilder.setAggFunction(pb.AggFunction.BLOOM_FILTER)
+        aggBuilder.addChildren(NativeConverters.convertExpr(e.child))
+        aggBuilder.addChildren(NativeConverters.convertExpr(Literal(estimatedNumItems, LongType)))
+        aggBuilder.addChildren(NativeConverters.convertExpr(Literal(numBits, LongType)))
+        Some(pb.PhysicalExprNode.newBuilder().setAggExpr(aggBuilder).build())
+
       case _ => None
     }
   }
@@ -428,6 +487,58 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[AggregateExpression].filter
   }
 
+  override def getWindowFunctionIgnoreNulls(expr: Expression): Boolean = {
+    expr match {
+      case e: FrameLessOffsetWindowFunction => e.ignoreNulls
+      case _ => false
+    }
+  }
+
+  override def getNthValueWindowFunction(expr: Expression): Option[(Expression, Int, Boolean)] = {
+    expr match {
+      case NthValue(input, offset, ignoreNulls) =>
+        Some((input, offset.eval().asInstanceOf[Int], ignoreNulls))
+      case _ => None
+    }
+  }
+
+  override def getDayTimeIntervalMicros(literal: Literal): Option[Long] = literal match {
+    case Literal(micros: Long, _: DayTimeIntervalType) => Some(micros)
+    case _ => None
+  }
+
+  override def isNullOnDivideByZero(aggr: AggregateFunction): Boolean = aggr match {
+    case e: StddevSamp => e.nullOnDivideByZero
+    case e: VarianceSamp => e.nullOnDivideByZero
+    case e: CovSample => e.nullOnDivideByZero
+    case e: Corr => e.nullOnDivideByZero
+    case _ => true // population aggregates never divide by zero
+  }
+
+  override def isFailOnError(aggr: AggregateFunction): Boolean = aggr match {
+    case e: Sum => e.failOnError
+    case e: Average => e.failOnError
+    case _ => false
+  }
+
+  override def getAggregateOrdering(aggr: AggregateFunction): Seq[SortOrder] = {
+    Nil // within-group ordering of aggregates is not supported in spark333
+  }
+
+  override def convertDataType(sparkDataType: DataType): Option[pb.ArrowType] = {
+    // ansi intervals are stored as months and microseconds, same as spark
+    sparkDataType match {
+      case _: YearMonthIntervalType =>
+        Some(pb.ArrowType.newBuilder().setINTERVAL(pb.IntervalUnit.YearMonth).build())
+      case _: DayTimeIntervalType =>
+        Some(pb.ArrowType.newBuilder().setDURATION(pb.TimeUnit.Microsecond).build())
+      case _ => None
+    }
+  }
+
+  override def isNullAwareAntiJoin(exec: BroadcastHashJoinExec): Boolean =
+    exec.isNullAwareAntiJoin
+
   private def executeNativeAQEShuffleReader(exec: AQEShuffleReadExec): NativeRDD = {
     exec match {
       case AQEShuffleReadExec(child, _) if isNative(child) =>
@@ -16,6 +16,7 @@
 package org.apache.spark.sql.execution.blaze.shuffle
 
 import java.io.InputStream
+import java.io.RandomAccessFile
 
 import org.apache.spark.MapOutputTracker
 import org.apache.spark.SparkEnv
@@ -25,10 +26,16 @@ import org.apache.spark.internal.Logging
 import org.apache.spark.internal.config
 import org.apache.spark.io.CompressionCodec
 import org.apache.spark.shuffle.BaseShuffleHandle
+import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleReadMetricsReporter
+import org.apache.spark.sql.blaze.BlazeConf
 import org.apache.spark.storage.BlockId
 import org.apache.spark.storage.BlockManager
+import org.apache.spark.storage.FileSegment
 import org.apache.spark.storage.ShuffleBlockFetcherIterator
+import org.apache.spark.storage.ShuffleBlockBatchId
+import org.apache.spark.storage.ShuffleBlockId
+import org.apache.spark.util.Utils
 
 class BlazeBlockStoreShuffleReader[K, C](
     handle: BaseShuffleHandle[K, _, C],
@@ -40,7 +47,8 @@ class BlazeBlockStoreShuffleReader[K, C](
     mapOutputTracker: MapOutputTracker = SparkEnv.get.mapOutputTracker,
     startMapId: Option[Int] = None,
     endMapId: Option[Int] = None,
-    shouldBatchFetch: Boolean = false)
+    shouldBatchFetch: Boolean = false,
+    override protected val partitionSplit: Option[(Int, Int)] = None)
     extends BlazeBlockStoreShuffleReaderBase[K, C](handle, context)
     with Logging {
 
@@ -70,7 +78,55 @@ class BlazeBlockStoreShuffleReader[K, C](
       false, // checksums not supported
       "ChecksumAlgorithmsNotSupported",
       readMetrics,
-      fetchContinuousBlocksInBatch).toCompletionIterator
+      fetchContinuousBlocksInBatch && partitionSplit.isEmpty)
+      .toCompletionIterator
+  }
+
+  override protected def getChecksummedSegments(
+      blockId: BlockId,
+      fileSegment: FileSegment): Option[Seq[BlazeChecksummedFileSegment]] = {
+    if (!BlazeConf.SHUFFLE_CHECKSUM_ENABLED.booleanConf()) {
+      return None
+    }
+    val (shuffleId, mapId, startReduceId, endReduceId) = blockId match {
+      case ShuffleBlockId(shuffleId, mapId, reduceId) =>
+        (shuffleId, mapId, reduceId, reduceId + 1)
+      case ShuffleBlockBatchId(shuffleId, mapId, startReduceId, endReduceId) =>
+        (shuffleId, mapId, startReduceId, endReduceId)
+      case _ =>
+        return None
+    }
+    val resolver =
+      SparkEnv.get.shuffleManager.shuffleBlockResolver.asInstanceOf[IndexShuffleBlockResolver]
+    val checksumFile = resolver
+      .getChecksumFile(shuffleId, mapId, BlazeConf.SHUFFLE_CHECKSUM_ALGORITHM.stringConf())
+    if (!checksumFile.exists()) {
+      return None
+    }
+    val checksums = Utils.tryWithResource(new RandomAccessFile(checksumFile, "r")) { file =>
+      file.seek(startReduceId * 8L)
+      Array.fill(endReduceId - startReduceId)(file.readLong())
+    }
+    if (checksums.length == 1) {
+      return Some(Seq(BlazeChecksummedFileSegment(blockId.name, fileSegment, checksums.head)))
+    }
+
+    // split batch block into segments of each partition with offsets in the index file
+    val offsets = Utils.tryWithResource(
+      new RandomAccessFile(resolver.getIndexFile(shuffleId, mapId), "r")) { file =>
+      file.seek(startReduceId * 8L)
+      Array.fill(endReduceId - startReduceId + 1)(file.readLong())
+    }
+    val segments = (startReduceId until endReduceId).map { reduceId =>
+      val i = reduceId - startReduceId
+      val segment = new FileSegment(
+        fileSegment.file,
+        fileSegment.offset + offsets(i) - offsets(0),
+        offsets(i + 1) - offsets(i))
+      val segmentBlockId = ShuffleBlockId(shuffleId, mapId, reduceId)
+      BlazeChecksummedFileSegment(segmentBlockId.name, segment, checksums(i))
+    }
+    Some(segments.filter(_.fileSegment.length > 0))
   }
 
   private def fetchContinuousBlocksInBatch: Boolean = {
@@ -76,6 +76,37 @@ class BlazeShuffleManager(conf: SparkConf) extends ShuffleManager with Logging {
     }
   }
 
+  /**
+   * Get a reader for the splitIndex-th of numSplits sub-ranges of a reduce partition in the map
+   * outputs (startMapIndex to endMapIndex-1, inclusive), so that a skewed partition can be read by
+   * several tasks. partitions are split at block boundaries recorded in the split index files
+   * (see spark.blaze.shuffle.splitIndex.enabled). Called on executors by reduce tasks.
+   */
+  def getPartitionSplitReader[K, C](
+      handle: ShuffleHandle,
+      startMapIndex: Int,
+      endMapIndex: Int,
+      partition: Int,
+      splitIndex: Int,
+      numSplits: Int,
+      context: TaskContext,
+      metrics: ShuffleReadMetricsReporter): ShuffleReader[K, C] = {
+
+    assert(isArrowShuffle(handle), "partition split reader requires native shuffle")
+    assert(0 <= splitIndex && splitIndex < numSplits, s"invalid split: $splitIndex/$numSplits")
+    new BlazeBlockStoreShuffleReader(
+      handle.asInstanceOf[BaseShuffleHandle[K, _, C]],
+      partition,
+      partition + 1,
+      context,
+      metrics,
+      SparkEnv.get.blockManager,
+      SparkEnv.get.mapOutputTracker,
+      startMapId = Some(startMapIndex),
+      endMapId = Some(endMapIndex),
+      partitionSplit = Some((splitIndex, numSplits)))
+  }
+
   /** Get a writer for a given partition. Called on executors by map tasks. */
   override def getWriter[K, V](
       handle: ShuffleHandle,
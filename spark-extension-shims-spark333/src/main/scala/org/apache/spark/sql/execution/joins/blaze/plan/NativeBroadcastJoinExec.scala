@@ -21,6 +21,7 @@ import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.codegen.CodegenContext
 import org.apache.spark.sql.catalyst.optimizer.BuildLeft
+import org.apache.spark.sql.catalyst.optimizer.BuildRight
 import org.apache.spark.sql.catalyst.optimizer.BuildSide
 import org.apache.spark.sql.catalyst.plans.physical.BroadcastDistribution
 import org.apache.spark.sql.catalyst.plans.physical.Distribution
@@ -31,6 +32,7 @@ import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastJoinBase
 import org.apache.spark.sql.execution.joins.HashedRelationBroadcastMode
 import org.apache.spark.sql.execution.joins.HashedRelationInfo
 import org.apache.spark.sql.execution.joins.HashJoin
+import org.blaze.protobuf.JoinSide
 
 case class NativeBroadcastJoinExec(
     override val left: SparkPlan,
@@ -39,7 +41,9 @@ case class NativeBroadcastJoinExec(
     override val leftKeys: Seq[Expression],
     override val rightKeys: Seq[Expression],
     override val joinType: JoinType,
-    override val condition: Option[Expression])
+    broadcastSide: JoinSide,
+    override val condition: Option[Expression],
+    isNullAwareAntiJoin: Boolean)
     extends NativeBroadcastJoinBase(
       left,
       right,
@@ -47,12 +51,17 @@ case class NativeBroadcastJoinExec(
       leftKeys,
       rightKeys,
       joinType,
-      condition)
+      broadcastSide,
+      condition,
+      isNullAwareAntiJoin)
     with HashJoin {
 
   override def requiredChildDistribution: Seq[Distribution] = {
-    val mode = HashedRelationBroadcastMode(buildBoundKeys, isNullAware = false)
-    BroadcastDistribution(mode) :: UnspecifiedDistribution :: Nil
+    val mode = HashedRelationBroadcastMode(buildBoundKeys, isNullAwareAntiJoin)
+    broadcastSide match {
+      case JoinSide.LEFT_SIDE => BroadcastDistribution(mode) :: UnspecifiedDistribution :: Nil
+      case _ => UnspecifiedDistribution :: BroadcastDistribution(mode) :: Nil
+    }
   }
 
   override def supportCodegen: Boolean = false
@@ -65,7 +74,10 @@ case class NativeBroadcastJoinExec(
     throw new NotImplementedError("NativeBroadcastJoin dose not support codegen")
   }
 
-  override def buildSide: BuildSide = BuildLeft
+  override def buildSide: BuildSide = broadcastSide match {
+    case JoinSide.LEFT_SIDE => BuildLeft
+    case _ => BuildRight
+  }
 
   override protected def withNewChildrenInternal(
       newLeft: SparkPlan,
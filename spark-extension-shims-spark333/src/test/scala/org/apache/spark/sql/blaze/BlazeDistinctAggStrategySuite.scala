@@ -0,0 +1,96 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import org.apache.spark.sql.catalyst.expressions.Alias
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.GreaterThan
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.Complete
+import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.Final
+import org.apache.spark.sql.catalyst.expressions.aggregate.Max
+import org.apache.spark.sql.catalyst.expressions.aggregate.Partial
+import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.execution.LocalTableScanExec
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.aggregate.HashAggregateExec
+import org.apache.spark.sql.execution.blaze.plan.NativeAggBase
+import org.apache.spark.sql.types.IntegerType
+import org.blaze.{protobuf => pb}
+import org.scalatest.funsuite.AnyFunSuite
+
+class BlazeDistinctAggStrategySuite extends AnyFunSuite {
+  private val a = AttributeReference("a", IntegerType)()
+  private val b = AttributeReference("b", IntegerType)()
+  private val c = AttributeReference("c", IntegerType)()
+  private val child = LocalTableScanExec(a :: b :: c :: Nil, Nil)
+
+  private def plan(aggExprs: AggregateExpression*): Option[Seq[SparkPlan]] = {
+    val resultExprs = a +: aggExprs.map(e => Alias(e.resultAttribute, "r")())
+    BlazeDistinctAggStrategy.planSingleStageDistinctAgg(a :: Nil, aggExprs, resultExprs, child)
+  }
+
+  private def nativeAggFunction(aggr: AggregateExpression): pb.AggFunction =
+    NativeAggBase
+      .getNativeAggrInfo(aggr, aggr.resultAttribute)
+      .nativeAggrs
+      .head
+      .getAggExpr
+      .getAggFunction
+
+  test("single distinct aggregates are planned without deduplicating aggregates") {
+    val countDistinct = AggregateExpression(Count(b), Complete, isDistinct = true)
+    val sumDistinct = AggregateExpression(Sum(b), Complete, isDistinct = true)
+    val max = AggregateExpression(Max(c), Complete, isDistinct = false)
+
+    val Some(Seq(finalAgg: HashAggregateExec)) = plan(countDistinct, sumDistinct, max)
+    val partialAgg = finalAgg.child.asInstanceOf[HashAggregateExec]
+    assert(partialAgg.child eq child)
+    assert(finalAgg.aggregateExpressions.forall(_.mode == Final))
+    assert(partialAgg.aggregateExpressions.forall(_.mode == Partial))
+    assert(partialAgg.aggregateExpressions.map(_.isDistinct) == Seq(true, true, false))
+    assert(BlazeDistinctAggStrategy.isNativeDistinctAgg(finalAgg))
+    assert(BlazeDistinctAggStrategy.isNativeDistinctAgg(partialAgg))
+
+    // distinct values are collected in both partial and final aggregates
+    for (agg <- Seq(partialAgg, finalAgg)) {
+      val nativeAggFunctions = agg.aggregateExpressions.map(nativeAggFunction)
+      assert(
+        nativeAggFunctions == Seq(
+          pb.AggFunction.COUNT_DISTINCT,
+          pb.AggFunction.SUM_DISTINCT,
+          pb.AggFunction.MAX))
+    }
+  }
+
+  test("unsupported distinct aggregates are planned by spark") {
+    // no distinct aggregates
+    assert(plan(AggregateExpression(Count(b), Complete, isDistinct = false)).isEmpty)
+
+    // multiple distinct columns
+    assert(plan(AggregateExpression(Count(b :: c :: Nil), Complete, isDistinct = true)).isEmpty)
+
+    // filter clause
+    val filtered = AggregateExpression(
+      Sum(b),
+      Complete,
+      isDistinct = true,
+      filter = Some(GreaterThan(c, Literal(0))))
+    assert(plan(filtered).isEmpty)
+  }
+}
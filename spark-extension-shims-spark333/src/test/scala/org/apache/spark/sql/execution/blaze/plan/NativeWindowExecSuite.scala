@@ -0,0 +1,148 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.plan
+
+import org.apache.spark.sql.catalyst.expressions.Alias
+import org.apache.spark.sql.catalyst.expressions.Ascending
+import org.apache.spark.sql.catalyst.expressions.AttributeReference
+import org.apache.spark.sql.catalyst.expressions.CurrentRow
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.Literal
+import org.apache.spark.sql.catalyst.expressions.NthValue
+import org.apache.spark.sql.catalyst.expressions.RangeFrame
+import org.apache.spark.sql.catalyst.expressions.RowFrame
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.SpecifiedWindowFrame
+import org.apache.spark.sql.catalyst.expressions.UnboundedFollowing
+import org.apache.spark.sql.catalyst.expressions.UnboundedPreceding
+import org.apache.spark.sql.catalyst.expressions.WindowExpression
+import org.apache.spark.sql.catalyst.expressions.WindowSpecDefinition
+import org.apache.spark.sql.catalyst.expressions.aggregate.BoolAnd
+import org.apache.spark.sql.catalyst.expressions.aggregate.BoolOr
+import org.apache.spark.sql.catalyst.expressions.aggregate.First
+import org.apache.spark.sql.catalyst.expressions.aggregate.Last
+import org.apache.spark.sql.catalyst.optimizer.ReplaceExpressions
+import org.apache.spark.sql.catalyst.plans.logical.LocalRelation
+import org.apache.spark.sql.catalyst.plans.logical.Project
+import org.apache.spark.sql.execution.LocalTableScanExec
+import org.apache.spark.sql.types.BooleanType
+import org.apache.spark.sql.types.CalendarIntervalType
+import org.apache.spark.sql.types.DayTimeIntervalType
+import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.YearMonthIntervalType
+import org.apache.spark.unsafe.types.CalendarInterval
+import org.blaze.{protobuf => pb}
+import org.scalatest.funsuite.AnyFunSuite
+
+class NativeWindowExecSuite extends AnyFunSuite {
+  private val a = AttributeReference("a", IntegerType)()
+  private val b = AttributeReference("b", IntegerType)()
+  private val c = AttributeReference("c", BooleanType)()
+  private val orderSpec = SortOrder(b, Ascending) :: Nil
+
+  private def convertWindowExpr(
+      function: Expression,
+      frame: SpecifiedWindowFrame): pb.WindowExprNode = {
+    val spec = WindowSpecDefinition(a :: Nil, orderSpec, frame)
+    val windowExpr = Alias(WindowExpression(function, spec), "w")()
+    val child = LocalTableScanExec(a :: b :: c :: Nil, Nil)
+    NativeWindowExec(windowExpr :: Nil, a :: Nil, orderSpec, child).nativeWindowExprs.head
+  }
+
+  test("nth_value is converted with offset, ignoreNulls and frame") {
+    val frame = SpecifiedWindowFrame(RowFrame, UnboundedPreceding, UnboundedFollowing)
+    val node = convertWindowExpr(NthValue(b, Literal(2), ignoreNulls = true), frame)
+    assert(node.getFuncType == pb.WindowFunctionType.Window)
+    assert(node.getWindowFunc == pb.WindowFunction.NTH_VALUE)
+    assert(node.getOffset == 2)
+    assert(node.getIgnoreNulls)
+    assert(node.getFrame.getUnits == pb.WindowFrameUnits.ROWS)
+    assert(node.getFrame.getStart.getBoundType == pb.WindowFrameBoundType.UNBOUNDED_PRECEDING)
+    assert(node.getFrame.getEnd.getBoundType == pb.WindowFrameBoundType.UNBOUNDED_FOLLOWING)
+    assert(node.getChildrenCount == 1)
+  }
+
+  test("first/last are converted to first_value/last_value") {
+    val frame = SpecifiedWindowFrame(RowFrame, UnboundedPreceding, CurrentRow)
+
+    val first = convertWindowExpr(First(b, ignoreNulls = false), frame)
+    assert(first.getFuncType == pb.WindowFunctionType.Window)
+    assert(first.getWindowFunc == pb.WindowFunction.FIRST_VALUE)
+    assert(!first.getIgnoreNulls)
+    assert(first.getFrame.getEnd.getBoundType == pb.WindowFrameBoundType.CURRENT_ROW)
+
+    val last = convertWindowExpr(Last(b, ignoreNulls = true), frame)
+    assert(last.getFuncType == pb.WindowFunctionType.Window)
+    assert(last.getWindowFunc == pb.WindowFunction.LAST_VALUE)
+    assert(last.getIgnoreNulls)
+    assert(last.getFrame.getEnd.getBoundType == pb.WindowFrameBoundType.CURRENT_ROW)
+  }
+
+  test("interval offsets of range frames are converted to microseconds") {
+    val dayTimeFrame = SpecifiedWindowFrame(
+      RangeFrame,
+      Literal(-3600000000L, DayTimeIntervalType()),
+      Literal(new CalendarInterval(0, 0, 60000000L), CalendarIntervalType))
+    val node = convertWindowExpr(First(b, ignoreNulls = false), dayTimeFrame)
+    assert(node.getFrame.getUnits == pb.WindowFrameUnits.RANGE)
+    assert(node.getFrame.getStart.getBoundType == pb.WindowFrameBoundType.PRECEDING)
+    assert(node.getFrame.getStart.getOffset.getInt64Value == 3600000000L)
+    assert(node.getFrame.getEnd.getBoundType == pb.WindowFrameBoundType.FOLLOWING)
+    assert(node.getFrame.getEnd.getOffset.getInt64Value == 60000000L)
+  }
+
+  test("calendar-dependent interval offsets of range frames are not supported") {
+    val yearMonthFrame =
+      SpecifiedWindowFrame(RangeFrame, Literal(-1, YearMonthIntervalType()), CurrentRow)
+    assertThrows[NotImplementedError] {
+      convertWindowExpr(First(b, ignoreNulls = false), yearMonthFrame)
+    }
+    val daysFrame = SpecifiedWindowFrame(
+      RangeFrame,
+      Literal(new CalendarInterval(0, -1, 0L), CalendarIntervalType),
+      CurrentRow)
+    assertThrows[NotImplementedError] {
+      convertWindowExpr(First(b, ignoreNulls = false), daysFrame)
+    }
+  }
+
+  test("bool_and/bool_or over frames are converted through min/max") {
+    val frame = SpecifiedWindowFrame(RowFrame, Literal(-1), Literal(1))
+
+    // spark's optimizer replaces bool_and/bool_or (every/any/some) with min/max
+    def optimized(function: Expression): Expression = {
+      val spec = WindowSpecDefinition(a :: Nil, orderSpec, frame)
+      val plan = Project(
+        Alias(WindowExpression(function, spec), "w")() :: Nil,
+        LocalRelation(a, b, c))
+      ReplaceExpressions(plan).expressions.head.collectFirst { case WindowExpression(f, _) =>
+        f
+      }.get
+    }
+
+    for ((function, aggFunc) <- Seq(
+        (BoolAnd(c), pb.AggFunction.MIN),
+        (BoolOr(c), pb.AggFunction.MAX))) {
+      val node = convertWindowExpr(optimized(function), frame)
+      assert(node.getFuncType == pb.WindowFunctionType.Agg)
+      assert(node.getAggFunc == aggFunc)
+      assert(node.getFrame.getUnits == pb.WindowFrameUnits.ROWS)
+      assert(node.getFrame.getStart.getBoundType == pb.WindowFrameBoundType.PRECEDING)
+      assert(node.getFrame.getEnd.getBoundType == pb.WindowFrameBoundType.FOLLOWING)
+      assert(node.getChildrenCount == 1)
+    }
+  }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.shuffle
+
+import java.io.File
+import java.io.RandomAccessFile
+import java.nio.ByteBuffer
+import java.nio.ByteOrder
+import java.nio.file.Files
+
+import org.apache.spark.storage.FileSegment
+import org.apache.spark.storage.ShuffleBlockBatchId
+import org.apache.spark.storage.ShuffleBlockId
+import org.apache.spark.util.Utils
+import org.scalatest.funsuite.AnyFunSuite
+
+class BlazeShuffleSplitIndexSuite extends AnyFunSuite {
+
+  // (length, numRows) of blocks in each partition
+  private val partitionBlocks = Seq(Seq((10L, 5L)), Seq((20L, 4L), (30L, 6L), (25L, 5L)), Nil)
+
+  private def withShuffleFiles(f: File => Unit): Unit = {
+    val dir = Utils.createTempDir()
+    try {
+      val dataFile = new File(dir, "shuffle_0_0_0.data")
+      val dataLength = partitionBlocks.flatten.map(_._1).sum.toInt
+      Files.write(dataFile.toPath, Array.tabulate(dataLength)(_.toByte))
+
+      val numBlocks = partitionBlocks.flatten.length
+      val buf = ByteBuffer
+        .allocate((partitionBlocks.length + 1 + numBlocks * 2) * 8)
+        .order(ByteOrder.LITTLE_ENDIAN)
+      partitionBlocks.map(_.length.toLong).scanLeft(0L)(_ + _).foreach(buf.putLong)
+      partitionBlocks.flatten.foreach { case (len, numRows) =>
+        buf.putLong(len)
+        buf.putLong(numRows)
+      }
+      Files.write(BlazeShuffleSplitIndex.getSplitIndexFile(dataFile).toPath, buf.array())
+      f(dataFile)
+    } finally {
+      Utils.deleteRecursively(dir)
+    }
+  }
+
+  private def readSegment(segment: FileSegment): Array[Byte] = {
+    Utils.tryWithResource(new RandomAccessFile(segment.file, "r")) { file =>
+      val bytes = new Array[Byte](segment.length.toInt)
+      file.seek(segment.offset)
+      file.readFully(bytes)
+      bytes
+    }
+  }
+
+  test("read split index") {
+    withShuffleFiles { dataFile =>
+      val index =
+        BlazeShuffleSplitIndex.read(BlazeShuffleSplitIndex.getSplitIndexFile(dataFile), 3)
+      assert(index.partitionBlocks.map(_.toSeq).toSeq == partitionBlocks)
+      assert((0 until 3).map(index.numRows) == Seq(5, 15, 0))
+      assert((0 until 3).map(index.partitionLength) == Seq(10, 75, 0))
+    }
+  }
+
+  test("read a partition in two sub-ranges") {
+    withShuffleFiles { dataFile =>
+      val blockId = ShuffleBlockId(0, 0, 1)
+      val partitionSegment = new FileSegment(dataFile, 10, 75)
+      val splits = (0 until 2).map { splitIndex =>
+        BlazeShuffleSplitIndex.getSplitSegment(blockId, partitionSegment, 3, splitIndex, 2).get
+      }
+
+      // split at block boundaries with similar numbers of rows (10 and 5)
+      assert(splits.map(s => (s.offset, s.length)) == Seq((10L, 50L), (60L, 25L)))
+      assert(splits.flatMap(readSegment) == readSegment(partitionSegment).toSeq)
+
+      // not enough blocks for more splits
+      assert(BlazeShuffleSplitIndex.getSplitSegment(blockId, partitionSegment, 3, 2, 3).isEmpty)
+    }
+  }
+
+  test("blocks without matched split index are read by the first split") {
+    withShuffleFiles { dataFile =>
+      val mismatchedSegment = new FileSegment(dataFile, 10, 50)
+      val batchBlockId = ShuffleBlockBatchId(0, 0, 0, 2)
+      val batchSegment = new FileSegment(dataFile, 0, 85)
+
+      for ((blockId, segment) <- Seq(
+          (ShuffleBlockId(0, 0, 1), mismatchedSegment),
+          (batchBlockId, batchSegment))) {
+        assert(BlazeShuffleSplitIndex.getSplitSegment(blockId, segment, 3, 0, 2).contains(segment))
+        assert(BlazeShuffleSplitIndex.getSplitSegment(blockId, segment, 3, 1, 2).isEmpty)
+      }
+    }
+  }
+}
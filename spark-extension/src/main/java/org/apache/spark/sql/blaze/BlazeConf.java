@@ -31,6 +31,10 @@ public enum BlazeConf {
     /// issues are found in special cases, like tpcds q72.
     SMJ_INEQUALITY_JOIN_ENABLE("spark.blaze.enable.smjInequalityJoin", false),
 
+    /// prunes candidate pairs of BroadcastNestedLoopJoin with range conditions (like
+    /// `a.ts BETWEEN b.start AND b.end`) by sorting the build side on the range key.
+    RANGE_JOIN_ENABLE("spark.blaze.enable.rangeJoin", true),
+
     /// fallbacks to SortMergeJoin when executing BroadcastHashJoin with big broadcasted table.
     BHJ_FALLBACKS_TO_SMJ_ENABLE("spark.blaze.enable.bhjFallbacksToSmj", true),
 
@@ -64,6 +68,41 @@ public enum BlazeConf {
 
     /// mininum number of rows to trigger partial aggregate skipping
     PARTIAL_AGG_SKIPPING_MIN_ROWS("spark.blaze.partialAggSkipping.minRows", BATCH_SIZE.intConf() * 2),
+
+    /// plans single-distinct count/sum/avg(DISTINCT ...) as native distinct aggregates, which keep
+    /// a set of distinct values for each group, instead of deduplicating inputs with two extra
+    /// aggregates and an extra shuffle.
+    NATIVE_DISTINCT_AGG_ENABLE("spark.blaze.enable.nativeDistinctAgg", false),
+
+    /// maximum number of elements collected by native collect_list/collect_set for each group.
+    /// extra elements are dropped instead of running out of memory. -1 means unlimited.
+    COLLECT_MAX_ELEMENTS("spark.blaze.collect.maxElements", -1),
+
+    /// compression codec of native shuffle data, follows spark.io.compression.codec if not set.
+    /// supported codecs: none, lz4, zstd, snappy.
+    SHUFFLE_COMPRESSION_CODEC(
+            "spark.blaze.shuffle.compression.codec", conf().get("spark.io.compression.codec", "lz4")),
+
+    /// compression level of native shuffle data when using zstd codec.
+    SHUFFLE_COMPRESSION_ZSTD_LEVEL(
+            "spark.blaze.shuffle.compression.zstd.level", conf().getInt("spark.io.compression.zstd.level", 1)),
+
+    /// size of uncompressed data in each compressed block of native shuffle data.
+    SHUFFLE_COMPRESSION_TARGET_BUF_SIZE("spark.blaze.shuffle.compression.targetBufSize", 4194304),
+
+    /// whether to write checksums of native shuffle partitions and verify them when reading
+    /// local shuffle blocks. only takes effect with spark versions supporting shuffle checksums.
+    SHUFFLE_CHECKSUM_ENABLED(
+            "spark.blaze.shuffle.checksum.enabled", conf().getBoolean("spark.shuffle.checksum.enabled", true)),
+
+    /// checksum algorithm of native shuffle partitions, supported algorithms: ADLER32, CRC32.
+    SHUFFLE_CHECKSUM_ALGORITHM(
+            "spark.blaze.shuffle.checksum.algorithm", conf().get("spark.shuffle.checksum.algorithm", "ADLER32")),
+
+    /// whether to write a split index file next to each native shuffle data file, recording size
+    /// and number of rows of each block in partitions, so that skewed partitions can be read in
+    /// sub-ranges split at block boundaries.
+    SHUFFLE_SPLIT_INDEX_ENABLED("spark.blaze.shuffle.splitIndex.enabled", false),
     ;
 
     private String key;
@@ -90,6 +129,10 @@ public enum BlazeConf {
         return conf().getDouble(key, (double) defaultValue);
     }
 
+    public String stringConf() {
+        return conf().get(key, (String) defaultValue);
+    }
+
     public static boolean booleanConf(String confName) {
         return BlazeConf.valueOf(confName).booleanConf();
     }
@@ -106,6 +149,10 @@ public enum BlazeConf {
         return BlazeConf.valueOf(confName).doubleConf();
     }
 
+    public static String stringConf(String confName) {
+        return BlazeConf.valueOf(confName).stringConf();
+    }
+
     private static SparkConf conf() {
         return SparkEnv$.MODULE$.get().conf();
     }
@@ -34,6 +34,8 @@ public class JniBridge {
 
     public static native void finalizeNative(long ptr);
 
+    public static native void releaseCachedJoinHashMap(String cachedId);
+
     public static ClassLoader getContextClassLoader() {
         return Thread.currentThread().getContextClassLoader();
     }
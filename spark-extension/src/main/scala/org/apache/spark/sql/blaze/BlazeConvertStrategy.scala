@@ -43,6 +43,7 @@ import org.apache.spark.sql.execution.joins.BroadcastNestedLoopJoinExec
 
 object BlazeConvertStrategy extends Logging {
   import BlazeConverters._
+  import BlazeDistinctAggStrategy.isNativeDistinctAgg
 
   val convertibleTag: TreeNodeTag[Boolean] = TreeNodeTag("blaze.convertible")
   val convertStrategyTag: TreeNodeTag[ConvertStrategy] = TreeNodeTag("blaze.convert.strategy")
@@ -81,6 +82,8 @@ object BlazeConvertStrategy extends Logging {
     exec.foreachUp {
       case exec if isNeverConvert(exec) || isAlwaysConvert(exec) =>
       // already decided, do nothing
+      case e if isNativeDistinctAgg(e) => // cannot be evaluated by spark
+        e.setTagValue(convertStrategyTag, AlwaysConvert)
       case e: ShuffleExchangeExec if isAlwaysConvert(e.child) || !isAggregate(e.child) =>
         e.setTagValue(convertStrategyTag, AlwaysConvert)
       case e: BroadcastExchangeExec =>
@@ -161,7 +164,7 @@ object BlazeConvertStrategy extends Logging {
 
         // NonNative -> NativeAgg
         // don't use NativeAgg because it requires ConvertToNative with a lot of records
-        if (!isNeverConvert(e) && isAggregate(e)) {
+        if (!isNeverConvert(e) && isAggregate(e) && !isNativeDistinctAgg(e)) {
           val child = e.children.head
           dontConvertIf(e, isNeverConvert(child))
         }
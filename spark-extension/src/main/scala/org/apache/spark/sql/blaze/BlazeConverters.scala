@@ -28,6 +28,8 @@ import org.apache.spark.internal.Logging
 import org.apache.spark.sql.blaze.BlazeConvertStrategy.convertibleTag
 import org.apache.spark.sql.blaze.BlazeConvertStrategy.convertStrategyTag
 import org.apache.spark.sql.blaze.BlazeConvertStrategy.isNeverConvert
+import org.apache.spark.sql.blaze.BlazeDistinctAggStrategy.isNativeDistinctAgg
+import org.apache.spark.sql.blaze.BlazeDistinctAggStrategy.nativeDistinctAggTag
 import org.apache.spark.sql.catalyst.expressions.Alias
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.AttributeReference
@@ -42,6 +44,8 @@ import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
 import org.apache.spark.sql.execution.FilterExec
 import org.apache.spark.sql.execution.GlobalLimitExec
@@ -72,6 +76,7 @@ import org.apache.spark.sql.execution.LocalTableScanExec
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanBase
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
+import org.blaze.{protobuf => pb}
 
 object BlazeConverters extends Logging {
   val enableScan: Boolean =
@@ -86,6 +91,8 @@ object BlazeConverters extends Logging {
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.union", defaultValue = true)
   val enableSmj: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.smj", defaultValue = true)
+  val enableShj: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.shj", defaultValue = true)
   val enableBhj: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.bhj", defaultValue = true)
   val enableBnlj: Boolean =
@@ -147,6 +154,8 @@ object BlazeConverters extends Logging {
         tryConvert(e, convertUnionExec)
       case e: SortMergeJoinExec if enableSmj => // sort merge join
         tryConvert(e, convertSortMergeJoinExec)
+      case e: ShuffledHashJoinExec if enableShj => // shuffled hash join
+        tryConvert(e, convertShuffledHashJoinExec)
       case e: BroadcastHashJoinExec if enableBhj => // broadcast hash join
         tryConvert(e, convertBroadcastHashJoinExec)
       case e: BroadcastNestedLoopJoinExec if enableBnlj => // broadcast nested loop join
@@ -161,6 +170,10 @@ object BlazeConverters extends Logging {
       case e: HashAggregateExec if enableAggr => // hash aggregate
         val convertedAgg = tryConvert(e, convertHashAggregateExec)
         if (!e.getTagValue(convertibleTag).contains(true)) {
+          assert(
+            !BlazeDistinctAggStrategy.isNativeDistinctAgg(e),
+            "native distinct agg cannot be converted, " +
+              "try disabling spark.blaze.enable.nativeDistinctAgg")
           if (e.requiredChildDistributionExpressions.isDefined) {
             assert(
               NativeAggBase.findPreviousNativeAggrExec(e).isEmpty,
@@ -172,6 +185,10 @@ object BlazeConverters extends Logging {
       case e: ObjectHashAggregateExec if enableAggr => // object hash aggregate
         val convertedAgg = tryConvert(e, convertObjectHashAggregateExec)
         if (!e.getTagValue(convertibleTag).contains(true)) {
+          assert(
+            !BlazeDistinctAggStrategy.isNativeDistinctAgg(e),
+            "native distinct agg cannot be converted, " +
+              "try disabling spark.blaze.enable.nativeDistinctAgg")
           if (e.requiredChildDistributionExpressions.isDefined) {
             assert(
               NativeAggBase.findPreviousNativeAggrExec(e).isEmpty,
@@ -183,6 +200,10 @@ object BlazeConverters extends Logging {
       case e: SortAggregateExec if enableAggr => // sort aggregate
         val convertedAgg = tryConvert(e, convertSortAggregateExec)
         if (!e.getTagValue(convertibleTag).contains(true)) {
+          assert(
+            !BlazeDistinctAggStrategy.isNativeDistinctAgg(e),
+            "native distinct agg cannot be converted, " +
+              "try disabling spark.blaze.enable.nativeDistinctAgg")
           if (e.requiredChildDistributionExpressions.isDefined) {
             assert(
               NativeAggBase.findPreviousNativeAggrExec(e).isEmpty,
@@ -240,14 +261,11 @@ object BlazeConverters extends Logging {
     val (outputPartitioning, child) = (exec.outputPartitioning, exec.child)
     logDebug(s"Converting ShuffleExchangeExec: ${Shims.get.simpleStringWithNodeId(exec)}")
 
-    assert(
-      exec.outputPartitioning.numPartitions == 1 || exec.outputPartitioning
-        .isInstanceOf[HashPartitioning])
-
     val convertedChild = outputPartitioning match {
-      case p if p.isInstanceOf[HashPartitioning] || p.numPartitions == 1 =>
+      case p if p.numPartitions == 1 => convertToNative(child)
+      case _: HashPartitioning | _: RoundRobinPartitioning | _: RangePartitioning =>
         convertToNative(child)
-      case _ => child
+      case p => throw new NotImplementedError(s"unsupported partitioning: $p")
     }
     Shims.get.createNativeShuffleExchangeExec(
       outputPartitioning,
@@ -368,6 +386,55 @@ object BlazeConverters extends Logging {
     }
   }
 
+  def convertShuffledHashJoinExec(exec: ShuffledHashJoinExec): SparkPlan = {
+    val (leftKeys, rightKeys, joinType, buildSide, condition, left, right) = (
+      exec.leftKeys,
+      exec.rightKeys,
+      exec.joinType,
+      exec.buildSide,
+      exec.condition,
+      exec.left,
+      exec.right)
+    logDebug(s"Converting ShuffledHashJoinExec: ${Shims.get.simpleStringWithNodeId(exec)}")
+    var nativeLeft = convertToNative(left)
+    var nativeRight = convertToNative(right)
+    var modifiedLeftKeys = leftKeys
+    var modifiedRightKeys = rightKeys
+    var needPostProject = false
+
+    if (leftKeys.exists(!_.isInstanceOf[AttributeReference])) {
+      val (keys, exec) = buildJoinColumnsProject(nativeLeft, leftKeys)
+      modifiedLeftKeys = keys
+      nativeLeft = exec
+      needPostProject = true
+    }
+    if (rightKeys.exists(!_.isInstanceOf[AttributeReference])) {
+      val (keys, exec) = buildJoinColumnsProject(nativeRight, rightKeys)
+      modifiedRightKeys = keys
+      nativeRight = exec
+      needPostProject = true
+    }
+
+    val nativeBuildSide = buildSide match {
+      case BuildLeft => pb.JoinSide.LEFT_SIDE
+      case BuildRight => pb.JoinSide.RIGHT_SIDE
+    }
+    val shj = Shims.get.createNativeShuffledHashJoinExec(
+      addRenameColumnsExec(nativeLeft),
+      addRenameColumnsExec(nativeRight),
+      modifiedLeftKeys,
+      modifiedRightKeys,
+      joinType,
+      nativeBuildSide,
+      condition)
+
+    if (needPostProject) {
+      buildPostJoinProject(shj, exec.output)
+    } else {
+      shj
+    }
+  }
+
   def convertBroadcastHashJoinExec(exec: BroadcastHashJoinExec): SparkPlan = {
     try {
       val (leftKeys, rightKeys, joinType, buildSide, condition, left, right) = (
@@ -418,30 +485,22 @@ object BlazeConverters extends Logging {
         needPostProject = true
       }
 
-      val modifiedJoinType = buildSide match {
-        case BuildLeft => joinType
-        case BuildRight =>
-          needPostProject = true
-          val modifiedJoinType = joinType match { // reverse join type
-            case Inner => Inner
-            case FullOuter => FullOuter
-            case LeftOuter => RightOuter
-            case RightOuter => LeftOuter
-            case _ =>
-              throw new NotImplementedError(
-                "BHJ Semi/Anti join with BuildRight is not yet supported")
-          }
-          modifiedJoinType
-      }
+      val (modifiedLeft, modifiedRight, modifiedLeftKeys, modifiedRightKeys, broadcastSide) =
+        buildSide match {
+          case BuildLeft =>
+            (hashed, nativeProbed, modifiedHashedKeys, modifiedProbedKeys, pb.JoinSide.LEFT_SIDE)
+          case BuildRight =>
+            (nativeProbed, hashed, modifiedProbedKeys, modifiedHashedKeys, pb.JoinSide.RIGHT_SIDE)
+        }
 
       val bhjOrig = BroadcastHashJoinExec(
-        modifiedHashedKeys,
-        modifiedProbedKeys,
-        modifiedJoinType,
-        BuildLeft,
+        modifiedLeftKeys,
+        modifiedRightKeys,
+        joinType,
+        buildSide,
         condition,
-        addRenameColumnsExec(hashed),
-        addRenameColumnsExec(nativeProbed))
+        addRenameColumnsExec(modifiedLeft),
+        addRenameColumnsExec(modifiedRight))
 
       val bhj = Shims.get.createNativeBroadcastJoinExec(
         bhjOrig.left,
@@ -450,7 +509,9 @@ object BlazeConverters extends Logging {
         bhjOrig.leftKeys,
         bhjOrig.rightKeys,
         bhjOrig.joinType,
-        bhjOrig.condition)
+        broadcastSide,
+        bhjOrig.condition,
+        Shims.get.isNullAwareAntiJoin(exec))
 
       if (needPostProject) {
         buildPostJoinProject(bhj, exec.output)
@@ -494,14 +555,14 @@ object BlazeConverters extends Logging {
             "Ignore BroadcastNestedLoopJoin with unsupported children structure")
       }
 
-      // the in-memory inner table is not the same in different join types
-      // reference: https://docs.rs/datafusion/latest/datafusion/physical_plan/joins/struct.NestedLoopJoinExec.html
+      // the in-memory build side is decided by join type in native BroadcastNestedLoopJoinExec:
+      // left side for RightOuter/FullOuter, right side for others
       var needPostProject = false
       val (modifiedLeft, modifiedRight, modifiedJoinType) = (buildSide, joinType) match {
         case (BuildLeft, RightOuter | FullOuter) =>
           (broadcasted, nativeProbed, joinType) // RightOuter, FullOuter => BuildLeft
-        case (BuildRight, Inner | LeftOuter | LeftSemi | LeftAnti) =>
-          (nativeProbed, broadcasted, joinType) // Inner, LeftOuter, LeftSemi, LeftAnti => BuildRight
+        case (BuildRight, Inner | LeftOuter | LeftSemi | LeftAnti | ExistenceJoin(_)) =>
+          (nativeProbed, broadcasted, joinType) // Inner, LeftOuter, LeftSemi, LeftAnti, Existence => BuildRight
         case _ =>
           needPostProject = true
           val modifiedJoinType = joinType match {
@@ -580,11 +641,12 @@ object BlazeConverters extends Logging {
     // for enabling filter-project optimization in native side
     getPartialAggProjection(exec.aggregateExpressions, exec.groupingExpressions) match {
       case Some((transformedAggregateExprs, transformedGroupingExprs, projections)) =>
-        return convertHashAggregateExec(
-          exec.copy(
-            aggregateExpressions = transformedAggregateExprs,
-            groupingExpressions = transformedGroupingExprs,
-            child = convertProjectExec(ProjectExec(projections, exec.child))))
+        val projectedExec = exec.copy(
+          aggregateExpressions = transformedAggregateExprs,
+          groupingExpressions = transformedGroupingExprs,
+          child = convertProjectExec(ProjectExec(projections, exec.child)))
+        projectedExec.setTagValue(nativeDistinctAggTag, isNativeDistinctAgg(exec))
+        return convertHashAggregateExec(projectedExec)
       case None => // passthrough
     }
 
@@ -598,7 +660,7 @@ object BlazeConverters extends Logging {
       NativeAggBase.HashAgg,
       exec.requiredChildDistributionExpressions,
       exec.groupingExpressions,
-      exec.aggregateExpressions,
+      getNativeAggregateExprs(exec, exec.aggregateExpressions),
       exec.aggregateAttributes,
       exec.initialInputBufferOffset,
       exec.requiredChildDistributionExpressions match {
@@ -637,11 +699,12 @@ object BlazeConverters extends Logging {
     // for enabling filter-project optimization in native side
     getPartialAggProjection(exec.aggregateExpressions, exec.groupingExpressions) match {
       case Some((transformedAggregateExprs, transformedGroupingExprs, projections)) =>
-        return convertObjectHashAggregateExec(
-          exec.copy(
-            aggregateExpressions = transformedAggregateExprs,
-            groupingExpressions = transformedGroupingExprs,
-            child = convertProjectExec(ProjectExec(projections, exec.child))))
+        val projectedExec = exec.copy(
+          aggregateExpressions = transformedAggregateExprs,
+          groupingExpressions = transformedGroupingExprs,
+          child = convertProjectExec(ProjectExec(projections, exec.child)))
+        projectedExec.setTagValue(nativeDistinctAggTag, isNativeDistinctAgg(exec))
+        return convertObjectHashAggregateExec(projectedExec)
       case None => // passthrough
     }
 
@@ -655,7 +718,7 @@ object BlazeConverters extends Logging {
       NativeAggBase.HashAgg,
       exec.requiredChildDistributionExpressions,
       exec.groupingExpressions,
-      exec.aggregateExpressions,
+      getNativeAggregateExprs(exec, exec.aggregateExpressions),
       exec.aggregateAttributes,
       exec.initialInputBufferOffset,
       exec.requiredChildDistributionExpressions match {
@@ -700,7 +763,7 @@ object BlazeConverters extends Logging {
       NativeAggBase.SortAgg,
       exec.requiredChildDistributionExpressions,
       exec.groupingExpressions,
-      exec.aggregateExpressions,
+      getNativeAggregateExprs(exec, exec.aggregateExpressions),
       exec.aggregateAttributes,
       exec.initialInputBufferOffset,
       exec.requiredChildDistributionExpressions match {
@@ -867,6 +930,18 @@ object BlazeConverters extends Logging {
     Shims.get.createNativeProjectExec(projectList, child)
   }
 
+  // distinct aggregates are deduplicated natively only if planned by BlazeDistinctAggStrategy,
+  // inputs of distinct aggregates in spark's multi-stage plans are already deduplicated
+  private def getNativeAggregateExprs(
+      exec: SparkPlan,
+      aggregateExprs: Seq[AggregateExpression]): Seq[AggregateExpression] = {
+    if (isNativeDistinctAgg(exec)) {
+      aggregateExprs
+    } else {
+      aggregateExprs.map(_.copy(isDistinct = false))
+    }
+  }
+
   private def getPartialAggProjection(
       aggregateExprs: Seq[AggregateExpression],
       groupingExprs: Seq[NamedExpression])
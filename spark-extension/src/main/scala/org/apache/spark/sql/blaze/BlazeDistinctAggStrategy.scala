@@ -0,0 +1,113 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.blaze
+
+import scala.util.Try
+
+import org.apache.spark.internal.Logging
+import org.apache.spark.sql.Strategy
+import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.NamedExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.Partial
+import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.planning.PhysicalAggregation
+import org.apache.spark.sql.catalyst.plans.logical.LogicalPlan
+import org.apache.spark.sql.catalyst.trees.TreeNodeTag
+import org.apache.spark.sql.execution.SparkPlan
+import org.apache.spark.sql.execution.aggregate.AggUtils
+import org.apache.spark.sql.execution.aggregate.HashAggregateExec
+import org.apache.spark.sql.execution.aggregate.ObjectHashAggregateExec
+import org.apache.spark.sql.execution.aggregate.SortAggregateExec
+import org.apache.spark.sql.types.AtomicType
+
+/**
+ * Plans single-distinct aggregates like count(DISTINCT v) as a partial and a final aggregate with
+ * only one shuffle, instead of spark's plan which deduplicates the inputs with two extra
+ * aggregates and an extra shuffle. The distinct values of each group are collected by native
+ * distinct aggregates, so the generated aggregates are tagged and must be converted to native.
+ */
+object BlazeDistinctAggStrategy extends Strategy with Logging {
+  val nativeDistinctAggTag: TreeNodeTag[Boolean] = TreeNodeTag("blaze.native.distinct.agg")
+
+  override def apply(plan: LogicalPlan): Seq[SparkPlan] = plan match {
+    case PhysicalAggregation(groupingExprs, aggExprs, resultExprs, child)
+        if isEnabled(plan) && aggExprs.forall(_.isInstanceOf[AggregateExpression]) =>
+      planSingleStageDistinctAgg(
+        groupingExprs,
+        aggExprs.map(_.asInstanceOf[AggregateExpression]),
+        resultExprs,
+        planLater(child)).getOrElse(Nil)
+    case _ => Nil
+  }
+
+  def planSingleStageDistinctAgg(
+      groupingExprs: Seq[NamedExpression],
+      aggExprs: Seq[AggregateExpression],
+      resultExprs: Seq[NamedExpression],
+      child: SparkPlan): Option[Seq[SparkPlan]] = {
+
+    if (!aggExprs.exists(_.isDistinct) || !aggExprs.forall(isSupported)) {
+      return None
+    }
+    if (!isConvertible(groupingExprs, aggExprs)) {
+      logWarning(s"not planning native distinct aggregates: $aggExprs")
+      return None
+    }
+
+    // without deduplicated inputs, distinct aggregates are kept in partial/final modes
+    val aggregateOperators =
+      AggUtils.planAggregateWithoutDistinct(groupingExprs, aggExprs, resultExprs, child)
+    aggregateOperators.foreach(_.foreach {
+      case e @ (_: HashAggregateExec | _: ObjectHashAggregateExec | _: SortAggregateExec) =>
+        e.setTagValue(nativeDistinctAggTag, true)
+      case _ =>
+    })
+    Some(aggregateOperators)
+  }
+
+  def isNativeDistinctAgg(exec: SparkPlan): Boolean =
+    exec.getTagValue(nativeDistinctAggTag).contains(true)
+
+  private def isEnabled(plan: LogicalPlan): Boolean = {
+    plan.conf.getConf(BlazeSparkSessionExtension.blazeEnabledKey) &&
+    BlazeConverters.enableAggr &&
+    BlazeConf.NATIVE_DISTINCT_AGG_ENABLE.booleanConf()
+  }
+
+  private def isSupported(e: AggregateExpression): Boolean = {
+    val isDistinctSupported = e.aggregateFunction match {
+      case agg @ (_: Count | _: Sum | _: Average) =>
+        agg.children.length == 1 &&
+        agg.dataType.isInstanceOf[AtomicType] &&
+        agg.children.head.dataType.isInstanceOf[AtomicType]
+      case _ => false
+    }
+    Shims.get.getAggregateExpressionFilter(e).isEmpty && (!e.isDistinct || isDistinctSupported)
+  }
+
+  // a tagged aggregate cannot fall back to spark, so all expressions must be convertible
+  private def isConvertible(
+      groupingExprs: Seq[Expression],
+      aggExprs: Seq[AggregateExpression]): Boolean = {
+    Try {
+      groupingExprs.foreach(NativeConverters.convertExpr(_))
+      aggExprs.foreach(e => NativeConverters.convertAggregateExpr(e.copy(mode = Partial)))
+    }.isSuccess
+  }
+}
@@ -37,6 +37,7 @@ class BlazeSparkSessionExtension extends (SparkSessionExtensions => Unit) with L
     assert(BlazeSparkSessionExtension.blazeEnabledKey != null)
     Shims.get.onApplyingExtension()
 
+    extensions.injectPlannerStrategy(_ => BlazeDistinctAggStrategy)
     extensions.injectColumnar(sparkSession => {
       BlazeColumnarOverrides(sparkSession)
     })
@@ -27,18 +27,34 @@ import com.google.protobuf.ByteString
 import org.apache.spark.SparkEnv
 import org.blaze.{protobuf => pb}
 import org.apache.spark.internal.Logging
-import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, Multiply, Murmur3Hash, Not, NullIf, OctetLength, Or, Pmod, PromotePrecision, Remainder, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TruncDate, Unevaluable, UnscaledValue, Upper}
+import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, Ascending, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, Multiply, Murmur3Hash, Not, NullIf, NullsFirst, OctetLength, Or, Pmod, PromotePrecision, Remainder, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TruncDate, Unevaluable, UnscaledValue, Upper, XxHash64}
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.ApproximatePercentile
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitAndAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitOrAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitXorAgg
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectList
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectSet
+import org.apache.spark.sql.catalyst.expressions.aggregate.Corr
 import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.CovPopulation
+import org.apache.spark.sql.catalyst.expressions.aggregate.CovSample
+import org.apache.spark.sql.catalyst.expressions.aggregate.HyperLogLogPlusPlus
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
+import org.apache.spark.sql.catalyst.expressions.aggregate.MaxBy
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
+import org.apache.spark.sql.catalyst.expressions.aggregate.MinBy
+import org.apache.spark.sql.catalyst.expressions.aggregate.StddevPop
+import org.apache.spark.sql.catalyst.expressions.aggregate.StddevSamp
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
+import org.apache.spark.sql.catalyst.expressions.aggregate.VariancePop
+import org.apache.spark.sql.catalyst.expressions.aggregate.VarianceSamp
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.BinaryArithmetic
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
+import org.apache.spark.sql.catalyst.expressions.aggregate.Last
+import org.apache.spark.sql.catalyst.plans.ExistenceJoin
 import org.apache.spark.sql.catalyst.plans.FullOuter
 import org.apache.spark.sql.catalyst.plans.Inner
 import org.apache.spark.sql.catalyst.plans.JoinType
@@ -66,9 +82,11 @@ import org.apache.spark.sql.types.DecimalType
 import org.apache.spark.sql.types.DoubleType
 import org.apache.spark.sql.types.FloatType
 import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.IntegralType
 import org.apache.spark.sql.types.LongType
 import org.apache.spark.sql.types.MapType
 import org.apache.spark.sql.types.NullType
+import org.apache.spark.sql.types.NumericType
 import org.apache.spark.sql.types.ShortType
 import org.apache.spark.sql.types.StringType
 import org.apache.spark.sql.types.StructField
@@ -186,8 +204,12 @@ object NativeConverters extends Logging {
                 .asJava)
             .build())
 
+      // version-specific types like ansi intervals
       case _ =>
-        throw new NotImplementedError(s"Data type conversion not implemented ${sparkDataType}")
+        val arrowType = Shims.get.convertDataType(sparkDataType).getOrElse {
+          throw new NotImplementedError(s"Data type conversion not implemented ${sparkDataType}")
+        }
+        arrowTypeBuilder.mergeFrom(arrowType)
     }
     arrowTypeBuilder.build()
   }
@@ -814,6 +836,8 @@ object NativeConverters extends Logging {
         buildScalarFunction(pb.ScalarFunction.SHA512, Seq(unpackBinaryTypeCast(_1)), StringType)
       case Murmur3Hash(children, 42) =>
         buildExtScalarFunction("Murmur3Hash", children, IntegerType)
+      case XxHash64(children, 42L) =>
+        buildExtScalarFunction("XxHash64", children, LongType)
 
       // startswith is converted to scalar function in pruning-expr mode
       case StartsWith(expr, Literal(prefix, StringType)) if isPruningExpr =>
@@ -1007,6 +1031,23 @@ object NativeConverters extends Logging {
     val aggBuilder = pb.PhysicalAggExprNode.newBuilder()
 
     e.aggregateFunction match {
+      // single-column distinct aggregates planned by BlazeDistinctAggStrategy
+      case agg @ (_: Count | _: Sum | _: Average)
+          if e.isDistinct && agg.children.length == 1 && agg.dataType.isInstanceOf[AtomicType]
+            && agg.children.head.dataType.isInstanceOf[AtomicType] =>
+        aggBuilder.setAggFunction(agg match {
+          case _: Count => pb.AggFunction.COUNT_DISTINCT
+          case _: Sum => pb.AggFunction.SUM_DISTINCT
+          case _: Average => pb.AggFunction.AVG_DISTINCT
+        })
+        aggBuilder.addChildren(convertExpr(agg.children.head))
+        agg match {
+          // failOnError (ansi mode of the plan) is passed as the last child
+          case _: Sum | _: Average =>
+            aggBuilder.addChildren(convertExpr(Literal(Shims.get.isFailOnError(agg), BooleanType)))
+          case _ =>
+        }
+
       case e: Max =>
         aggBuilder.setAggFunction(pb.AggFunction.MAX)
         aggBuilder.addChildren(convertExpr(e.child))
@@ -1016,9 +1057,13 @@ object NativeConverters extends Logging {
       case e: Sum if e.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.SUM)
         aggBuilder.addChildren(convertExpr(e.child))
+        // failOnError (ansi mode of the plan) is passed as the last child
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.isFailOnError(e), BooleanType)))
       case e: Average if e.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.AVG)
         aggBuilder.addChildren(convertExpr(e.child))
+        // failOnError (ansi mode of the plan) is passed as the last child
+        aggBuilder.addChildren(convertExpr(Literal(Shims.get.isFailOnError(e), BooleanType)))
       case Count(children) if !children.exists(_.nullable) =>
         aggBuilder.setAggFunction(pb.AggFunction.COUNT)
         aggBuilder.addChildren(convertExpr(Literal.apply(1)))
@@ -1047,13 +1092,92 @@ object NativeConverters extends Logging {
         })
         aggBuilder.addChildren(convertExpr(child))
 
-      case CollectList(child, _, _) if child.dataType.isInstanceOf[AtomicType] =>
+      case Last(child, ignoresNullExpr) =>
+        val ignoresNull = ignoresNullExpr.asInstanceOf[Any] match {
+          case Literal(v: Boolean, BooleanType) => v
+          case v: Boolean => v
+        }
+        aggBuilder.setAggFunction(if (ignoresNull) {
+          pb.AggFunction.LAST_IGNORES_NULL
+        } else {
+          pb.AggFunction.LAST
+        })
+        aggBuilder.addChildren(convertExpr(child))
+
+      case e @ CollectList(child, _, _) if child.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.COLLECT_LIST)
         aggBuilder.addChildren(convertExpr(child))
+        Shims.get.getAggregateOrdering(e).foreach { sortOrder =>
+          aggBuilder.addOrderBy(
+            pb.PhysicalSortExprNode
+              .newBuilder()
+              .setExpr(convertExpr(sortOrder.child))
+              .setAsc(sortOrder.direction == Ascending)
+              .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+              .build())
+        }
       case CollectSet(child, _, _) if child.dataType.isInstanceOf[AtomicType] =>
         aggBuilder.setAggFunction(pb.AggFunction.COLLECT_SET)
         aggBuilder.addChildren(convertExpr(child))
 
+      case e @ (_: StddevSamp | _: StddevPop | _: VarianceSamp | _: VariancePop | _: CovSample |
+          _: CovPopulation | _: Corr) =>
+        aggBuilder.setAggFunction(e match {
+          case _: StddevSamp => pb.AggFunction.STDDEV_SAMP
+          case _: StddevPop => pb.AggFunction.STDDEV_POP
+          case _: VarianceSamp => pb.AggFunction.VAR_SAMP
+          case _: VariancePop => pb.AggFunction.VAR_POP
+          case _: CovSample => pb.AggFunction.COVAR_SAMP
+          case _: CovPopulation => pb.AggFunction.COVAR_POP
+          case _: Corr => pb.AggFunction.CORR
+        })
+        e.children.foreach(child => aggBuilder.addChildren(convertExpr(child)))
+        // nullOnDivideByZero is passed as the last child
+        aggBuilder.addChildren(
+          convertExpr(Literal(Shims.get.isNullOnDivideByZero(e), BooleanType)))
+
+      // bool_and/bool_or/every/any/some are rewritten to min/max by spark, so only bitwise
+      // aggregates need to be converted here
+      case e @ (_: BitAndAgg | _: BitOrAgg | _: BitXorAgg)
+          if e.children.head.dataType.isInstanceOf[IntegralType] =>
+        aggBuilder.setAggFunction(e match {
+          case _: BitAndAgg => pb.AggFunction.BIT_AND
+          case _: BitOrAgg => pb.AggFunction.BIT_OR
+          case _: BitXorAgg => pb.AggFunction.BIT_XOR
+        })
+        aggBuilder.addChildren(convertExpr(e.children.head))
+
+      case e @ (_: MaxBy | _: MinBy) =>
+        // children: value, ordering key
+        aggBuilder.setAggFunction(e match {
+          case _: MaxBy => pb.AggFunction.MAX_BY
+          case _: MinBy => pb.AggFunction.MIN_BY
+        })
+        e.children.foreach(child => aggBuilder.addChildren(convertExpr(child)))
+
+      case e: HyperLogLogPlusPlus if e.child.dataType.isInstanceOf[AtomicType] =>
+        aggBuilder.setAggFunction(pb.AggFunction.APPROX_COUNT_DISTINCT)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(e.relativeSD, DoubleType)))
+
+      case e: ApproximatePercentile
+          if e.percentageExpression.foldable && e.accuracyExpression.foldable &&
+            e.percentageExpression.eval() != null && (e.child.dataType match {
+              case _: NumericType | DateType | TimestampType => true
+              case _ => false
+            }) =>
+        val accuracy = e.accuracyExpression.eval().asInstanceOf[Number].longValue
+        val (returnPercentileArray, percentages) = e.percentageExpression.eval() match {
+          case num: Double => (false, Seq(num))
+          case arrayData: ArrayData => (true, arrayData.toDoubleArray().toSeq)
+        }
+        // children: child, accuracy, returnPercentileArray, percentages...
+        aggBuilder.setAggFunction(pb.AggFunction.APPROX_PERCENTILE)
+        aggBuilder.addChildren(convertExpr(e.child))
+        aggBuilder.addChildren(convertExpr(Literal(accuracy, LongType)))
+        aggBuilder.addChildren(convertExpr(Literal(returnPercentileArray, BooleanType)))
+        percentages.foreach(p => aggBuilder.addChildren(convertExpr(Literal(p, DoubleType))))
+
       case _ =>
         Shims.get.convertAggregateExpr(e) match {
           case Some(converted) => return converted
@@ -1075,6 +1199,7 @@ object NativeConverters extends Logging {
       case FullOuter => pb.JoinType.FULL
       case LeftSemi => pb.JoinType.SEMI
       case LeftAnti => pb.JoinType.ANTI
+      case ExistenceJoin(_) => pb.JoinType.EXISTENCE
       case _ => throw new NotImplementedError(s"unsupported join type: ${joinType}")
     }
   }
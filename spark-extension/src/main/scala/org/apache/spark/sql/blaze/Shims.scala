@@ -27,7 +27,9 @@ import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleHandle
 import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
 import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.Literal
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
+import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
 import org.apache.spark.sql.catalyst.plans.physical.BroadcastMode
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
@@ -35,6 +37,7 @@ import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.blaze.plan._
 import org.apache.spark.sql.execution.blaze.shuffle.RssPartitionWriterBase
 import org.apache.spark.sql.execution.exchange.BroadcastExchangeLike
+import org.apache.spark.sql.execution.joins.BroadcastHashJoinExec
 import org.apache.spark.sql.SQLContext
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Generator
@@ -42,6 +45,7 @@ import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastJoinBase
+import org.apache.spark.sql.execution.blaze.plan.NativeShuffledHashJoinBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortMergeJoinBase
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
@@ -79,7 +83,9 @@ abstract class Shims {
       leftKeys: Seq[Expression],
       rightKeys: Seq[Expression],
       joinType: JoinType,
-      condition: Option[Expression]): NativeBroadcastJoinBase
+      broadcastSide: pb.JoinSide,
+      condition: Option[Expression],
+      isNullAwareAntiJoin: Boolean): NativeBroadcastJoinBase
 
   def createNativeBroadcastNestedLoopJoinExec(
       left: SparkPlan,
@@ -95,6 +101,15 @@ abstract class Shims {
       joinType: JoinType,
       condition: Option[Expression]): NativeSortMergeJoinBase
 
+  def createNativeShuffledHashJoinExec(
+      left: SparkPlan,
+      right: SparkPlan,
+      leftKeys: Seq[Expression],
+      rightKeys: Seq[Expression],
+      joinType: JoinType,
+      buildSide: pb.JoinSide,
+      condition: Option[Expression]): NativeShuffledHashJoinBase
+
   def createNativeExpandExec(
       projections: Seq[Seq[Expression]],
       output: Seq[Attribute],
@@ -195,14 +210,40 @@ abstract class Shims {
 
   def getAggregateExpressionFilter(expr: Expression): Option[Expression]
 
+  def getWindowFunctionIgnoreNulls(expr: Expression): Boolean
+
+  /** extracts (input, offset, ignoreNulls) of nth_value, which is not available in spark303 */
+  def getNthValueWindowFunction(expr: Expression): Option[(Expression, Int, Boolean)]
+
+  /** microseconds of day-time interval literals, which are not available in spark303 */
+  def getDayTimeIntervalMicros(literal: Literal): Option[Long]
+
+  /** whether statistical aggregates return null instead of NaN when dividing by zero */
+  def isNullOnDivideByZero(aggr: AggregateFunction): Boolean
+
+  /** whether sum/avg raise errors instead of returning wrapped/null results on overflow */
+  def isFailOnError(aggr: AggregateFunction): Boolean
+
+  /** within-group ordering of aggregates like array_agg(v ORDER BY k), empty if unordered */
+  def getAggregateOrdering(aggr: AggregateFunction): Seq[SortOrder]
+
+  /** converts data types not available in all spark versions, like ansi intervals */
+  def convertDataType(sparkDataType: DataType): Option[pb.ArrowType]
+
+  def isNullAwareAntiJoin(exec: BroadcastHashJoinExec): Boolean
+
   def createFileSegment(file: File, offset: Long, length: Long, numRecords: Long): FileSegment
 
+  /** whether spark supports shuffle checksums (since spark 3.2) */
+  def isShuffleChecksumSupported: Boolean
+
   def commit(
       dep: ShuffleDependency[_, _, _],
       shuffleBlockResolver: IndexShuffleBlockResolver,
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus
 
@@ -219,7 +260,7 @@ abstract class Shims {
 
   def getShuffleWriteExec(
       input: pb.PhysicalPlanNode,
-      nativeOutputPartitioning: pb.PhysicalHashRepartition.Builder): pb.PhysicalPlanNode
+      nativeOutputPartitioning: pb.PhysicalRepartition.Builder): pb.PhysicalPlanNode
 
   def convertMoreSparkPlan(exec: SparkPlan): Option[SparkPlan]
 
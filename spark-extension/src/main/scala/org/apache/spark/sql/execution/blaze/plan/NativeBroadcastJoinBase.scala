@@ -17,10 +17,13 @@ package org.apache.spark.sql.execution.blaze.plan
 
 import scala.collection.JavaConverters._
 import scala.collection.immutable.SortedMap
+import scala.collection.mutable
 
 import org.apache.spark.OneToOneDependency
 import org.apache.spark.Partition
+import org.apache.spark.TaskContext
 import org.apache.spark.sql.blaze.BlazeConf
+import org.apache.spark.sql.blaze.JniBridge
 import org.apache.spark.sql.blaze.MetricNode
 import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
@@ -28,8 +31,6 @@ import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.catalyst.expressions.Expression
 import org.apache.spark.sql.catalyst.plans.JoinType
-import org.apache.spark.sql.catalyst.plans.LeftAnti
-import org.apache.spark.sql.catalyst.plans.LeftSemi
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.metric.SQLMetric
@@ -43,14 +44,12 @@ abstract class NativeBroadcastJoinBase(
     leftKeys: Seq[Expression],
     rightKeys: Seq[Expression],
     joinType: JoinType,
-    condition: Option[Expression])
+    broadcastSide: pb.JoinSide,
+    condition: Option[Expression],
+    isNullAwareAntiJoin: Boolean)
     extends BinaryExecNode
     with NativeSupports {
 
-  assert(
-    (joinType != LeftSemi && joinType != LeftAnti) || condition.isEmpty,
-    "Semi/Anti join with filter is not supported yet")
-
   assert(
     !BlazeConf.BHJ_FALLBACKS_TO_SMJ_ENABLE.booleanConf() || BlazeConf.SMJ_INEQUALITY_JOIN_ENABLE
       .booleanConf() || condition.isEmpty,
@@ -79,7 +78,11 @@ abstract class NativeBroadcastJoinBase(
       .build()
   }
 
-  private def nativeJoinType = NativeConverters.convertJoinType(joinType)
+  private def nativeJoinType = if (isNullAwareAntiJoin) {
+    pb.JoinType.NULL_AWARE_ANTI
+  } else {
+    NativeConverters.convertJoinType(joinType)
+  }
 
   private def nativeJoinFilter =
     condition.map(NativeConverters.convertJoinFilter(_, left.output, right.output))
@@ -95,26 +98,38 @@ abstract class NativeBroadcastJoinBase(
     val nativeJoinType = this.nativeJoinType
     val nativeJoinOn = this.nativeJoinOn
     val nativeJoinFilter = this.nativeJoinFilter
-    val partitions = rightRDD.partitions
+    val (broadcastRDD, probedRDD) = broadcastSide match {
+      case pb.JoinSide.LEFT_SIDE => (leftRDD, rightRDD)
+      case _ => (rightRDD, leftRDD)
+    }
+    val partitions = probedRDD.partitions
 
     new NativeRDD(
       sparkContext,
       nativeMetrics,
       partitions,
-      rddDependencies = new OneToOneDependency(rightRDD) :: Nil,
-      rightRDD.isShuffleReadFull,
+      rddDependencies = new OneToOneDependency(probedRDD) :: Nil,
+      probedRDD.isShuffleReadFull,
       (partition, context) => {
         val partition0 = new Partition() {
           override def index: Int = 0
         }
-        val leftChild = leftRDD.nativePlan(partition0, context)
-        val rightChild = rightRDD.nativePlan(rightRDD.partitions(partition.index), context)
+        val broadcastChild = broadcastRDD.nativePlan(partition0, context)
+        val probedChild = probedRDD.nativePlan(probedRDD.partitions(partition.index), context)
+        val (leftChild, rightChild) = broadcastSide match {
+          case pb.JoinSide.LEFT_SIDE => (broadcastChild, probedChild)
+          case _ => (probedChild, broadcastChild)
+        }
+        val cachedBuildHashMapId = s"bhm_stage${context.stageId}_rdd${broadcastRDD.id}"
+        NativeBroadcastJoinBase.registerCachedBuildHashMap(context, cachedBuildHashMapId)
         val broadcastJoinExec = pb.BroadcastJoinExecNode
           .newBuilder()
           .setLeft(leftChild)
           .setRight(rightChild)
           .setJoinType(nativeJoinType)
           .addAllOn(nativeJoinOn.asJava)
+          .setBroadcastSide(broadcastSide)
+          .setCachedBuildHashMapId(cachedBuildHashMapId)
 
         nativeJoinFilter.foreach(joinFilter => broadcastJoinExec.setJoinFilter(joinFilter))
         pb.PhysicalPlanNode.newBuilder().setBroadcastJoin(broadcastJoinExec).build()
@@ -122,3 +137,39 @@ abstract class NativeBroadcastJoinBase(
       friendlyName = "NativeRDD.BroadcastJoin")
   }
 }
+
+object NativeBroadcastJoinBase {
+  // executor-side states of the cached build hash maps, which are shared by all tasks of a stage
+  // running in the executor, including tasks running one after another
+  private val cachedBuildHashMapIds = mutable.HashMap[Int, mutable.Set[String]]()
+  private val runningTaskIds = mutable.HashMap[Int, mutable.Set[Long]]()
+
+  /**
+   * Registers the cached build hash map used by a task, and releases the hash maps of finished
+   * stages. executors are not notified when a stage is finished, so the hash maps of a stage are
+   * released when a task of another stage starts and no task of the stage is running.
+   */
+  def registerCachedBuildHashMap(context: TaskContext, cachedBuildHashMapId: String): Unit =
+    synchronized {
+      val stageId = context.stageId()
+      val taskId = context.taskAttemptId()
+      if (runningTaskIds.getOrElseUpdate(stageId, mutable.Set()).add(taskId)) {
+        context.addTaskCompletionListener[Unit] { _ =>
+          NativeBroadcastJoinBase.synchronized {
+            runningTaskIds.get(stageId).foreach(_ -= taskId)
+          }
+        }
+      }
+      cachedBuildHashMapIds.getOrElseUpdate(stageId, mutable.Set()) += cachedBuildHashMapId
+
+      val finishedStageIds = cachedBuildHashMapIds.keys.filter { id =>
+        id != stageId && runningTaskIds.get(id).forall(_.isEmpty)
+      }
+      for (finishedStageId <- finishedStageIds.toList) {
+        cachedBuildHashMapIds.remove(finishedStageId).foreach(_.foreach { id =>
+          JniBridge.releaseCachedJoinHashMap(id)
+        })
+        runningTaskIds.remove(finishedStageId)
+      }
+    }
+}
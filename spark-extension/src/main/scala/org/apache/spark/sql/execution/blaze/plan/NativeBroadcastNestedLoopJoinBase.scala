@@ -91,7 +91,7 @@ abstract class NativeBroadcastNestedLoopJoinBase(
   nativeJoinFilter
 
   private val probedSide = joinType match {
-    case Inner | LeftOuter | LeftSemi | LeftAnti => "left"
+    case Inner | LeftOuter | LeftSemi | LeftAnti | ExistenceJoin(_) => "left"
     case RightOuter | FullOuter => "right"
     case other => s"NativeBroadcastNestedLoopJoin does not support join type $other"
   }
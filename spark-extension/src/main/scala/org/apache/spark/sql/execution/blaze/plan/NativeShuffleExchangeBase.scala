@@ -18,12 +18,28 @@ package org.apache.spark.sql.execution.blaze.plan
 import java.util.UUID
 
 import scala.collection.JavaConverters._
+import scala.collection.mutable
+import scala.collection.mutable.ArrayBuffer
+import scala.util.hashing.byteswap32
 
 import org.apache.spark.Partitioner
+import org.apache.spark.RangePartitioner
 import org.apache.spark.ShuffleDependency
 import org.apache.spark.SparkEnv
 import org.apache.spark.TaskContext
-import org.blaze.protobuf.{IpcReaderExecNode, PhysicalHashRepartition, PhysicalPlanNode, Schema}
+import org.blaze.protobuf.IpcReaderExecNode
+import org.blaze.protobuf.PhysicalExprNode
+import org.blaze.protobuf.PhysicalHashRepartition
+import org.blaze.protobuf.PhysicalPlanNode
+import org.blaze.protobuf.PhysicalRangeBound
+import org.blaze.protobuf.PhysicalRangeRepartition
+import org.blaze.protobuf.PhysicalRepartition
+import org.blaze.protobuf.PhysicalRoundRobinRepartition
+import org.blaze.protobuf.PhysicalSingleRepartition
+import org.blaze.protobuf.PhysicalSortExprNode
+import org.blaze.protobuf.Schema
+import org.blaze.protobuf.SortExecNode
+import org.apache.spark.rdd.PartitionPruningRDD
 import org.apache.spark.rdd.RDD
 import org.apache.spark.serializer.Serializer
 import org.apache.spark.shuffle.ShuffleWriteProcessor
@@ -36,8 +52,17 @@ import org.apache.spark.sql.blaze.NativeSupports
 import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.catalyst.InternalRow
+import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.BoundReference
+import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.RowOrdering
+import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.UnsafeProjection
+import org.apache.spark.sql.catalyst.expressions.codegen.LazilyGeneratedOrdering
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RangePartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
 import org.apache.spark.sql.execution.exchange.ShuffleExchangeLike
 import org.apache.spark.sql.execution.metric.SQLMetric
@@ -47,6 +72,7 @@ import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnsafeRowSerializer
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeBlockStoreShuffleReaderBase
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeShuffleDependency
+import org.apache.spark.sql.internal.SQLConf
 import org.apache.spark.sql.types.StructType
 import org.apache.spark.util.CompletionIterator
 import org.apache.spark.OneToOneDependency
@@ -92,9 +118,49 @@ abstract class NativeShuffleExchangeBase(
     case _ => null
   }
 
+  private def nativeSortExprs = outputPartitioning match {
+    case RangePartitioning(sortOrder, _) =>
+      sortOrder.map { sortOrder =>
+        PhysicalSortExprNode
+          .newBuilder()
+          .setExpr(NativeConverters.convertExpr(sortOrder.child))
+          .setAsc(sortOrder.direction == Ascending)
+          .setNullsFirst(sortOrder.nullOrdering == NullsFirst)
+          .build()
+      }.toList
+    case _ => null
+  }
+
+  // like spark, sort all rows before round-robin repartitioning so that the
+  // partition ids do not depend on the input order, which may change when
+  // the map task is retried
+  private def nativeSortBeforeRepartitionExprs = outputPartitioning match {
+    case RoundRobinPartitioning(numPartitions)
+        if numPartitions > 1 && SQLConf.get.sortBeforeRepartition =>
+      child.output.map { attr =>
+        if (!RowOrdering.isOrderable(attr.dataType)) {
+          throw new NotImplementedError(
+            s"cannot sort before round-robin repartition on type: ${attr.dataType}")
+        }
+        PhysicalExprNode
+          .newBuilder()
+          .setSort(
+            PhysicalSortExprNode
+              .newBuilder()
+              .setExpr(NativeConverters.convertExpr(attr))
+              .setAsc(true)
+              .setNullsFirst(true)
+              .build())
+          .build()
+      }.toList
+    case _ => Nil
+  }
+
   // check whether native converting is supported
   nativeSchema
   nativeHashExprs
+  nativeSortExprs
+  nativeSortBeforeRepartitionExprs
 
   protected def doExecuteNonNative(): RDD[InternalRow]
 
@@ -187,6 +253,19 @@ abstract class NativeShuffleExchangeBase(
         case _ =>
       }))
     val nativeHashExprs = this.nativeHashExprs
+    val nativeSortExprs = this.nativeSortExprs
+    val nativeSortBeforeRepartitionExprs = this.nativeSortBeforeRepartitionExprs
+    val nativeRangeBounds = outputPartitioning match {
+      case RangePartitioning(sortOrder, numPartitions) if numPartitions > 1 =>
+        computeRangeBounds(rdd, outputAttributes, sortOrder, numPartitions).map { bound =>
+          val nativeBound = PhysicalRangeBound.newBuilder()
+          sortOrder.zipWithIndex.foreach { case (s, i) =>
+            nativeBound.addValue(NativeConverters.convertValue(bound.get(i, s.dataType), s.dataType))
+          }
+          nativeBound.build()
+        }.toList
+      case _ => Nil
+    }
 
     val nativeShuffleRDD = new NativeRDD(
       nativeInputRDD.sparkContext,
@@ -198,19 +277,49 @@ abstract class NativeShuffleExchangeBase(
         val nativeInputPartition = nativeInputRDD.partitions(partition.index)
         val nativeOutputPartitioning = outputPartitioning match {
           case SinglePartition =>
-            PhysicalHashRepartition
+            PhysicalRepartition
               .newBuilder()
-              .setPartitionCount(1)
+              .setSingleRepartition(PhysicalSingleRepartition.newBuilder())
           case HashPartitioning(_, _) =>
-            PhysicalHashRepartition
+            PhysicalRepartition
+              .newBuilder()
+              .setHashRepartition(
+                PhysicalHashRepartition
+                  .newBuilder()
+                  .setPartitionCount(numPartitions)
+                  .addAllHashExpr(nativeHashExprs.asJava))
+          case RoundRobinPartitioning(_) =>
+            PhysicalRepartition
               .newBuilder()
-              .setPartitionCount(numPartitions)
-              .addAllHashExpr(nativeHashExprs.asJava)
+              .setRoundRobinRepartition(
+                PhysicalRoundRobinRepartition
+                  .newBuilder()
+                  .setPartitionCount(numPartitions))
+          case RangePartitioning(_, _) =>
+            PhysicalRepartition
+              .newBuilder()
+              .setRangeRepartition(
+                PhysicalRangeRepartition
+                  .newBuilder()
+                  .setPartitionCount(numPartitions)
+                  .addAllSortExpr(nativeSortExprs.asJava)
+                  .addAllBound(nativeRangeBounds.asJava))
           case p =>
             throw new NotImplementedError(s"cannot convert partitioning to native: $p")
         }
 
-        val input = nativeInputRDD.nativePlan(nativeInputPartition, taskContext)
+        val input = nativeSortBeforeRepartitionExprs match {
+          case Nil => nativeInputRDD.nativePlan(nativeInputPartition, taskContext)
+          case sortExprs =>
+            PhysicalPlanNode
+              .newBuilder()
+              .setSort(
+                SortExecNode
+                  .newBuilder()
+                  .setInput(nativeInputRDD.nativePlan(nativeInputPartition, taskContext))
+                  .addAllExpr(sortExprs.asJava))
+              .build()
+        }
         val nativeShuffleWriteExec =
           Shims.get.getShuffleWriteExec(input, nativeOutputPartitioning)
         nativeShuffleWriteExec
@@ -229,4 +338,55 @@ abstract class NativeShuffleExchangeBase(
       schema = StructType.fromAttributes(outputAttributes))
     dependency
   }
+
+  /**
+   * Computes upper bounds of range partitioning by sampling the sorting keys, the same as
+   * [[RangePartitioner]] used by spark's ShuffleExchangeExec.
+   */
+  private def computeRangeBounds(
+      rdd: RDD[InternalRow],
+      outputAttributes: Seq[Attribute],
+      sortOrder: Seq[SortOrder],
+      numPartitions: Int): Array[InternalRow] = {
+
+    val rddForSampling = rdd.mapPartitionsInternal { iter =>
+      val projection = UnsafeProjection.create(sortOrder.map(_.child), outputAttributes)
+      iter.map(row => projection(row).copy().asInstanceOf[InternalRow])
+    }
+    val boundSortOrder = sortOrder.zipWithIndex.map { case (s, i) =>
+      s.copy(child = BoundReference(i, s.dataType, s.nullable))
+    }
+    implicit val ordering: Ordering[InternalRow] = new LazilyGeneratedOrdering(boundSortOrder)
+
+    // this is the sample size we need to have roughly balanced output partitions, capped at 1M
+    val samplePointsPerPartitionHint = SQLConf.get.rangeExchangeSampleSizePerPartition
+    val sampleSize = math.min(samplePointsPerPartitionHint.toDouble * numPartitions, 1e6)
+    val sampleSizePerPartition = math.ceil(3.0 * sampleSize / rdd.partitions.length).toInt
+    val (numItems, sketched) = RangePartitioner.sketch(rddForSampling, sampleSizePerPartition)
+    if (numItems == 0L) {
+      return Array.empty
+    }
+
+    // if a partition contains much more than the average number of items, we re-sample from it
+    // to ensure that enough items are collected from that partition
+    val fraction = math.min(sampleSize / math.max(numItems, 1L), 1.0)
+    val candidates = ArrayBuffer.empty[(InternalRow, Float)]
+    val imbalancedPartitions = mutable.Set.empty[Int]
+    sketched.foreach { case (idx, n, sample) =>
+      if (fraction * n > sampleSizePerPartition) {
+        imbalancedPartitions += idx
+      } else {
+        val weight = (n.toDouble / sample.length).toFloat
+        sample.foreach(key => candidates += ((key, weight)))
+      }
+    }
+    if (imbalancedPartitions.nonEmpty) {
+      val imbalanced = new PartitionPruningRDD(rddForSampling, imbalancedPartitions.contains)
+      val seed = byteswap32(-rddForSampling.id - 1)
+      val reSampled = imbalanced.sample(withReplacement = false, fraction, seed).collect()
+      val weight = (1.0 / fraction).toFloat
+      candidates ++= reSampled.map(x => (x, weight))
+    }
+    RangePartitioner.determineBounds(candidates, math.min(numPartitions, candidates.size))
+  }
 }
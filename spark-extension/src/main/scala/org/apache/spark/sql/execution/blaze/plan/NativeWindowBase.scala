@@ -24,13 +24,28 @@ import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.CumeDist
+import org.apache.spark.sql.catalyst.expressions.CurrentRow
 import org.apache.spark.sql.catalyst.expressions.Expression
+import org.apache.spark.sql.catalyst.expressions.Lag
+import org.apache.spark.sql.catalyst.expressions.Lead
+import org.apache.spark.sql.catalyst.expressions.LessThan
+import org.apache.spark.sql.catalyst.expressions.Literal
 import org.apache.spark.sql.catalyst.expressions.NamedExpression
+import org.apache.spark.sql.catalyst.expressions.NTile
 import org.apache.spark.sql.catalyst.expressions.NullsFirst
+import org.apache.spark.sql.catalyst.expressions.PercentRank
+import org.apache.spark.sql.catalyst.expressions.RangeFrame
 import org.apache.spark.sql.catalyst.expressions.Rank
+import org.apache.spark.sql.catalyst.expressions.RowFrame
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.SpecifiedWindowFrame
+import org.apache.spark.sql.catalyst.expressions.UnaryMinus
+import org.apache.spark.sql.catalyst.expressions.UnboundedFollowing
+import org.apache.spark.sql.catalyst.expressions.UnboundedPreceding
 import org.apache.spark.sql.catalyst.plans.physical.AllTuples
 import org.apache.spark.sql.catalyst.plans.physical.ClusteredDistribution
 import org.apache.spark.sql.catalyst.plans.physical.Distribution
@@ -38,12 +53,23 @@ import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.metric.SQLMetric
+import org.apache.spark.sql.types.BooleanType
+import org.apache.spark.sql.types.CalendarIntervalType
+import org.apache.spark.sql.types.LongType
+import org.apache.spark.sql.types.NumericType
+import org.apache.spark.unsafe.types.CalendarInterval
 import org.blaze.{protobuf => pb}
 import org.apache.spark.sql.catalyst.expressions.DenseRank
 import org.apache.spark.sql.catalyst.expressions.RowNumber
 import org.apache.spark.sql.catalyst.expressions.WindowExpression
+import org.apache.spark.sql.catalyst.expressions.WindowFrame
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitAndAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitOrAgg
+import org.apache.spark.sql.catalyst.expressions.aggregate.BitXorAgg
 import org.apache.spark.sql.catalyst.expressions.aggregate.Count
+import org.apache.spark.sql.catalyst.expressions.aggregate.First
+import org.apache.spark.sql.catalyst.expressions.aggregate.Last
 import org.apache.spark.sql.catalyst.expressions.aggregate.Max
 import org.apache.spark.sql.catalyst.expressions.aggregate.Min
 import org.apache.spark.sql.catalyst.expressions.aggregate.Sum
@@ -74,7 +100,7 @@ abstract class NativeWindowBase(
     }
   }
 
-  private def nativeWindowExprs = windowExpression.map { named =>
+  private[plan] def nativeWindowExprs = windowExpression.map { named =>
     val field = NativeConverters.convertField(Util.getSchema(named :: Nil).fields(0))
     val windowExprBuilder = pb.WindowExprNode.newBuilder().setField(field)
 
@@ -102,46 +128,113 @@ abstract class NativeWindowBase(
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
             windowExprBuilder.setWindowFunc(pb.WindowFunction.DENSE_RANK)
 
-          case e: Sum =>
+          case e: PercentRank =>
             assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounde, CurrentRow)
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.PERCENT_RANK)
+
+          case e: CumeDist =>
+            assert(
+              spec.frameSpecification == e.frame,
               s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.CUME_DIST)
+
+          case e: NTile =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.NTILE)
+            windowExprBuilder.setOffset(e.buckets.eval().asInstanceOf[Int])
+
+          case e: Lag =>
+            // children of lag/lead are (input, offset, default) in all spark versions
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.LAG)
+            windowExprBuilder.setOffset(e.children(1).eval().asInstanceOf[Int])
+            windowExprBuilder.setIgnoreNulls(Shims.get.getWindowFunctionIgnoreNulls(e))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.input))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.default))
+
+          case e: Lead =>
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.LEAD)
+            windowExprBuilder.setOffset(e.children(1).eval().asInstanceOf[Int])
+            windowExprBuilder.setIgnoreNulls(Shims.get.getWindowFunctionIgnoreNulls(e))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.input))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.default))
+
+          case NthValueWindowFunction(input, offset, ignoreNulls) =>
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.NTH_VALUE)
+            windowExprBuilder.setOffset(offset)
+            windowExprBuilder.setIgnoreNulls(ignoreNulls)
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(input))
+
+          // first/last are aggregate functions in spark, which are evaluated as
+          // first_value/last_value window functions
+          case First(child, ignoresNullExpr) =>
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.FIRST_VALUE)
+            windowExprBuilder.setIgnoreNulls(isIgnoresNull(ignoresNullExpr))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
+
+          case Last(child, ignoresNullExpr) =>
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.LAST_VALUE)
+            windowExprBuilder.setIgnoreNulls(isIgnoresNull(ignoresNullExpr))
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
+
+          case e: Sum =>
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.SUM)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.addChildren(
+              NativeConverters.convertExpr(Literal(Shims.get.isFailOnError(e), BooleanType)))
 
           case e: Average =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.AVG)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
+            windowExprBuilder.addChildren(
+              NativeConverters.convertExpr(Literal(Shims.get.isFailOnError(e), BooleanType)))
 
           case e: Max =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.MAX)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case e: Min =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.MIN)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(e.child))
 
           case Count(child :: Nil) =>
-            assert(
-              spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
-              s"window frame not supported: ${spec.frameSpecification}")
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
             windowExprBuilder.setAggFunc(pb.AggFunction.COUNT)
             windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
 
+          case e @ (_: BitAndAgg | _: BitOrAgg | _: BitXorAgg) =>
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Agg)
+            windowExprBuilder.setFrame(convertWindowFrame(spec.frameSpecification))
+            windowExprBuilder.setAggFunc(e match {
+              case _: BitAndAgg => pb.AggFunction.BIT_AND
+              case _: BitOrAgg => pb.AggFunction.BIT_OR
+              case _: BitXorAgg => pb.AggFunction.BIT_XOR
+            })
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.children.head))
+
           case other =>
             throw new NotImplementedError(s"window function not supported: $other")
         }
@@ -151,6 +244,77 @@ abstract class NativeWindowBase(
     windowExprBuilder.build()
   }
 
+  // ignoreNulls of first/last is an expression in spark303 and a boolean in spark333
+  private def isIgnoresNull(ignoresNullExpr: Any): Boolean = ignoresNullExpr match {
+    case Literal(v: Boolean, BooleanType) => v
+    case v: Boolean => v
+  }
+
+  private object NthValueWindowFunction {
+    def unapply(e: Expression): Option[(Expression, Int, Boolean)] =
+      Shims.get.getNthValueWindowFunction(e)
+  }
+
+  private def convertWindowFrame(frame: WindowFrame): pb.WindowFrame = frame match {
+    case SpecifiedWindowFrame(frameType, lower, upper) =>
+      val units = frameType match {
+        case RowFrame => pb.WindowFrameUnits.ROWS
+        case RangeFrame => pb.WindowFrameUnits.RANGE
+      }
+      pb.WindowFrame
+        .newBuilder()
+        .setUnits(units)
+        .setStart(convertWindowFrameBound(lower))
+        .setEnd(convertWindowFrameBound(upper))
+        .build()
+    case other =>
+      throw new NotImplementedError(s"window frame not supported: $other")
+  }
+
+  private def convertWindowFrameBound(bound: Expression): pb.WindowFrameBound = {
+    val builder = pb.WindowFrameBound.newBuilder()
+    bound match {
+      case UnboundedPreceding =>
+        builder.setBoundType(pb.WindowFrameBoundType.UNBOUNDED_PRECEDING)
+      case UnboundedFollowing =>
+        builder.setBoundType(pb.WindowFrameBoundType.UNBOUNDED_FOLLOWING)
+      case CurrentRow =>
+        builder.setBoundType(pb.WindowFrameBoundType.CURRENT_ROW)
+      case offset @ Literal(_, _: NumericType) =>
+        // spark represents preceding offsets as negative values
+        val isPreceding =
+          LessThan(offset, Literal.default(offset.dataType)).eval().asInstanceOf[Boolean]
+        if (isPreceding) {
+          builder.setBoundType(pb.WindowFrameBoundType.PRECEDING)
+          builder.setOffset(
+            NativeConverters.convertValue(UnaryMinus(offset).eval(), offset.dataType))
+        } else {
+          builder.setBoundType(pb.WindowFrameBoundType.FOLLOWING)
+          builder.setOffset(NativeConverters.convertValue(offset.value, offset.dataType))
+        }
+      case IntervalOffset(micros) =>
+        // interval offsets of RANGE frames over timestamps are converted to
+        // microseconds, which are added to the microsecond timestamp keys natively
+        builder.mergeFrom(convertWindowFrameBound(Literal(micros, LongType)))
+      case other =>
+        // other offsets fall back to spark, including year-month intervals and
+        // calendar intervals with months/days, whose results depend on the calendar
+        // and time zone
+        throw new NotImplementedError(s"window frame bound not supported: $other")
+    }
+    builder.build()
+  }
+
+  private object IntervalOffset {
+    def unapply(e: Expression): Option[Long] = e match {
+      case Literal(interval: CalendarInterval, CalendarIntervalType)
+          if interval.months == 0 && interval.days == 0 =>
+        Some(interval.microseconds)
+      case offset: Literal => Shims.get.getDayTimeIntervalMicros(offset)
+      case _ => None
+    }
+  }
+
   private def nativePartitionSpecExprs = partitionSpec.map { partition =>
     NativeConverters.convertExpr(partition)
   }
@@ -43,13 +43,43 @@ abstract class BlazeBlockStoreShuffleReaderBase[K, C](
   protected val dep: ShuffleDependency[K, _, C] = handle.dependency
   protected def readBlocks(): Iterator[(BlockId, InputStream)]
 
-  def readIpc(): Iterator[Object] = { // FileSegment | ReadableByteChannel
-    val ipcIterator = readBlocks().map { case (_, inputStream) =>
-      getFileSegmentFromInputStream(inputStream) match {
-        case Some(fileSegment) =>
-          fileSegment
-        case None =>
-          Channels.newChannel(inputStream)
+  /**
+   * segments of a local shuffle block with checksums recorded by shuffle writer, verified by
+   * native reader. a batch block is split into one segment for each contained partition.
+   * returns None if checksums are not available.
+   */
+  protected def getChecksummedSegments(
+      blockId: BlockId,
+      fileSegment: FileSegment): Option[Seq[BlazeChecksummedFileSegment]] = None
+
+  /**
+   * (splitIndex, numSplits) if only a sub-range of each block is read, for reading a skewed
+   * partition in several tasks. local blocks are split at ipc block boundaries recorded in the
+   * split index files, other blocks are not split and read by the first split only.
+   * sub-ranges are read without checksum verification.
+   */
+  protected def partitionSplit: Option[(Int, Int)] = None
+
+  // FileSegment | BlazeChecksummedFileSegment | ReadableByteChannel
+  def readIpc(): Iterator[Object] = {
+    val ipcIterator = readBlocks().flatMap { case (blockId, inputStream) =>
+      (getFileSegmentFromInputStream(inputStream), partitionSplit) match {
+        case (Some(fileSegment), Some((splitIndex, numSplits))) =>
+          BlazeShuffleSplitIndex
+            .getSplitSegment(
+              blockId,
+              fileSegment,
+              dep.partitioner.numPartitions,
+              splitIndex,
+              numSplits)
+            .toSeq
+        case (Some(fileSegment), None) =>
+          getChecksummedSegments(blockId, fileSegment).getOrElse(Seq(fileSegment))
+        case (None, Some((splitIndex, _))) if splitIndex > 0 =>
+          inputStream.close()
+          Nil
+        case (None, _) =>
+          Seq(Channels.newChannel(inputStream))
       }
     }
 
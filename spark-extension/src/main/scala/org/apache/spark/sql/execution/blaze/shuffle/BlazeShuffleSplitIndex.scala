@@ -0,0 +1,135 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.shuffle
+
+import java.io.BufferedInputStream
+import java.io.DataInputStream
+import java.io.File
+import java.io.FileInputStream
+
+import scala.collection.mutable.ArrayBuffer
+
+import org.apache.spark.sql.blaze.Shims
+import org.apache.spark.storage.BlockId
+import org.apache.spark.storage.FileSegment
+import org.apache.spark.storage.ShuffleBlockId
+import org.apache.spark.util.Utils
+
+/**
+ * Sizes and numbers of rows of the ipc blocks in each partition of a native shuffle data file,
+ * read from the split index file written next to the data file. blocks can be decoded
+ * independently, so a skewed partition can be read in sub-ranges split at block boundaries.
+ */
+case class BlazeShuffleSplitIndex(partitionBlocks: Array[Array[(Long, Long)]]) {
+
+  def numRows(partitionId: Int): Long =
+    partitionBlocks(partitionId).map(_._2).sum
+
+  def partitionLength(partitionId: Int): Long =
+    partitionBlocks(partitionId).map(_._1).sum
+
+  /**
+   * Splits a partition starting at partitionOffset of the data file into at most numSplits
+   * sub-ranges with similar numbers of rows, returns offset, length and number of rows of each
+   * sub-range. this is the same as ShuffleSplitIndex::split_partition() in native engine.
+   */
+  def splitPartition(
+      partitionId: Int,
+      partitionOffset: Long,
+      numSplits: Int): Seq[(Long, Long, Long)] = {
+    val totalRows = numRows(partitionId)
+    val n = math.max(numSplits, 1).toLong
+    val targetRows = math.max((totalRows + n - 1) / n, 1L)
+    val splits = ArrayBuffer[(Long, Long, Long)]()
+    var splitStart = partitionOffset
+    var splitEnd = partitionOffset
+    var splitRows = 0L
+
+    for ((len, rows) <- partitionBlocks(partitionId)) {
+      splitEnd += len
+      splitRows += rows
+      if (splitRows >= targetRows) {
+        splits += ((splitStart, splitEnd - splitStart, splitRows))
+        splitStart = splitEnd
+        splitRows = 0
+      }
+    }
+    if (splitEnd > splitStart) {
+      splits += ((splitStart, splitEnd - splitStart, splitRows))
+    }
+    splits
+  }
+}
+
+object BlazeShuffleSplitIndex {
+
+  def getSplitIndexFile(dataFile: File): File =
+    new File(dataFile.getPath.replace(".data", ".splits"))
+
+  /**
+   * Reads a split index file: (numPartitions + 1) starting block indices of each partition,
+   * followed by length and number of rows of each block, all written as little-endian longs.
+   */
+  def read(splitIndexFile: File, numPartitions: Int): BlazeShuffleSplitIndex = {
+    Utils.tryWithResource(
+      new DataInputStream(new BufferedInputStream(new FileInputStream(splitIndexFile)))) { in =>
+      def readLong(): Long = java.lang.Long.reverseBytes(in.readLong())
+
+      val blockIndices = Array.fill(numPartitions + 1)(readLong())
+      if (blockIndices(0) != 0 || blockIndices.sliding(2).exists(w => w(0) > w(1))) {
+        throw new IllegalStateException(
+          s"invalid shuffle split index: ${blockIndices.mkString(",")}")
+      }
+      BlazeShuffleSplitIndex(Array.tabulate(numPartitions) { partitionId =>
+        val numBlocks = (blockIndices(partitionId + 1) - blockIndices(partitionId)).toInt
+        Array.fill(numBlocks)((readLong(), readLong()))
+      })
+    }
+  }
+
+  /**
+   * Returns the splitIndex-th of numSplits sub-ranges of a local shuffle block. blocks without
+   * a split index are not split and read by the first split only, so all splits together read
+   * the complete block.
+   */
+  def getSplitSegment(
+      blockId: BlockId,
+      fileSegment: FileSegment,
+      numPartitions: Int,
+      splitIndex: Int,
+      numSplits: Int): Option[FileSegment] = {
+
+    val splitIndexFile = getSplitIndexFile(fileSegment.file)
+    val splits = blockId match {
+      case ShuffleBlockId(_, _, reduceId) if splitIndexFile.exists() =>
+        val index = read(splitIndexFile, numPartitions)
+        if (index.partitionLength(reduceId) == fileSegment.length) {
+          Some(index.splitPartition(reduceId, fileSegment.offset, numSplits))
+        } else {
+          None // split index does not match the block, read the complete block
+        }
+      case _ => None
+    }
+    splits match {
+      case Some(splits) =>
+        splits.lift(splitIndex).map { case (offset, length, numRows) =>
+          Shims.get.createFileSegment(fileSegment.file, offset, length, numRows)
+        }
+      case None if splitIndex == 0 => Some(fileSegment)
+      case None => None
+    }
+  }
+}
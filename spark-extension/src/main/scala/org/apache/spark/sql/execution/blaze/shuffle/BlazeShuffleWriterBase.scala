@@ -19,6 +19,7 @@ import java.nio.ByteBuffer
 import java.nio.ByteOrder
 import java.nio.file.Files
 import java.nio.file.Paths
+import java.nio.file.StandardCopyOption
 
 import org.apache.spark.Partition
 import org.apache.spark.ShuffleDependency
@@ -32,6 +33,7 @@ import org.apache.spark.scheduler.MapStatus
 import org.apache.spark.shuffle.IndexShuffleBlockResolver
 import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
 import org.apache.spark.shuffle.ShuffleWriter
+import org.apache.spark.sql.blaze.BlazeConf
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.Shims
@@ -63,15 +65,27 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
     val tempIndexFilename = dataFile.getPath.replace(".data", ".index.tmp")
     val tempDataFilePath = Paths.get(tempDataFilename)
     val tempIndexFilePath = Paths.get(tempIndexFilename)
+    val tempChecksumFilename = dataFile.getPath.replace(".data", ".checksum.tmp")
+    val tempChecksumFilePath = Paths.get(tempChecksumFilename)
+    val checksumEnabled =
+      Shims.get.isShuffleChecksumSupported && BlazeConf.SHUFFLE_CHECKSUM_ENABLED.booleanConf()
+    val tempSplitIndexFilename = dataFile.getPath.replace(".data", ".splits.tmp")
+    val tempSplitIndexFilePath = Paths.get(tempSplitIndexFilename)
+    val splitIndexEnabled = BlazeConf.SHUFFLE_SPLIT_INDEX_ENABLED.booleanConf()
 
+    val nativeShuffleWriterExecNode = ShuffleWriterExecNode
+      .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
+      .setOutputDataFile(tempDataFilename)
+      .setOutputIndexFile(tempIndexFilename)
+    if (checksumEnabled) {
+      nativeShuffleWriterExecNode.setOutputChecksumFile(tempChecksumFilename)
+    }
+    if (splitIndexEnabled) {
+      nativeShuffleWriterExecNode.setOutputSplitIndexFile(tempSplitIndexFilename)
+    }
     val nativeShuffleWriterExec = PhysicalPlanNode
       .newBuilder()
-      .setShuffleWriter(
-        ShuffleWriterExecNode
-          .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
-          .setOutputDataFile(tempDataFilename)
-          .setOutputIndexFile(tempIndexFilename)
-          .build())
+      .setShuffleWriter(nativeShuffleWriterExecNode.build())
       .build()
     val iterator = NativeHelper.executeNativePlan(
       nativeShuffleWriterExec,
@@ -95,18 +109,39 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
       })
       .toArray
 
+    // get partition checksums from shuffle write output checksum file
+    val checksums = if (checksumEnabled) {
+      val checksumBuffer = ByteBuffer.wrap(Files.readAllBytes(tempChecksumFilePath))
+      Files.delete(tempChecksumFilePath)
+      Array.fill(checksumBuffer.remaining() / 8)(checksumBuffer.getLong) // big-endian
+    } else {
+      Array[Long]()
+    }
+
     // update metrics
     val dataSize = Files.size(tempDataFilePath)
     metrics.incBytesWritten(dataSize)
 
-    Shims.get.commit(
+    val mapStatus = Shims.get.commit(
       dep,
       shuffleBlockResolver,
       tempDataFilePath.toFile,
       mapId,
       partitionLengths,
+      checksums,
       dataSize,
       context)
+
+    // move split index file next to the committed data file
+    // (not written by single partition shuffle writer)
+    if (splitIndexEnabled && Files.exists(tempSplitIndexFilePath)) {
+      Files.move(
+        tempSplitIndexFilePath,
+        BlazeShuffleSplitIndex.getSplitIndexFile(dataFile).toPath,
+        StandardCopyOption.REPLACE_EXISTING,
+        StandardCopyOption.ATOMIC_MOVE)
+    }
+    mapStatus
   }
 
   override def stop(success: Boolean): Option[MapStatus] = None